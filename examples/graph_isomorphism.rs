@@ -37,12 +37,14 @@ fn example_1_variable_renaming() -> Result<(), Box<dyn std::error::Error>> {
         subject: TripleNode::Variable("x".to_string()),
         predicate: TripleNode::IRI("http://example.org/knows".to_string()),
         object: TripleNode::Variable("y".to_string()),
+        graph: None,
     }];
 
     let graph2 = vec![Triple {
         subject: TripleNode::Variable("person".to_string()),
         predicate: TripleNode::IRI("http://example.org/knows".to_string()),
         object: TripleNode::Variable("friend".to_string()),
+        graph: None,
     }];
 
     let result = GraphIsomorphism::are_isomorphic(&graph1, &graph2)?;
@@ -61,12 +63,14 @@ fn example_2_non_isomorphic() -> Result<(), Box<dyn std::error::Error>> {
         subject: TripleNode::Variable("x".to_string()),
         predicate: TripleNode::IRI("http://example.org/knows".to_string()),
         object: TripleNode::Variable("y".to_string()),
+        graph: None,
     }];
 
     let graph2 = vec![Triple {
         subject: TripleNode::Variable("a".to_string()),
         predicate: TripleNode::IRI("http://example.org/likes".to_string()),
         object: TripleNode::Variable("b".to_string()),
+        graph: None,
     }];
 
     let result = GraphIsomorphism::are_isomorphic(&graph1, &graph2)?;
@@ -86,16 +90,19 @@ fn example_3_complex_graph() -> Result<(), Box<dyn std::error::Error>> {
             subject: TripleNode::Variable("person".to_string()),
             predicate: TripleNode::IRI("http://xmlns.com/foaf/0.1/name".to_string()),
             object: TripleNode::Literal("Alice".to_string()),
+            graph: None,
         },
         Triple {
             subject: TripleNode::Variable("person".to_string()),
             predicate: TripleNode::IRI("http://xmlns.com/foaf/0.1/age".to_string()),
             object: TripleNode::Literal("30".to_string()),
+            graph: None,
         },
         Triple {
             subject: TripleNode::Variable("person".to_string()),
             predicate: TripleNode::IRI("http://xmlns.com/foaf/0.1/knows".to_string()),
             object: TripleNode::Variable("friend".to_string()),
+            graph: None,
         },
     ];
 
@@ -104,16 +111,19 @@ fn example_3_complex_graph() -> Result<(), Box<dyn std::error::Error>> {
             subject: TripleNode::Variable("x".to_string()),
             predicate: TripleNode::IRI("http://xmlns.com/foaf/0.1/name".to_string()),
             object: TripleNode::Literal("Alice".to_string()),
+            graph: None,
         },
         Triple {
             subject: TripleNode::Variable("x".to_string()),
             predicate: TripleNode::IRI("http://xmlns.com/foaf/0.1/age".to_string()),
             object: TripleNode::Literal("30".to_string()),
+            graph: None,
         },
         Triple {
             subject: TripleNode::Variable("x".to_string()),
             predicate: TripleNode::IRI("http://xmlns.com/foaf/0.1/knows".to_string()),
             object: TripleNode::Variable("y".to_string()),
+            graph: None,
         },
     ];
 
@@ -134,11 +144,13 @@ fn example_4_blank_nodes() -> Result<(), Box<dyn std::error::Error>> {
             subject: TripleNode::BlankNode("b1".to_string()),
             predicate: TripleNode::IRI("http://example.org/type".to_string()),
             object: TripleNode::IRI("http://example.org/Person".to_string()),
+            graph: None,
         },
         Triple {
             subject: TripleNode::BlankNode("b1".to_string()),
             predicate: TripleNode::IRI("http://example.org/name".to_string()),
             object: TripleNode::Literal("Bob".to_string()),
+            graph: None,
         },
     ];
 
@@ -147,11 +159,13 @@ fn example_4_blank_nodes() -> Result<(), Box<dyn std::error::Error>> {
             subject: TripleNode::BlankNode("blank0".to_string()),
             predicate: TripleNode::IRI("http://example.org/type".to_string()),
             object: TripleNode::IRI("http://example.org/Person".to_string()),
+            graph: None,
         },
         Triple {
             subject: TripleNode::BlankNode("blank0".to_string()),
             predicate: TripleNode::IRI("http://example.org/name".to_string()),
             object: TripleNode::Literal("Bob".to_string()),
+            graph: None,
         },
     ];
 
@@ -172,11 +186,13 @@ fn example_5_symmetric_structure() -> Result<(), Box<dyn std::error::Error>> {
             subject: TripleNode::Variable("x".to_string()),
             predicate: TripleNode::IRI("http://example.org/connected".to_string()),
             object: TripleNode::Variable("y".to_string()),
+            graph: None,
         },
         Triple {
             subject: TripleNode::Variable("y".to_string()),
             predicate: TripleNode::IRI("http://example.org/connected".to_string()),
             object: TripleNode::Variable("x".to_string()),
+            graph: None,
         },
     ];
 
@@ -185,11 +201,13 @@ fn example_5_symmetric_structure() -> Result<(), Box<dyn std::error::Error>> {
             subject: TripleNode::Variable("a".to_string()),
             predicate: TripleNode::IRI("http://example.org/connected".to_string()),
             object: TripleNode::Variable("b".to_string()),
+            graph: None,
         },
         Triple {
             subject: TripleNode::Variable("b".to_string()),
             predicate: TripleNode::IRI("http://example.org/connected".to_string()),
             object: TripleNode::Variable("a".to_string()),
+            graph: None,
         },
     ];
 
@@ -210,11 +228,13 @@ fn example_6_with_literals() -> Result<(), Box<dyn std::error::Error>> {
             subject: TripleNode::Variable("person1".to_string()),
             predicate: TripleNode::IRI("http://xmlns.com/foaf/0.1/name".to_string()),
             object: TripleNode::Literal("Alice".to_string()),
+            graph: None,
         },
         Triple {
             subject: TripleNode::Variable("person2".to_string()),
             predicate: TripleNode::IRI("http://xmlns.com/foaf/0.1/name".to_string()),
             object: TripleNode::Literal("Bob".to_string()),
+            graph: None,
         },
     ];
 
@@ -223,11 +243,13 @@ fn example_6_with_literals() -> Result<(), Box<dyn std::error::Error>> {
             subject: TripleNode::Variable("x".to_string()),
             predicate: TripleNode::IRI("http://xmlns.com/foaf/0.1/name".to_string()),
             object: TripleNode::Literal("Alice".to_string()),
+            graph: None,
         },
         Triple {
             subject: TripleNode::Variable("y".to_string()),
             predicate: TripleNode::IRI("http://xmlns.com/foaf/0.1/name".to_string()),
             object: TripleNode::Literal("Bob".to_string()),
+            graph: None,
         },
     ];
 
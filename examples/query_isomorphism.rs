@@ -150,8 +150,7 @@ WHERE {
         Ok(parsed) => {
             println!("Query Language: {:?}", parsed.query_language);
             println!("Number of triples in BGP: {}", parsed.bgp.len());
-            println!("Stream Name: {:?}", parsed.stream_name);
-            println!("Window Name: {:?}", parsed.window_name);
+            println!("Windows: {:?}", parsed.windows);
         }
         Err(e) => println!("Error parsing query: {}", e),
     }
@@ -16,6 +16,15 @@ pub enum TulnaError {
 
     #[error("Invalid input: {0}")]
     InvalidInput(String),
+
+    #[error("Search limit exceeded: {0}")]
+    LimitExceeded(String),
+
+    #[error("Timed out: {0}")]
+    Timeout(String),
+
+    #[error("Unbalanced braces: {0}")]
+    UnbalancedBraces(String),
     
     #[error("Unknown error: {0}")]
     Unknown(String),
@@ -1,5 +1,10 @@
 use crate::isomorphism::core::{IsomorphismQuery, QueryIsomorphism, Triple};
+use crate::parsing::janusql_parser::{JanusQLParser, ParsedJanusQuery};
+use crate::parsing::parsed_rspql_query::ParsedQuery;
+use crate::parsing::rspql_parser::RSPQLParser;
+use crate::parsing::sparql_parser::{ParsedSparqlQuery, SparqlParser};
 use crate::TulnaError;
+use std::fmt;
 
 /// Public API for checking query isomorphism
 ///
@@ -15,6 +20,8 @@ impl QueryIsomorphismAPI {
     /// - Variable renaming (canonicalization)
     /// - Blank node isomorphism
     /// - Stream/Window parameter comparison (for streaming queries)
+    /// - R2S operator comparison (`RStream`, `IStream`, `DStream`)
+    /// - Solution modifier comparison (DISTINCT, REDUCED, LIMIT, OFFSET)
     ///
     /// # Arguments
     ///
@@ -30,6 +37,120 @@ impl QueryIsomorphismAPI {
         QueryIsomorphism::is_isomorphic(query1, query2)
     }
 
+    /// Check if two queries are isomorphic, ignoring their solution modifiers
+    ///
+    /// This compares the queries as `is_isomorphic` does, except that the DISTINCT,
+    /// REDUCED, LIMIT and OFFSET modifiers may differ. Use it when only the graph patterns
+    /// of the queries matter.
+    ///
+    /// # Arguments
+    ///
+    /// * `query1` - First query string
+    /// * `query2` - Second query string
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(true)` - Queries are isomorphic up to their solution modifiers
+    /// * `Ok(false)` - Queries are not isomorphic
+    /// * `Err(_)` - Error parsing or processing queries
+    pub fn is_isomorphic_ignoring_modifiers(
+        query1: &str,
+        query2: &str,
+    ) -> Result<bool, TulnaError> {
+        QueryIsomorphism::is_isomorphic_ignoring_modifiers(query1, query2)
+    }
+
+    /// Check if the graph patterns of two queries are isomorphic
+    ///
+    /// Unlike `is_isomorphic`, this ignores all streaming metadata (windows, streams and R2S
+    /// operators) as well as the query form and solution modifiers, and compares only the
+    /// BGPs with their OPTIONAL groups, UNIONs, FILTERs and VALUES blocks. Use it to group queries by shape,
+    /// for instance RSP-QL queries that differ only in their window widths.
+    ///
+    /// # Arguments
+    ///
+    /// * `query1` - First query string
+    /// * `query2` - Second query string
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(true)` - The graph patterns are isomorphic
+    /// * `Ok(false)` - The graph patterns are not isomorphic
+    /// * `Err(_)` - Error parsing or processing queries
+    pub fn is_bgp_isomorphic(query1: &str, query2: &str) -> Result<bool, TulnaError> {
+        QueryIsomorphism::is_bgp_isomorphic(query1, query2)
+    }
+
+    /// Check if the BGPs of two queries are isomorphic, whatever their languages
+    ///
+    /// This compares the triple patterns returned by `extract_bgp` for both queries, so the
+    /// WHERE clause of a SPARQL query can be matched against the pattern inside the WINDOW
+    /// of an RSP-QL or JanusQL query. Everything else is ignored, including OPTIONAL groups,
+    /// UNIONs and FILTERs; use `is_bgp_isomorphic` to compare those too.
+    ///
+    /// # Arguments
+    ///
+    /// * `query1` - First query string
+    /// * `query2` - Second query string
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(true)` - The BGPs are isomorphic
+    /// * `Ok(false)` - The BGPs are not isomorphic
+    /// * `Err(_)` - Error parsing or processing queries
+    pub fn bgp_equivalent_cross_language(query1: &str, query2: &str) -> Result<bool, TulnaError> {
+        QueryIsomorphism::bgp_equivalent_cross_language(query1, query2)
+    }
+
+    /// Find the candidate queries that are isomorphic to a query
+    ///
+    /// Use this to look up duplicates of a query in a large collection: the query is
+    /// parsed only once, and candidates with different windows or BGP sizes are rejected
+    /// before the graph isomorphism check.
+    ///
+    /// # Arguments
+    ///
+    /// * `query` - The query to look up
+    /// * `candidates` - The queries to compare it against
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(indices)` - Indices into `candidates` of the isomorphic queries, in increasing order
+    /// * `Err(_)` - Error parsing the query or one of the candidates
+    pub fn find_matches(query: &str, candidates: &[&str]) -> Result<Vec<usize>, TulnaError> {
+        QueryIsomorphism::find_matches(query, candidates)
+    }
+
+    /// Check if two queries, provided as raw bytes, are isomorphic
+    ///
+    /// This is a convenience for callers reading queries from files or the network. Both
+    /// inputs are validated as UTF-8 and a leading byte order mark is stripped before
+    /// the queries are compared with `is_isomorphic`.
+    ///
+    /// # Arguments
+    ///
+    /// * `query1` - First query as UTF-8 bytes
+    /// * `query2` - Second query as UTF-8 bytes
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(true)` - Queries are isomorphic
+    /// * `Ok(false)` - Queries are not isomorphic
+    /// * `Err(TulnaError::InvalidInput)` - One of the inputs is not valid UTF-8
+    /// * `Err(_)` - Error parsing or processing queries
+    pub fn is_isomorphic_bytes(query1: &[u8], query2: &[u8]) -> Result<bool, TulnaError> {
+        let query1 = Self::decode_query(query1)?;
+        let query2 = Self::decode_query(query2)?;
+        QueryIsomorphism::is_isomorphic(query1, query2)
+    }
+
+    /// Decode a query from UTF-8 bytes, stripping a leading byte order mark
+    fn decode_query(bytes: &[u8]) -> Result<&str, TulnaError> {
+        let query = std::str::from_utf8(bytes)
+            .map_err(|e| TulnaError::InvalidInput(format!("Query is not valid UTF-8: {}", e)))?;
+        Ok(query.strip_prefix('\u{feff}').unwrap_or(query))
+    }
+
     /// Detect the language of a query
     ///
     /// # Arguments
@@ -40,7 +161,23 @@ impl QueryIsomorphismAPI {
     ///
     /// Detected `QueryLanguage` (SPARQL, RSPQL, or JanusQL)
     pub fn detect_query_language(query: &str) -> crate::isomorphism::core::QueryLanguage {
-        QueryIsomorphism::detect_query_type(query)
+        Self::detect_query_language_detailed(query).0
+    }
+
+    /// Detect the query language and explain the decision
+    ///
+    /// # Arguments
+    ///
+    /// * `query` - Query string
+    ///
+    /// # Returns
+    ///
+    /// The detected `QueryLanguage` together with the keywords or syntax that decided it,
+    /// e.g. `["matched [OFFSET n RANGE n STEP n]"]` for a JanusQL query
+    pub fn detect_query_language_detailed(
+        query: &str,
+    ) -> (crate::isomorphism::core::QueryLanguage, Vec<String>) {
+        QueryIsomorphism::detect_query_type_detailed(query)
     }
 
     /// Extract the Basic Graph Pattern (BGP) from a query
@@ -72,10 +209,144 @@ impl QueryIsomorphismAPI {
         QueryIsomorphism::parse_query(query)
     }
 
+    /// Parse a SPARQL query with the SPARQL parser
+    ///
+    /// Unlike `parse_query`, this keeps the clauses of the query as they are written, such
+    /// as the ORDER BY clause and the LIMIT and OFFSET values.
+    ///
+    /// # Arguments
+    ///
+    /// * `query` - SPARQL query string
+    ///
+    /// # Returns
+    ///
+    /// `ParsedSparqlQuery` structure containing the clauses of the query
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tulna_rs::query::QueryIsomorphismAPI;
+    ///
+    /// let parsed =
+    ///     QueryIsomorphismAPI::parse_sparql("SELECT ?s WHERE { ?s ?p ?o } ORDER BY ?s LIMIT 10")
+    ///         .unwrap();
+    /// assert_eq!(parsed.limit, Some(10));
+    /// assert_eq!(parsed.order_by.as_deref(), Some("?s"));
+    /// ```
+    pub fn parse_sparql(query: &str) -> Result<ParsedSparqlQuery, TulnaError> {
+        SparqlParser::new()?.parse(query)
+    }
+
+    /// Parse an RSP-QL query with the RSP-QL parser
+    ///
+    /// # Arguments
+    ///
+    /// * `query` - RSP-QL query string
+    ///
+    /// # Returns
+    ///
+    /// `ParsedQuery` structure containing the R2S operator, the windows and the SPARQL
+    /// query evaluated over them
+    pub fn parse_rspql(query: &str) -> Result<ParsedQuery, TulnaError> {
+        Ok(RSPQLParser::new(query.to_string()).parse())
+    }
+
+    /// Parse a JanusQL query with the JanusQL parser
+    ///
+    /// Besides the live and historical windows, the result holds the RSP-QL query over the
+    /// live windows and a SPARQL query for every historical window.
+    ///
+    /// # Arguments
+    ///
+    /// * `query` - JanusQL query string
+    ///
+    /// # Returns
+    ///
+    /// `ParsedJanusQuery` structure containing the windows and generated queries
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tulna_rs::query::QueryIsomorphismAPI;
+    ///
+    /// let parsed = QueryIsomorphismAPI::parse_janusql(
+    ///     "SELECT ?s FROM NAMED WINDOW <w> ON STREAM <s> [START 1000 END 2000] \
+    ///      WHERE { WINDOW <w> { ?s ?p ?o . } }",
+    /// )
+    /// .unwrap();
+    /// assert_eq!(parsed.historical_windows[0].start, Some(1000));
+    /// assert_eq!(parsed.sparql_queries.len(), 1);
+    /// ```
+    pub fn parse_janusql(query: &str) -> Result<ParsedJanusQuery, TulnaError> {
+        JanusQLParser::new()?.parse(query)
+    }
+
+    /// Rename the variables of a query to a canonical form
+    ///
+    /// Variables are renamed to `?v0`, `?v1`, ... in the order they first appear, while the
+    /// rest of the query text (formatting, prefixes, comments) is preserved. Two queries that
+    /// only differ in their variable names produce identical output.
+    ///
+    /// # Arguments
+    ///
+    /// * `query` - Query string
+    ///
+    /// # Returns
+    ///
+    /// The query string with canonical variable names
+    pub fn rename_variables_canonical(query: &str) -> Result<String, TulnaError> {
+        QueryIsomorphism::rename_variables_canonical(query)
+    }
+
+    /// Normalize a query to a canonical query string
+    ///
+    /// Unlike `rename_variables_canonical`, the query is parsed and rebuilt: variables are
+    /// numbered in an order derived from the structure of the query rather than their first
+    /// appearance, IRIs are written in full and the triple patterns are sorted. Isomorphic
+    /// queries with the same projection produce identical strings, which makes the result
+    /// usable as a cache key.
+    ///
+    /// # Arguments
+    ///
+    /// * `query` - Query string
+    ///
+    /// # Returns
+    ///
+    /// The canonical query string
+    pub fn normalize_query(query: &str) -> Result<String, TulnaError> {
+        QueryIsomorphism::normalize_query(query)
+    }
+
+    /// Check if two queries are strictly equivalent
+    ///
+    /// Where `is_isomorphic` looks at the structure of the queries, this checks whether they
+    /// are truly interchangeable. On top of BGP isomorphism it requires:
+    /// - The same query form (SELECT, CONSTRUCT, ASK, DESCRIBE)
+    /// - The same projection, matched under the variable renaming
+    /// - The same solution modifiers (DISTINCT, REDUCED, ORDER BY, LIMIT, OFFSET)
+    /// - The same dataset clauses (FROM, FROM NAMED)
+    /// - For streaming queries, the same windows and R2S operator
+    ///
+    /// # Arguments
+    ///
+    /// * `query1` - First query string
+    /// * `query2` - Second query string
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(true)` - Queries are strictly equivalent
+    /// * `Ok(false)` - Queries are not strictly equivalent
+    /// * `Err(_)` - Error parsing or processing queries
+    pub fn is_strictly_equivalent(query1: &str, query2: &str) -> Result<bool, TulnaError> {
+        QueryIsomorphism::is_strictly_equivalent(query1, query2)
+    }
+
     /// Compare two queries and return detailed comparison results
     ///
     /// This provides granular information about why two queries might or might not
-    /// be isomorphic (e.g., same language, same BGP structure, etc.).
+    /// be isomorphic (e.g., same language, same BGP structure, etc.). When they are not
+    /// isomorphic, `mismatch_reason` explains the first difference found, such as
+    /// `stream width 10 != 20`.
     ///
     /// # Arguments
     ///
@@ -97,25 +368,70 @@ impl QueryIsomorphismAPI {
         let q1_parsed = QueryIsomorphism::parse_query(query1)?;
         let q2_parsed = QueryIsomorphism::parse_query(query2)?;
 
-        let bgp1 = &q1_parsed.bgp;
-        let bgp2 = &q2_parsed.bgp;
+        let same_query_form = q1_parsed.query_type == q2_parsed.query_type;
+
+        // Triple patterns written twice count once, as in `find_mismatch`
+        let bgp1 = &QueryIsomorphism::distinct_triples(&q1_parsed.bgp);
+        let bgp2 = &QueryIsomorphism::distinct_triples(&q2_parsed.bgp);
 
         let same_bgp_size = bgp1.len() == bgp2.len();
 
         let bgp_isomorphic = crate::isomorphism::graph_isomorphism::GraphIsomorphism::check_bgp_isomorphism(bgp1, bgp2)?;
 
-        let is_isomorphic = QueryIsomorphism::is_isomorphic(query1, query2)?;
+        let mismatch_reason = QueryIsomorphism::find_mismatch(&q1_parsed, &q2_parsed, true)?;
 
         Ok(QueryComparisonResult {
-            is_isomorphic,
+            is_isomorphic: mismatch_reason.is_none(),
             same_language,
+            same_query_form,
             same_bgp_size,
             bgp_isomorphic,
+            mismatch_reason,
         })
     }
 
+    /// Compare two queries, telling apart queries that differ from queries that do not parse
+    ///
+    /// Gives the same answer as `is_isomorphic`, but never fails: an error while parsing
+    /// either query is reported as `ComparisonOutcome::ParseError` along with the number of
+    /// the query, which suits command-line tools that map the outcome to an exit code.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tulna_rs::query::{ComparisonOutcome, QueryIsomorphismAPI};
+    ///
+    /// let query = "SELECT ?s WHERE { ?s ?p ?o }";
+    /// let outcome = QueryIsomorphismAPI::compare(query, "SELECT ?x WHERE { ?x ?y ?z }");
+    /// assert_eq!(outcome, ComparisonOutcome::Isomorphic);
+    /// assert_eq!(outcome.exit_code(), 0);
+    ///
+    /// let outcome = QueryIsomorphismAPI::compare(query, "not a query");
+    /// assert!(matches!(outcome, ComparisonOutcome::ParseError(2, _)));
+    /// assert_eq!(outcome.exit_code(), 2);
+    /// ```
+    pub fn compare(query1: &str, query2: &str) -> ComparisonOutcome {
+        let q1 = match QueryIsomorphism::parse_query(query1) {
+            Ok(query) => query,
+            Err(error) => return ComparisonOutcome::ParseError(1, error.to_string()),
+        };
+        let q2 = match QueryIsomorphism::parse_query(query2) {
+            Ok(query) => query,
+            Err(error) => return ComparisonOutcome::ParseError(2, error.to_string()),
+        };
+
+        match QueryIsomorphism::find_mismatch(&q1, &q2, true) {
+            Ok(None) => ComparisonOutcome::Isomorphic,
+            Ok(Some(reason)) => ComparisonOutcome::NotIsomorphic(reason),
+            Err(error) => ComparisonOutcome::Error(error.to_string()),
+        }
+    }
+
     /// Check if stream parameters match between two queries
     ///
+    /// The windows of both queries are matched as a set: every window must be declared in
+    /// the other query with the same name, stream and parameters, in any order.
+    ///
     /// # Arguments
     ///
     /// * `query1` - First query string
@@ -127,16 +443,7 @@ impl QueryIsomorphismAPI {
     pub fn check_stream_parameters(query1: &str, query2: &str) -> Result<bool, TulnaError> {
         let q1 = QueryIsomorphism::parse_query(query1)?;
         let q2 = QueryIsomorphism::parse_query(query2)?;
-
-        let params_match = q1.stream_name == q2.stream_name
-            && q1.window_name == q2.window_name
-            && q1.width == q2.width
-            && q1.slide == q2.slide
-            && q1.offset == q2.offset
-            && q1.start == q2.start
-            && q1.end == q2.end;
-
-        Ok(params_match)
+        Ok(QueryIsomorphism::check_windows_equal(&q1, &q2))
     }
 
     /// Check if window names match between two queries
@@ -152,25 +459,79 @@ impl QueryIsomorphismAPI {
     pub fn check_window_names(query1: &str, query2: &str) -> Result<bool, TulnaError> {
         let q1 = QueryIsomorphism::parse_query(query1)?;
         let q2 = QueryIsomorphism::parse_query(query2)?;
-        Ok(q1.window_name == q2.window_name)
+        Ok(QueryIsomorphism::check_window_names_equal(&q1, &q2))
     }
 }
 
 /// Detailed comparison result
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct QueryComparisonResult {
     pub is_isomorphic: bool,
     pub same_language: bool,
+    /// Whether both queries use the same query form (SELECT, CONSTRUCT, ASK, DESCRIBE)
+    pub same_query_form: bool,
     pub same_bgp_size: bool,
     pub bgp_isomorphic: bool,
+    /// Explanation of the first difference that keeps the queries from being isomorphic,
+    /// `None` when they are isomorphic
+    pub mismatch_reason: Option<String>,
 }
 
 impl QueryComparisonResult {
     pub fn summary(&self) -> String {
-        format!(
-            "Isomorphic: {}, Same Language: {}, Same BGP Size: {}, BGP Isomorphic: {}",
-            self.is_isomorphic, self.same_language, self.same_bgp_size, self.bgp_isomorphic
-        )
+        let mut summary = format!(
+            "Isomorphic: {}, Same Language: {}, Same Query Form: {}, Same BGP Size: {}, BGP Isomorphic: {}",
+            self.is_isomorphic,
+            self.same_language,
+            self.same_query_form,
+            self.same_bgp_size,
+            self.bgp_isomorphic
+        );
+        if let Some(reason) = &self.mismatch_reason {
+            summary.push_str(&format!(", Mismatch: {}", reason));
+        }
+        summary
+    }
+}
+
+/// Outcome of `QueryIsomorphismAPI::compare`
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ComparisonOutcome {
+    /// The queries are isomorphic
+    Isomorphic,
+    /// The queries are not isomorphic, with the first difference found
+    NotIsomorphic(String),
+    /// The query with the given number, 1 or 2, could not be parsed
+    ParseError(usize, String),
+    /// Both queries were parsed but could not be compared, e.g. because the search for a
+    /// bijection exceeded its limit
+    Error(String),
+}
+
+impl ComparisonOutcome {
+    /// Exit code for a command-line tool, following `diff`: 0 for isomorphic queries, 1 for
+    /// queries that are not, and 2 when they could not be compared
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            ComparisonOutcome::Isomorphic => 0,
+            ComparisonOutcome::NotIsomorphic(_) => 1,
+            ComparisonOutcome::ParseError(..) | ComparisonOutcome::Error(_) => 2,
+        }
+    }
+}
+
+impl fmt::Display for ComparisonOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ComparisonOutcome::Isomorphic => write!(f, "isomorphic"),
+            ComparisonOutcome::NotIsomorphic(reason) => write!(f, "not isomorphic: {}", reason),
+            ComparisonOutcome::ParseError(query, message) => {
+                write!(f, "query {} could not be parsed: {}", query, message)
+            }
+            ComparisonOutcome::Error(message) => write!(f, "comparison failed: {}", message),
+        }
     }
 }
 
@@ -187,6 +548,22 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_detect_language_detailed() {
+        let janusql = "SELECT ?s FROM NAMED WINDOW :w ON STREAM :s [OFFSET 100 RANGE 10 STEP 5] WHERE { ?s ?p ?o }";
+        let (language, reasons) = QueryIsomorphismAPI::detect_query_language_detailed(janusql);
+        assert_eq!(language, crate::isomorphism::core::QueryLanguage::JanusQL);
+        assert_eq!(
+            reasons,
+            vec!["matched [OFFSET n RANGE n STEP n]".to_string()]
+        );
+
+        let (language, reasons) =
+            QueryIsomorphismAPI::detect_query_language_detailed("SELECT * WHERE { ?s ?p ?o }");
+        assert_eq!(language, crate::isomorphism::core::QueryLanguage::SPARQL);
+        assert_eq!(reasons.len(), 1);
+    }
+
     #[test]
     fn test_extract_bgp() {
         let query = "SELECT * WHERE { ?s <http://p> ?o }";
@@ -208,7 +585,110 @@ mod tests {
         let result = QueryIsomorphismAPI::compare_queries(q1, q2).unwrap();
         assert!(result.is_isomorphic);
         assert!(result.same_language);
+        assert!(result.same_query_form);
         assert!(result.same_bgp_size);
         assert!(result.bgp_isomorphic);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_rename_variables_canonical() {
+        let q1 = "SELECT ?name WHERE { ?person <http://ex.org/name?x=1> ?name . FILTER(?name != \"?no\") }";
+        let q2 = "SELECT $n WHERE { ?p <http://ex.org/name?x=1> ?n . FILTER(?n != \"?no\") }";
+
+        let renamed1 = QueryIsomorphismAPI::rename_variables_canonical(q1).unwrap();
+        let renamed2 = QueryIsomorphismAPI::rename_variables_canonical(q2).unwrap();
+        assert_eq!(renamed1, renamed2);
+        assert_eq!(
+            renamed1,
+            "SELECT ?v0 WHERE { ?v1 <http://ex.org/name?x=1> ?v0 . FILTER(?v0 != \"?no\") }"
+        );
+    }
+
+    #[test]
+    fn test_normalize_query() {
+        let q1 = r#"PREFIX ex: <http://ex.org/>
+SELECT ?name WHERE {
+  ?person ex:name ?name .
+  ?person ex:knows ?friend .
+  OPTIONAL { ?friend ex:age ?age }
+  FILTER(?name != "x")
+}
+LIMIT 10"#;
+        let q2 = r#"SELECT ?n WHERE {
+  ?p <http://ex.org/knows> ?f .
+  FILTER(?n != "x")
+  ?p <http://ex.org/name> ?n .
+  OPTIONAL { ?f <http://ex.org/age> ?a }
+}
+LIMIT 10"#;
+
+        let normalized1 = QueryIsomorphismAPI::normalize_query(q1).unwrap();
+        let normalized2 = QueryIsomorphismAPI::normalize_query(q2).unwrap();
+        assert_eq!(normalized1, normalized2);
+        assert!(!normalized1.contains("?name"));
+        assert!(normalized1.contains("OPTIONAL {"));
+        assert!(normalized1.ends_with("LIMIT 10"));
+
+        let q3 = "SELECT ?n WHERE { ?p <http://ex.org/knows> ?n . ?p <http://ex.org/name> ?n }";
+        assert_ne!(
+            QueryIsomorphismAPI::normalize_query(q3).unwrap(),
+            normalized1
+        );
+    }
+
+    #[test]
+    fn test_is_isomorphic_bytes_with_bom() {
+        let q1 = "\u{feff}SELECT ?s WHERE { ?s <http://p> ?o }".as_bytes();
+        let q2 = b"SELECT ?x WHERE { ?x <http://p> ?y }";
+        assert!(QueryIsomorphismAPI::is_isomorphic_bytes(q1, q2).unwrap());
+    }
+
+    #[test]
+    fn test_is_isomorphic_bytes_invalid_utf8() {
+        let q1 = b"SELECT ?s WHERE { ?s <http://p> ?o }";
+        let q2 = b"SELECT ?s WHERE { ?s <http://p> \xff }";
+        let result = QueryIsomorphismAPI::is_isomorphic_bytes(q1, q2);
+        assert!(matches!(result, Err(TulnaError::InvalidInput(_))));
+    }
+
+    #[test]
+    fn test_compare_outcomes() {
+        let query = "SELECT ?s WHERE { ?s <http://ex.org/p> ?o }";
+
+        let outcome =
+            QueryIsomorphismAPI::compare(query, "SELECT ?x WHERE { ?x <http://ex.org/p> ?y }");
+        assert_eq!(outcome, ComparisonOutcome::Isomorphic);
+        assert_eq!(outcome.exit_code(), 0);
+        assert_eq!(outcome.to_string(), "isomorphic");
+
+        let outcome =
+            QueryIsomorphismAPI::compare(query, "SELECT ?x WHERE { ?x <http://ex.org/q> ?y }");
+        let ComparisonOutcome::NotIsomorphic(reason) = &outcome else {
+            panic!("expected NotIsomorphic, got {:?}", outcome);
+        };
+        assert!(reason.starts_with("BGP not isomorphic"), "{}", reason);
+        assert_eq!(outcome.exit_code(), 1);
+
+        let outcome = QueryIsomorphismAPI::compare("SELECT ?s WHERE { ?s ?p ?o", query);
+        assert!(
+            matches!(outcome, ComparisonOutcome::ParseError(1, _)),
+            "{:?}",
+            outcome
+        );
+        assert_eq!(outcome.exit_code(), 2);
+        assert!(outcome
+            .to_string()
+            .starts_with("query 1 could not be parsed"));
+
+        let outcome = QueryIsomorphismAPI::compare(query, "DELETE WHERE { ?s ?p ?o }");
+        assert!(
+            matches!(outcome, ComparisonOutcome::ParseError(2, _)),
+            "{:?}",
+            outcome
+        );
+
+        // The first query is parsed first, so its error is the one reported
+        let outcome = QueryIsomorphismAPI::compare("not a query", "not a query either");
+        assert!(matches!(outcome, ComparisonOutcome::ParseError(1, _)));
+    }
+}
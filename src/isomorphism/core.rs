@@ -1,45 +1,506 @@
-use crate::parsing::janusql_parser::JanusQLParser;
+use crate::isomorphism::graph_isomorphism::IsoResult;
+use crate::parsing::janusql_parser::{JanusQLParser, WindowType};
 use crate::parsing::rspql_parser::RSPQLParser;
-use crate::parsing::sparql_parser::SparqlParser;
+use crate::parsing::sparql_parser::{ParsedSparqlQuery, QueryType, SparqlParser};
+use crate::parsing::{resolve_iri, strip_comments, unify_variable_markers};
 use crate::TulnaError;
+use regex::Regex;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
+use std::sync::LazyLock;
+
+/// Namespace for the IRIs used to encode query parts (projection, ORDER BY, ...) as triples
+const ENCODING_NAMESPACE: &str = "urn:tulna:";
+
+/// Prefix of the labels of the blank nodes that stand for parts of a query, such as
+/// anonymous nodes and collections, or the OPTIONAL groups in the graphs built to compare
+/// queries
+///
+/// `#` starts a comment in a query, so no blank node label read from a query can hold it.
+const FRESH_LABEL_PREFIX: &str = "tulna#";
+
+/// IRI of `rdf:type`, abbreviated as `a` in triple patterns
+pub(crate) const RDF_TYPE: &str = "http://www.w3.org/1999/02/22-rdf-syntax-ns#type";
+
+/// IRIs of `rdf:first`, `rdf:rest` and `rdf:nil`, which collections such as `( :a :b )` expand to
+pub(crate) const RDF_FIRST: &str = "http://www.w3.org/1999/02/22-rdf-syntax-ns#first";
+pub(crate) const RDF_REST: &str = "http://www.w3.org/1999/02/22-rdf-syntax-ns#rest";
+pub(crate) const RDF_NIL: &str = "http://www.w3.org/1999/02/22-rdf-syntax-ns#nil";
+
+/// Namespace of the XML Schema datatypes given to numbers and booleans written without quotes
+const XSD_NAMESPACE: &str = "http://www.w3.org/2001/XMLSchema#";
+
+/// Bracketed specification of a JanusQL historical window, `[OFFSET n RANGE n STEP n]` or
+/// `[START n END n]`
+static HISTORICAL_WINDOW: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?i)\[\s*(OFFSET\s+\S+\s+RANGE\s+\S+\s+STEP\s+\S+|START\s+\S+\s+END\s+\S+)\s*\]")
+        .unwrap()
+});
+
+/// Window declaration, capturing the parameters between its brackets
+static WINDOW_DECLARATION: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?i)FROM\s+NAMED\s+WINDOW\s+[^\[\n]*\[([^\]]*)\]").unwrap());
 
 /// Supported query types for isomorphism checking
+///
+/// The enum is `#[non_exhaustive]`: more query languages may be supported in a minor
+/// release, so a `match` outside this crate needs a wildcard arm.
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
 pub enum QueryLanguage {
     SPARQL,
     RSPQL,
     JanusQL,
 }
 
+/// Formats the language under its usual name: `SPARQL`, `RSP-QL` or `JanusQL`
+///
+/// ```rust
+/// use tulna_rs::query::QueryLanguage;
+///
+/// assert_eq!(QueryLanguage::RSPQL.to_string(), "RSP-QL");
+/// assert_eq!("rsp-ql".parse::<QueryLanguage>().unwrap(), QueryLanguage::RSPQL);
+/// ```
+impl fmt::Display for QueryLanguage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            QueryLanguage::SPARQL => write!(f, "SPARQL"),
+            QueryLanguage::RSPQL => write!(f, "RSP-QL"),
+            QueryLanguage::JanusQL => write!(f, "JanusQL"),
+        }
+    }
+}
+
+/// Parses a language name as written by `Display`, ignoring case
+///
+/// `RSPQL` is accepted as well, as it is how the variant is spelled.
+impl std::str::FromStr for QueryLanguage {
+    type Err = TulnaError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "sparql" => Ok(QueryLanguage::SPARQL),
+            "rsp-ql" | "rspql" => Ok(QueryLanguage::RSPQL),
+            "janusql" => Ok(QueryLanguage::JanusQL),
+            _ => Err(TulnaError::InvalidInput(format!(
+                "unknown query language: {}",
+                s
+            ))),
+        }
+    }
+}
+
 /// A simple triple representation for BGP extraction
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Triple {
     pub subject: TripleNode,
     pub predicate: TripleNode,
     pub object: TripleNode,
+    /// Named graph holding the triple, `None` for the default graph
+    pub graph: Option<TripleNode>,
 }
 
 /// Node types in a triple
+///
+/// With the `serde` feature, a node is serialized with its kind next to its value, e.g.
+/// `{"kind":"variable","value":"x"}` or
+/// `{"kind":"lang_literal","value":{"value":"hello","lang":"en"}}`.
+///
+/// The enum is `#[non_exhaustive]`: new kinds of nodes may be added in a minor release, so a
+/// `match` outside this crate needs a wildcard arm.
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "serde",
+    serde(tag = "kind", content = "value", rename_all = "snake_case")
+)]
 pub enum TripleNode {
+    #[cfg_attr(feature = "serde", serde(rename = "iri"))]
     IRI(String),
     Variable(String),
     Literal(String),
+    /// A literal with an explicit datatype, e.g. `"30"^^xsd:integer`
+    TypedLiteral {
+        value: String,
+        datatype: String,
+    },
+    /// A literal with a language tag, e.g. `"hello"@en`
+    LangLiteral {
+        value: String,
+        lang: String,
+    },
     BlankNode(String),
+    /// A property path in predicate position, e.g. `foaf:knows+`, kept as its expression
+    /// with every IRI written in full, such as `<http://xmlns.com/foaf/0.1/knows>+`
+    PropertyPath(String),
+}
+
+impl TripleNode {
+    /// Create a variable node, named without the leading `?`
+    pub fn var(name: impl Into<String>) -> Self {
+        TripleNode::Variable(name.into())
+    }
+
+    /// Create an IRI node, written without angle brackets
+    pub fn iri(iri: impl Into<String>) -> Self {
+        TripleNode::IRI(iri.into())
+    }
+
+    /// Create a plain literal node, written without quotes
+    pub fn lit(value: impl Into<String>) -> Self {
+        TripleNode::Literal(value.into())
+    }
+
+    /// Check if this node is a literal, with or without a datatype or language tag
+    pub fn is_literal(&self) -> bool {
+        matches!(
+            self,
+            TripleNode::Literal(_)
+                | TripleNode::TypedLiteral { .. }
+                | TripleNode::LangLiteral { .. }
+        )
+    }
+}
+
+/// Reads a node written in query syntax
+///
+/// `?x` is a variable, `<iri>` an IRI, `"value"` a literal (optionally with `@lang` or
+/// `^^<datatype>`) and `_:b` a blank node. Numbers and booleans written without quotes, such
+/// as `42` or `true`, are typed literals. Other text, such as `http://example.org/a`, is
+/// taken as an IRI.
+impl From<&str> for TripleNode {
+    fn from(node: &str) -> Self {
+        QueryIsomorphism::parse_node(node, &HashMap::new())
+    }
+}
+
+impl From<String> for TripleNode {
+    fn from(node: String) -> Self {
+        TripleNode::from(node.as_str())
+    }
+}
+
+/// Formats the node in N-Triples style: `<iri>`, `?var`, `"literal"` or `_:blank`
+///
+/// Property paths are not part of N-Triples and are written as SPARQL path expressions.
+impl fmt::Display for TripleNode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TripleNode::IRI(iri) => write!(f, "<{}>", iri),
+            TripleNode::Variable(var) => write!(f, "?{}", var),
+            TripleNode::Literal(lit) => write!(f, "\"{}\"", lit),
+            TripleNode::TypedLiteral { value, datatype } => {
+                write!(f, "\"{}\"^^<{}>", value, datatype)
+            }
+            TripleNode::LangLiteral { value, lang } => write!(f, "\"{}\"@{}", value, lang),
+            TripleNode::BlankNode(id) => write!(f, "_:{}", id),
+            TripleNode::PropertyPath(path) => write!(f, "{}", path),
+        }
+    }
+}
+
+/// Formats the triple as an N-Triples statement
+///
+/// ```rust
+/// use tulna_rs::graph::Triple;
+///
+/// let triple = Triple::new("?person", "<http://xmlns.com/foaf/0.1/name>", "\"Alice\"@en");
+/// assert_eq!(
+///     triple.to_string(),
+///     "?person <http://xmlns.com/foaf/0.1/name> \"Alice\"@en ."
+/// );
+/// ```
+impl fmt::Display for Triple {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {} {}", self.subject, self.predicate, self.object)?;
+        if let Some(graph) = &self.graph {
+            write!(f, " {}", graph)?;
+        }
+        write!(f, " .")
+    }
+}
+
+impl Triple {
+    /// Create a triple from its subject, predicate and object
+    ///
+    /// ```rust
+    /// use tulna_rs::graph::{Triple, TripleNode};
+    ///
+    /// let triple = Triple::new("?person", "<http://xmlns.com/foaf/0.1/name>", "\"Alice\"");
+    /// assert_eq!(triple.subject, TripleNode::var("person"));
+    /// assert_eq!(triple.predicate, TripleNode::iri("http://xmlns.com/foaf/0.1/name"));
+    /// assert_eq!(triple.object, TripleNode::lit("Alice"));
+    /// ```
+    pub fn new(
+        subject: impl Into<TripleNode>,
+        predicate: impl Into<TripleNode>,
+        object: impl Into<TripleNode>,
+    ) -> Self {
+        Triple {
+            subject: subject.into(),
+            predicate: predicate.into(),
+            object: object.into(),
+            graph: None,
+        }
+    }
+
+    /// Place this triple in a named graph
+    ///
+    /// ```rust
+    /// use tulna_rs::graph::{Triple, TripleNode};
+    ///
+    /// let triple = Triple::new("?s", "<http://example.org/p>", "?o").with_graph("?g");
+    /// assert_eq!(triple.graph, Some(TripleNode::var("g")));
+    /// assert_eq!(triple.to_string(), "?s <http://example.org/p> ?o ?g .");
+    /// ```
+    pub fn with_graph(mut self, graph: impl Into<TripleNode>) -> Self {
+        self.graph = Some(graph.into());
+        self
+    }
+
+    /// Returns the subject, predicate, object and, if any, the graph name of this triple
+    fn nodes(&self) -> impl Iterator<Item = &TripleNode> {
+        [&self.subject, &self.predicate, &self.object]
+            .into_iter()
+            .chain(self.graph.as_ref())
+    }
+
+    /// Check if any position of this triple holds a variable
+    pub fn has_variable(&self) -> bool {
+        self.nodes()
+            .any(|node| matches!(node, TripleNode::Variable(_)))
+    }
+
+    /// Check if any position of this triple holds a blank node
+    pub fn has_blank_node(&self) -> bool {
+        self.nodes()
+            .any(|node| matches!(node, TripleNode::BlankNode(_)))
+    }
+
+    /// Check if any position of this triple holds a literal
+    pub fn has_literal(&self) -> bool {
+        self.nodes().any(|node| node.is_literal())
+    }
+
+    /// Check if any position of this triple holds an IRI
+    pub fn has_iri(&self) -> bool {
+        self.nodes().any(|node| matches!(node, TripleNode::IRI(_)))
+    }
+}
+
+/// Solution modifiers applied to the results of a query
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SolutionModifiers {
+    /// DISTINCT flag
+    pub distinct: bool,
+    /// REDUCED flag
+    pub reduced: bool,
+    /// GROUP BY clause
+    pub group_by: Option<String>,
+    /// Whether the projection uses an aggregate function such as `COUNT`
+    pub has_aggregates: bool,
+    /// ORDER BY clause
+    pub order_by: Option<String>,
+    /// LIMIT value
+    pub limit: Option<u64>,
+    /// OFFSET value
+    pub offset: Option<u64>,
+}
+
+/// Inline data bound by a `VALUES` block
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct InlineData {
+    /// Names of the bound variables, without the leading `?`
+    pub variables: Vec<String>,
+    /// Data rows, with one value per variable; `None` stands for `UNDEF`
+    pub rows: Vec<Vec<Option<TripleNode>>>,
+}
+
+/// A window declared by a streaming query with `FROM NAMED WINDOW`
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct StreamWindow {
+    pub window_name: String,
+    pub stream_name: String,
+    pub width: i64,
+    pub slide: i64,
+    /// Offset of a historical sliding window
+    pub offset: Option<u64>,
+    /// Start time of a historical fixed window
+    pub start: Option<u64>,
+    /// End time of a historical fixed window
+    pub end: Option<u64>,
+    /// Whether the window is live or historical; RSP-QL windows are always live
+    pub window_type: WindowType,
+}
+
+impl StreamWindow {
+    /// The window parameters without its name, which is local to the query
+    fn parameters(&self) -> (&str, i64, i64, Option<u64>, Option<u64>, Option<u64>) {
+        (
+            &self.stream_name,
+            self.width,
+            self.slide,
+            self.offset,
+            self.start,
+            self.end,
+        )
+    }
 }
 
 /// Result of parsing a query for isomorphism checking
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct IsomorphismQuery {
     pub query_language: QueryLanguage,
     pub bgp: Vec<Triple>,
-    pub stream_name: Option<String>,
-    pub window_name: Option<String>,
-    pub width: Option<i64>,
-    pub slide: Option<i64>,
-    pub offset: Option<u64>,
-    pub start: Option<u64>,
-    pub end: Option<u64>,
+    /// Triple patterns of the CONSTRUCT template, empty for other query forms
+    pub construct_template: Vec<Triple>,
+    /// Expressions of the `FILTER` constraints in the WHERE clause, outside the OPTIONAL
+    /// groups and UNION branches
+    pub filters: Vec<String>,
+    /// Triple patterns of each `OPTIONAL` group, kept apart from the required BGP
+    pub optional_bgps: Vec<Vec<Triple>>,
+    /// Expressions of the `FILTER` constraints of each `OPTIONAL` group, in the order of
+    /// `optional_bgps`
+    pub optional_filters: Vec<Vec<String>>,
+    /// Alternatives of each `UNION`, with the triple patterns of every branch
+    pub union_bgps: Vec<Vec<Vec<Triple>>>,
+    /// Expressions of the `FILTER` constraints of every branch of each `UNION`, in the
+    /// order of `union_bgps`
+    pub union_filters: Vec<Vec<Vec<String>>>,
+    /// Inline data of the `VALUES` blocks in the WHERE clause
+    pub values: Vec<InlineData>,
+    /// Windows declared by a streaming query, in declaration order
+    pub windows: Vec<StreamWindow>,
+    /// Default graph IRIs declared with `FROM`
+    pub default_graphs: Vec<String>,
+    /// Named graph IRIs declared with `FROM NAMED`
+    pub named_graphs: Vec<String>,
+    /// Query form (SELECT, CONSTRUCT, ASK, DESCRIBE)
+    pub query_type: QueryType,
+    /// Projected variables and expressions, or `*`
+    pub projection: Vec<String>,
+    /// Resources named by a DESCRIBE query, empty for other query forms
+    pub describe_targets: Vec<TripleNode>,
+    /// Solution modifiers (DISTINCT, REDUCED, GROUP BY, ORDER BY, LIMIT, OFFSET)
+    pub modifiers: SolutionModifiers,
+    /// R2S operator (`RStream`, `IStream`, `DStream`) of a registered streaming query
+    pub r2s_operator: Option<String>,
+    /// IRI of the output stream named by the `REGISTER` clause of a streaming query
+    ///
+    /// Isomorphism checks ignore it, as it only names where the results are sent.
+    pub output_stream: Option<String>,
+}
+
+impl IsomorphismQuery {
+    /// The type of the windows of a streaming query
+    ///
+    /// Returns `None` for a query without windows, and for a JanusQL query that mixes live
+    /// and historical windows; the type of each window is then found in `windows`.
+    pub fn window_type(&self) -> Option<WindowType> {
+        let first = self.windows.first()?.window_type;
+        self.windows
+            .iter()
+            .all(|window| window.window_type == first)
+            .then_some(first)
+    }
+
+    /// The triples of the BGP grouped by graph, in order of first appearance
+    ///
+    /// Triples of the default graph are grouped under `None`, those of a `GRAPH` or
+    /// `WINDOW` block under its name, which is an IRI or a variable.
+    pub fn graph_bgps(&self) -> Vec<(Option<TripleNode>, Vec<Triple>)> {
+        let mut groups: Vec<(Option<TripleNode>, Vec<Triple>)> = Vec::new();
+        for triple in &self.bgp {
+            match groups.iter_mut().find(|(graph, _)| *graph == triple.graph) {
+                Some((_, triples)) => triples.push(triple.clone()),
+                None => groups.push((triple.graph.clone(), vec![triple.clone()])),
+            }
+        }
+        groups
+    }
+}
+
+/// Structural equality of parsed queries
+///
+/// The triple patterns of the BGP, of the CONSTRUCT template, of each OPTIONAL group and of
+/// each UNION branch, the FILTER expressions and the VALUES blocks are compared as sets, since
+/// the order in which they are written is irrelevant, and so are the declared windows and
+/// dataset clauses. The projection is compared in order, as it fixes the result columns.
+/// Variables are compared by name: use `QueryIsomorphism::is_isomorphic` to compare queries
+/// up to variable renaming.
+impl PartialEq for IsomorphismQuery {
+    fn eq(&self, other: &Self) -> bool {
+        fn as_set<T: Eq + std::hash::Hash>(items: &[T]) -> HashSet<&T> {
+            items.iter().collect()
+        }
+        fn same_groups<T: Eq + std::hash::Hash>(groups1: &[Vec<T>], groups2: &[Vec<T>]) -> bool {
+            let contains = |groups: &[Vec<T>], group: &[T]| {
+                groups.iter().any(|other| as_set(other) == as_set(group))
+            };
+            groups1.len() == groups2.len()
+                && groups1.iter().all(|group| contains(groups2, group))
+                && groups2.iter().all(|group| contains(groups1, group))
+        }
+        fn same_unions<T: Eq + std::hash::Hash>(
+            unions1: &[Vec<Vec<T>>],
+            unions2: &[Vec<Vec<T>>],
+        ) -> bool {
+            let contains = |unions: &[Vec<Vec<T>>], union: &[Vec<T>]| {
+                unions.iter().any(|other| same_groups(other, union))
+            };
+            unions1.len() == unions2.len()
+                && unions1.iter().all(|union| contains(unions2, union))
+                && unions2.iter().all(|union| contains(unions1, union))
+        }
+
+        let mut windows1 = self.windows.clone();
+        let mut windows2 = other.windows.clone();
+        windows1.sort();
+        windows2.sort();
+
+        self.query_language == other.query_language
+            && as_set(&self.bgp) == as_set(&other.bgp)
+            && as_set(&self.construct_template) == as_set(&other.construct_template)
+            && as_set(&self.filters) == as_set(&other.filters)
+            && same_groups(&self.optional_bgps, &other.optional_bgps)
+            && same_groups(&self.optional_filters, &other.optional_filters)
+            && same_unions(&self.union_bgps, &other.union_bgps)
+            && same_unions(&self.union_filters, &other.union_filters)
+            && as_set(&self.values) == as_set(&other.values)
+            && windows1 == windows2
+            && as_set(&self.default_graphs) == as_set(&other.default_graphs)
+            && as_set(&self.named_graphs) == as_set(&other.named_graphs)
+            && self.query_type == other.query_type
+            && self.projection == other.projection
+            && as_set(&self.describe_targets) == as_set(&other.describe_targets)
+            && self.modifiers == other.modifiers
+            && self.r2s_operator == other.r2s_operator
+            && self.output_stream == other.output_stream
+    }
+}
+
+/// Source of fresh blank node labels
+///
+/// One source is shared by all groups of a query, so that the anonymous nodes of an
+/// OPTIONAL group or a UNION branch never get the label of a node elsewhere in the query.
+#[derive(Debug, Default)]
+pub(crate) struct FreshLabels {
+    count: usize,
+}
+
+impl FreshLabels {
+    /// Return a blank node label, with its `_:`, that was not returned before
+    pub(crate) fn next(&mut self, kind: &str) -> String {
+        self.count += 1;
+        format!("_:{}{}_{}", FRESH_LABEL_PREFIX, kind, self.count - 1)
+    }
 }
 
 /// Main API for checking query isomorphism
@@ -50,23 +511,46 @@ impl QueryIsomorphism {
     ///
     /// JanusQL is an extension of RSP-QL that adds support for historical windows.
     /// Detection priority:
-    /// 1. JanusQL - if historical window keywords are present (OFFSET, START, END)
+    /// 1. JanusQL - if a historical window specification is present
+    ///    (`[OFFSET n RANGE n STEP n]` or `[START n END n]`)
     /// 2. RSP-QL - if streaming keywords are present (REGISTER, STREAM, or window syntax)
     /// 3. SPARQL - default for standard queries
     pub fn detect_query_type(query: &str) -> QueryLanguage {
+        Self::detect_query_type_detailed(query).0
+    }
+
+    /// Detect the query language type, along with the reasons for the decision
+    ///
+    /// The reasons name the syntax that matched, e.g. `matched [OFFSET n RANGE n STEP n]`
+    /// for a JanusQL query, which helps to debug why a query was routed to a parser.
+    pub fn detect_query_type_detailed(query: &str) -> (QueryLanguage, Vec<String>) {
+        let query = &strip_comments(query);
         let upper = query.to_uppercase();
 
         // JanusQL extends RSP-QL with historical windows
-        // Check for JanusQL-specific keywords (OFFSET with sliding window, or START/END for fixed window)
-        if (upper.contains("OFFSET") && upper.contains("RANGE") && upper.contains("STEP"))
-            || (upper.contains("START") && upper.contains("END"))
-        {
-            return QueryLanguage::JanusQL;
+        // Only the bracketed window specifications count, so that a LIMIT/OFFSET modifier
+        // or variables such as ?start and ?end do not make a SPARQL query look like JanusQL
+        let mut reasons = Vec::new();
+        for captures in HISTORICAL_WINDOW.captures_iter(query) {
+            let reason = if captures[1].to_uppercase().starts_with("OFFSET") {
+                "matched [OFFSET n RANGE n STEP n]"
+            } else {
+                "matched [START n END n]"
+            };
+            if !reasons.iter().any(|existing| existing == reason) {
+                reasons.push(reason.to_string());
+            }
+        }
+        if !reasons.is_empty() {
+            return (QueryLanguage::JanusQL, reasons);
         }
 
         // RSP-QL queries with REGISTER operator (can also be JanusQL if historical keywords present)
         if upper.contains("REGISTER") && upper.contains("STREAM") {
-            return QueryLanguage::RSPQL;
+            return (
+                QueryLanguage::RSPQL,
+                vec!["found REGISTER and STREAM keywords".to_string()],
+            );
         }
 
         // RSP-QL queries without REGISTER (direct window syntax)
@@ -75,15 +559,23 @@ impl QueryIsomorphism {
             && upper.contains("WINDOW")
             && upper.contains("ON STREAM")
         {
-            return QueryLanguage::RSPQL;
+            return (
+                QueryLanguage::RSPQL,
+                vec!["found FROM NAMED WINDOW ... ON STREAM".to_string()],
+            );
         }
 
         // Standard SPARQL queries
-        QueryLanguage::SPARQL
+        (
+            QueryLanguage::SPARQL,
+            vec!["no streaming or historical window syntax found".to_string()],
+        )
     }
 
     /// Parse a query based on its detected type
     pub fn parse_query(query: &str) -> Result<IsomorphismQuery, TulnaError> {
+        Self::validate_brackets(query)?;
+        let query = &unify_variable_markers(&strip_comments(query));
         let query_type = Self::detect_query_type(query);
 
         match query_type {
@@ -95,133 +587,530 @@ impl QueryIsomorphism {
 
     /// Parse a SPARQL query
     fn parse_sparql(query: &str) -> Result<IsomorphismQuery, TulnaError> {
-        let parser = SparqlParser::new().map_err(|e| TulnaError::ParseError(e.to_string()))?;
-        let parsed = parser
-            .parse(query)
-            .map_err(|e| TulnaError::ParseError(e.to_string()))?;
-        let bgp = Self::extract_bgp_from_where(&parsed.where_clause)?;
+        let parser = SparqlParser::new()?;
+        let parsed = parser.parse(query)?;
+        let (where_clause, values) = Self::split_values(&parsed.where_clause);
+        let (where_clause, optional_groups) = Self::split_optional_groups(&where_clause);
+        let (where_clause, unions) = Self::split_unions(&where_clause);
+        let (where_clause, filters) = Self::split_filters(&where_clause, &parsed.prefixes);
+        let (optional_groups, optional_filters) =
+            Self::split_group_filters(&optional_groups, &parsed.prefixes);
+        let (unions, union_filters): (Vec<_>, Vec<_>) = unions
+            .iter()
+            .map(|branches| Self::split_group_filters(branches, &parsed.prefixes))
+            .unzip();
+        let labels = &mut FreshLabels::default();
+        let bgp = Self::extract_bgp_from_where(&where_clause, &parsed.prefixes, labels)?;
+        let optional_bgps = Self::extract_group_bgps(&optional_groups, &parsed.prefixes, labels)?;
+        let union_bgps = unions
+            .iter()
+            .map(|branches| Self::extract_group_bgps(branches, &parsed.prefixes, labels))
+            .collect::<Result<_, _>>()?;
+        let values = Self::extract_values(&values, &parsed.prefixes)?;
+        let construct_template = Self::extract_construct_template(&parsed, labels)?;
+        let (projection, describe_targets) = Self::split_projection(&parsed);
+        let base = parsed.base.clone();
 
-        Ok(IsomorphismQuery {
+        let mut query = IsomorphismQuery {
             query_language: QueryLanguage::SPARQL,
             bgp,
-            stream_name: None,
-            window_name: None,
-            width: None,
-            slide: None,
-            offset: None,
-            start: None,
-            end: None,
-        })
+            construct_template,
+            filters,
+            optional_bgps,
+            optional_filters,
+            union_bgps,
+            union_filters,
+            values,
+            windows: Vec::new(),
+            projection,
+            describe_targets,
+            modifiers: Self::solution_modifiers(&parsed),
+            query_type: parsed.query_type,
+            default_graphs: parsed.from_clauses,
+            named_graphs: parsed.from_named_clauses,
+            r2s_operator: None,
+            output_stream: None,
+        };
+        if let Some(base) = base {
+            Self::resolve_relative_iris(&mut query, &base);
+        }
+        Ok(query)
+    }
+
+    /// Resolve the relative IRIs of the patterns and inline data of a query against the
+    /// BASE IRI declared by the query
+    ///
+    /// The dataset clauses are already resolved by the parser. IRIs inside property paths
+    /// and FILTER expressions are left as they are.
+    fn resolve_relative_iris(query: &mut IsomorphismQuery, base: &str) {
+        let resolve = |node: &mut TripleNode| match node {
+            TripleNode::IRI(iri) => *iri = resolve_iri(base, iri),
+            TripleNode::TypedLiteral { datatype, .. } => *datatype = resolve_iri(base, datatype),
+            _ => {}
+        };
+
+        let triples = query
+            .bgp
+            .iter_mut()
+            .chain(query.construct_template.iter_mut())
+            .chain(query.optional_bgps.iter_mut().flatten())
+            .chain(query.union_bgps.iter_mut().flatten().flatten());
+        for triple in triples {
+            resolve(&mut triple.subject);
+            resolve(&mut triple.predicate);
+            resolve(&mut triple.object);
+            if let Some(graph) = &mut triple.graph {
+                resolve(graph);
+            }
+        }
+        for data in &mut query.values {
+            for value in data.rows.iter_mut().flatten().flatten() {
+                resolve(value);
+            }
+        }
+        query.describe_targets.iter_mut().for_each(resolve);
+    }
+
+    /// Check that every numeric window parameter is a non-negative integer
+    ///
+    /// The window patterns of the streaming parsers only accept integers, so a value such as
+    /// `RANGE -5` or `RANGE 1.5` would otherwise make the window silently disappear. The
+    /// OFFSET, START and END timestamps may also be quoted ISO-8601 datetimes, which the
+    /// JanusQL parser checks itself.
+    fn validate_window_parameters(query: &str) -> Result<(), TulnaError> {
+        for captures in WINDOW_DECLARATION.captures_iter(query) {
+            let spec = captures[1].trim();
+            let tokens: Vec<&str> = spec.split_whitespace().collect();
+            for parameter in tokens.chunks(2) {
+                match parameter {
+                    [keyword, value] => {
+                        let timestamp = ["OFFSET", "START", "END"]
+                            .iter()
+                            .any(|name| keyword.eq_ignore_ascii_case(name));
+                        if timestamp && value.starts_with('"') {
+                            continue;
+                        }
+                        if value.parse::<u64>().is_err() {
+                            return Err(TulnaError::ParseError(format!(
+                                "Invalid value '{}' for window parameter {}: expected a non-negative integer",
+                                value, keyword
+                            )));
+                        }
+                    }
+                    _ => {
+                        return Err(TulnaError::ParseError(format!(
+                            "Malformed window specification '[{}]'",
+                            spec
+                        )));
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Check that every `{` has a matching `}` and that every IRI is closed by `>`
+    ///
+    /// Comments are removed first with `strip_comments`, which keeps the line numbers. Braces
+    /// inside IRIs and string literals are ignored. A `<` counts as the start of an IRI when
+    /// it begins a term or a datatype (`^^<...>`) and is not followed by whitespace or `=`,
+    /// so that comparisons such as `?x < 5` in a FILTER are left alone.
+    fn validate_brackets(query: &str) -> Result<(), TulnaError> {
+        let chars: Vec<char> = strip_comments(query).chars().collect();
+        let line_of = |index: usize| chars[..index].iter().filter(|&&c| c == '\n').count() + 1;
+        let mut open_braces = Vec::new();
+        let mut i = 0;
+
+        while i < chars.len() {
+            match chars[i] {
+                '"' | '\'' => {
+                    i = Self::skip_string_literal(&chars, i);
+                    continue;
+                }
+                '<' if Self::starts_iri(&chars, i) => {
+                    let end = chars[i..]
+                        .iter()
+                        .position(|&c| c == '>' || c.is_whitespace());
+                    match end {
+                        Some(end) if chars[i + end] == '>' => i += end,
+                        _ => {
+                            return Err(TulnaError::UnbalancedBraces(format!(
+                                "IRI opened on line {} is not closed by '>'",
+                                line_of(i)
+                            )))
+                        }
+                    }
+                }
+                '{' => open_braces.push(i),
+                '}' if open_braces.pop().is_none() => {
+                    return Err(TulnaError::UnbalancedBraces(format!(
+                        "'}}' on line {} has no matching '{{'",
+                        line_of(i)
+                    )));
+                }
+                _ => {}
+            }
+            i += 1;
+        }
+
+        match open_braces.pop() {
+            Some(index) => Err(TulnaError::UnbalancedBraces(format!(
+                "'{{' on line {} is never closed",
+                line_of(index)
+            ))),
+            None => Ok(()),
+        }
+    }
+
+    /// Check whether the `<` at `index` opens an IRI rather than being a comparison
+    fn starts_iri(chars: &[char], index: usize) -> bool {
+        let starts_term =
+            index == 0 || chars[index - 1].is_whitespace() || "{(,;^".contains(chars[index - 1]);
+        starts_term
+            && chars
+                .get(index + 1)
+                .is_some_and(|c| !c.is_whitespace() && *c != '=')
+    }
+
+    /// Extract the triple patterns of the CONSTRUCT template of a parsed query
+    fn extract_construct_template(
+        parsed: &ParsedSparqlQuery,
+        labels: &mut FreshLabels,
+    ) -> Result<Vec<Triple>, TulnaError> {
+        match &parsed.construct_template {
+            Some(template) => Self::extract_bgp_from_where(
+                &format!("{{ {} }}", template),
+                &parsed.prefixes,
+                labels,
+            ),
+            None => Ok(Vec::new()),
+        }
     }
 
     /// Parse an RSPQL query
     fn parse_rspql(query: &str) -> Result<IsomorphismQuery, TulnaError> {
+        Self::validate_window_parameters(query)?;
         let parser = RSPQLParser::new(query.to_string());
         let parsed = parser.parse();
-        let bgp = Self::extract_bgp_from_where(&parsed.sparql_query)?;
+        let solution = Self::parse_embedded_sparql(&parsed.sparql_query)?;
+        let (where_clause, values) = Self::split_values(&parsed.sparql_query);
+        let (where_clause, optional_groups) = Self::split_optional_groups(&where_clause);
+        let (where_clause, unions) = Self::split_unions(&where_clause);
+        let (where_clause, filters) = Self::split_filters(&where_clause, &solution.prefixes);
+        let (optional_groups, optional_filters) =
+            Self::split_group_filters(&optional_groups, &solution.prefixes);
+        let (unions, union_filters): (Vec<_>, Vec<_>) = unions
+            .iter()
+            .map(|branches| Self::split_group_filters(branches, &solution.prefixes))
+            .unzip();
+        let labels = &mut FreshLabels::default();
+        let bgp = Self::extract_bgp_from_where(&where_clause, &solution.prefixes, labels)?;
+        let optional_bgps = Self::extract_group_bgps(&optional_groups, &solution.prefixes, labels)?;
+        let union_bgps = unions
+            .iter()
+            .map(|branches| Self::extract_group_bgps(branches, &solution.prefixes, labels))
+            .collect::<Result<_, _>>()?;
+        let values = Self::extract_values(&values, &solution.prefixes)?;
 
-        let (stream_name, window_name, width, slide) = if !parsed.s2r.is_empty() {
-            let window = &parsed.s2r[0];
-            (
-                Some(window.stream_name.clone()),
-                Some(window.window_name.clone()),
-                Some(window.width),
-                Some(window.slide),
-            )
-        } else {
-            (None, None, None, None)
-        };
+        let (r2s_operator, output_stream) = parsed
+            .r2s
+            .map(|r2s| (format!("{:?}", r2s.operator), r2s.name))
+            .unzip();
+
+        let windows = parsed
+            .s2r
+            .iter()
+            .map(|window| StreamWindow {
+                window_name: window.window_name.clone(),
+                stream_name: window.stream_name.clone(),
+                width: window.width,
+                slide: window.slide,
+                offset: None,
+                start: None,
+                end: None,
+                window_type: WindowType::Live,
+            })
+            .collect();
+
+        let (projection, describe_targets) = Self::split_projection(&solution);
 
-        Ok(IsomorphismQuery {
+        let mut query = IsomorphismQuery {
             query_language: QueryLanguage::RSPQL,
             bgp,
-            stream_name,
-            window_name,
-            width,
-            slide,
-            offset: None,
-            start: None,
-            end: None,
-        })
+            construct_template: Self::extract_construct_template(&solution, labels)?,
+            filters,
+            optional_bgps,
+            optional_filters,
+            union_bgps,
+            union_filters,
+            values,
+            windows,
+            default_graphs: Vec::new(),
+            named_graphs: Vec::new(),
+            query_type: solution.query_type.clone(),
+            projection,
+            describe_targets,
+            modifiers: Self::solution_modifiers(&solution),
+            r2s_operator,
+            output_stream,
+        };
+        if let Some(base) = &solution.base {
+            Self::resolve_relative_iris(&mut query, base);
+        }
+        Ok(query)
     }
 
     /// Parse a JanusQL query
     fn parse_janusql(query: &str) -> Result<IsomorphismQuery, TulnaError> {
-        let parser = JanusQLParser::new().map_err(|e| TulnaError::ParseError(e.to_string()))?;
-        let parsed = parser
-            .parse(query)
-            .map_err(|e| TulnaError::ParseError(e.to_string()))?;
-        let bgp = Self::extract_bgp_from_where(&parsed.where_clause)?;
-
-        let (stream_name, window_name, width, slide, offset, start, end) =
-            if !parsed.live_windows.is_empty() {
-                let window = &parsed.live_windows[0];
-                (
-                    Some(window.stream_name.clone()),
-                    Some(window.window_name.clone()),
-                    Some(window.width as i64),
-                    Some(window.slide as i64),
-                    None,
-                    None,
-                    None,
-                )
-            } else if !parsed.historical_windows.is_empty() {
-                let window = &parsed.historical_windows[0];
-                (
-                    Some(window.stream_name.clone()),
-                    Some(window.window_name.clone()),
-                    Some(window.width as i64),
-                    Some(window.slide as i64),
-                    window.offset,
-                    window.start,
-                    window.end,
-                )
-            } else {
-                (None, None, None, None, None, None, None)
-            };
+        Self::validate_window_parameters(query)?;
+        let parser = JanusQLParser::new()?;
+        let parsed = parser.parse(query)?;
+        let (where_clause, values) = Self::split_values(&parsed.where_clause);
+        let (where_clause, optional_groups) = Self::split_optional_groups(&where_clause);
+        let (where_clause, unions) = Self::split_unions(&where_clause);
+        let (where_clause, filters) = Self::split_filters(&where_clause, &parsed.prefixes);
+        let (optional_groups, optional_filters) =
+            Self::split_group_filters(&optional_groups, &parsed.prefixes);
+        let (unions, union_filters): (Vec<_>, Vec<_>) = unions
+            .iter()
+            .map(|branches| Self::split_group_filters(branches, &parsed.prefixes))
+            .unzip();
+        let labels = &mut FreshLabels::default();
+        let bgp = Self::extract_bgp_from_where(&where_clause, &parsed.prefixes, labels)?;
+        let optional_bgps = Self::extract_group_bgps(&optional_groups, &parsed.prefixes, labels)?;
+        let union_bgps = unions
+            .iter()
+            .map(|branches| Self::extract_group_bgps(branches, &parsed.prefixes, labels))
+            .collect::<Result<_, _>>()?;
+        let values = Self::extract_values(&values, &parsed.prefixes)?;
+        let (r2s_operator, output_stream) = parsed.r2s.map(|r2s| (r2s.operator, r2s.name)).unzip();
+        let solution = Self::parse_embedded_sparql(&format!(
+            "{}\n{}",
+            parsed.select_clause, parsed.where_clause
+        ))?;
+
+        let windows = parsed
+            .live_windows
+            .iter()
+            .chain(parsed.historical_windows.iter())
+            .map(|window| StreamWindow {
+                window_name: window.window_name.clone(),
+                stream_name: window.stream_name.clone(),
+                width: window.width as i64,
+                slide: window.slide as i64,
+                offset: window.offset,
+                start: window.start,
+                end: window.end,
+                window_type: window.window_type,
+            })
+            .collect();
 
-        Ok(IsomorphismQuery {
+        let (projection, describe_targets) = Self::split_projection(&solution);
+
+        let mut query = IsomorphismQuery {
             query_language: QueryLanguage::JanusQL,
             bgp,
-            stream_name,
-            window_name,
-            width,
-            slide,
-            offset,
-            start,
-            end,
-        })
+            construct_template: Self::extract_construct_template(&solution, labels)?,
+            filters,
+            optional_bgps,
+            optional_filters,
+            union_bgps,
+            union_filters,
+            values,
+            windows,
+            default_graphs: Vec::new(),
+            named_graphs: Vec::new(),
+            query_type: solution.query_type.clone(),
+            projection,
+            describe_targets,
+            modifiers: Self::solution_modifiers(&solution),
+            r2s_operator,
+            output_stream,
+        };
+        if let Some(base) = &parsed.base {
+            Self::resolve_relative_iris(&mut query, base);
+        }
+        Ok(query)
+    }
+
+    /// Parse the plain SPARQL part of a streaming query for its query form, projection
+    /// and solution modifiers
+    ///
+    /// The streaming parsers do not check this part, so its errors, such as a missing
+    /// query form, are returned here.
+    fn parse_embedded_sparql(sparql: &str) -> Result<ParsedSparqlQuery, TulnaError> {
+        SparqlParser::new()?.parse(sparql)
+    }
+
+    /// Collect the solution modifiers of a parsed SPARQL query
+    ///
+    /// The prefixed names of the GROUP BY and ORDER BY expressions are expanded.
+    fn solution_modifiers(parsed: &ParsedSparqlQuery) -> SolutionModifiers {
+        let expand =
+            |expression: &String| Self::expand_prefixed_names(expression, &parsed.prefixes);
+        SolutionModifiers {
+            distinct: parsed.distinct,
+            reduced: parsed.reduced,
+            group_by: parsed.group_by.as_ref().map(expand),
+            has_aggregates: parsed.has_aggregates,
+            order_by: parsed.order_by.as_ref().map(expand),
+            limit: parsed.limit,
+            offset: parsed.offset,
+        }
+    }
+
+    /// The projection and the DESCRIBE resources of a parsed SPARQL query
+    ///
+    /// The prefixed names of the projected expressions are expanded. The clause after
+    /// DESCRIBE names the resources to describe, which are kept as nodes so that their IRIs
+    /// are compared expanded as well; only a `*` is kept as projection.
+    fn split_projection(parsed: &ParsedSparqlQuery) -> (Vec<String>, Vec<TripleNode>) {
+        if parsed.query_type != QueryType::Describe {
+            let projection = Self::parse_projection(&parsed.select_clause)
+                .iter()
+                .map(|item| Self::expand_prefixed_names(item, &parsed.prefixes))
+                .collect();
+            return (projection, Vec::new());
+        }
+
+        let (wildcard, targets): (Vec<&str>, Vec<&str>) = parsed
+            .select_clause
+            .split_whitespace()
+            .partition(|item| *item == "*");
+        let targets = targets
+            .into_iter()
+            .map(|target| Self::parse_node(target, &parsed.prefixes))
+            .collect();
+        (wildcard.into_iter().map(String::from).collect(), targets)
+    }
+
+    /// Split a SELECT clause into its projected variables and `(expression AS ?var)` items
+    fn parse_projection(select_clause: &str) -> Vec<String> {
+        let mut items = Vec::new();
+        let mut current = String::new();
+        let mut depth = 0;
+        let mut quote: Option<char> = None;
+
+        for c in select_clause.chars() {
+            // Parentheses and whitespace inside string literals (e.g. a GROUP_CONCAT
+            // separator) are part of the expression
+            if let Some(q) = quote {
+                current.push(c);
+                if c == q {
+                    quote = None;
+                }
+                continue;
+            }
+            match c {
+                '"' | '\'' => {
+                    quote = Some(c);
+                    current.push(c);
+                }
+                '(' => {
+                    depth += 1;
+                    current.push(c);
+                }
+                ')' => {
+                    depth -= 1;
+                    current.push(c);
+                    if depth <= 0 {
+                        depth = 0;
+                        items.push(current.trim().to_string());
+                        current.clear();
+                    }
+                }
+                c if c.is_whitespace() && depth == 0 => {
+                    if !current.trim().is_empty() {
+                        items.push(current.trim().to_string());
+                    }
+                    current.clear();
+                }
+                _ => current.push(c),
+            }
+        }
+        if !current.trim().is_empty() {
+            items.push(current.trim().to_string());
+        }
+
+        items
     }
 
     /// Extract Basic Graph Pattern from WHERE clause
     ///
+    /// By default the clause is read by a lightweight tokenizer, see
+    /// `extract_bgp_with_tokenizer`. With the `spargebra-backend` feature it is parsed into
+    /// the SPARQL algebra of the `spargebra` crate instead, which rejects syntax errors and
+    /// undeclared prefixes with a `ParseError`.
+    ///
+    /// The triples inside a `GRAPH` or `WINDOW` block get the name of the block as their
+    /// graph, so that triples of different named graphs never match each other.
+    pub(crate) fn extract_bgp_from_where(
+        where_clause: &str,
+        prefixes: &HashMap<String, String>,
+        labels: &mut FreshLabels,
+    ) -> Result<Vec<Triple>, TulnaError> {
+        // Comments go first, as they may hold braces
+        let (rest, graph_groups) = Self::split_graph_groups(&strip_comments(where_clause));
+
+        let mut bgp = Self::extract_default_graph_bgp(&rest, prefixes, labels)?;
+        for (name, group) in graph_groups {
+            let graph = Self::parse_node(&name, prefixes);
+            // A block nested in another one keeps its own name
+            let group = format!("{{ {} }}", group);
+            for mut triple in Self::extract_bgp_from_where(&group, prefixes, labels)? {
+                triple.graph.get_or_insert_with(|| graph.clone());
+                bgp.push(triple);
+            }
+        }
+        Ok(bgp)
+    }
+
+    /// Extract the triple patterns of a WHERE clause without `GRAPH` and `WINDOW` blocks
+    ///
+    /// `spargebra` gives the anonymous nodes random labels of its own, so `labels` is only
+    /// used by the tokenizer.
+    #[cfg_attr(feature = "spargebra-backend", allow(unused_variables))]
+    fn extract_default_graph_bgp(
+        where_clause: &str,
+        prefixes: &HashMap<String, String>,
+        labels: &mut FreshLabels,
+    ) -> Result<Vec<Triple>, TulnaError> {
+        #[cfg(feature = "spargebra-backend")]
+        {
+            crate::isomorphism::algebra::extract_bgp(where_clause, prefixes)
+        }
+
+        #[cfg(not(feature = "spargebra-backend"))]
+        {
+            Self::extract_bgp_with_tokenizer(where_clause, prefixes, labels)
+        }
+    }
+
+    /// Extract Basic Graph Pattern from WHERE clause without a SPARQL grammar
+    ///
     /// Handles basic triple patterns, including those ending with `.` or `;` (predicate lists)
-    /// and `,` (object lists).
-    /// Note: Does NOT support nested groups or UNIONs yet.
-    fn extract_bgp_from_where(where_clause: &str) -> Result<Vec<Triple>, TulnaError> {
+    /// and `,` (object lists). Prefixed names with a prefix in `prefixes` are expanded to
+    /// full IRIs. Anonymous blank nodes (`[ :p ?o ]`) and collections (`( :a :b )`) are
+    /// expanded to triples about blank nodes with fresh labels from `labels`. Triples inside
+    /// nested groups are collected into the same BGP; `extract_bgp_from_where` takes the
+    /// `WINDOW` and `GRAPH` blocks out beforehand. `OPTIONAL` groups and `UNION` branches
+    /// are split off by `split_optional_groups` and `split_unions` before this runs.
+    #[cfg_attr(feature = "spargebra-backend", allow(dead_code))]
+    fn extract_bgp_with_tokenizer(
+        where_clause: &str,
+        prefixes: &HashMap<String, String>,
+        labels: &mut FreshLabels,
+    ) -> Result<Vec<Triple>, TulnaError> {
         let mut bgp = Vec::new();
 
         // Extract content between braces
-        let content = Self::extract_inner_braces(where_clause);
-        if content.is_empty() {
+        // Comments go first, as they may hold braces
+        let clean_content = Self::extract_inner_braces(&strip_comments(where_clause));
+        if clean_content.is_empty() {
             return Ok(bgp);
         }
 
-        // Naive approach to handle comments: Remove lines starting with #
-        // Better: Remove text from # to newline, unless inside quotes.
-        // For now, we assume simplified queries without complex comments inside patterns.
-        let clean_content = content
-            .lines()
-            .map(|line| {
-                if let Some(idx) = line.find('#') {
-                    &line[..idx]
-                } else {
-                    line
-                }
-            })
-            .collect::<Vec<&str>>()
-            .join(" ");
-
         // Tokenizer logic: split by spaces, keeping quotes intact
         // This is a simplified lexer.
         let mut tokens = Vec::new();
@@ -229,8 +1118,10 @@ impl QueryIsomorphism {
         let mut in_quote = false;
         let mut quote_char = '\0';
         let mut in_iri = false;
+        let mut open_collections = 0;
 
-        for c in clean_content.chars() {
+        let mut chars = clean_content.char_indices().peekable();
+        while let Some((idx, c)) = chars.next() {
             if in_quote {
                 current_token.push(c);
                 if c == quote_char {
@@ -258,7 +1149,28 @@ impl QueryIsomorphism {
                             current_token.clear();
                         }
                     }
-                    '.' | ';' | ',' => {
+                    // The dot of a decimal such as `3.14` does not end the triple
+                    '.' if Self::is_integer(&current_token)
+                        && chars.peek().is_some_and(|(_, c)| c.is_ascii_digit()) =>
+                    {
+                        current_token.push(c)
+                    }
+                    // Parentheses of a property path such as `(ex:p|ex:q)+` stay in its token
+                    '(' if current_token.is_empty()
+                        && Self::opens_collection(&clean_content[idx + 1..]) =>
+                    {
+                        open_collections += 1;
+                        tokens.push(c.to_string());
+                    }
+                    ')' if open_collections > 0 => {
+                        if !current_token.is_empty() {
+                            tokens.push(current_token.clone());
+                            current_token.clear();
+                        }
+                        open_collections -= 1;
+                        tokens.push(c.to_string());
+                    }
+                    '.' | ';' | ',' | '[' | ']' => {
                         if !current_token.is_empty() {
                             tokens.push(current_token.clone());
                             current_token.clear();
@@ -272,6 +1184,7 @@ impl QueryIsomorphism {
         if !current_token.is_empty() {
             tokens.push(current_token);
         }
+        let tokens = Self::expand_anonymous_nodes(tokens, labels);
 
         // Parser state machine
         let mut current_subject: Option<TripleNode> = None;
@@ -281,6 +1194,12 @@ impl QueryIsomorphism {
         while i < tokens.len() {
             let token = &tokens[i];
 
+            // Nested groups leave separators without a triple before them
+            if token == "." {
+                i += 1;
+                continue;
+            }
+
             // Skip explicit WINDOW clause or GRAPH clause keywords if they appear inside where (simplified)
             if token.eq_ignore_ascii_case("WINDOW")
                 || token.eq_ignore_ascii_case("GRAPH")
@@ -297,7 +1216,7 @@ impl QueryIsomorphism {
             let subject = if let Some(s) = current_subject.clone() {
                 s
             } else {
-                let s = Self::parse_node(token);
+                let s = Self::parse_node(token, prefixes);
                 i += 1;
                 s
             };
@@ -307,15 +1226,17 @@ impl QueryIsomorphism {
             }
             let token = &tokens[i];
 
+            // A bracketed node may stand alone, as in `[ :p ?o ] .`, since its property list
+            // was moved to a statement of its own
+            if current_subject.is_none() && token == "." {
+                continue;
+            }
+
             // Expect Predicate
             let predicate = if let Some(p) = current_predicate.clone() {
                 p
             } else {
-                let p = if token == "a" {
-                    TripleNode::IRI("http://www.w3.org/1999/02/22-rdf-syntax-ns#type".to_string())
-                } else {
-                    Self::parse_node(token)
-                };
+                let p = Self::parse_predicate(token, prefixes);
                 i += 1;
                 p
             };
@@ -326,13 +1247,14 @@ impl QueryIsomorphism {
             let token = &tokens[i];
 
             // Expect Object
-            let object = Self::parse_node(token);
+            let object = Self::parse_node(token, prefixes);
             i += 1;
 
             bgp.push(Triple {
                 subject: subject.clone(),
                 predicate: predicate.clone(),
                 object,
+                graph: None,
             });
 
             if i >= tokens.len() {
@@ -350,6 +1272,14 @@ impl QueryIsomorphism {
                     current_subject = Some(subject);
                     current_predicate = None;
                     i += 1;
+                    // Repeated and trailing semicolons (`; ;` or `; .`) are allowed
+                    while i < tokens.len() && tokens[i] == ";" {
+                        i += 1;
+                    }
+                    if i < tokens.len() && tokens[i] == "." {
+                        current_subject = None;
+                        i += 1;
+                    }
                 }
                 "," => {
                     current_subject = Some(subject);
@@ -370,28 +1300,133 @@ impl QueryIsomorphism {
         Ok(bgp)
     }
 
-    /// Extract content from innermost braces
-    fn extract_inner_braces(text: &str) -> String {
-        let mut result = String::new();
+    /// Check whether the `(` that `rest` follows opens a collection rather than a group of
+    /// a property path
+    ///
+    /// A group holds `|` or `/` operators, or is followed by one of `|`, `/`, `*`, `+`
+    /// or `?`.
+    fn opens_collection(rest: &str) -> bool {
+        let mut depth = 1;
+        let mut quote = None;
+        let mut in_iri = false;
+
+        let mut chars = rest.chars().peekable();
+        while let Some(c) = chars.next() {
+            match quote {
+                Some(q) if c == q => quote = None,
+                Some(_) => {}
+                None if in_iri => in_iri = c != '>',
+                None => match c {
+                    '"' | '\'' => quote = Some(c),
+                    '<' => in_iri = true,
+                    '(' => depth += 1,
+                    ')' if depth == 1 => {
+                        return !chars.peek().is_some_and(|c| "|/*+?".contains(*c));
+                    }
+                    ')' => depth -= 1,
+                    '|' | '/' => return false,
+                    _ => {}
+                },
+            }
+        }
+        false
+    }
+
+    /// Replace anonymous blank nodes (`[]` and `[ :p ?o ]`) and collections (`( :a :b )`)
+    /// with fresh blank node labels
+    ///
+    /// The property list of a bracketed node is moved to a statement of its own, with the
+    /// fresh label as subject, that is appended after the other tokens. A collection becomes
+    /// a chain of `rdf:first` and `rdf:rest` statements ending in `rdf:nil`, and the empty
+    /// collection `()` is `rdf:nil` itself.
+    fn expand_anonymous_nodes(tokens: Vec<String>, labels: &mut FreshLabels) -> Vec<String> {
+        let mut main = Vec::new();
+        let mut statements = Vec::new();
+        let mut open: Vec<(&str, String, Vec<String>)> = Vec::new();
+        let iri = |iri: &str| format!("<{}>", iri);
+
+        for token in tokens {
+            match token.as_str() {
+                "[" => open.push(("]", labels.next("anon"), Vec::new())),
+                "(" => open.push((")", labels.next("list"), Vec::new())),
+                "]" | ")" if open.last().is_some_and(|(close, _, _)| *close == token) => {
+                    let (close, label, contents) = open.pop().unwrap();
+                    let node = if close == "]" {
+                        if !contents.is_empty() {
+                            statements.push(label.clone());
+                            statements.extend(contents);
+                            statements.push(".".to_string());
+                        }
+                        label
+                    } else if contents.is_empty() {
+                        iri(RDF_NIL)
+                    } else {
+                        let mut cells = vec![label.clone()];
+                        while cells.len() < contents.len() {
+                            cells.push(labels.next("list"));
+                        }
+                        for (i, item) in contents.into_iter().enumerate() {
+                            let rest = cells.get(i + 1).cloned().unwrap_or(iri(RDF_NIL));
+                            statements.extend([
+                                cells[i].clone(),
+                                iri(RDF_FIRST),
+                                item,
+                                ";".to_string(),
+                                iri(RDF_REST),
+                                rest,
+                                ".".to_string(),
+                            ]);
+                        }
+                        label
+                    };
+                    open.last_mut()
+                        .map_or(&mut main, |(_, _, tokens)| tokens)
+                        .push(node);
+                }
+                _ => open
+                    .last_mut()
+                    .map_or(&mut main, |(_, _, tokens)| tokens)
+                    .push(token),
+            }
+        }
+
+        // Brackets that were never closed keep their contents in place
+        for (close, label, contents) in open {
+            main.push(if close == "]" { label } else { "(".to_string() });
+            main.extend(contents);
+        }
+
+        if !statements.is_empty() {
+            main.push(".".to_string());
+            main.extend(statements);
+        }
+        main
+    }
+
+    /// Extract content from braces, flattening nested groups
+    ///
+    /// The braces of nested groups are replaced by `.` separators, so that the triples
+    /// inside them end up next to the triples of the enclosing group.
+    fn extract_inner_braces(text: &str) -> String {
+        let mut result = String::new();
         let mut depth = 0;
-        let mut start_collecting = false;
 
         for ch in text.chars() {
             match ch {
                 '{' => {
-                    depth += 1;
-                    if depth == 1 {
-                        start_collecting = true;
+                    if depth > 0 {
+                        result.push_str(" . ");
                     }
+                    depth += 1;
                 }
                 '}' => {
                     depth -= 1;
-                    if depth == 0 {
-                        start_collecting = false;
+                    if depth > 0 {
+                        result.push_str(" . ");
                     }
                 }
                 _ => {
-                    if start_collecting && depth == 1 {
+                    if depth > 0 {
                         result.push(ch);
                     }
                 }
@@ -401,8 +1436,72 @@ impl QueryIsomorphism {
         result.trim().to_string()
     }
 
+    /// Parse a node in predicate position
+    ///
+    /// Besides the nodes handled by `parse_node`, a predicate may be the `a` shorthand for
+    /// `rdf:type` or a property path built with the `/`, `|`, `^`, `+`, `*` and `?` operators.
+    fn parse_predicate(token: &str, prefixes: &HashMap<String, String>) -> TripleNode {
+        if token == "a" {
+            return TripleNode::IRI(RDF_TYPE.to_string());
+        }
+        if token.starts_with('?') || token.starts_with('$') {
+            return Self::parse_node(token, prefixes);
+        }
+        Self::parse_property_path(token, prefixes)
+            .unwrap_or_else(|| Self::parse_node(token, prefixes))
+    }
+
+    /// Parse a property path, expanding every IRI in it to its full `<iri>` form
+    ///
+    /// Returns `None` when the token uses no path operator.
+    fn parse_property_path(token: &str, prefixes: &HashMap<String, String>) -> Option<TripleNode> {
+        let chars: Vec<char> = token.chars().collect();
+        let mut path = String::new();
+        let mut name = String::new();
+        let mut has_operator = false;
+
+        let flush = |name: &mut String, path: &mut String| {
+            if name.is_empty() {
+                return;
+            }
+            let iri = match name.as_str() {
+                "a" => RDF_TYPE.to_string(),
+                _ => match Self::parse_node(name, prefixes) {
+                    TripleNode::IRI(iri) => iri,
+                    _ => name.clone(),
+                },
+            };
+            path.push_str(&format!("<{}>", iri));
+            name.clear();
+        };
+
+        let mut i = 0;
+        while i < chars.len() {
+            match chars[i] {
+                '<' => {
+                    let end = chars[i..].iter().position(|&c| c == '>')? + i;
+                    name.extend(&chars[i..=end]);
+                    i = end;
+                }
+                c if "/|^+*?()!".contains(c) => {
+                    flush(&mut name, &mut path);
+                    path.push(c);
+                    has_operator = true;
+                }
+                c => name.push(c),
+            }
+            i += 1;
+        }
+        flush(&mut name, &mut path);
+
+        has_operator.then_some(TripleNode::PropertyPath(path))
+    }
+
     /// Parse a node from string representation
-    fn parse_node(node_str: &str) -> TripleNode {
+    ///
+    /// Prefixed names are expanded with `prefixes`; names with an unknown prefix are kept
+    /// as they are.
+    fn parse_node(node_str: &str, prefixes: &HashMap<String, String>) -> TripleNode {
         let trimmed = node_str.trim();
 
         if trimmed.starts_with('?') || trimmed.starts_with('$') {
@@ -410,168 +1509,2384 @@ impl QueryIsomorphism {
         } else if trimmed.starts_with('<') && trimmed.ends_with('>') {
             TripleNode::IRI(trimmed[1..trimmed.len() - 1].to_string())
         } else if trimmed.starts_with('"') || trimmed.starts_with('\'') {
-            TripleNode::Literal(trimmed.trim_matches(|c| c == '"' || c == '\'').to_string())
+            Self::parse_literal(trimmed, prefixes)
         } else if let Some(stripped) = trimmed.strip_prefix("_:") {
             TripleNode::BlankNode(stripped.to_string())
+        } else if let Some(literal) = Self::parse_bare_literal(trimmed) {
+            literal
         } else {
-            // Assume it's a prefixed IRI or a number/bool literal
-            if trimmed == "true" || trimmed == "false" || trimmed.chars().all(|c| c.is_numeric() || c == '.') {
-                 TripleNode::Literal(trimmed.to_string())
-            } else {
-                 TripleNode::IRI(trimmed.to_string())
+            // Assume it's a prefixed IRI
+            match trimmed
+                .split_once(':')
+                .and_then(|(prefix, local)| Some((prefixes.get(prefix)?, local)))
+            {
+                Some((namespace, local)) => TripleNode::IRI(format!("{}{}", namespace, local)),
+                None => TripleNode::IRI(trimmed.to_string()),
             }
         }
     }
 
-    /// Convert BGP to normalized graph format (as Vec of string triples)
-    fn bgp_to_normalized_graph(bgp: &[Triple]) -> Vec<(String, String, String)> {
-        bgp.iter()
-            .map(|triple| {
-                let s = Self::node_to_string(&triple.subject);
-                let p = Self::node_to_string(&triple.predicate);
-                let o = Self::node_to_string(&triple.object);
-                (s, p, o)
-            })
-            .collect()
+    /// Parse a number or boolean written without quotes, such as `42`, `-3.14`, `1e6` or `true`
+    ///
+    /// As in SPARQL, the literal gets the `xsd:integer`, `xsd:decimal`, `xsd:double` or
+    /// `xsd:boolean` datatype, so `42` is the same node as `"42"^^xsd:integer`. Returns `None`
+    /// for any other token.
+    fn parse_bare_literal(token: &str) -> Option<TripleNode> {
+        let datatype = if token == "true" || token == "false" {
+            "boolean"
+        } else {
+            let unsigned = token.strip_prefix(['+', '-']).unwrap_or(token);
+            let (mantissa, exponent) = match unsigned.split_once(['e', 'E']) {
+                Some((mantissa, exponent)) => (
+                    mantissa,
+                    Some(exponent.strip_prefix(['+', '-']).unwrap_or(exponent)),
+                ),
+                None => (unsigned, None),
+            };
+            let (integer, fraction) = match mantissa.split_once('.') {
+                Some((integer, fraction)) => (integer, Some(fraction)),
+                None => (mantissa, None),
+            };
+            let digits = |part: &str| part.bytes().all(|b| b.is_ascii_digit());
+            if !digits(integer)
+                || !fraction.is_none_or(digits)
+                || integer.is_empty() && fraction.is_none_or(str::is_empty)
+            {
+                return None;
+            }
+
+            match (fraction, exponent) {
+                (_, Some(exponent)) if !exponent.is_empty() && digits(exponent) => "double",
+                (None, None) => "integer",
+                // A decimal needs digits after its dot, as `3.` ends a triple instead
+                (Some(fraction), None) if !fraction.is_empty() => "decimal",
+                _ => return None,
+            }
+        };
+
+        Some(TripleNode::TypedLiteral {
+            value: token.to_string(),
+            datatype: format!("{}{}", XSD_NAMESPACE, datatype),
+        })
     }
 
-    /// Convert TripleNode to string, replacing variables with blank nodes
-    fn node_to_string(node: &TripleNode) -> String {
-        match node {
-            TripleNode::IRI(iri) => format!("<{}>", iri),
-            TripleNode::Variable(var) => format!("_:{}", var), // Variables become blank nodes
-            TripleNode::Literal(lit) => format!("\"{}\"", lit),
-            TripleNode::BlankNode(id) => format!("_:{}", id),
+    /// Check if a token is an integer, with an optional sign
+    fn is_integer(token: &str) -> bool {
+        let digits = token.strip_prefix(['+', '-']).unwrap_or(token);
+        !digits.is_empty() && digits.bytes().all(|b| b.is_ascii_digit())
+    }
+
+    /// Parse a quoted literal, keeping its `^^datatype` or `@lang` suffix if present
+    ///
+    /// Language tags are case-insensitive, so they are stored in lowercase.
+    fn parse_literal(literal: &str, prefixes: &HashMap<String, String>) -> TripleNode {
+        let quote = &literal[..1];
+        if let Some(end) = literal.rfind(quote).filter(|&end| end > 0) {
+            if let Some(lang) = literal[end + 1..].strip_prefix('@') {
+                return TripleNode::LangLiteral {
+                    value: literal[1..end].to_string(),
+                    lang: lang.to_lowercase(),
+                };
+            }
+            if let Some(datatype) = literal[end + 1..].strip_prefix("^^") {
+                let datatype = match Self::parse_node(datatype, prefixes) {
+                    TripleNode::IRI(iri) => iri,
+                    _ => datatype.to_string(),
+                };
+                return TripleNode::TypedLiteral {
+                    value: literal[1..end].to_string(),
+                    datatype,
+                };
+            }
+            return TripleNode::Literal(literal[1..end].to_string());
         }
+
+        TripleNode::Literal(literal.trim_matches(|c| c == '"' || c == '\'').to_string())
     }
 
     /// Check if stream parameters are equal
-    /// For historical windows, also checks offset, start, and end times
-    fn check_stream_parameters_equal(q1: &IsomorphismQuery, q2: &IsomorphismQuery) -> bool {
-        q1.stream_name == q2.stream_name
-            && q1.width == q2.width
-            && q1.slide == q2.slide
-            && q1.offset == q2.offset
-            && q1.start == q2.start
-            && q1.end == q2.end
+    /// Every window is compared on its stream, width and slide, and for historical windows
+    /// also on its offset, start and end times. The order in which the windows are declared
+    /// is irrelevant.
+    pub(crate) fn check_stream_parameters_equal(
+        q1: &IsomorphismQuery,
+        q2: &IsomorphismQuery,
+    ) -> bool {
+        let mut parameters1: Vec<_> = q1.windows.iter().map(StreamWindow::parameters).collect();
+        let mut parameters2: Vec<_> = q2.windows.iter().map(StreamWindow::parameters).collect();
+        parameters1.sort();
+        parameters2.sort();
+        parameters1 == parameters2
+    }
+
+    /// Check if window names are equal, independent of the declaration order
+    pub(crate) fn check_window_names_equal(q1: &IsomorphismQuery, q2: &IsomorphismQuery) -> bool {
+        let mut names1: Vec<&str> = q1.windows.iter().map(|w| w.window_name.as_str()).collect();
+        let mut names2: Vec<&str> = q2.windows.iter().map(|w| w.window_name.as_str()).collect();
+        names1.sort();
+        names2.sort();
+        names1 == names2
+    }
+
+    /// Check if both queries declare the same windows
+    /// Unlike comparing parameters and names separately, this also requires each window name
+    /// to be bound to the same stream and parameters in both queries
+    pub(crate) fn check_windows_equal(q1: &IsomorphismQuery, q2: &IsomorphismQuery) -> bool {
+        let mut windows1 = q1.windows.clone();
+        let mut windows2 = q2.windows.clone();
+        windows1.sort();
+        windows2.sort();
+        windows1 == windows2
+    }
+
+    /// Check if both queries are registered with the same R2S operator, or both are not
+    /// A registered query continuously emits results through its R2S operator, while an
+    /// unregistered one is evaluated once, so the two are never interchangeable. `RStream`,
+    /// `IStream` and `DStream` emit different streams from the same results, so the operator
+    /// must match as well.
+    fn check_register_equal(q1: &IsomorphismQuery, q2: &IsomorphismQuery) -> bool {
+        q1.r2s_operator == q2.r2s_operator
     }
 
-    /// Check if window names are equal
-    fn check_window_names_equal(q1: &IsomorphismQuery, q2: &IsomorphismQuery) -> bool {
-        q1.window_name == q2.window_name
+    /// Check if the dataset clauses are equal
+    /// The order of `FROM` and `FROM NAMED` declarations is irrelevant, so they are compared as sets
+    fn check_dataset_clauses_equal(q1: &IsomorphismQuery, q2: &IsomorphismQuery) -> bool {
+        let as_set = |graphs: &[String]| graphs.iter().cloned().collect::<HashSet<String>>();
+
+        as_set(&q1.default_graphs) == as_set(&q2.default_graphs)
+            && as_set(&q1.named_graphs) == as_set(&q2.named_graphs)
     }
 
     /// Check if two queries are isomorphic
+    ///
+    /// The DISTINCT, REDUCED, LIMIT and OFFSET modifiers change the solutions of a query, so
+    /// they have to be equal. Use `is_isomorphic_ignoring_modifiers` to compare the patterns
+    /// only.
     pub fn is_isomorphic(
         query_one: &str,
         query_two: &str,
     ) -> Result<bool, TulnaError> {
         let q1 = Self::parse_query(query_one)?;
         let q2 = Self::parse_query(query_two)?;
+        Ok(Self::find_mismatch(&q1, &q2, true)?.is_none())
+    }
+
+    /// Check if two queries are isomorphic as in `is_isomorphic`, without comparing their
+    /// DISTINCT, REDUCED, LIMIT and OFFSET modifiers
+    ///
+    /// `SELECT DISTINCT ?s WHERE { ... } LIMIT 10` then matches `SELECT ?s WHERE { ... }`.
+    /// Grouping is still compared, since it changes the shape of the solutions.
+    pub fn is_isomorphic_ignoring_modifiers(
+        query_one: &str,
+        query_two: &str,
+    ) -> Result<bool, TulnaError> {
+        let q1 = Self::parse_query(query_one)?;
+        let q2 = Self::parse_query(query_two)?;
+        Ok(Self::find_mismatch(&q1, &q2, false)?.is_none())
+    }
+
+    /// Check if the graph patterns of two queries are isomorphic, ignoring everything else
+    ///
+    /// Only the BGPs are compared, together with their OPTIONAL groups, UNIONs, FILTERs and
+    /// VALUES blocks.
+    /// The windows, streams and R2S operators of streaming queries are not, so queries that
+    /// only differ in a window's RANGE or STEP match, as do an RSP-QL query and a SPARQL
+    /// query over the same pattern. The query form and solution modifiers are ignored too.
+    pub fn is_bgp_isomorphic(query_one: &str, query_two: &str) -> Result<bool, TulnaError> {
+        let graph1 = Self::bgp_graph(Self::parse_query(query_one)?)?;
+        let graph2 = Self::bgp_graph(Self::parse_query(query_two)?)?;
+        Self::check_bgp_isomorphism(&graph1, &graph2)
+    }
+
+    /// Check if the BGPs of two queries are isomorphic, whatever their languages
+    ///
+    /// Only the required triple patterns are compared, as returned by
+    /// `generate_bgp_quads_from_query`: the triple patterns of a SPARQL WHERE clause and of
+    /// the WINDOW and GRAPH blocks of a streaming query all count alike. Unlike
+    /// `is_bgp_isomorphic`, OPTIONAL groups, UNIONs and FILTERs are left out as well.
+    pub fn bgp_equivalent_cross_language(
+        query_one: &str,
+        query_two: &str,
+    ) -> Result<bool, TulnaError> {
+        let mut q1 = Self::parse_query(query_one)?;
+        let mut q2 = Self::parse_query(query_two)?;
+        Self::remove_window_graphs(&mut q1);
+        Self::remove_window_graphs(&mut q2);
+        Self::check_bgp_isomorphism(
+            &Self::distinct_triples(&q1.bgp),
+            &Self::distinct_triples(&q2.bgp),
+        )
+    }
+
+    /// Move the triples of the WINDOW blocks of a streaming query to the default graph
+    ///
+    /// Triples inside named graphs that are not windows keep their graph.
+    fn remove_window_graphs(query: &mut IsomorphismQuery) {
+        let windows: HashSet<String> = query
+            .windows
+            .iter()
+            .map(|window| window.window_name.clone())
+            .collect();
+        let triples = query
+            .bgp
+            .iter_mut()
+            .chain(query.optional_bgps.iter_mut().flatten())
+            .chain(query.union_bgps.iter_mut().flatten().flatten());
+        for triple in triples {
+            if let Some(TripleNode::IRI(graph)) = &triple.graph {
+                if windows.contains(graph) {
+                    triple.graph = None;
+                }
+            }
+        }
+    }
+
+    /// Build the graph compared by `is_bgp_isomorphic`: the pattern graph of a query without
+    /// its windows, CONSTRUCT template, DESCRIBE resources and GROUP BY clause
+    fn bgp_graph(mut query: IsomorphismQuery) -> Result<Vec<Triple>, TulnaError> {
+        Self::remove_window_graphs(&mut query);
+        query.construct_template.clear();
+        query.describe_targets.clear();
+        query.modifiers.group_by = None;
+        Self::pattern_graph(&query)
+    }
+
+    /// Find the candidates that are isomorphic to a query
+    ///
+    /// The query is parsed once and compared against every candidate in turn, so this is
+    /// much cheaper than calling `is_isomorphic` for each pair.
+    ///
+    /// # Returns
+    ///
+    /// The indices of the isomorphic candidates, in increasing order
+    pub fn find_matches(query: &str, candidates: &[&str]) -> Result<Vec<usize>, TulnaError> {
+        let parsed = Self::parse_query(query)?;
+
+        let mut matches = Vec::new();
+        for (index, candidate) in candidates.iter().enumerate() {
+            let candidate = Self::parse_query(candidate)?;
+            if Self::find_mismatch(&parsed, &candidate, true)?.is_none() {
+                matches.push(index);
+            }
+        }
+        Ok(matches)
+    }
+
+    /// Find the first difference that keeps two parsed queries from being isomorphic
+    ///
+    /// The cheap comparisons of windows, query form, modifiers, dataset and pattern sizes
+    /// come first, so that most mismatches are rejected before the graph isomorphism check.
+    /// The DISTINCT, REDUCED, LIMIT and OFFSET modifiers are only compared when
+    /// `compare_modifiers` is set.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(None)` - The queries are isomorphic
+    /// * `Ok(Some(reason))` - A human-readable description of the first mismatch found
+    pub(crate) fn find_mismatch(
+        q1: &IsomorphismQuery,
+        q2: &IsomorphismQuery,
+        compare_modifiers: bool,
+    ) -> Result<Option<String>, TulnaError> {
+        let show = |value: Option<String>| value.unwrap_or_else(|| "none".to_string());
 
         // For RSPQL and JanusQL, check stream parameters first
+        // This must happen before the BGP check, as two empty BGPs are always isomorphic
         if q1.query_language != QueryLanguage::SPARQL || q2.query_language != QueryLanguage::SPARQL
         {
-            if !Self::check_stream_parameters_equal(&q1, &q2) {
-                return Ok(false);
+            if let Some(reason) = Self::stream_parameters_mismatch(q1, q2) {
+                return Ok(Some(reason));
             }
-            if !Self::check_window_names_equal(&q1, &q2) {
-                return Ok(false);
+            if !Self::check_window_names_equal(q1, q2) {
+                let names = |q: &IsomorphismQuery| {
+                    let mut names: Vec<&str> =
+                        q.windows.iter().map(|w| w.window_name.as_str()).collect();
+                    names.sort();
+                    names.join(", ")
+                };
+                return Ok(Some(format!(
+                    "window names [{}] != [{}]",
+                    names(q1),
+                    names(q2)
+                )));
+            }
+            if !Self::check_windows_equal(q1, q2) {
+                return Ok(Some(
+                    "windows of the same name are on different streams".to_string(),
+                ));
             }
+            if !Self::check_register_equal(q1, q2) {
+                return Ok(Some(format!(
+                    "R2S operator {} != {}",
+                    show(q1.r2s_operator.clone()),
+                    show(q2.r2s_operator.clone())
+                )));
+            }
+        }
+
+        // An ASK query only reports whether a match exists, so it never equals a SELECT
+        if q1.query_type != q2.query_type {
+            return Ok(Some(format!(
+                "query form {:?} != {:?}",
+                q1.query_type, q2.query_type
+            )));
+        }
+
+        // Grouping turns the solutions into groups, so it has to match; the GROUP BY
+        // expressions themselves are compared as part of the pattern graph
+        if !Self::check_grouping_equal(q1, q2) {
+            let (m1, m2) = (&q1.modifiers, &q2.modifiers);
+            return Ok(Some(if m1.group_by.is_some() != m2.group_by.is_some() {
+                format!(
+                    "GROUP BY {} != {}",
+                    show(m1.group_by.clone()),
+                    show(m2.group_by.clone())
+                )
+            } else {
+                format!("aggregates {} != {}", m1.has_aggregates, m2.has_aggregates)
+            }));
+        }
+
+        if compare_modifiers && !Self::check_modifiers_equal(q1, q2) {
+            let (m1, m2) = (&q1.modifiers, &q2.modifiers);
+            let number = |value: Option<u64>| show(value.map(|value| value.to_string()));
+            return Ok(Some(if m1.distinct != m2.distinct {
+                format!("DISTINCT {} != {}", m1.distinct, m2.distinct)
+            } else if m1.reduced != m2.reduced {
+                format!("REDUCED {} != {}", m1.reduced, m2.reduced)
+            } else if m1.limit != m2.limit {
+                format!("LIMIT {} != {}", number(m1.limit), number(m2.limit))
+            } else {
+                format!("OFFSET {} != {}", number(m1.offset), number(m2.offset))
+            }));
+        }
+
+        if !Self::check_dataset_clauses_equal(q1, q2) {
+            return Ok(Some("FROM or FROM NAMED graphs differ".to_string()));
+        }
+
+        let sizes = [
+            (
+                "BGP size",
+                Self::distinct_triples(&q1.bgp).len(),
+                Self::distinct_triples(&q2.bgp).len(),
+            ),
+            (
+                "CONSTRUCT template size",
+                Self::distinct_triples(&q1.construct_template).len(),
+                Self::distinct_triples(&q2.construct_template).len(),
+            ),
+            ("FILTER count", q1.filters.len(), q2.filters.len()),
+            (
+                "OPTIONAL group count",
+                q1.optional_bgps.len(),
+                q2.optional_bgps.len(),
+            ),
+            ("UNION count", q1.union_bgps.len(), q2.union_bgps.len()),
+            ("VALUES block count", q1.values.len(), q2.values.len()),
+            (
+                "DESCRIBE resource count",
+                q1.describe_targets.len(),
+                q2.describe_targets.len(),
+            ),
+        ];
+        if let Some((what, size1, size2)) = sizes.iter().find(|(_, size1, size2)| size1 != size2) {
+            return Ok(Some(format!("{} {} != {}", what, size1, size2)));
         }
 
-        // Check BGP isomorphism
-        Ok(Self::check_bgp_isomorphism(&q1.bgp, &q2.bgp))
+        // Check BGP isomorphism, together with the CONSTRUCT template, OPTIONAL groups, UNIONs
+        // and FILTERs
+        match Self::classify_bgps(&Self::pattern_graph(q1)?, &Self::pattern_graph(q2)?)? {
+            IsoResult::Isomorphic => Ok(None),
+            reason => Ok(Some(format!("BGP not isomorphic: {}", reason))),
+        }
     }
 
-    /// Check if two BGPs are isomorphic using hash-based graph isomorphism
-    fn check_bgp_isomorphism(bgp1: &[Triple], bgp2: &[Triple]) -> bool {
-        if bgp1.len() != bgp2.len() {
-            return false;
+    /// Describe the first difference between the stream parameters of two queries, if any
+    ///
+    /// The windows are compared as in `check_stream_parameters_equal`, sorted by their
+    /// parameters, so the reported difference is the first one between the sorted windows.
+    fn stream_parameters_mismatch(q1: &IsomorphismQuery, q2: &IsomorphismQuery) -> Option<String> {
+        if Self::check_stream_parameters_equal(q1, q2) {
+            return None;
+        }
+        if q1.windows.len() != q2.windows.len() {
+            return Some(format!(
+                "window count {} != {}",
+                q1.windows.len(),
+                q2.windows.len()
+            ));
+        }
+
+        let mut parameters1: Vec<_> = q1.windows.iter().map(StreamWindow::parameters).collect();
+        let mut parameters2: Vec<_> = q2.windows.iter().map(StreamWindow::parameters).collect();
+        parameters1.sort();
+        parameters2.sort();
+
+        let number = |value: Option<u64>| value.map_or("none".to_string(), |v| v.to_string());
+        parameters1
+            .into_iter()
+            .zip(parameters2)
+            .find(|(p1, p2)| p1 != p2)
+            .map(|(p1, p2)| {
+                if p1.0 != p2.0 {
+                    format!("stream {} != {}", p1.0, p2.0)
+                } else if p1.1 != p2.1 {
+                    format!("stream width {} != {}", p1.1, p2.1)
+                } else if p1.2 != p2.2 {
+                    format!("stream slide {} != {}", p1.2, p2.2)
+                } else if p1.3 != p2.3 {
+                    format!("window offset {} != {}", number(p1.3), number(p2.3))
+                } else if p1.4 != p2.4 {
+                    format!("window start {} != {}", number(p1.4), number(p2.4))
+                } else {
+                    format!("window end {} != {}", number(p1.5), number(p2.5))
+                }
+            })
+    }
+
+    /// Check if both queries group their solutions in the same way: both or neither have a
+    /// GROUP BY clause and both or neither use aggregates
+    fn check_grouping_equal(q1: &IsomorphismQuery, q2: &IsomorphismQuery) -> bool {
+        let (m1, m2) = (&q1.modifiers, &q2.modifiers);
+        m1.group_by.is_some() == m2.group_by.is_some() && m1.has_aggregates == m2.has_aggregates
+    }
+
+    /// Check if both queries have the same DISTINCT, REDUCED, LIMIT and OFFSET modifiers
+    fn check_modifiers_equal(q1: &IsomorphismQuery, q2: &IsomorphismQuery) -> bool {
+        let (m1, m2) = (&q1.modifiers, &q2.modifiers);
+        m1.distinct == m2.distinct
+            && m1.reduced == m2.reduced
+            && m1.limit == m2.limit
+            && m1.offset == m2.offset
+    }
+
+    /// Check if two queries are strictly equivalent
+    ///
+    /// On top of BGP isomorphism this requires the same query form, the same projection
+    /// (under the variable bijection), the same solution modifiers and dataset clauses, and
+    /// for streaming queries the same windows and R2S operator. The order of the projected
+    /// variables is not taken into account, but their number is, so `SELECT ?s` and
+    /// `SELECT ?s ?p` over the same pattern are not equivalent.
+    pub fn is_strictly_equivalent(query_one: &str, query_two: &str) -> Result<bool, TulnaError> {
+        let q1 = Self::parse_query(query_one)?;
+        let q2 = Self::parse_query(query_two)?;
+
+        if q1.query_language != q2.query_language
+            || q1.query_type != q2.query_type
+            || q1.r2s_operator != q2.r2s_operator
+            || q1.projection.len() != q2.projection.len()
+        {
+            return Ok(false);
+        }
+
+        if !Self::check_stream_parameters_equal(&q1, &q2)
+            || !Self::check_windows_equal(&q1, &q2)
+            || !Self::check_grouping_equal(&q1, &q2)
+            || !Self::check_dataset_clauses_equal(&q1, &q2)
+        {
+            return Ok(false);
         }
 
-        // Use graph isomorphism checker for proper isomorphism checking
-        match crate::isomorphism::graph_isomorphism::GraphIsomorphism::check_bgp_isomorphism(
-            bgp1, bgp2,
-        ) {
-            Ok(result) => result,
-            Err(_) => {
-                // Fallback to simple comparison if graph isomorphism fails
-                let graph1 = Self::bgp_to_normalized_graph(bgp1);
-                let graph2 = Self::bgp_to_normalized_graph(bgp2);
-                let mut g1_sorted = graph1.clone();
-                let mut g2_sorted = graph2.clone();
-                g1_sorted.sort();
-                g2_sorted.sort();
-                g1_sorted == g2_sorted
+        // ORDER BY mentions variables, so it is compared as part of the graph below
+        if !Self::check_modifiers_equal(&q1, &q2)
+            || q1.modifiers.order_by.is_some() != q2.modifiers.order_by.is_some()
+        {
+            return Ok(false);
+        }
+
+        let graph1 = Self::strict_graph(&q1)?;
+        let graph2 = Self::strict_graph(&q2)?;
+        Self::check_bgp_isomorphism(&graph1, &graph2)
+    }
+
+    /// Build the graph of the patterns of a query: the BGP extended with the reified triples
+    /// of the CONSTRUCT template, OPTIONAL groups and UNION branches, the encoded FILTER
+    /// expressions and GROUP BY clause, and the resources of a DESCRIBE query
+    ///
+    /// Template, optional and union triples must not mix with the required BGP. Each one is
+    /// described by a fresh blank node instead, linked to its subject, predicate and object,
+    /// which keeps the variables they share with the BGP under a single bijection. Optional
+    /// triples are also linked to a node for their group, so that the grouping is preserved.
+    /// Likewise, union triples are linked to a node for their branch, which is linked to a
+    /// node for the union, so the branches of a union can map onto each other in any order.
+    /// The FILTER constraints of a group or branch are linked to its node in the same way.
+    /// The DESCRIBE resources are all linked to a single node, as their order is irrelevant.
+    fn pattern_graph(query: &IsomorphismQuery) -> Result<Vec<Triple>, TulnaError> {
+        let mut graph = Self::distinct_triples(&query.bgp);
+        let template = Self::distinct_triples(&query.construct_template);
+        for (index, triple) in template.iter().enumerate() {
+            let node = Self::encoding_node(format!("template_{}", index));
+            graph.extend(Self::reified_triples(node, triple));
+        }
+        for (group, optional_bgp) in query.optional_bgps.iter().enumerate() {
+            let group_node = Self::encoding_node(format!("optional_{}", group));
+            for (index, triple) in optional_bgp.iter().enumerate() {
+                let node = Self::encoding_node(format!("optional_{}_{}", group, index));
+                graph.push(Triple {
+                    subject: node.clone(),
+                    predicate: TripleNode::IRI(format!("{}optional", ENCODING_NAMESPACE)),
+                    object: group_node.clone(),
+                    graph: None,
+                });
+                graph.extend(Self::reified_triples(node, triple));
+            }
+            for (index, filter) in query.optional_filters[group].iter().enumerate() {
+                let node = Self::encoding_node(format!("optional_{}_filter_{}", group, index));
+                graph.push(Triple {
+                    subject: node.clone(),
+                    predicate: TripleNode::IRI(format!("{}optional", ENCODING_NAMESPACE)),
+                    object: group_node.clone(),
+                    graph: None,
+                });
+                graph.extend(Self::expression_triples(node, "filter", filter)?);
+            }
+        }
+        let iri = |name: &str| TripleNode::IRI(format!("{}{}", ENCODING_NAMESPACE, name));
+        for (union, branches) in query.union_bgps.iter().enumerate() {
+            let union_node = Self::encoding_node(format!("union_{}", union));
+            for (branch, branch_bgp) in branches.iter().enumerate() {
+                let branch_node = Self::encoding_node(format!("union_{}_{}", union, branch));
+                graph.push(Triple {
+                    subject: branch_node.clone(),
+                    predicate: iri("branch"),
+                    object: union_node.clone(),
+                    graph: None,
+                });
+                for (index, triple) in branch_bgp.iter().enumerate() {
+                    let node = Self::encoding_node(format!("union_{}_{}_{}", union, branch, index));
+                    graph.push(Triple {
+                        subject: node.clone(),
+                        predicate: iri("alternative"),
+                        object: branch_node.clone(),
+                        graph: None,
+                    });
+                    graph.extend(Self::reified_triples(node, triple));
+                }
+                for (index, filter) in query.union_filters[union][branch].iter().enumerate() {
+                    let node =
+                        Self::encoding_node(format!("union_{}_{}_filter_{}", union, branch, index));
+                    graph.push(Triple {
+                        subject: node.clone(),
+                        predicate: iri("alternative"),
+                        object: branch_node.clone(),
+                        graph: None,
+                    });
+                    graph.extend(Self::expression_triples(node, "filter", filter)?);
+                }
             }
         }
+        for (index, filter) in query.filters.iter().enumerate() {
+            let node = Self::encoding_node(format!("filter_{}", index));
+            graph.extend(Self::expression_triples(node, "filter", filter)?);
+        }
+        for (index, data) in query.values.iter().enumerate() {
+            graph.extend(Self::values_triples(index, data));
+        }
+        if let Some(group_by) = &query.modifiers.group_by {
+            let node = Self::encoding_node("groupBy".to_string());
+            graph.extend(Self::expression_triples(node, "groupBy", group_by)?);
+        }
+        let describe_node = Self::encoding_node("describe".to_string());
+        for target in &query.describe_targets {
+            graph.push(Triple {
+                subject: describe_node.clone(),
+                predicate: iri("describes"),
+                object: target.clone(),
+                graph: None,
+            });
+        }
+        Ok(graph)
     }
 
-    /// Generate BGP quads from a query string (similar to TypeScript version)
-    pub fn generate_bgp_quads_from_query(
-        query: &str,
-    ) -> Result<Vec<Triple>, TulnaError> {
-        let parsed = Self::parse_query(query)?;
-        Ok(parsed.bgp)
+    /// Describe a VALUES block as triples
+    ///
+    /// The block node is linked to its variables and to a node for every row. Each bound
+    /// value of a row gets a node of its own that links the variable to the value, so the
+    /// order of the rows and of the variables does not matter.
+    fn values_triples(index: usize, data: &InlineData) -> Vec<Triple> {
+        let iri = |name: &str| TripleNode::IRI(format!("{}{}", ENCODING_NAMESPACE, name));
+        let block = Self::encoding_node(format!("values_{}", index));
+        let mut triples: Vec<Triple> = data
+            .variables
+            .iter()
+            .map(|variable| Triple {
+                subject: block.clone(),
+                predicate: iri("variable"),
+                object: TripleNode::Variable(variable.clone()),
+                graph: None,
+            })
+            .collect();
+
+        for (row_index, row) in data.rows.iter().enumerate() {
+            let row_node = Self::encoding_node(format!("values_{}_{}", index, row_index));
+            triples.push(Triple {
+                subject: row_node.clone(),
+                predicate: iri("row"),
+                object: block.clone(),
+                graph: None,
+            });
+            for (position, (variable, value)) in data.variables.iter().zip(row).enumerate() {
+                // UNDEF leaves the variable unbound in this row
+                let Some(value) = value else { continue };
+                let binding =
+                    Self::encoding_node(format!("values_{}_{}_{}", index, row_index, position));
+                triples.extend([
+                    Triple {
+                        subject: row_node.clone(),
+                        predicate: iri("binding"),
+                        object: binding.clone(),
+                        graph: None,
+                    },
+                    Triple {
+                        subject: binding.clone(),
+                        predicate: iri("variable"),
+                        object: TripleNode::Variable(variable.clone()),
+                        graph: None,
+                    },
+                    Triple {
+                        subject: binding,
+                        predicate: iri("value"),
+                        object: value.clone(),
+                        graph: None,
+                    },
+                ]);
+            }
+        }
+
+        triples
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    /// Drop the repeated triple patterns of a BGP, keeping the first of each
+    ///
+    /// A triple pattern written twice matches the same solutions as written once.
+    pub(crate) fn distinct_triples(triples: &[Triple]) -> Vec<Triple> {
+        let mut seen = HashSet::new();
+        triples
+            .iter()
+            .filter(|triple| seen.insert(*triple))
+            .cloned()
+            .collect()
+    }
 
-    #[test]
-    fn test_detect_sparql() {
-        let query = "SELECT ?s ?p ?o WHERE { ?s ?p ?o }";
-        assert_eq!(
-            QueryIsomorphism::detect_query_type(query),
-            QueryLanguage::SPARQL
-        );
+    /// Return the blank node that stands for the part of a query called `name`
+    ///
+    /// Its label starts with `FRESH_LABEL_PREFIX`, so it never matches a blank node of the
+    /// query itself.
+    fn encoding_node(name: String) -> TripleNode {
+        TripleNode::BlankNode(format!("{}{}", FRESH_LABEL_PREFIX, name))
     }
 
-    #[test]
-    fn test_detect_rspql() {
-        let query = "REGISTER STREAM <output> AS SELECT ?s ?p ?o FROM NAMED WINDOW <w> ON STREAM <s> [RANGE 10 STEP 5]";
-        assert_eq!(
-            QueryIsomorphism::detect_query_type(query),
-            QueryLanguage::RSPQL
-        );
+    /// Describe a triple pattern by linking a node to its subject, predicate, object and
+    /// graph name
+    fn reified_triples(node: TripleNode, triple: &Triple) -> Vec<Triple> {
+        [
+            ("subject", &triple.subject),
+            ("predicate", &triple.predicate),
+            ("object", &triple.object),
+        ]
+        .into_iter()
+        .chain(triple.graph.as_ref().map(|graph| ("graph", graph)))
+        .map(|(position, term)| Triple {
+            subject: node.clone(),
+            predicate: TripleNode::IRI(format!("{}{}", ENCODING_NAMESPACE, position)),
+            object: term.clone(),
+            graph: None,
+        })
+        .collect()
     }
 
-    #[test]
-    fn test_parse_node_variable() {
-        let node = QueryIsomorphism::parse_node("?var");
-        assert!(matches!(node, TripleNode::Variable(_)));
+    /// Build the graph used for strict equivalence: the BGP extended with triples encoding
+    /// the projection and ORDER BY, so their variables are matched by the same bijection
+    fn strict_graph(query: &IsomorphismQuery) -> Result<Vec<Triple>, TulnaError> {
+        let mut graph = Self::pattern_graph(query)?;
+        for (index, item) in query.projection.iter().enumerate() {
+            let node = Self::encoding_node(format!("projection_{}", index));
+            graph.extend(Self::expression_triples(node, "projection", item)?);
+        }
+        if let Some(order_by) = &query.modifiers.order_by {
+            let node = Self::encoding_node("orderBy".to_string());
+            graph.extend(Self::expression_triples(node, "orderBy", order_by)?);
+        }
+        Ok(graph)
     }
 
-    #[test]
-    fn test_parse_node_iri() {
-        let node = QueryIsomorphism::parse_node("<http://example.org/resource>");
-        assert!(matches!(node, TripleNode::IRI(_)));
+    /// Encode an expression as triples around `node`
+    ///
+    /// The expression with its variables renamed to `?v0`, `?v1`, ... becomes a literal,
+    /// and each argument position is linked to the variable it holds. Two expressions then
+    /// map onto each other exactly when they are equal up to variable renaming.
+    fn expression_triples(
+        node: TripleNode,
+        kind: &str,
+        expression: &str,
+    ) -> Result<Vec<Triple>, TulnaError> {
+        let (template, variables) = Self::canonicalize_variables(expression)?;
+
+        let mut triples = vec![Triple {
+            subject: node.clone(),
+            predicate: TripleNode::IRI(format!("{}{}", ENCODING_NAMESPACE, kind)),
+            object: TripleNode::Literal(Self::normalize_expression(&template)),
+            graph: None,
+        }];
+        for (position, variable) in variables.into_iter().enumerate() {
+            triples.push(Triple {
+                subject: node.clone(),
+                predicate: TripleNode::IRI(format!("{}arg{}", ENCODING_NAMESPACE, position)),
+                object: TripleNode::Variable(variable),
+                graph: None,
+            });
+        }
+        Ok(triples)
     }
 
-    #[test]
-    fn test_bgp_extraction() {
-        let where_clause = "WHERE { ?s <http://example.org/p> ?o . }";
-        let bgp = QueryIsomorphism::extract_bgp_from_where(where_clause).unwrap();
-        assert_eq!(bgp.len(), 1);
+    /// Split the blocks recognized by `block_at` off a WHERE clause
+    ///
+    /// This is the scanner shared by the `split_*` functions. String literals and IRIs are
+    /// copied as they are, so keywords and braces inside them are never taken for syntax. At
+    /// every other position, `block_at` may recognize a block starting there and return the
+    /// index just past it, together with what to keep of it. The block is then replaced by
+    /// `replacement` in the returned clause.
+    fn split_blocks<T>(
+        where_clause: &str,
+        replacement: &str,
+        mut block_at: impl FnMut(&[char], usize) -> Option<(usize, T)>,
+    ) -> (String, Vec<T>) {
+        let chars: Vec<char> = where_clause.chars().collect();
+        let mut rest = String::new();
+        let mut blocks = Vec::new();
+        let mut i = 0;
+
+        while i < chars.len() {
+            let verbatim_end = match chars[i] {
+                '"' | '\'' => Some(Self::skip_string_literal(&chars, i)),
+                '<' => Self::iri_end(&chars, i),
+                _ => None,
+            };
+            if let Some(end) = verbatim_end {
+                rest.extend(&chars[i..end]);
+                i = end;
+                continue;
+            }
+
+            if let Some((end, block)) = block_at(&chars, i) {
+                blocks.push(block);
+                rest.push_str(replacement);
+                i = end;
+                continue;
+            }
+
+            rest.push(chars[i]);
+            i += 1;
+        }
+
+        (rest, blocks)
     }
 
-    #[test]
-    fn test_bgp_extraction_with_lists() {
-        let where_clause = "WHERE { ?s <http://p> ?o ; <http://q> ?o2 . }";
-        let bgp = QueryIsomorphism::extract_bgp_from_where(where_clause).unwrap();
-        assert_eq!(bgp.len(), 2);
-        assert_eq!(bgp[0].subject, bgp[1].subject);
+    /// If an IRI opens at `start`, return the index just past its closing `>`
+    ///
+    /// As in `strip_comments`, a `<` opens an IRI when the word following it holds a `>`, so
+    /// that a comparison such as `?x < 5` is not taken for one.
+    fn iri_end(chars: &[char], start: usize) -> Option<usize> {
+        if chars.get(start) != Some(&'<') {
+            return None;
+        }
+        chars[start..]
+            .iter()
+            .take_while(|ch| !ch.is_whitespace())
+            .position(|&ch| ch == '>')
+            .map(|len| start + len + 1)
     }
-    
-    #[test]
+
+    /// Whether `keyword` starts at `i` as a word of its own, ignoring case
+    ///
+    /// Only the start of the word is checked, as what may follow the keyword differs.
+    fn keyword_at(chars: &[char], i: usize, keyword: &str) -> bool {
+        let at_word_start =
+            i == 0 || !(chars[i - 1].is_alphanumeric() || "_?$:".contains(chars[i - 1]));
+        let word: String = chars[i..].iter().take(keyword.len()).collect();
+        at_word_start && word.eq_ignore_ascii_case(keyword)
+    }
+
+    /// Return the index of the first character at or after `i` that is not whitespace
+    fn skip_whitespace(chars: &[char], mut i: usize) -> usize {
+        while i < chars.len() && chars[i].is_whitespace() {
+            i += 1;
+        }
+        i
+    }
+
+    /// The contents of the group opening at `start` and ending just before `end`, without
+    /// its braces
+    ///
+    /// An unterminated group runs to the end of the clause.
+    fn group_contents(chars: &[char], start: usize, end: usize) -> String {
+        let inner_end = if chars[end - 1] == '}' { end - 1 } else { end };
+        chars[start + 1..inner_end].iter().collect()
+    }
+
+    /// Split the `FILTER` constraints off a WHERE clause
+    ///
+    /// Returns the WHERE clause without its constraints, which would otherwise be read as
+    /// triple patterns, together with the constraint expressions in order of appearance,
+    /// their prefixed names expanded with `prefixes`. Both the bracketted form
+    /// `FILTER (?x > 5)` and the function call form `FILTER regex(?x, "a")` are recognized.
+    /// `FILTER` inside string literals and IRIs is left alone.
+    fn split_filters(
+        where_clause: &str,
+        prefixes: &HashMap<String, String>,
+    ) -> (String, Vec<String>) {
+        let (rest, filters) = Self::split_blocks(where_clause, " ", |chars, i| {
+            if !Self::keyword_at(chars, i, "FILTER") {
+                return None;
+            }
+            let start = Self::skip_whitespace(chars, i + 6);
+            let mut j = start;
+            // Optional function name, such as `regex` or `bound`
+            while j < chars.len() && (chars[j].is_alphanumeric() || "_:".contains(chars[j])) {
+                j += 1;
+            }
+            // Without whitespace after the keyword, `FILTERED(` would look like a call
+            let separated = start > i + 6 || j == start;
+            if chars.get(j) != Some(&'(') || !separated {
+                return None;
+            }
+            let end = Self::skip_parentheses(chars, j);
+            Some((end, chars[start..end].iter().collect::<String>()))
+        });
+        let filters = filters
+            .iter()
+            .map(|filter| Self::expand_prefixed_names(filter, prefixes))
+            .collect();
+        (rest, filters)
+    }
+
+    /// Split the `FILTER` constraints off each group, such as the OPTIONAL groups or the
+    /// branches of a UNION, so that they stay in the scope of their group
+    fn split_group_filters(
+        groups: &[String],
+        prefixes: &HashMap<String, String>,
+    ) -> (Vec<String>, Vec<Vec<String>>) {
+        groups
+            .iter()
+            .map(|group| Self::split_filters(group, prefixes))
+            .unzip()
+    }
+
+    /// Split the `GRAPH` and `WINDOW` blocks off a WHERE clause
+    ///
+    /// Returns the WHERE clause without these blocks together with the name and contents of
+    /// each block, in order of appearance. A block nested inside another one stays part of
+    /// the enclosing block.
+    fn split_graph_groups(where_clause: &str) -> (String, Vec<(String, String)>) {
+        // An empty group keeps the surrounding triples apart in both backends
+        Self::split_blocks(where_clause, " {} ", |chars, i| {
+            let keyword = ["GRAPH", "WINDOW"].into_iter().find(|keyword| {
+                Self::keyword_at(chars, i, keyword)
+                    && chars
+                        .get(i + keyword.len())
+                        .is_some_and(|c| c.is_whitespace())
+            })?;
+            let name_start = Self::skip_whitespace(chars, i + keyword.len());
+            let mut j = name_start;
+            while j < chars.len() && !chars[j].is_whitespace() && chars[j] != '{' {
+                j += 1;
+            }
+            let name: String = chars[name_start..j].iter().collect();
+            let open = Self::skip_whitespace(chars, j);
+            if name.is_empty() || chars.get(open) != Some(&'{') {
+                return None;
+            }
+            let end = Self::skip_braces(chars, open);
+            Some((end, (name, Self::group_contents(chars, open, end))))
+        })
+    }
+
+    /// Split the `OPTIONAL` groups off a WHERE clause
+    ///
+    /// Returns the WHERE clause without its optional groups together with the contents of
+    /// each group, in order of appearance. An OPTIONAL nested inside another one stays part
+    /// of the enclosing group.
+    fn split_optional_groups(where_clause: &str) -> (String, Vec<String>) {
+        // An empty group keeps the surrounding triples apart in both backends
+        Self::split_blocks(where_clause, " {} ", |chars, i| {
+            if !Self::keyword_at(chars, i, "OPTIONAL") {
+                return None;
+            }
+            let open = Self::skip_whitespace(chars, i + 8);
+            if chars.get(open) != Some(&'{') {
+                return None;
+            }
+            let end = Self::skip_braces(chars, open);
+            Some((end, Self::group_contents(chars, open, end)))
+        })
+    }
+
+    /// Split the `UNION` patterns off a WHERE clause
+    ///
+    /// Returns the WHERE clause without its unions together with the contents of the
+    /// branches of each union, in order of appearance. A union nested inside a branch of
+    /// another one stays part of that branch.
+    fn split_unions(where_clause: &str) -> (String, Vec<Vec<String>>) {
+        Self::split_blocks(where_clause, " {} ", |chars, i| {
+            if chars[i] != '{' {
+                return None;
+            }
+            let mut branches = Vec::new();
+            let mut start = i;
+            let mut end = Self::skip_braces(chars, start);
+            while let Some(next) = Self::union_branch_start(chars, end) {
+                branches.push(Self::group_contents(chars, start, end));
+                start = next;
+                end = Self::skip_braces(chars, start);
+            }
+            if branches.is_empty() {
+                return None;
+            }
+            branches.push(Self::group_contents(chars, start, end));
+            Some((end, branches))
+        })
+    }
+
+    /// If the group ending at `end` is followed by `UNION`, return the index of the opening
+    /// brace of the next branch
+    fn union_branch_start(chars: &[char], end: usize) -> Option<usize> {
+        let i = Self::skip_whitespace(chars, end);
+        let keyword: String = chars.get(i..i + 5)?.iter().collect();
+        if !keyword.eq_ignore_ascii_case("UNION") {
+            return None;
+        }
+        let j = Self::skip_whitespace(chars, i + 5);
+        (chars.get(j) == Some(&'{')).then_some(j)
+    }
+
+    /// Split the `VALUES` blocks off a WHERE clause
+    ///
+    /// Returns the WHERE clause without its inline data together with the text of each
+    /// block after the `VALUES` keyword, such as `(?x ?y) { (:a :b) }` or `?x { :a :b }`.
+    fn split_values(where_clause: &str) -> (String, Vec<String>) {
+        Self::split_blocks(where_clause, " {} ", |chars, i| {
+            if !Self::keyword_at(chars, i, "VALUES") {
+                return None;
+            }
+            let start = Self::skip_whitespace(chars, i + 6);
+            let mut j = start;
+            // Either a list of variables or a single variable
+            if chars.get(j) == Some(&'(') {
+                j = Self::skip_parentheses(chars, j);
+            } else {
+                while j < chars.len() && !chars[j].is_whitespace() && chars[j] != '{' {
+                    j += 1;
+                }
+            }
+            let open = Self::skip_whitespace(chars, j);
+            if j == start || chars.get(open) != Some(&'{') {
+                return None;
+            }
+            let end = Self::skip_braces(chars, open);
+            Some((end, chars[start..end].iter().collect()))
+        })
+    }
+
+    /// Parse the variables and data rows of each VALUES block
+    fn extract_values(
+        blocks: &[String],
+        prefixes: &HashMap<String, String>,
+    ) -> Result<Vec<InlineData>, TulnaError> {
+        blocks
+            .iter()
+            .map(|block| Self::parse_values_block(block, prefixes))
+            .collect()
+    }
+
+    /// Parse a VALUES block, given the text after the `VALUES` keyword
+    fn parse_values_block(
+        block: &str,
+        prefixes: &HashMap<String, String>,
+    ) -> Result<InlineData, TulnaError> {
+        let (header, data) = block.split_once('{').unwrap_or((block, ""));
+        let data = data.trim_end().strip_suffix('}').unwrap_or(data);
+        let header = header.trim();
+
+        let variables: Vec<String> = header
+            .trim_start_matches('(')
+            .trim_end_matches(')')
+            .split_whitespace()
+            .map(|variable| variable.trim_start_matches(['?', '$']).to_string())
+            .collect();
+
+        let value = |term: &str| {
+            if term.eq_ignore_ascii_case("UNDEF") {
+                None
+            } else {
+                Some(Self::parse_node(term, prefixes))
+            }
+        };
+
+        let terms = Self::split_values_terms(data);
+        let rows: Vec<Vec<Option<TripleNode>>> = if header.starts_with('(') {
+            terms
+                .split(|term| term == "(" || term == ")")
+                .filter(|row| !row.is_empty())
+                .map(|row| row.iter().map(|term| value(term)).collect())
+                .collect()
+        } else {
+            terms.iter().map(|term| vec![value(term)]).collect()
+        };
+
+        if let Some(row) = rows.iter().find(|row| row.len() != variables.len()) {
+            return Err(TulnaError::ParseError(format!(
+                "VALUES row has {} values for {} variables in 'VALUES {}'",
+                row.len(),
+                variables.len(),
+                block.trim()
+            )));
+        }
+
+        Ok(InlineData { variables, rows })
+    }
+
+    /// Split the data of a VALUES block into terms, keeping parentheses as separate terms
+    fn split_values_terms(data: &str) -> Vec<String> {
+        let chars: Vec<char> = data.chars().collect();
+        let mut terms = Vec::new();
+        let mut current = String::new();
+        let mut i = 0;
+
+        while i < chars.len() {
+            match chars[i] {
+                '"' | '\'' => {
+                    let end = Self::skip_string_literal(&chars, i);
+                    current.extend(&chars[i..end]);
+                    i = end;
+                    continue;
+                }
+                c if c.is_whitespace() || c == '(' || c == ')' => {
+                    if !current.is_empty() {
+                        terms.push(std::mem::take(&mut current));
+                    }
+                    if !c.is_whitespace() {
+                        terms.push(c.to_string());
+                    }
+                }
+                c => current.push(c),
+            }
+            i += 1;
+        }
+        if !current.is_empty() {
+            terms.push(current);
+        }
+
+        terms
+    }
+
+    /// Extract the triple patterns of each group, such as the OPTIONAL groups or the branches
+    /// of a UNION
+    fn extract_group_bgps(
+        groups: &[String],
+        prefixes: &HashMap<String, String>,
+        labels: &mut FreshLabels,
+    ) -> Result<Vec<Vec<Triple>>, TulnaError> {
+        groups
+            .iter()
+            .map(|group| {
+                Self::extract_bgp_from_where(&format!("{{ {} }}", group), prefixes, labels)
+            })
+            .collect()
+    }
+
+    /// Return the index just past the balanced braces opening at `start`
+    pub(crate) fn skip_braces(chars: &[char], start: usize) -> usize {
+        let mut depth = 0;
+        let mut i = start;
+        while i < chars.len() {
+            match chars[i] {
+                '"' | '\'' => {
+                    i = Self::skip_string_literal(chars, i);
+                    continue;
+                }
+                '{' => depth += 1,
+                '}' => {
+                    depth -= 1;
+                    if depth == 0 {
+                        return i + 1;
+                    }
+                }
+                _ => {}
+            }
+            i += 1;
+        }
+        chars.len()
+    }
+
+    /// Return the index just past the string literal starting at `start`
+    fn skip_string_literal(chars: &[char], start: usize) -> usize {
+        let quote = chars[start];
+        let mut i = start + 1;
+        while i < chars.len() && chars[i] != quote {
+            if chars[i] == '\\' {
+                i += 1;
+            }
+            i += 1;
+        }
+        (i + 1).min(chars.len())
+    }
+
+    /// Return the index just past the balanced parentheses opening at `start`
+    fn skip_parentheses(chars: &[char], start: usize) -> usize {
+        let mut depth = 0;
+        let mut i = start;
+        while i < chars.len() {
+            match chars[i] {
+                '"' | '\'' => {
+                    i = Self::skip_string_literal(chars, i);
+                    continue;
+                }
+                '(' => depth += 1,
+                ')' => {
+                    depth -= 1;
+                    if depth == 0 {
+                        return i + 1;
+                    }
+                }
+                _ => {}
+            }
+            i += 1;
+        }
+        chars.len()
+    }
+
+    /// Normalize the layout of an expression
+    ///
+    /// Whitespace is collapsed and dropped inside parentheses and around `,`, `;` and `=`,
+    /// and the keywords of aggregate expressions are uppercased. String literals (such as a
+    /// GROUP_CONCAT separator) and IRIs are kept verbatim.
+    fn normalize_expression(expression: &str) -> String {
+        const KEYWORDS: [&str; 10] = [
+            "AS",
+            "AVG",
+            "COUNT",
+            "DISTINCT",
+            "GROUP_CONCAT",
+            "MAX",
+            "MIN",
+            "SAMPLE",
+            "SEPARATOR",
+            "SUM",
+        ];
+        let chars: Vec<char> = expression.chars().collect();
+        let mut normalized = String::new();
+        let mut pending_space = false;
+        let mut i = 0;
+
+        while i < chars.len() {
+            let c = chars[i];
+            if c.is_whitespace() {
+                pending_space = true;
+                i += 1;
+                continue;
+            }
+
+            let start = i;
+            if c == '"' || c == '\'' {
+                i += 1;
+                while i < chars.len() && chars[i] != c {
+                    if chars[i] == '\\' {
+                        i += 1;
+                    }
+                    i += 1;
+                }
+                i = (i + 1).min(chars.len());
+            } else if let Some(end) = Self::iri_end(&chars, i) {
+                i = end;
+            } else if c.is_alphanumeric() || c == '_' {
+                while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_') {
+                    i += 1;
+                }
+            } else {
+                i += 1;
+            }
+
+            let mut token: String = chars[start..i].iter().collect();
+            // Leave variables and prefixed names that happen to spell a keyword alone
+            let is_name_part = normalized.ends_with(['?', '$', ':']) || chars.get(i) == Some(&':');
+            if !is_name_part && KEYWORDS.iter().any(|k| k.eq_ignore_ascii_case(&token)) {
+                token = token.to_uppercase();
+            }
+
+            if pending_space
+                && !normalized.is_empty()
+                && !normalized.ends_with(['(', ',', ';', '='])
+                && !token.starts_with([')', ',', ';', '='])
+            {
+                normalized.push(' ');
+            }
+            pending_space = false;
+            normalized.push_str(&token);
+        }
+
+        normalized
+    }
+
+    /// Replace the prefixed names of an expression, such as the function of `ex:f(?x)` or
+    /// the datatype of `"5"^^xsd:integer`, by full IRIs
+    ///
+    /// Only names with a prefix in `prefixes` are expanded. String literals, IRIs and
+    /// variables are kept verbatim.
+    fn expand_prefixed_names(expression: &str, prefixes: &HashMap<String, String>) -> String {
+        let chars: Vec<char> = expression.chars().collect();
+        let mut expanded = String::new();
+        let mut i = 0;
+
+        while i < chars.len() {
+            let c = chars[i];
+            let end = if c == '"' || c == '\'' {
+                Self::skip_string_literal(&chars, i)
+            } else if let Some(end) = Self::iri_end(&chars, i) {
+                end
+            } else if c.is_alphanumeric() || "_:?$".contains(c) {
+                let mut end = i + 1;
+                while end < chars.len()
+                    && (chars[end].is_alphanumeric() || "_-.:%".contains(chars[end]))
+                {
+                    end += 1;
+                }
+                // A name never ends with a dot
+                while chars[end - 1] == '.' {
+                    end -= 1;
+                }
+                let word: String = chars[i..end].iter().collect();
+                let expansion = word
+                    .split_once(':')
+                    .filter(|_| c != '?' && c != '$')
+                    .and_then(|(prefix, local)| Some((prefixes.get(prefix)?, local)));
+                if let Some((namespace, local)) = expansion {
+                    expanded.push_str(&format!("<{}{}>", namespace, local));
+                    i = end;
+                    continue;
+                }
+                end
+            } else {
+                i + 1
+            };
+            expanded.extend(&chars[i..end]);
+            i = end;
+        }
+
+        expanded
+    }
+
+    /// Check if two BGPs are isomorphic using hash-based graph isomorphism
+    ///
+    /// Errors of the graph isomorphism check, such as `TulnaError::LimitExceeded` when the
+    /// speculative search gives up, are returned instead of a possibly wrong answer.
+    fn check_bgp_isomorphism(bgp1: &[Triple], bgp2: &[Triple]) -> Result<bool, TulnaError> {
+        Ok(Self::classify_bgps(bgp1, bgp2)? == IsoResult::Isomorphic)
+    }
+
+    /// Compare two BGPs as in `check_bgp_isomorphism`, reporting which check ruled out an
+    /// isomorphism
+    fn classify_bgps(bgp1: &[Triple], bgp2: &[Triple]) -> Result<IsoResult, TulnaError> {
+        crate::isomorphism::graph_isomorphism::GraphIsomorphism::classify(bgp1, bgp2)
+    }
+
+    /// Generate BGP quads from a query string (similar to TypeScript version)
+    pub fn generate_bgp_quads_from_query(
+        query: &str,
+    ) -> Result<Vec<Triple>, TulnaError> {
+        let parsed = Self::parse_query(query)?;
+        Ok(parsed.bgp)
+    }
+
+    /// Rename every variable in a query to `?v0`, `?v1`, ... in order of first appearance
+    ///
+    /// Everything apart from the variable tokens (prefixes, whitespace, comments) is kept
+    /// as is. Question marks inside IRIs, string literals and comments are not variables
+    /// and are left untouched.
+    pub fn rename_variables_canonical(query: &str) -> Result<String, TulnaError> {
+        Self::canonicalize_variables(query).map(|(renamed, _)| renamed)
+    }
+
+    /// Normalize a query to a canonical query string
+    ///
+    /// Variables are renamed to `?v0`, `?v1`, ... and blank nodes to `_:b0`, `_:b1`, ... in
+    /// the order of their canonical labels, as computed by `GraphIsomorphism::canonical_form`
+    /// on the query patterns, projection and ORDER BY. IRIs of triple patterns are written in
+    /// full, and the triple patterns, UNION branches, OPTIONAL groups, FILTERs, VALUES rows
+    /// and dataset clauses are sorted. The triple patterns of a named graph or a window are
+    /// written in its `GRAPH` or `WINDOW` block. The result is serialized with `ParsedSparqlQuery::to_query_string`,
+    /// preceded by the `REGISTER` clause and followed by the window declarations of a
+    /// streaming query, and parses again to the same query.
+    ///
+    /// Queries that only differ in variable names, blank node labels, prefixes of their
+    /// triple patterns and the order of their patterns produce identical strings. The
+    /// projection keeps its order.
+    ///
+    /// Returns `TulnaError::LimitExceeded` when the canonical labels need more speculative
+    /// groundings than `GraphIsomorphism::DEFAULT_MAX_SPECULATIONS`.
+    pub fn normalize_query(query: &str) -> Result<String, TulnaError> {
+        let parsed = Self::parse_query(query)?;
+
+        // Only blank nodes written in the query get a label, not those of the encoding
+        let query_triples = parsed
+            .bgp
+            .iter()
+            .chain(&parsed.construct_template)
+            .chain(parsed.optional_bgps.iter().flatten())
+            .chain(parsed.union_bgps.iter().flatten().flatten());
+        let mut query_blank_nodes: HashSet<String> = HashSet::new();
+        for triple in query_triples {
+            for node in triple.nodes() {
+                if let TripleNode::BlankNode(id) = node {
+                    query_blank_nodes.insert(id.clone());
+                }
+            }
+        }
+
+        let mut variables: HashMap<String, String> = HashMap::new();
+        let mut blank_nodes: HashMap<String, String> = HashMap::new();
+        let strict_graph = Self::strict_graph(&parsed)?;
+        for node in
+            crate::isomorphism::graph_isomorphism::GraphIsomorphism::canonical_order(&strict_graph)?
+        {
+            match node {
+                TripleNode::Variable(name) => {
+                    let label = format!("v{}", variables.len());
+                    variables.insert(name, label);
+                }
+                TripleNode::BlankNode(id) if query_blank_nodes.contains(&id) => {
+                    let label = format!("b{}", blank_nodes.len());
+                    blank_nodes.insert(id, label);
+                }
+                _ => {}
+            }
+        }
+
+        let rename_node = |node: &TripleNode| match node {
+            TripleNode::Variable(name) => {
+                TripleNode::Variable(variables.get(name).unwrap_or(name).clone())
+            }
+            TripleNode::BlankNode(id) => {
+                TripleNode::BlankNode(blank_nodes.get(id).unwrap_or(id).clone())
+            }
+            other => other.clone(),
+        };
+        // The triple patterns of a named graph or a window go in a GRAPH or WINDOW block,
+        // after those of the default graph
+        let window_names: HashSet<&str> = parsed
+            .windows
+            .iter()
+            .map(|window| window.window_name.as_str())
+            .collect();
+        let sorted_triples = |triples: &[Triple]| {
+            let mut lines: Vec<String> = Vec::new();
+            let mut blocks: BTreeMap<String, Vec<String>> = BTreeMap::new();
+            for triple in triples {
+                let renamed = Triple {
+                    subject: rename_node(&triple.subject),
+                    predicate: rename_node(&triple.predicate),
+                    object: rename_node(&triple.object),
+                    graph: None,
+                };
+                match &triple.graph {
+                    None => lines.push(renamed.to_string()),
+                    Some(graph) => {
+                        let keyword = match graph {
+                            TripleNode::IRI(name) if window_names.contains(name.as_str()) => {
+                                "WINDOW"
+                            }
+                            _ => "GRAPH",
+                        };
+                        let header = format!("{} {}", keyword, rename_node(graph));
+                        blocks.entry(header).or_default().push(renamed.to_string());
+                    }
+                }
+            }
+            lines.sort();
+            for (header, mut block) in blocks {
+                block.sort();
+                lines.push(format!("{} {{ {} }}", header, block.join(" ")));
+            }
+            lines.join(" ")
+        };
+        let rename_expression = |expression: &str| {
+            let renamed = Self::map_variables(expression, |name| {
+                variables.get(&name).cloned().unwrap_or(name)
+            })?;
+            Ok::<String, TulnaError>(Self::normalize_expression(&renamed))
+        };
+
+        let sorted_filters = |filters: &[String]| {
+            let mut lines: Vec<String> = filters
+                .iter()
+                .map(|filter| Ok(format!("FILTER {}", rename_expression(filter)?)))
+                .collect::<Result<_, TulnaError>>()?;
+            lines.sort();
+            Ok::<Vec<String>, TulnaError>(lines)
+        };
+        // The triples and then the filters of an OPTIONAL group or UNION branch
+        let group = |triples: &[Triple], filters: &[String]| {
+            let mut body = vec![sorted_triples(triples)];
+            body.extend(sorted_filters(filters)?);
+            Ok::<String, TulnaError>(format!("{{ {} }}", body.join(" ").trim()))
+        };
+
+        let mut patterns: Vec<String> = Vec::new();
+        if !parsed.bgp.is_empty() {
+            patterns.push(sorted_triples(&parsed.bgp));
+        }
+        let mut optionals: Vec<String> = parsed
+            .optional_bgps
+            .iter()
+            .zip(&parsed.optional_filters)
+            .map(|(triples, filters)| Ok(format!("OPTIONAL {}", group(triples, filters)?)))
+            .collect::<Result<_, TulnaError>>()?;
+        optionals.sort();
+        let mut unions: Vec<String> = parsed
+            .union_bgps
+            .iter()
+            .zip(&parsed.union_filters)
+            .map(|(branches, filters)| {
+                let mut branches: Vec<String> = branches
+                    .iter()
+                    .zip(filters)
+                    .map(|(triples, filters)| group(triples, filters))
+                    .collect::<Result<_, TulnaError>>()?;
+                branches.sort();
+                Ok(branches.join(" UNION "))
+            })
+            .collect::<Result<_, TulnaError>>()?;
+        unions.sort();
+        let filters = sorted_filters(&parsed.filters)?;
+        let mut values: Vec<String> = parsed
+            .values
+            .iter()
+            .map(|data| Self::canonical_values(data, rename_node))
+            .collect();
+        values.sort();
+        patterns.extend(
+            unions
+                .into_iter()
+                .chain(optionals)
+                .chain(filters)
+                .chain(values),
+        );
+
+        let mut projection: Vec<String> = parsed
+            .projection
+            .iter()
+            .map(|item| rename_expression(item))
+            .collect::<Result<_, TulnaError>>()?;
+        let mut describe_targets: Vec<String> = parsed
+            .describe_targets
+            .iter()
+            .map(|target| rename_node(target).to_string())
+            .collect();
+        describe_targets.sort();
+        projection.extend(describe_targets);
+        let group_by = match &parsed.modifiers.group_by {
+            Some(group_by) => Some(rename_expression(group_by)?),
+            None => None,
+        };
+        let order_by = match &parsed.modifiers.order_by {
+            Some(order_by) => Some(rename_expression(order_by)?),
+            None => None,
+        };
+
+        let mut default_graphs = parsed.default_graphs.clone();
+        default_graphs.sort();
+        let mut named_graphs = parsed.named_graphs.clone();
+        named_graphs.sort();
+
+        // Relative IRIs were resolved and prefixed names expanded while parsing, so no BASE
+        // or PREFIX is needed
+        let canonical = ParsedSparqlQuery {
+            query_type: parsed.query_type.clone(),
+            base: None,
+            prefixes: HashMap::new(),
+            select_clause: projection.join(" "),
+            construct_template: (parsed.query_type == QueryType::Construct)
+                .then(|| sorted_triples(&parsed.construct_template)),
+            from_clauses: default_graphs,
+            from_named_clauses: named_graphs,
+            where_clause: if patterns.is_empty() {
+                "WHERE { }".to_string()
+            } else {
+                format!("WHERE {{\n  {}\n}}", patterns.join("\n  "))
+            },
+            group_by,
+            order_by,
+            limit: parsed.modifiers.limit,
+            offset: parsed.modifiers.offset,
+            distinct: parsed.modifiers.distinct,
+            reduced: parsed.modifiers.reduced,
+            has_aggregates: parsed.modifiers.has_aggregates,
+            original_query: query.to_string(),
+        };
+
+        let serialized = canonical.to_query_string();
+        let mut lines: Vec<String> = serialized.lines().map(str::to_string).collect();
+
+        // The windows are declared before the WHERE clause, and REGISTER before the query form
+        let where_line = lines
+            .iter()
+            .position(|line| line.starts_with("WHERE"))
+            .unwrap_or(lines.len());
+        let mut windows = parsed.windows.clone();
+        windows.sort();
+        for window in windows.iter().rev() {
+            let parameters = match (window.start, window.end, window.offset) {
+                (Some(start), Some(end), _) => format!("START {} END {}", start, end),
+                (_, _, Some(offset)) => format!(
+                    "OFFSET {} RANGE {} STEP {}",
+                    offset, window.width, window.slide
+                ),
+                _ => format!("RANGE {} STEP {}", window.width, window.slide),
+            };
+            let declaration = format!(
+                "FROM NAMED WINDOW <{}> ON STREAM <{}> [{}]",
+                window.window_name, window.stream_name, parameters
+            );
+            lines.insert(where_line, declaration);
+        }
+        if let Some((operator, stream)) = parsed.r2s_operator.zip(parsed.output_stream) {
+            let form_line = lines
+                .iter()
+                .position(|line| !line.is_empty() && !line.starts_with("PREFIX"))
+                .unwrap_or(0);
+            lines.insert(form_line, format!("REGISTER {} <{}> AS", operator, stream));
+        }
+        Ok(lines.join("\n"))
+    }
+
+    /// Write a VALUES block with its variables and rows in a canonical order
+    fn canonical_values(
+        data: &InlineData,
+        rename_node: impl Fn(&TripleNode) -> TripleNode,
+    ) -> String {
+        let mut columns: Vec<(String, usize)> = data
+            .variables
+            .iter()
+            .enumerate()
+            .map(|(index, variable)| {
+                (
+                    rename_node(&TripleNode::Variable(variable.clone())).to_string(),
+                    index,
+                )
+            })
+            .collect();
+        columns.sort();
+
+        let mut rows: Vec<String> = data
+            .rows
+            .iter()
+            .map(|row| {
+                let cells: Vec<String> = columns
+                    .iter()
+                    .map(|(_, index)| match row.get(*index).cloned().flatten() {
+                        Some(value) => rename_node(&value).to_string(),
+                        None => "UNDEF".to_string(),
+                    })
+                    .collect();
+                format!("({})", cells.join(" "))
+            })
+            .collect();
+        rows.sort();
+
+        let header: Vec<&str> = columns
+            .iter()
+            .map(|(variable, _)| variable.as_str())
+            .collect();
+        format!("VALUES ({}) {{ {} }}", header.join(" "), rows.join(" "))
+    }
+
+    /// Rename the variables of a text to `?v0`, `?v1`, ... and return the renamed text
+    /// together with the original variable names, ordered by their canonical index
+    fn canonicalize_variables(text: &str) -> Result<(String, Vec<String>), TulnaError> {
+        let mut var_map: HashMap<String, String> = HashMap::new();
+        let mut names: Vec<String> = Vec::new();
+        let renamed = Self::map_variables(text, |name| {
+            var_map
+                .entry(name.clone())
+                .or_insert_with(|| {
+                    names.push(name);
+                    format!("v{}", names.len() - 1)
+                })
+                .clone()
+        })?;
+        Ok((renamed, names))
+    }
+
+    /// Replace every variable of a text by `?` followed by the name `rename` returns for it
+    ///
+    /// Question marks inside IRIs, string literals and comments are not variables and are
+    /// left untouched.
+    fn map_variables(
+        text: &str,
+        mut rename: impl FnMut(String) -> String,
+    ) -> Result<String, TulnaError> {
+        let chars: Vec<char> = text.chars().collect();
+        let mut renamed = String::with_capacity(text.len());
+        let mut i = 0;
+
+        while i < chars.len() {
+            let c = chars[i];
+            match c {
+                '"' | '\'' => {
+                    // Copy the string literal verbatim, honouring escaped quotes
+                    let start = i;
+                    i += 1;
+                    while i < chars.len() && chars[i] != c {
+                        if chars[i] == '\\' {
+                            i += 1;
+                        }
+                        i += 1;
+                    }
+                    if i >= chars.len() {
+                        return Err(TulnaError::ParseError(format!(
+                            "Unterminated string literal starting at character {}",
+                            start
+                        )));
+                    }
+                    renamed.extend(&chars[start..=i]);
+                    i += 1;
+                }
+                '<' => {
+                    // An IRI runs up to the next '>' without whitespace; otherwise it's an operator
+                    let end = chars[i + 1..]
+                        .iter()
+                        .position(|&ch| ch == '>' || ch.is_whitespace())
+                        .map(|offset| i + 1 + offset);
+                    match end {
+                        Some(end) if chars[end] == '>' => {
+                            renamed.extend(&chars[i..=end]);
+                            i = end + 1;
+                        }
+                        _ => {
+                            renamed.push(c);
+                            i += 1;
+                        }
+                    }
+                }
+                '#' => {
+                    while i < chars.len() && chars[i] != '\n' {
+                        renamed.push(chars[i]);
+                        i += 1;
+                    }
+                }
+                '?' | '$' => {
+                    let start = i + 1;
+                    let mut end = start;
+                    while end < chars.len() && (chars[end].is_alphanumeric() || chars[end] == '_') {
+                        end += 1;
+                    }
+                    if end == start {
+                        renamed.push(c);
+                    } else {
+                        renamed.push('?');
+                        renamed.push_str(&rename(chars[start..end].iter().collect()));
+                    }
+                    i = end;
+                }
+                _ => {
+                    renamed.push(c);
+                    i += 1;
+                }
+            }
+        }
+
+        Ok(renamed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect_sparql() {
+        let query = "SELECT ?s ?p ?o WHERE { ?s ?p ?o }";
+        assert_eq!(
+            QueryIsomorphism::detect_query_type(query),
+            QueryLanguage::SPARQL
+        );
+    }
+
+    #[test]
+    fn test_detect_rspql() {
+        let query = "REGISTER STREAM <output> AS SELECT ?s ?p ?o FROM NAMED WINDOW <w> ON STREAM <s> [RANGE 10 STEP 5]";
+        assert_eq!(
+            QueryIsomorphism::detect_query_type(query),
+            QueryLanguage::RSPQL
+        );
+    }
+
+    #[test]
+    fn test_parse_node_variable() {
+        let node = QueryIsomorphism::parse_node("?var", &HashMap::new());
+        assert!(matches!(node, TripleNode::Variable(_)));
+    }
+
+    #[test]
+    fn test_parse_node_iri() {
+        let node = QueryIsomorphism::parse_node("<http://example.org/resource>", &HashMap::new());
+        assert!(matches!(node, TripleNode::IRI(_)));
+    }
+
+    #[test]
+    fn test_bgp_extraction() {
+        let where_clause = "WHERE { ?s <http://example.org/p> ?o . }";
+        let bgp = QueryIsomorphism::extract_bgp_from_where(
+            where_clause,
+            &HashMap::new(),
+            &mut FreshLabels::default(),
+        )
+        .unwrap();
+        assert_eq!(bgp.len(), 1);
+    }
+
+    #[test]
+    fn test_bgp_extraction_with_lists() {
+        let where_clause = "WHERE { ?s <http://p> ?o ; <http://q> ?o2 . }";
+        let bgp = QueryIsomorphism::extract_bgp_from_where(
+            where_clause,
+            &HashMap::new(),
+            &mut FreshLabels::default(),
+        )
+        .unwrap();
+        assert_eq!(bgp.len(), 2);
+        assert_eq!(bgp[0].subject, bgp[1].subject);
+    }
+    
+    #[test]
+    fn test_bgp_extraction_with_trailing_semicolons() {
+        let where_clause = "WHERE {
+            ?s <http://p> ?o ;
+               <http://q> ?o2 ; ;
+               <http://r> ?o3 ;
+            .
+            ?x <http://p> ?y ;
+        }";
+        let bgp = QueryIsomorphism::extract_bgp_from_where(
+            where_clause,
+            &HashMap::new(),
+            &mut FreshLabels::default(),
+        )
+        .unwrap();
+        assert_eq!(bgp.len(), 4);
+        assert!(bgp[..3].iter().all(|t| t.subject == bgp[0].subject));
+        assert_eq!(bgp[2].predicate, TripleNode::IRI("http://r".to_string()));
+        assert_eq!(bgp[3].subject, TripleNode::Variable("x".to_string()));
+    }
+
+    #[test]
     fn test_bgp_extraction_with_commas() {
         let where_clause = "WHERE { ?s <http://p> ?o , ?o2 . }";
-        let bgp = QueryIsomorphism::extract_bgp_from_where(where_clause).unwrap();
+        let bgp = QueryIsomorphism::extract_bgp_from_where(
+            where_clause,
+            &HashMap::new(),
+            &mut FreshLabels::default(),
+        )
+        .unwrap();
         assert_eq!(bgp.len(), 2);
         assert_eq!(bgp[0].subject, bgp[1].subject);
         assert_eq!(bgp[0].predicate, bgp[1].predicate);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_bgp_extraction_with_commas_and_semicolons() {
+        let where_clause = "WHERE { ?s foaf:knows ?a, ?b, ?c . ?s :p ?d, ?e ; :q ?f . }";
+        let bgp = QueryIsomorphism::extract_bgp_with_tokenizer(
+            where_clause,
+            &HashMap::new(),
+            &mut FreshLabels::default(),
+        )
+        .unwrap();
+        assert_eq!(bgp.len(), 6);
+        assert!(bgp
+            .iter()
+            .all(|t| t.subject == TripleNode::Variable("s".to_string())));
+
+        let objects: Vec<&TripleNode> = bgp.iter().map(|t| &t.object).collect();
+        let expected: Vec<TripleNode> = ["a", "b", "c", "d", "e", "f"]
+            .iter()
+            .map(|v| TripleNode::Variable(v.to_string()))
+            .collect();
+        assert_eq!(objects, expected.iter().collect::<Vec<_>>());
+
+        assert!(bgp[..3].iter().all(|t| t.predicate == bgp[0].predicate));
+        assert_eq!(bgp[3].predicate, TripleNode::IRI(":p".to_string()));
+        assert_eq!(bgp[4].predicate, TripleNode::IRI(":p".to_string()));
+        assert_eq!(bgp[5].predicate, TripleNode::IRI(":q".to_string()));
+    }
+
+    #[test]
+    fn test_parse_node_expands_known_prefixes() {
+        let prefixes = HashMap::from([
+            ("ex".to_string(), "http://example.org/".to_string()),
+            (
+                "xsd".to_string(),
+                "http://www.w3.org/2001/XMLSchema#".to_string(),
+            ),
+        ]);
+
+        assert_eq!(
+            QueryIsomorphism::parse_node("ex:property1", &prefixes),
+            TripleNode::IRI("http://example.org/property1".to_string())
+        );
+        assert_eq!(
+            QueryIsomorphism::parse_node("unknown:property1", &prefixes),
+            TripleNode::IRI("unknown:property1".to_string())
+        );
+        assert_eq!(
+            QueryIsomorphism::parse_node("\"30\"^^xsd:integer", &prefixes),
+            TripleNode::TypedLiteral {
+                value: "30".to_string(),
+                datatype: "http://www.w3.org/2001/XMLSchema#integer".to_string(),
+            }
+        );
+    }
+
+    #[test]
+    fn test_triple_node_kind_helpers() {
+        let mixed = Triple {
+            subject: TripleNode::Variable("s".to_string()),
+            predicate: TripleNode::IRI("http://p".to_string()),
+            object: TripleNode::Literal("o".to_string()),
+            graph: None,
+        };
+        assert!(mixed.has_variable());
+        assert!(mixed.has_iri());
+        assert!(mixed.has_literal());
+        assert!(!mixed.has_blank_node());
+
+        let blank = Triple {
+            subject: TripleNode::BlankNode("b".to_string()),
+            predicate: TripleNode::IRI("http://p".to_string()),
+            object: TripleNode::IRI("http://o".to_string()),
+            graph: None,
+        };
+        assert!(blank.has_blank_node());
+        assert!(blank.has_iri());
+        assert!(!blank.has_variable());
+        assert!(!blank.has_literal());
+    }
+
+    #[test]
+    fn test_normalize_aggregate_expression() {
+        assert_eq!(
+            QueryIsomorphism::normalize_expression("( sum( ?v0 )  as ?v1 )"),
+            "(SUM(?v0) AS ?v1)"
+        );
+        assert_eq!(
+            QueryIsomorphism::normalize_expression(
+                "(group_concat(?v0 ; separator = \"( , )\") AS ?v1)"
+            ),
+            "(GROUP_CONCAT(?v0;SEPARATOR=\"( , )\") AS ?v1)"
+        );
+        assert_eq!(
+            QueryIsomorphism::normalize_expression("(max(?v0) AS ?v1)"),
+            QueryIsomorphism::normalize_expression("(MAX( ?v0 ) AS ?v1)")
+        );
+    }
+
+    #[test]
+    fn test_parse_projection_with_separator() {
+        let items =
+            QueryIsomorphism::parse_projection("?g (GROUP_CONCAT(?n; SEPARATOR=\") \") AS ?names)");
+        assert_eq!(
+            items,
+            vec!["?g", "(GROUP_CONCAT(?n; SEPARATOR=\") \") AS ?names)"]
+        );
+    }
+
+    #[test]
+    fn test_parse_node_typed_literal() {
+        assert_eq!(
+            QueryIsomorphism::parse_node("\"30\"^^xsd:integer", &HashMap::new()),
+            TripleNode::TypedLiteral {
+                value: "30".to_string(),
+                datatype: "xsd:integer".to_string(),
+            }
+        );
+        assert_eq!(
+            QueryIsomorphism::parse_node(
+                "\"30\"^^<http://www.w3.org/2001/XMLSchema#string>",
+                &HashMap::new()
+            ),
+            TripleNode::TypedLiteral {
+                value: "30".to_string(),
+                datatype: "http://www.w3.org/2001/XMLSchema#string".to_string(),
+            }
+        );
+        assert_eq!(
+            QueryIsomorphism::parse_node("\"30\"", &HashMap::new()),
+            TripleNode::Literal("30".to_string())
+        );
+    }
+
+    #[test]
+    fn test_parse_node_bare_literals() {
+        let typed = |value: &str, datatype: &str| TripleNode::TypedLiteral {
+            value: value.to_string(),
+            datatype: format!("{}{}", XSD_NAMESPACE, datatype),
+        };
+        let parse = |node| QueryIsomorphism::parse_node(node, &HashMap::new());
+
+        assert_eq!(parse("42"), typed("42", "integer"));
+        assert_eq!(parse("-7"), typed("-7", "integer"));
+        assert_eq!(parse("3.14"), typed("3.14", "decimal"));
+        assert_eq!(parse(".5"), typed(".5", "decimal"));
+        assert_eq!(parse("1.5E-3"), typed("1.5E-3", "double"));
+        assert_eq!(parse("true"), typed("true", "boolean"));
+        assert_eq!(parse("1e"), TripleNode::iri("1e"));
+        assert_eq!(parse("."), TripleNode::iri("."));
+
+        let bgp = QueryIsomorphism::extract_bgp_with_tokenizer(
+            "{ ?s <http://ex.org/p> 3.14 . ?s <http://ex.org/q> 2. }",
+            &HashMap::new(),
+            &mut FreshLabels::default(),
+        )
+        .unwrap();
+        assert_eq!(bgp.len(), 2);
+        assert_eq!(bgp[0].object, typed("3.14", "decimal"));
+        assert_eq!(bgp[1].object, typed("2", "integer"));
+    }
+
+    #[test]
+    fn test_parse_node_lang_literal() {
+        assert_eq!(
+            QueryIsomorphism::parse_node("\"hello\"@en", &HashMap::new()),
+            TripleNode::LangLiteral {
+                value: "hello".to_string(),
+                lang: "en".to_string(),
+            }
+        );
+        assert_eq!(
+            QueryIsomorphism::parse_node("\"colour\"@en-GB", &HashMap::new()),
+            QueryIsomorphism::parse_node("\"colour\"@en-gb", &HashMap::new())
+        );
+        assert_ne!(
+            QueryIsomorphism::parse_node("\"hello\"@en", &HashMap::new()),
+            QueryIsomorphism::parse_node("\"hello\"@fr", &HashMap::new())
+        );
+    }
+
+    #[test]
+    fn test_isomorphism_query_equality_ignores_triple_order() {
+        let query1 = QueryIsomorphism::parse_query(
+            "SELECT ?s ?o WHERE { ?s <http://example.org/p> ?o . ?o <http://example.org/q> ?s . }",
+        )
+        .unwrap();
+        let query2 = QueryIsomorphism::parse_query(
+            "SELECT ?s ?o WHERE { ?o <http://example.org/q> ?s . ?s <http://example.org/p> ?o . }",
+        )
+        .unwrap();
+        let reordered_projection = QueryIsomorphism::parse_query(
+            "SELECT ?o ?s WHERE { ?s <http://example.org/p> ?o . ?o <http://example.org/q> ?s . }",
+        )
+        .unwrap();
+        let renamed = QueryIsomorphism::parse_query(
+            "SELECT ?x ?y WHERE { ?x <http://example.org/p> ?y . ?y <http://example.org/q> ?x . }",
+        )
+        .unwrap();
+
+        assert_eq!(query1, query2);
+        assert_ne!(query1, reordered_projection);
+        assert_ne!(query1, renamed);
+    }
+
+    #[test]
+    fn test_split_filters() {
+        let where_clause = r#"{ ?s <http://example.org/FILTER(x)> ?o . FILTER (?o > 5) FILTER regex(?s, "FILTER(") ?s ex:filtered ?f . }"#;
+        let (rest, filters) = QueryIsomorphism::split_filters(where_clause, &HashMap::new());
+
+        assert_eq!(filters, vec!["(?o > 5)", r#"regex(?s, "FILTER(")"#]);
+        assert!(rest.contains("<http://example.org/FILTER(x)>"));
+        assert!(rest.contains("ex:filtered ?f"));
+        assert!(!rest.contains("regex"));
+    }
+
+    #[test]
+    fn test_expand_prefixed_names() {
+        let prefixes = HashMap::from([
+            ("ex".to_string(), "http://example.org/".to_string()),
+            (String::new(), "http://default.org/".to_string()),
+        ]);
+        let expand = |expression| QueryIsomorphism::expand_prefixed_names(expression, &prefixes);
+
+        assert_eq!(
+            expand(r#"(ex:f(?ex) = "ex:a"^^ex:t && ?x != :b)"#),
+            r#"(<http://example.org/f>(?ex) = "ex:a"^^<http://example.org/t> && ?x != <http://default.org/b>)"#
+        );
+        assert_eq!(
+            expand("(?x = <ex:a> || ?x = other:a.)"),
+            "(?x = <ex:a> || ?x = other:a.)"
+        );
+    }
+
+    #[test]
+    fn test_extract_inner_braces_flattens_nested_groups() {
+        let content =
+            QueryIsomorphism::extract_inner_braces("WHERE { ?s ?p ?o . GRAPH <g> { ?o ?q ?x } }");
+        assert_eq!(content, "?s ?p ?o . GRAPH <g>  .  ?o ?q ?x  .");
+
+        let bgp = QueryIsomorphism::extract_bgp_from_where(
+            "WHERE { ?s ?p ?o . GRAPH <g> { ?o ?q ?x } }",
+            &HashMap::new(),
+            &mut FreshLabels::default(),
+        )
+        .unwrap();
+        assert_eq!(bgp.len(), 2);
+    }
+
+    #[test]
+    fn test_split_graph_groups() {
+        let where_clause = "{ ?s <http://example.org/p> ?o . GRAPH <http://example.org/g> { ?o ?q \"}\" GRAPH ?h { ?a ?b ?c } } ?s <http://example.org/GRAPH> ?x . WINDOW ex:w{ ?x ?y ?z } }";
+        let (rest, groups) = QueryIsomorphism::split_graph_groups(where_clause);
+
+        assert_eq!(
+            groups,
+            vec![
+                (
+                    "<http://example.org/g>".to_string(),
+                    " ?o ?q \"}\" GRAPH ?h { ?a ?b ?c } ".to_string()
+                ),
+                ("ex:w".to_string(), " ?x ?y ?z ".to_string()),
+            ]
+        );
+        assert!(rest.contains("<http://example.org/GRAPH> ?x"));
+
+        let bgp = QueryIsomorphism::extract_bgp_from_where(
+            where_clause,
+            &HashMap::new(),
+            &mut FreshLabels::default(),
+        )
+        .unwrap();
+        let graphs: Vec<Option<TripleNode>> = bgp.iter().map(|t| t.graph.clone()).collect();
+        assert_eq!(
+            graphs,
+            vec![
+                None,
+                None,
+                Some(TripleNode::iri("http://example.org/g")),
+                Some(TripleNode::var("h")),
+                Some(TripleNode::iri("ex:w")),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_split_optional_groups() {
+        let where_clause = "{ ?s <http://example.org/p> ?o . OPTIONAL { ?o <http://example.org/q> \"}\" } ?s <http://example.org/OPTIONAL> ?x }";
+        let (rest, groups) = QueryIsomorphism::split_optional_groups(where_clause);
+
+        assert_eq!(groups, vec![" ?o <http://example.org/q> \"}\" "]);
+        assert!(rest.contains("<http://example.org/OPTIONAL> ?x"));
+
+        let bgp = QueryIsomorphism::extract_bgp_from_where(
+            &rest,
+            &HashMap::new(),
+            &mut FreshLabels::default(),
+        )
+        .unwrap();
+        assert_eq!(bgp.len(), 2);
+    }
+
+    #[test]
+    fn test_split_unions() {
+        let where_clause = "{ ?s <http://example.org/p> ?o . { ?o <http://example.org/q> \"{\" } union { ?o <http://example.org/UNION> ?x } UNION { } { ?s ?p ?x } }";
+        let (rest, unions) = QueryIsomorphism::split_unions(where_clause);
+
+        assert_eq!(
+            unions,
+            vec![vec![
+                " ?o <http://example.org/q> \"{\" ".to_string(),
+                " ?o <http://example.org/UNION> ?x ".to_string(),
+                " ".to_string(),
+            ]]
+        );
+        assert!(rest.contains("{ ?s ?p ?x }"));
+
+        let bgp = QueryIsomorphism::extract_bgp_from_where(
+            &rest,
+            &HashMap::new(),
+            &mut FreshLabels::default(),
+        )
+        .unwrap();
+        assert_eq!(bgp.len(), 2);
+    }
+
+    #[test]
+    fn test_detect_sparql_with_janusql_keywords() {
+        let queries = [
+            "SELECT ?s WHERE { ?s ?p ?o } LIMIT 10 OFFSET 5",
+            "SELECT ?start ?end WHERE { ?start <http://example.org/to> ?end }",
+            "SELECT ?s WHERE { ?s <http://example.org/range> ?step } OFFSET 5",
+        ];
+        for query in queries {
+            assert_eq!(
+                QueryIsomorphism::detect_query_type(query),
+                QueryLanguage::SPARQL,
+                "Misclassified: {}",
+                query
+            );
+        }
+    }
+
+    #[test]
+    fn test_detect_janusql() {
+        let sliding = "SELECT ?s FROM NAMED WINDOW <w> ON STREAM <s> [OFFSET 0 RANGE 10 STEP 5] WHERE { WINDOW <w> { ?s ?p ?o } }";
+        let fixed = "SELECT ?s FROM NAMED WINDOW <w> ON STREAM <s> [START 0 END 100] WHERE { WINDOW <w> { ?s ?p ?o } }";
+        assert_eq!(
+            QueryIsomorphism::detect_query_type(sliding),
+            QueryLanguage::JanusQL
+        );
+        assert_eq!(
+            QueryIsomorphism::detect_query_type(fixed),
+            QueryLanguage::JanusQL
+        );
+    }
+
+    #[test]
+    fn test_query_language_display_round_trip() {
+        let parse = |name: &str| name.parse::<QueryLanguage>();
+        for language in [
+            QueryLanguage::SPARQL,
+            QueryLanguage::RSPQL,
+            QueryLanguage::JanusQL,
+        ] {
+            let name = language.to_string();
+            assert_eq!(parse(&name).unwrap(), language);
+            assert_eq!(parse(&name.to_lowercase()).unwrap(), language);
+            assert_eq!(parse(&name.to_uppercase()).unwrap(), language);
+        }
+
+        assert_eq!(QueryLanguage::SPARQL.to_string(), "SPARQL");
+        assert_eq!(QueryLanguage::RSPQL.to_string(), "RSP-QL");
+        assert_eq!(QueryLanguage::JanusQL.to_string(), "JanusQL");
+        assert_eq!(parse("RSPQL").unwrap(), QueryLanguage::RSPQL);
+        assert!(matches!(parse("Cypher"), Err(TulnaError::InvalidInput(_))));
+    }
+
+    #[test]
+    fn test_extract_bgp_with_anonymous_blank_nodes() {
+        let where_clause =
+            "WHERE { ?s <http://ex.org/p> [ <http://ex.org/q> ?o ; <http://ex.org/r> [] ] . }";
+        let bgp = QueryIsomorphism::extract_bgp_with_tokenizer(
+            where_clause,
+            &HashMap::new(),
+            &mut FreshLabels::default(),
+        )
+        .unwrap();
+
+        let iri = |local: &str| TripleNode::IRI(format!("http://ex.org/{}", local));
+        let outer = TripleNode::BlankNode("tulna#anon_0".to_string());
+        let inner = TripleNode::BlankNode("tulna#anon_1".to_string());
+        assert_eq!(
+            bgp,
+            vec![
+                Triple {
+                    subject: TripleNode::Variable("s".to_string()),
+                    predicate: iri("p"),
+                    object: outer.clone(),
+                    graph: None,
+                },
+                Triple {
+                    subject: outer.clone(),
+                    predicate: iri("q"),
+                    object: TripleNode::Variable("o".to_string()),
+                    graph: None,
+                },
+                Triple {
+                    subject: outer,
+                    predicate: iri("r"),
+                    object: inner,
+                    graph: None,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_extract_bgp_with_collections() {
+        let where_clause = "WHERE { ?s <http://ex.org/p> (<http://ex.org/a> (1) ()) . \
+                            ?s (<http://ex.org/q>|<http://ex.org/r>)+ ?o }";
+        let bgp = QueryIsomorphism::extract_bgp_with_tokenizer(
+            where_clause,
+            &HashMap::new(),
+            &mut FreshLabels::default(),
+        )
+        .unwrap();
+
+        let list = |n: usize| TripleNode::BlankNode(format!("tulna#list_{}", n));
+        let nil = || TripleNode::iri(RDF_NIL);
+        let triple = |subject, predicate: &str, object| Triple {
+            subject,
+            predicate: TripleNode::iri(predicate),
+            object,
+            graph: None,
+        };
+        let one = TripleNode::TypedLiteral {
+            value: "1".to_string(),
+            datatype: format!("{}integer", XSD_NAMESPACE),
+        };
+        assert_eq!(
+            bgp,
+            vec![
+                triple(TripleNode::var("s"), "http://ex.org/p", list(0)),
+                Triple {
+                    subject: TripleNode::var("s"),
+                    predicate: TripleNode::PropertyPath(
+                        "(<http://ex.org/q>|<http://ex.org/r>)+".to_string()
+                    ),
+                    object: TripleNode::var("o"),
+                    graph: None,
+                },
+                triple(list(1), RDF_FIRST, one),
+                triple(list(1), RDF_REST, nil()),
+                triple(list(0), RDF_FIRST, TripleNode::iri("http://ex.org/a")),
+                triple(list(0), RDF_REST, list(3)),
+                triple(list(3), RDF_FIRST, list(1)),
+                triple(list(3), RDF_REST, list(4)),
+                triple(list(4), RDF_FIRST, nil()),
+                triple(list(4), RDF_REST, nil()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_triple_node_from_str() {
+        assert_eq!(TripleNode::from("?x"), TripleNode::var("x"));
+        assert_eq!(
+            TripleNode::from("<http://ex.org/a>"),
+            TripleNode::iri("http://ex.org/a")
+        );
+        assert_eq!(
+            TripleNode::from("http://ex.org/a"),
+            TripleNode::iri("http://ex.org/a")
+        );
+        assert_eq!(TripleNode::from("\"Alice\""), TripleNode::lit("Alice"));
+        assert_eq!(
+            TripleNode::from("\"hello\"@en"),
+            TripleNode::LangLiteral {
+                value: "hello".to_string(),
+                lang: "en".to_string(),
+            }
+        );
+        assert_eq!(
+            TripleNode::from("_:b0"),
+            TripleNode::BlankNode("b0".to_string())
+        );
+    }
+
+    #[test]
+    fn test_triple_node_display() {
+        assert_eq!(TripleNode::var("x").to_string(), "?x");
+        assert_eq!(
+            TripleNode::iri("http://ex.org/a").to_string(),
+            "<http://ex.org/a>"
+        );
+        assert_eq!(TripleNode::lit("Alice").to_string(), "\"Alice\"");
+        assert_eq!(
+            TripleNode::from("\"30\"^^<http://www.w3.org/2001/XMLSchema#integer>").to_string(),
+            "\"30\"^^<http://www.w3.org/2001/XMLSchema#integer>"
+        );
+        assert_eq!(TripleNode::BlankNode("b0".to_string()).to_string(), "_:b0");
+    }
+
+    #[test]
+    fn test_validate_brackets() {
+        let valid = "SELECT ?s WHERE { ?s <http://ex.org/p#q> \"}\" . # a } in a comment\n\
+                     FILTER(?s < 5 && ?s <= 10) { ?s ?p ?o } }";
+        assert!(QueryIsomorphism::validate_brackets(valid).is_ok());
+
+        // The `#` of a datatype IRI does not start a comment, even with more on the line
+        let typed = "SELECT ?s WHERE { ?s <http://ex.org/p> \"5\"^^<http://www.w3.org/2001/XMLSchema#integer> . ?s ?p ?o }";
+        assert!(QueryIsomorphism::validate_brackets(typed).is_ok());
+
+        for invalid in [
+            "SELECT ?s WHERE { ?s ?p ?o .",
+            "SELECT ?s WHERE { ?s ?p \"5\"^^<http://www.w3.org/2001/XMLSchema#integer . }",
+            "SELECT ?s WHERE { ?s ?p ?o . } }",
+            "SELECT ?s WHERE { ?s <http://ex.org/p ?o . }",
+        ] {
+            assert!(matches!(
+                QueryIsomorphism::validate_brackets(invalid),
+                Err(TulnaError::UnbalancedBraces(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_split_values() {
+        let where_clause =
+            "WHERE { ?s ?p ?o . VALUES (?s ?o) { (<http://ex.org/a> \"x y\") (UNDEF 5) } }";
+        let (rest, blocks) = QueryIsomorphism::split_values(where_clause);
+        assert!(!rest.contains("VALUES"));
+        assert_eq!(blocks.len(), 1);
+
+        let data = QueryIsomorphism::parse_values_block(&blocks[0], &HashMap::new()).unwrap();
+        assert_eq!(data.variables, vec!["s".to_string(), "o".to_string()]);
+        assert_eq!(
+            data.rows,
+            vec![
+                vec![
+                    Some(TripleNode::iri("http://ex.org/a")),
+                    Some(TripleNode::lit("x y"))
+                ],
+                vec![
+                    None,
+                    Some(TripleNode::TypedLiteral {
+                        value: "5".to_string(),
+                        datatype: format!("{}integer", XSD_NAMESPACE),
+                    })
+                ],
+            ]
+        );
+
+        let (_, blocks) = QueryIsomorphism::split_values("{ VALUES ?x { 1 2 3 } ?x ?p ?o }");
+        let data = QueryIsomorphism::parse_values_block(&blocks[0], &HashMap::new()).unwrap();
+        assert_eq!(data.variables, vec!["x".to_string()]);
+        assert_eq!(data.rows.len(), 3);
+
+        assert!(QueryIsomorphism::parse_values_block("(?x ?y) { (1) }", &HashMap::new()).is_err());
+    }
+
+    #[test]
+    fn test_parse_predicate_property_paths() {
+        let mut prefixes = HashMap::new();
+        prefixes.insert("foaf".to_string(), "http://xmlns.com/foaf/0.1/".to_string());
+        let path = |token| QueryIsomorphism::parse_predicate(token, &prefixes);
+
+        assert_eq!(
+            path("foaf:knows+"),
+            TripleNode::PropertyPath("<http://xmlns.com/foaf/0.1/knows>+".to_string())
+        );
+        assert_eq!(
+            path("foaf:knows+"),
+            path("<http://xmlns.com/foaf/0.1/knows>+")
+        );
+        assert_eq!(
+            path("^foaf:knows/foaf:name"),
+            TripleNode::PropertyPath(
+                "^<http://xmlns.com/foaf/0.1/knows>/<http://xmlns.com/foaf/0.1/name>".to_string()
+            )
+        );
+        assert_eq!(
+            path("(a|foaf:knows)*"),
+            TripleNode::PropertyPath(format!(
+                "(<{}>|<http://xmlns.com/foaf/0.1/knows>)*",
+                RDF_TYPE
+            ))
+        );
+        assert_ne!(path("foaf:knows*"), path("foaf:knows?"));
+
+        assert_eq!(
+            path("foaf:knows"),
+            TripleNode::iri("http://xmlns.com/foaf/0.1/knows")
+        );
+        assert_eq!(path("a"), TripleNode::iri(RDF_TYPE));
+        assert_eq!(path("?p"), TripleNode::var("p"));
+    }
+}
@@ -69,6 +69,12 @@
 //! - Low collision probability
 //! - Fast computation
 //!
+//! Isomorphism checks seed the hash with a value drawn once per process, so that inputs
+//! crafted to collide, which would push the algorithm into expensive speculation, cannot be
+//! prepared in advance. The answer never depends on the seed, and
+//! `GraphIsomorphism::are_isomorphic_seeded` fixes it for reproducible runs. Hashes that
+//! outlive a single comparison, such as `graph_hash` and `canonical_form`, use a fixed seed.
+//!
 //! ## Algorithm Sources
 //!
 //! This implementation is based on:
@@ -125,6 +131,7 @@
 //!         subject: TripleNode::Variable("x".to_string()),
 //!         predicate: TripleNode::IRI("http://example.org/knows".to_string()),
 //!         object: TripleNode::Variable("y".to_string()),
+//!         graph: None,
 //!     }
 //! ];
 //!
@@ -133,6 +140,7 @@
 //!         subject: TripleNode::Variable("a".to_string()),
 //!         predicate: TripleNode::IRI("http://example.org/knows".to_string()),
 //!         object: TripleNode::Variable("b".to_string()),
+//!         graph: None,
 //!     }
 //! ];
 //!
@@ -151,11 +159,13 @@
 //!         subject: TripleNode::Variable("person".to_string()),
 //!         predicate: TripleNode::IRI("http://xmlns.com/foaf/0.1/name".to_string()),
 //!         object: TripleNode::Literal("Alice".to_string()),
+//!         graph: None,
 //!     },
 //!     Triple {
 //!         subject: TripleNode::Variable("person".to_string()),
 //!         predicate: TripleNode::IRI("http://xmlns.com/foaf/0.1/knows".to_string()),
 //!         object: TripleNode::Variable("friend".to_string()),
+//!         graph: None,
 //!     },
 //! ];
 //!
@@ -165,11 +175,13 @@
 //!         subject: TripleNode::Variable("x".to_string()),
 //!         predicate: TripleNode::IRI("http://xmlns.com/foaf/0.1/name".to_string()),
 //!         object: TripleNode::Literal("Alice".to_string()),
+//!         graph: None,
 //!     },
 //!     Triple {
 //!         subject: TripleNode::Variable("x".to_string()),
 //!         predicate: TripleNode::IRI("http://xmlns.com/foaf/0.1/knows".to_string()),
 //!         object: TripleNode::Variable("y".to_string()),
+//!         graph: None,
 //!     },
 //! ];
 //!
@@ -177,9 +189,106 @@
 //! ```
 
 use crate::isomorphism::core::{Triple, TripleNode};
+use crate::parsing::turtle_parser::TurtleParser;
 use crate::TulnaError;
-use std::collections::{HashMap, HashSet};
-use std::io::Cursor;
+use std::borrow::Cow;
+use std::collections::hash_map::RandomState;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::fmt;
+use std::hash::BuildHasher;
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+
+/// Coarse outcome of comparing two graphs.
+///
+/// Each non-isomorphic variant names the check that ruled out a bijection, in the order
+/// the algorithm performs them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IsoResult {
+    /// The graphs are isomorphic
+    Isomorphic,
+    /// The graphs have a different number of triples
+    DifferentSize,
+    /// The triples without blank nodes differ between the graphs
+    DifferentGroundTriples,
+    /// The graphs have a different number of blank nodes
+    DifferentBlankCount,
+    /// No bijection between the blank nodes preserves the graph structure
+    NoBijection,
+}
+
+impl fmt::Display for IsoResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let description = match self {
+            IsoResult::Isomorphic => "the graphs are isomorphic",
+            IsoResult::DifferentSize => "the number of triples differs",
+            IsoResult::DifferentGroundTriples => "the triples without blank nodes differ",
+            IsoResult::DifferentBlankCount => "the number of blank nodes differs",
+            IsoResult::NoBijection => {
+                "no bijection between the blank nodes preserves the structure"
+            }
+        };
+        f.write_str(description)
+    }
+}
+
+/// Differences between two graphs, as found by `GraphIsomorphism::diff`.
+///
+/// Every field compares a property that isomorphic graphs share, so whatever is listed
+/// here rules out isomorphism. Variables count as blank nodes, as in `are_isomorphic`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct GraphDiff {
+    /// Triples without blank nodes that are in the first graph but not in the second
+    pub only_in_first: Vec<Triple>,
+    /// Triples without blank nodes that are in the second graph but not in the first
+    pub only_in_second: Vec<Triple>,
+    /// Number of distinct blank nodes in the first and in the second graph
+    pub blank_node_counts: (usize, usize),
+    /// Blank nodes of the first and of the second graph that share a hash signature, for
+    /// every signature held by a different number of nodes in each graph
+    pub signature_mismatches: Vec<(Vec<TripleNode>, Vec<TripleNode>)>,
+}
+
+impl GraphDiff {
+    /// Check whether no difference was found.
+    ///
+    /// Graphs without differences are not necessarily isomorphic: hashing cannot tell every
+    /// pair of non-isomorphic graphs apart, and `are_isomorphic` has the final say.
+    pub fn is_empty(&self) -> bool {
+        self.only_in_first.is_empty()
+            && self.only_in_second.is_empty()
+            && self.blank_node_counts.0 == self.blank_node_counts.1
+            && self.signature_mismatches.is_empty()
+    }
+}
+
+/// How variables and blank nodes may be matched against each other.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum NodeMode {
+    /// Variables and blank nodes are interchangeable, so a variable may map onto a blank node
+    #[default]
+    Lenient,
+    /// Variables only map onto variables and blank nodes only onto blank nodes, as a variable
+    /// can be observed in the results of a query while a blank node cannot
+    Strict,
+}
+
+/// How the literals of a data graph may be matched against the literals of a pattern graph.
+///
+/// The default matches literals exactly, as `are_isomorphic` does.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LiteralMatching {
+    /// Match language tags by basic BCP47 filtering, so that `"foo"@en-US` in the data
+    /// matches the range `"foo"@en` in the pattern
+    pub lang_range_matching: bool,
+    /// Datatype IRIs to treat as another one, keyed by the alias and written without angle
+    /// brackets, e.g. `http://www.w3.org/2001/XMLSchema#int` mapped to
+    /// `http://www.w3.org/2001/XMLSchema#integer`. Aliases are not followed transitively.
+    pub datatype_aliases: HashMap<String, String>,
+}
+
+/// Predicate of the triples marking the variables of a graph compared in `NodeMode::Strict`
+const VARIABLE_MARKER: &str = "<urn:tulna:variable>";
 
 /// Graph isomorphism checker for RDF graphs using hash-based grounding algorithm.
 ///
@@ -187,7 +296,470 @@ use std::io::Cursor;
 /// documentation for detailed information about the algorithm implementation.
 pub struct GraphIsomorphism;
 
+/// Number of speculative groundings and time a bijection search may spend before giving up.
+struct SearchBudget {
+    max_speculations: usize,
+    remaining: usize,
+    exceeded: bool,
+    /// Instant the search has to stop at, along with the timeout it was derived from
+    deadline: Option<(Instant, Duration)>,
+    /// Number of deadline checks so far, used to only read the clock now and then
+    checks: usize,
+    timed_out: bool,
+}
+
+impl SearchBudget {
+    /// Number of deadline checks between two reads of the clock
+    const CLOCK_INTERVAL: usize = 64;
+
+    fn new(max_speculations: usize) -> Self {
+        Self {
+            max_speculations,
+            remaining: max_speculations,
+            exceeded: false,
+            deadline: None,
+            checks: 0,
+            timed_out: false,
+        }
+    }
+
+    /// Stop the search once `timeout` has passed from now
+    fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.deadline = Instant::now()
+            .checked_add(timeout)
+            .map(|deadline| (deadline, timeout));
+        self
+    }
+
+    /// Check whether the deadline has passed, marking the budget as exceeded if so.
+    ///
+    /// The clock is only read on every `CLOCK_INTERVAL`th call, starting with the first.
+    fn out_of_time(&mut self) -> bool {
+        if let Some((deadline, _)) = self.deadline {
+            if self.checks % Self::CLOCK_INTERVAL == 0 && Instant::now() >= deadline {
+                self.timed_out = true;
+                self.exceeded = true;
+            }
+            self.checks += 1;
+        }
+        self.timed_out
+    }
+
+    /// Turn the outcome of a search into an error if the budget ran out during it
+    fn check<T>(&self, outcome: T) -> Result<T, TulnaError> {
+        if let Some((_, timeout)) = self.deadline.filter(|_| self.timed_out) {
+            return Err(TulnaError::Timeout(format!(
+                "no answer within {:?}",
+                timeout
+            )));
+        }
+        if self.exceeded {
+            return Err(TulnaError::LimitExceeded(format!(
+                "no answer after {} speculative groundings",
+                self.max_speculations
+            )));
+        }
+        Ok(outcome)
+    }
+
+    /// Take one speculation from the budget, returning `false` once it is used up
+    fn spend(&mut self) -> bool {
+        if self.remaining == 0 {
+            self.exceeded = true;
+            return false;
+        }
+        self.remaining -= 1;
+        true
+    }
+}
+
+/// Grounded and ungrounded hashes of the blank nodes of a graph, as returned by `hash_terms`.
+type TermHashes = (HashMap<String, u64>, HashMap<String, u64>);
+
+/// Hash a blank node got in one iteration of `hash_terms`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct IterationHash {
+    hash: u64,
+    /// Whether `hash_term` grounded the node, as all its neighbours were grounded
+    grounded: bool,
+    /// Whether the hash was taken from an earlier version of the graph instead of computed
+    reused: bool,
+}
+
+/// Hashes the blank nodes of a graph got in the iterations of `hash_terms`.
+///
+/// A node keeps its hash until it is hashed again, so only the hashes computed in an
+/// iteration are stored for it.
+#[derive(Debug, Clone, Default)]
+struct IterationHistory {
+    /// Number of iterations
+    count: usize,
+    /// The hashes computed for each blank node, with the index of their iteration
+    hashes: HashMap<String, Vec<(usize, IterationHash)>>,
+    /// Index of the first iteration each grounded blank node no longer takes part in
+    grounded: HashMap<String, usize>,
+}
+
+impl IterationHistory {
+    /// The hash `term` had in iteration `index`, if it was not grounded before
+    fn get(&self, term: &str, index: usize) -> Option<IterationHash> {
+        if self.grounded.get(term).is_some_and(|&until| index >= until) {
+            return None;
+        }
+        let hashes = self.hashes.get(term)?;
+        let computed = hashes.partition_point(|(iteration, _)| *iteration <= index);
+        Some(hashes[computed.checked_sub(1)?].1)
+    }
+}
+
+/// Hashes of the blank nodes of a graph, with the hash every blank node got in each
+/// iteration of `hash_terms`, so they can be updated after a few triples change.
+#[derive(Debug, Clone, Default)]
+struct HashHistory {
+    hashes: TermHashes,
+    iterations: IterationHistory,
+}
+
+/// Hashes of an earlier version of a graph, with the blank nodes whose triples changed
+/// since.
+#[derive(Debug, Clone)]
+struct StaleHashes {
+    history: HashHistory,
+    changed: HashSet<String>,
+}
+
+impl StaleHashes {
+    /// The hash `term` got in iteration `index` of the earlier version, if hashing it again
+    /// would give the same result.
+    ///
+    /// That is the case when its triples did not change and each of its `neighbours` is
+    /// grounded with the same hash as it was at that point of the earlier version. Nodes are
+    /// hashed in sorted order, so a neighbour before `term` may have been grounded by
+    /// `hash_term` earlier in the iteration.
+    fn reusable(
+        &self,
+        term: &str,
+        index: usize,
+        neighbours: &HashSet<&str>,
+        hashes: &HashMap<String, u64>,
+    ) -> Option<IterationHash> {
+        if self.changed.contains(term) {
+            return None;
+        }
+        // The last iteration grounded nothing, so any later one would repeat it
+        let iterations = &self.history.iterations;
+        let index = index.min(iterations.count.checked_sub(1)?);
+        let previous = iterations.get(term, index)?;
+
+        let earlier = |neighbour: &str| match iterations.get(neighbour, index) {
+            Some(hashed) => (hashed.grounded && neighbour < term).then_some(hashed.hash),
+            None => self.history.hashes.0.get(neighbour).copied(),
+        };
+        neighbours
+            .iter()
+            .all(|&neighbour| hashes.get(neighbour).copied() == earlier(neighbour))
+            .then_some(IterationHash {
+                reused: true,
+                ..previous
+            })
+    }
+}
+
+/// An RDF graph preprocessed for repeated isomorphism checks.
+///
+/// Created by `GraphIsomorphism::prepare`. The normalized triples, the index of triples
+/// without blank nodes, the blank node set and the initial blank node hashes are computed
+/// once, so comparing the same graph against many candidates only pays for the candidates.
+///
+/// A prepared graph can be updated one triple at a time with `with_added_triple` and
+/// `with_removed_triple`, as when the content of a stream window changes.
+///
+/// With the `binary` feature, a prepared graph can be stored with `to_bytes` and reloaded
+/// with `from_bytes`.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PreparedGraph {
+    size: usize,
+    ground_index: HashMap<String, bool>,
+    blank_quads: Vec<NormalizedTriple>,
+    blank_nodes: Vec<String>,
+    /// Seed of the hashes, shared by every graph this one is compared against
+    seed: u32,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    initial_hashes: OnceLock<HashHistory>,
+    /// Hashes of an earlier version of the graph, which `initial_hashes` is updated from
+    #[cfg_attr(feature = "serde", serde(skip))]
+    stale_hashes: Option<StaleHashes>,
+    /// Every normalized triple, duplicates included, kept by `GraphIsomorphism::prepare` so
+    /// the graph can be updated
+    quads: Vec<NormalizedTriple>,
+    /// Normalized identifier of every variable seen so far
+    variables: HashMap<String, String>,
+    variable_count: u32,
+}
+
+impl PreparedGraph {
+    fn new(size: usize, graph: &[NormalizedTriple], seed: u32) -> Self {
+        Self {
+            size,
+            ground_index: GraphIsomorphism::index_graph(
+                &GraphIsomorphism::get_quads_without_blank_nodes(graph),
+            ),
+            blank_quads: GraphIsomorphism::uniq_graph(
+                &GraphIsomorphism::get_quads_with_blank_nodes(graph),
+            ),
+            blank_nodes: GraphIsomorphism::get_graph_blank_nodes(graph),
+            seed,
+            initial_hashes: OnceLock::new(),
+            stale_hashes: None,
+            quads: Vec::new(),
+            variables: HashMap::new(),
+            variable_count: 0,
+        }
+    }
+
+    /// Hashes of the blank nodes before any speculative grounding, computed on first use
+    fn initial_hashes(&self) -> &TermHashes {
+        &self.hash_history().hashes
+    }
+
+    /// Hashes of the blank nodes with their history, computed on first use
+    ///
+    /// After an update, only the blank nodes near the changed triples are hashed again.
+    fn hash_history(&self) -> &HashHistory {
+        self.initial_hashes.get_or_init(|| {
+            let mut iterations = Some(IterationHistory::default());
+            let hashes = GraphIsomorphism::hash_terms_reusing(
+                &self.blank_quads,
+                &self.blank_nodes,
+                &HashMap::new(),
+                self.seed,
+                &mut None,
+                self.stale_hashes.as_ref(),
+                &mut iterations,
+            );
+            HashHistory {
+                hashes,
+                iterations: iterations.unwrap_or_default(),
+            }
+        })
+    }
+
+    /// Forget the hashes after the triples of the blank nodes `changed` changed, keeping
+    /// them to update the hashes from
+    fn invalidate_hashes<'a>(&mut self, changed: impl Iterator<Item = &'a String>) {
+        let stale = match self.initial_hashes.take() {
+            Some(history) => Some(StaleHashes {
+                history,
+                changed: HashSet::new(),
+            }),
+            None => self.stale_hashes.take(),
+        };
+        self.stale_hashes = stale.map(|mut stale| {
+            stale.changed.extend(changed.cloned());
+            stale
+        });
+    }
+
+    /// Check if `graph` is isomorphic to the prepared graph.
+    ///
+    /// Gives the same answer as calling `GraphIsomorphism::are_isomorphic` with the prepared
+    /// graph and `graph`, including the default speculation limit.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tulna_rs::graph::{GraphIsomorphism, Triple};
+    ///
+    /// let reference = vec![Triple::new("?x", "<http://example.org/knows>", "?y")];
+    /// let prepared = GraphIsomorphism::prepare(&reference);
+    ///
+    /// let candidate = vec![Triple::new("?a", "<http://example.org/knows>", "?b")];
+    /// let self_loop = vec![Triple::new("?a", "<http://example.org/knows>", "?a")];
+    /// assert!(prepared.is_isomorphic_to(&candidate).unwrap());
+    /// assert!(!prepared.is_isomorphic_to(&self_loop).unwrap());
+    /// ```
+    pub fn is_isomorphic_to(&self, graph: &[Triple]) -> Result<bool, TulnaError> {
+        if self.size != graph.len() {
+            return Ok(false);
+        }
+
+        let other = PreparedGraph::new(
+            graph.len(),
+            &GraphIsomorphism::normalize_bgp(graph),
+            self.seed,
+        );
+        self.compare(&other)
+    }
+
+    /// Check if another prepared graph is isomorphic to this one.
+    ///
+    /// Like `is_isomorphic_to`, but reuses the preprocessing of both graphs, so two graphs
+    /// that are both updated incrementally can be compared without preparing either again.
+    pub fn is_isomorphic_to_prepared(&self, other: &PreparedGraph) -> Result<bool, TulnaError> {
+        if self.size != other.size {
+            return Ok(false);
+        }
+        self.compare(other)
+    }
+
+    /// Prepare the graph with `triple` added, as if the whole graph had been prepared again.
+    ///
+    /// Only the added triple is normalized and indexed. The hashes of the blank nodes are
+    /// kept when the triple has no blank nodes or is already in the graph, as they cannot
+    /// change then. Otherwise they are updated on first use, hashing again only the blank
+    /// nodes whose neighbourhood changed; the result is the same as for a graph prepared
+    /// from scratch.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tulna_rs::graph::{GraphIsomorphism, Triple};
+    ///
+    /// let reference = vec![
+    ///     Triple::new("?x", "<http://example.org/knows>", "?y"),
+    ///     Triple::new("?y", "<http://example.org/knows>", "?z"),
+    /// ];
+    /// let reference = GraphIsomorphism::prepare(&reference);
+    ///
+    /// let window = GraphIsomorphism::prepare(&[]);
+    /// let window = window.with_added_triple(&Triple::new("_:a", "<http://example.org/knows>", "_:b"));
+    /// assert!(!window.is_isomorphic_to_prepared(&reference).unwrap());
+    ///
+    /// let window = window.with_added_triple(&Triple::new("_:b", "<http://example.org/knows>", "_:c"));
+    /// assert!(window.is_isomorphic_to_prepared(&reference).unwrap());
+    /// ```
+    pub fn with_added_triple(&self, triple: &Triple) -> PreparedGraph {
+        let mut updated = self.clone();
+        let quad = GraphIsomorphism::normalize_triple(
+            triple,
+            &mut updated.variables,
+            &mut updated.variable_count,
+        );
+        updated.size += 1;
+
+        if !self.quads.contains(&quad) {
+            if quad.is_ground() {
+                updated.ground_index.insert(quad.index_key(), true);
+            } else {
+                for term in quad.terms().filter(|term| term.starts_with("_:")) {
+                    if let Err(position) = updated.blank_nodes.binary_search(term) {
+                        updated.blank_nodes.insert(position, term.clone());
+                    }
+                }
+                updated.blank_quads.push(quad.clone());
+                updated.invalidate_hashes(quad.terms().filter(|term| term.starts_with("_:")));
+            }
+        }
+        updated.quads.push(quad);
+        updated
+    }
+
+    /// Prepare the graph with one occurrence of `triple` removed, as if the whole graph had
+    /// been prepared again.
+    ///
+    /// The triple is matched as written, variable and blank node names included. Removing a
+    /// triple that is not in the graph leaves it unchanged. As in `with_added_triple`, the
+    /// hashes of the blank nodes are kept unless a triple with blank nodes leaves the graph,
+    /// and are then updated around its blank nodes.
+    pub fn with_removed_triple(&self, triple: &Triple) -> PreparedGraph {
+        // A variable that is new to the normalization cannot be in the graph, so the
+        // identifiers assigned here are dropped again
+        let quad = GraphIsomorphism::normalize_triple(
+            triple,
+            &mut self.variables.clone(),
+            &mut self.variable_count.clone(),
+        );
+        let Some(position) = self.quads.iter().position(|other| *other == quad) else {
+            return self.clone();
+        };
+
+        let mut updated = self.clone();
+        updated.quads.remove(position);
+        updated.size -= 1;
+
+        if !updated.quads.contains(&quad) {
+            if quad.is_ground() {
+                updated.ground_index.remove(&quad.index_key());
+            } else {
+                updated.blank_quads.retain(|other| *other != quad);
+                for term in quad.terms().filter(|term| term.starts_with("_:")) {
+                    let still_used = updated
+                        .blank_quads
+                        .iter()
+                        .any(|other| other.terms().any(|other_term| other_term == term));
+                    if !still_used {
+                        updated.blank_nodes.retain(|node| node != term);
+                    }
+                }
+                updated.invalidate_hashes(quad.terms().filter(|term| term.starts_with("_:")));
+            }
+        }
+        updated
+    }
+
+    /// Encode the prepared graph in a compact binary form, which `from_bytes` reloads.
+    ///
+    /// The normalized triples and their indexes are stored, so reloading skips parsing and
+    /// normalizing the graph again. The hashes of the blank nodes are not stored, and are
+    /// computed again on first use.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tulna_rs::graph::{GraphIsomorphism, PreparedGraph, Triple};
+    ///
+    /// let graph = vec![Triple::new("?x", "<http://example.org/knows>", "?y")];
+    /// let bytes = GraphIsomorphism::prepare(&graph).to_bytes();
+    ///
+    /// let reloaded = PreparedGraph::from_bytes(&bytes).unwrap();
+    /// assert!(reloaded.is_isomorphic_to(&graph).unwrap());
+    /// ```
+    #[cfg(feature = "binary")]
+    pub fn to_bytes(&self) -> Vec<u8> {
+        bincode::serialize(self).expect("a prepared graph only holds serializable data")
+    }
+
+    /// Reload a prepared graph stored with `to_bytes`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `TulnaError::ParseError` if the bytes do not encode a prepared graph.
+    #[cfg(feature = "binary")]
+    pub fn from_bytes(bytes: &[u8]) -> Result<PreparedGraph, TulnaError> {
+        bincode::deserialize(bytes)
+            .map_err(|e| TulnaError::ParseError(format!("invalid prepared graph: {}", e)))
+    }
+
+    /// Check for a bijection between two prepared graphs of the same size
+    fn compare(&self, other: &PreparedGraph) -> Result<bool, TulnaError> {
+        Ok(GraphIsomorphism::get_prepared_bijection_or_reason(
+            self,
+            other,
+            &mut None,
+            SearchBudget::new(GraphIsomorphism::DEFAULT_MAX_SPECULATIONS),
+        )?
+        .is_ok())
+    }
+}
+
 impl GraphIsomorphism {
+    /// Default number of speculative groundings a single comparison may try.
+    ///
+    /// Highly symmetric graphs can make the speculative search explore a factorial number of
+    /// branches. All entry points without an explicit limit stop with
+    /// `TulnaError::LimitExceeded` once this many speculations have been tried.
+    pub const DEFAULT_MAX_SPECULATIONS: usize = 1_000;
+
+    /// Seed of the hashes that outlive a single comparison, such as `graph_hash`
+    const FIXED_SEED: u32 = 0;
+
+    /// Seed of the hashes of all comparisons without an explicit seed, drawn once per process
+    fn default_seed() -> u32 {
+        static SEED: OnceLock<u32> = OnceLock::new();
+        *SEED.get_or_init(|| RandomState::new().hash_one("tulna") as u32)
+    }
+
     /// Check if two RDF graphs are isomorphic.
     ///
     /// This is the main public API for graph isomorphism checking. It uses the hash-based
@@ -205,100 +777,1329 @@ impl GraphIsomorphism {
     /// * `Ok(false)` - Graphs are not isomorphic
     /// * `Err(_)` - An error occurred during processing
     ///
+    /// # Empty graphs
+    ///
+    /// Two empty graphs are isomorphic, while an empty graph is never isomorphic to a graph
+    /// with triples. A graph without variables or blank nodes is isomorphic to exactly the
+    /// graphs that hold the same triples.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tulna_rs::graph::{GraphIsomorphism, Triple, TripleNode};
+    ///
+    /// let graph1 = vec![
+    ///     Triple {
+    ///         subject: TripleNode::IRI("http://example.org/alice".to_string()),
+    ///         predicate: TripleNode::IRI("http://example.org/name".to_string()),
+    ///         object: TripleNode::Literal("Alice".to_string()),
+    ///         graph: None,
+    ///     }
+    /// ];
+    ///
+    /// let graph2 = vec![
+    ///     Triple {
+    ///         subject: TripleNode::IRI("http://example.org/alice".to_string()),
+    ///         predicate: TripleNode::IRI("http://example.org/name".to_string()),
+    ///         object: TripleNode::Literal("Alice".to_string()),
+    ///         graph: None,
+    ///     }
+    /// ];
+    ///
+    /// assert!(GraphIsomorphism::are_isomorphic(&graph1, &graph2).unwrap());
+    /// assert!(GraphIsomorphism::are_isomorphic(&[], &[]).unwrap());
+    /// assert!(!GraphIsomorphism::are_isomorphic(&[], &graph1).unwrap());
+    /// ```
+    pub fn are_isomorphic(graph1: &[Triple], graph2: &[Triple]) -> Result<bool, TulnaError> {
+        Self::are_isomorphic_with_limit(graph1, graph2, Self::DEFAULT_MAX_SPECULATIONS)
+    }
+
+    /// Check many pairs of RDF graphs for isomorphism.
+    ///
+    /// Returns the result of `are_isomorphic` for every pair, in the order of `pairs`. With
+    /// the `parallel` feature enabled the pairs are spread over rayon's thread pool; the
+    /// comparison of a single pair stays single-threaded either way.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tulna_rs::graph::{GraphIsomorphism, Triple};
+    ///
+    /// let graph1 = vec![Triple::new("?x", "<http://example.org/knows>", "?y")];
+    /// let graph2 = vec![Triple::new("?a", "<http://example.org/knows>", "?b")];
+    /// let graph3 = vec![Triple::new("?a", "<http://example.org/knows>", "?a")];
+    ///
+    /// let results = GraphIsomorphism::are_isomorphic_batch(&[
+    ///     (graph1.as_slice(), graph2.as_slice()),
+    ///     (graph1.as_slice(), graph3.as_slice()),
+    /// ]);
+    /// assert!(results[0].as_ref().unwrap());
+    /// assert!(!results[1].as_ref().unwrap());
+    /// ```
+    pub fn are_isomorphic_batch(pairs: &[(&[Triple], &[Triple])]) -> Vec<Result<bool, TulnaError>> {
+        #[cfg(feature = "parallel")]
+        {
+            use rayon::prelude::*;
+            pairs
+                .par_iter()
+                .map(|(graph1, graph2)| Self::are_isomorphic(graph1, graph2))
+                .collect()
+        }
+
+        #[cfg(not(feature = "parallel"))]
+        {
+            pairs
+                .iter()
+                .map(|(graph1, graph2)| Self::are_isomorphic(graph1, graph2))
+                .collect()
+        }
+    }
+
+    /// Preprocess a graph that will be compared against many others.
+    ///
+    /// See `PreparedGraph::is_isomorphic_to`.
+    pub fn prepare(graph: &[Triple]) -> PreparedGraph {
+        let mut variables = HashMap::new();
+        let mut variable_count = 0;
+        let quads: Vec<NormalizedTriple> = graph
+            .iter()
+            .map(|triple| Self::normalize_triple(triple, &mut variables, &mut variable_count))
+            .collect();
+
+        let prepared = PreparedGraph::new(graph.len(), &quads, Self::default_seed());
+        PreparedGraph {
+            quads,
+            variables,
+            variable_count,
+            ..prepared
+        }
+    }
+
+    /// Check if two RDF graphs are isomorphic, trying at most `max_speculations` speculative
+    /// groundings.
+    ///
+    /// Graphs that hashing alone cannot ground, such as large symmetric graphs, need
+    /// speculation to find a bijection. This bounds the search so that such inputs fail fast.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(true)` - Graphs are isomorphic
+    /// * `Ok(false)` - Graphs are not isomorphic
+    /// * `Err(TulnaError::LimitExceeded(_))` - The answer needs more speculations
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tulna_rs::graph::{GraphIsomorphism, Triple, TripleNode};
+    /// use tulna_rs::TulnaError;
+    ///
+    /// let next = |s: &str, o: &str| Triple {
+    ///     subject: TripleNode::Variable(s.to_string()),
+    ///     predicate: TripleNode::IRI("http://example.org/next".to_string()),
+    ///     object: TripleNode::Variable(o.to_string()),
+    ///     graph: None,
+    /// };
+    /// let cycle = vec![next("a", "b"), next("b", "c"), next("c", "a")];
+    ///
+    /// assert!(GraphIsomorphism::are_isomorphic_with_limit(&cycle, &cycle, 10).unwrap());
+    /// assert!(matches!(
+    ///     GraphIsomorphism::are_isomorphic_with_limit(&cycle, &cycle, 0),
+    ///     Err(TulnaError::LimitExceeded(_))
+    /// ));
+    /// ```
+    pub fn are_isomorphic_with_limit(
+        graph1: &[Triple],
+        graph2: &[Triple],
+        max_speculations: usize,
+    ) -> Result<bool, TulnaError> {
+        let budget = SearchBudget::new(max_speculations);
+        Ok(
+            Self::find_bijection_with_budget(graph1, graph2, budget, Self::default_seed())?
+                .is_some(),
+        )
+    }
+
+    /// Check if two RDF graphs are isomorphic, seeding the hashes with `seed`.
+    ///
+    /// `are_isomorphic` seeds the hashes with a value drawn once per process. The answer is
+    /// the same for every seed, but the order in which ambiguous blank nodes are tried, and
+    /// thereby the number of speculations a comparison needs, may differ. A fixed seed makes
+    /// that reproducible, for example in tests of the speculation limit.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tulna_rs::graph::{GraphIsomorphism, Triple};
+    ///
+    /// let graph1 = vec![Triple::new("?a", "<http://example.org/knows>", "?b")];
+    /// let graph2 = vec![Triple::new("?x", "<http://example.org/knows>", "?y")];
+    ///
+    /// assert!(GraphIsomorphism::are_isomorphic_seeded(&graph1, &graph2, 42).unwrap());
+    /// ```
+    pub fn are_isomorphic_seeded(
+        graph1: &[Triple],
+        graph2: &[Triple],
+        seed: u32,
+    ) -> Result<bool, TulnaError> {
+        let budget = SearchBudget::new(Self::DEFAULT_MAX_SPECULATIONS);
+        Ok(Self::find_bijection_with_budget(graph1, graph2, budget, seed)?.is_some())
+    }
+
+    /// Check if two RDF graphs are isomorphic, giving up once `timeout` has passed.
+    ///
+    /// Unlike `are_isomorphic`, the number of speculative groundings is not limited, so this
+    /// bounds the time a single comparison may take instead. The clock is only read every few
+    /// steps of the speculative search, so the check may run slightly past the timeout.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(true)` - Graphs are isomorphic
+    /// * `Ok(false)` - Graphs are not isomorphic
+    /// * `Err(TulnaError::Timeout(_))` - No answer was found within `timeout`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::time::Duration;
+    /// use tulna_rs::graph::{GraphIsomorphism, Triple};
+    ///
+    /// let graph1 = vec![Triple::new("?a", "<http://example.org/knows>", "?b")];
+    /// let graph2 = vec![Triple::new("?x", "<http://example.org/knows>", "?y")];
+    ///
+    /// let timeout = Duration::from_secs(1);
+    /// assert!(GraphIsomorphism::are_isomorphic_timeout(&graph1, &graph2, timeout).unwrap());
+    /// ```
+    pub fn are_isomorphic_timeout(
+        graph1: &[Triple],
+        graph2: &[Triple],
+        timeout: Duration,
+    ) -> Result<bool, TulnaError> {
+        let budget = SearchBudget::new(usize::MAX).with_timeout(timeout);
+        Ok(
+            Self::find_bijection_with_budget(graph1, graph2, budget, Self::default_seed())?
+                .is_some(),
+        )
+    }
+
+    /// Check if two RDF graphs are isomorphic, matching variables and blank nodes as set by
+    /// `mode`.
+    ///
+    /// `NodeMode::Lenient` behaves like `are_isomorphic`. In `NodeMode::Strict` every
+    /// variable has to map onto a variable and every blank node onto a blank node, so
+    /// `?s <p> ?o` no longer matches `_:b <p> ?o`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tulna_rs::graph::{GraphIsomorphism, NodeMode, Triple, TripleNode};
+    ///
+    /// let variables = vec![Triple::new("?s", "<http://example.org/p>", "?o")];
+    /// let blank = vec![Triple {
+    ///     subject: TripleNode::BlankNode("b".to_string()),
+    ///     predicate: TripleNode::IRI("http://example.org/p".to_string()),
+    ///     object: TripleNode::Variable("o".to_string()),
+    ///     graph: None,
+    /// }];
+    ///
+    /// assert!(GraphIsomorphism::are_isomorphic_with_mode(&variables, &blank, NodeMode::Lenient)
+    ///     .unwrap());
+    /// assert!(!GraphIsomorphism::are_isomorphic_with_mode(&variables, &blank, NodeMode::Strict)
+    ///     .unwrap());
+    /// ```
+    pub fn are_isomorphic_with_mode(
+        graph1: &[Triple],
+        graph2: &[Triple],
+        mode: NodeMode,
+    ) -> Result<bool, TulnaError> {
+        match mode {
+            NodeMode::Lenient => Self::are_isomorphic(graph1, graph2),
+            NodeMode::Strict => {
+                if graph1.len() != graph2.len() {
+                    return Ok(false);
+                }
+                let graph1 = Self::mark_variables(Self::normalize_bgp(graph1));
+                let graph2 = Self::mark_variables(Self::normalize_bgp(graph2));
+                Self::is_isomorphic(&graph1, &graph2)
+            }
+        }
+    }
+
+    /// Check if a data graph is isomorphic to a pattern graph, matching their literals as set
+    /// by `matching`.
+    ///
+    /// The datatypes of typed literals in both graphs are first replaced as given by
+    /// `datatype_aliases`, so with `xsd:int` mapped to `xsd:integer`, `"5"^^xsd:int` and
+    /// `"5"^^xsd:integer` are the same literal. Lexical forms are still compared as they are.
+    ///
+    /// With `lang_range_matching`, a language-tagged literal of `data` matches a literal of
+    /// `pattern` with the same value whose tag is a range of its own: either the same tag or
+    /// a prefix of it ending at a `-`, so `en` matches `en-US` but not `eng`. When several
+    /// ranges of `pattern` match, the longest one is used.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tulna_rs::graph::{GraphIsomorphism, LiteralMatching, Triple, TripleNode};
+    ///
+    /// let triple = |lang: &str| Triple {
+    ///     subject: TripleNode::Variable("s".to_string()),
+    ///     predicate: TripleNode::IRI("http://example.org/label".to_string()),
+    ///     object: TripleNode::LangLiteral {
+    ///         value: "foo".to_string(),
+    ///         lang: lang.to_string(),
+    ///     },
+    ///     graph: None,
+    /// };
+    /// let pattern = vec![triple("en")];
+    /// let data = vec![triple("en-us")];
+    ///
+    /// let exact = LiteralMatching::default();
+    /// let ranges = LiteralMatching {
+    ///     lang_range_matching: true,
+    ///     ..LiteralMatching::default()
+    /// };
+    /// assert!(!GraphIsomorphism::are_isomorphic_with_literal_matching(&pattern, &data, &exact)
+    ///     .unwrap());
+    /// assert!(GraphIsomorphism::are_isomorphic_with_literal_matching(&pattern, &data, &ranges)
+    ///     .unwrap());
+    /// ```
+    pub fn are_isomorphic_with_literal_matching(
+        pattern: &[Triple],
+        data: &[Triple],
+        matching: &LiteralMatching,
+    ) -> Result<bool, TulnaError> {
+        let pattern = Self::alias_datatypes(pattern, &matching.datatype_aliases);
+        let data = Self::alias_datatypes(data, &matching.datatype_aliases);
+        let data = Self::match_literals(&pattern, &data, matching);
+        Self::are_isomorphic(&pattern, &data)
+    }
+
+    /// Find the bijection between the blank nodes and variables of two RDF graphs.
+    ///
+    /// The mapping is keyed by the names used in the input triples, so it tells which
+    /// variable or blank node of `graph1` corresponds to which one of `graph2`.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(Some(bijection))` - Graphs are isomorphic under the returned mapping
+    /// * `Ok(None)` - Graphs are not isomorphic
+    /// * `Err(_)` - An error occurred during processing
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tulna_rs::graph::{GraphIsomorphism, Triple, TripleNode};
+    ///
+    /// let graph1 = vec![Triple {
+    ///     subject: TripleNode::Variable("person".to_string()),
+    ///     predicate: TripleNode::IRI("http://xmlns.com/foaf/0.1/name".to_string()),
+    ///     object: TripleNode::Variable("name".to_string()),
+    ///     graph: None,
+    /// }];
+    ///
+    /// let graph2 = vec![Triple {
+    ///     subject: TripleNode::Variable("x".to_string()),
+    ///     predicate: TripleNode::IRI("http://xmlns.com/foaf/0.1/name".to_string()),
+    ///     object: TripleNode::Variable("y".to_string()),
+    ///     graph: None,
+    /// }];
+    ///
+    /// let bijection = GraphIsomorphism::find_bijection(&graph1, &graph2).unwrap().unwrap();
+    /// assert_eq!(bijection["person"], "x");
+    /// assert_eq!(bijection["name"], "y");
+    /// ```
+    pub fn find_bijection(
+        graph1: &[Triple],
+        graph2: &[Triple],
+    ) -> Result<Option<HashMap<String, String>>, TulnaError> {
+        Self::find_bijection_with_budget(
+            graph1,
+            graph2,
+            SearchBudget::new(Self::DEFAULT_MAX_SPECULATIONS),
+            Self::default_seed(),
+        )
+    }
+
+    /// Find the bijection as in `find_bijection`, stopping once the budget runs out
+    fn find_bijection_with_budget(
+        graph1: &[Triple],
+        graph2: &[Triple],
+        budget: SearchBudget,
+        seed: u32,
+    ) -> Result<Option<HashMap<String, String>>, TulnaError> {
+        if graph1.len() != graph2.len() {
+            return Ok(None);
+        }
+        if graph1.is_empty() {
+            return Ok(Some(HashMap::new()));
+        }
+
+        let (normalized1, names1) = Self::normalize_bgp_with_names(graph1);
+        let (normalized2, names2) = Self::normalize_bgp_with_names(graph2);
+
+        if Self::quick_reject(&normalized1, &normalized2) {
+            return Ok(None);
+        }
+
+        let Some(bijection) = Self::get_bijection(&normalized1, &normalized2, budget, seed)? else {
+            return Ok(None);
+        };
+
+        Ok(Some(
+            bijection
+                .into_iter()
+                .map(|(a, b)| {
+                    let original_a = names1.get(&a).cloned().unwrap_or(a);
+                    let original_b = names2.get(&b).cloned().unwrap_or(b);
+                    (original_a, original_b)
+                })
+                .collect(),
+        ))
+    }
+
+    /// Check if a graph is contained in another one.
+    ///
+    /// Returns `true` iff there is an injective mapping of the variables and blank nodes of
+    /// `sub` onto terms of `super_` that maps every triple of `sub` onto a triple of
+    /// `super_`. IRIs and literals map to themselves, while a variable may map onto any
+    /// term that does not already occur in `sub`, so `?s <p> ?o` is contained in
+    /// `<a> <p> "b"`. For query patterns this means that `super_` is at least as specific as
+    /// `sub`, up to variable renaming. Duplicate triples are ignored.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(true)` - `sub` is contained in `super_`
+    /// * `Ok(false)` - `sub` is not contained in `super_`
+    /// * `Err(TulnaError::LimitExceeded(_))` - The search tried more than
+    ///   `DEFAULT_MAX_SPECULATIONS` ambiguous triple assignments
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tulna_rs::graph::{GraphIsomorphism, Triple};
+    ///
+    /// let sub = vec![Triple::new("?a", "<http://example.org/knows>", "?b")];
+    /// let super_ = vec![
+    ///     Triple::new("?x", "<http://example.org/knows>", "?y"),
+    ///     Triple::new("?y", "<http://example.org/name>", "?n"),
+    /// ];
+    ///
+    /// assert!(GraphIsomorphism::is_subgraph(&sub, &super_).unwrap());
+    /// assert!(!GraphIsomorphism::is_subgraph(&super_, &sub).unwrap());
+    /// ```
+    pub fn is_subgraph(sub: &[Triple], super_: &[Triple]) -> Result<bool, TulnaError> {
+        let sub = Self::uniq_graph(&Self::normalize_bgp(sub));
+        let super_ = Self::uniq_graph(&Self::normalize_bgp(super_));
+
+        // An injective mapping of the terms maps distinct triples onto distinct triples
+        if sub.len() > super_.len() {
+            return Ok(false);
+        }
+
+        Self::search_embeddings(&sub, &super_, &mut || true)
+    }
+
+    /// Count the automorphisms of a graph.
+    ///
+    /// An automorphism is a bijection of the variables and blank nodes of the graph onto
+    /// themselves that maps the graph onto itself, while IRIs and literals map to themselves.
+    /// The identity is one, so the count is at least 1. Symmetric graphs have many
+    /// automorphisms, and they are also the graphs that need the most speculation to compare.
+    /// Duplicate triples are ignored.
+    ///
+    /// The automorphisms are enumerated one by one. For very symmetric graphs, such as large
+    /// cliques, this gives up with `TulnaError::LimitExceeded` after
+    /// `DEFAULT_MAX_SPECULATIONS` speculative triple assignments.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tulna_rs::graph::{GraphIsomorphism, Triple};
+    ///
+    /// let knows = |s: &str, o: &str| Triple::new(s, "<http://example.org/knows>", o);
+    /// let chain = vec![knows("?a", "?b"), knows("?b", "?c")];
+    /// let cycle = vec![knows("?a", "?b"), knows("?b", "?a")];
+    ///
+    /// assert_eq!(GraphIsomorphism::automorphism_count(&chain).unwrap(), 1);
+    /// assert_eq!(GraphIsomorphism::automorphism_count(&cycle).unwrap(), 2);
+    /// ```
+    pub fn automorphism_count(graph: &[Triple]) -> Result<usize, TulnaError> {
+        let graph = Self::uniq_graph(&Self::normalize_bgp(graph));
+
+        let mut count = 0;
+        Self::search_embeddings(&graph, &graph, &mut || {
+            count += 1;
+            false
+        })?;
+        Ok(count)
+    }
+
+    /// Search the embeddings of `sub` into `super_`, calling `on_embedding` for each one
+    /// until it returns `true`.
+    ///
+    /// Returns whether `on_embedding` stopped the search, or `TulnaError::LimitExceeded` when
+    /// the default speculation limit ran out first.
+    fn search_embeddings(
+        sub: &[NormalizedTriple],
+        super_: &[NormalizedTriple],
+        on_embedding: &mut dyn FnMut() -> bool,
+    ) -> Result<bool, TulnaError> {
+        // The triples of `super_` every triple of `sub` could be mapped onto, judging by their
+        // IRIs and literals; the most constrained triples are mapped first
+        let mut candidates: Vec<(&NormalizedTriple, Vec<&NormalizedTriple>)> = sub
+            .iter()
+            .map(|quad| {
+                let images = super_
+                    .iter()
+                    .filter(|image| Self::ground_terms_match(quad, image))
+                    .collect();
+                (quad, images)
+            })
+            .collect();
+        if candidates.iter().any(|(_, images)| images.is_empty()) {
+            return Ok(false);
+        }
+        candidates.sort_by_key(|(_, images)| images.len());
+
+        // Terms of `sub` map to themselves, so no blank node may take them as image
+        let mut used: HashSet<&str> = sub
+            .iter()
+            .flat_map(|quad| quad.positions())
+            .filter(|term| !term.starts_with("_:"))
+            .map(String::as_str)
+            .collect();
+        let mut mapping: HashMap<&str, &str> = HashMap::new();
+        let mut budget = SearchBudget::new(Self::DEFAULT_MAX_SPECULATIONS);
+
+        let stopped = Self::extend_embedding(
+            &candidates,
+            0,
+            &mut mapping,
+            &mut used,
+            &mut budget,
+            on_embedding,
+        );
+        if budget.exceeded {
+            return Err(TulnaError::LimitExceeded(format!(
+                "no answer after {} speculative triple assignments",
+                Self::DEFAULT_MAX_SPECULATIONS
+            )));
+        }
+        Ok(stopped)
+    }
+
+    /// Compare two RDF graphs and classify the result.
+    ///
+    /// This runs the same algorithm as `are_isomorphic`, but reports which check ruled out
+    /// isomorphism instead of a plain `false`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tulna_rs::graph::{GraphIsomorphism, IsoResult, Triple, TripleNode};
+    ///
+    /// let graph1 = vec![Triple {
+    ///     subject: TripleNode::Variable("x".to_string()),
+    ///     predicate: TripleNode::IRI("http://example.org/knows".to_string()),
+    ///     object: TripleNode::Variable("x".to_string()),
+    ///     graph: None,
+    /// }];
+    ///
+    /// let graph2 = vec![Triple {
+    ///     subject: TripleNode::Variable("x".to_string()),
+    ///     predicate: TripleNode::IRI("http://example.org/knows".to_string()),
+    ///     object: TripleNode::Variable("y".to_string()),
+    ///     graph: None,
+    /// }];
+    ///
+    /// assert_eq!(
+    ///     GraphIsomorphism::classify(&graph1, &graph2).unwrap(),
+    ///     IsoResult::DifferentBlankCount
+    /// );
+    /// ```
+    pub fn classify(graph1: &[Triple], graph2: &[Triple]) -> Result<IsoResult, TulnaError> {
+        if graph1.len() != graph2.len() {
+            return Ok(IsoResult::DifferentSize);
+        }
+
+        let graph1 = Self::normalize_bgp(graph1);
+        let graph2 = Self::normalize_bgp(graph2);
+
+        let result = Self::get_bijection_or_reason(
+            &graph1,
+            &graph2,
+            &mut None,
+            SearchBudget::new(Self::DEFAULT_MAX_SPECULATIONS),
+            Self::default_seed(),
+        )?;
+        Ok(match result {
+            Ok(_) => IsoResult::Isomorphic,
+            Err(reason) => reason,
+        })
+    }
+
+    /// Check if two RDF graphs are isomorphic, explaining how the answer was derived.
+    ///
+    /// Alongside the result this returns a human-readable trace of the algorithm: how the
+    /// variables were normalized, which blank nodes were grounded in which hashing iteration,
+    /// and which speculative assignments were tried. It is intended for teaching and
+    /// debugging; use `are_isomorphic` when only the answer matters.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tulna_rs::graph::{GraphIsomorphism, Triple, TripleNode};
+    ///
+    /// let graph = vec![Triple {
+    ///     subject: TripleNode::Variable("x".to_string()),
+    ///     predicate: TripleNode::IRI("http://example.org/knows".to_string()),
+    ///     object: TripleNode::IRI("http://example.org/bob".to_string()),
+    ///     graph: None,
+    /// }];
+    ///
+    /// let (isomorphic, trace) = GraphIsomorphism::are_isomorphic_explained(&graph, &graph).unwrap();
+    /// assert!(isomorphic);
+    /// for step in &trace {
+    ///     println!("{}", step);
+    /// }
+    /// ```
+    pub fn are_isomorphic_explained(
+        graph1: &[Triple],
+        graph2: &[Triple],
+    ) -> Result<(bool, Vec<String>), TulnaError> {
+        if graph1.len() != graph2.len() {
+            let step = format!(
+                "Graphs differ in size: {} vs {} triples",
+                graph1.len(),
+                graph2.len()
+            );
+            return Ok((false, vec![step]));
+        }
+
+        let mut trace = Some(Vec::new());
+        for (label, graph) in [("A", graph1), ("B", graph2)] {
+            let (_, names) = Self::normalize_bgp_with_names(graph);
+            let mut renamed: Vec<String> = names
+                .iter()
+                .filter(|(id, _)| id.starts_with("_:v"))
+                .map(|(id, name)| format!("?{} as {}", name, id))
+                .collect();
+            renamed.sort();
+            if !renamed.is_empty() {
+                Self::record(&mut trace, || {
+                    format!("Graph {}: normalized {}", label, renamed.join(", "))
+                });
+            }
+        }
+
+        let normalized1 = Self::normalize_bgp(graph1);
+        let normalized2 = Self::normalize_bgp(graph2);
+        let result = Self::get_bijection_or_reason(
+            &normalized1,
+            &normalized2,
+            &mut trace,
+            SearchBudget::new(Self::DEFAULT_MAX_SPECULATIONS),
+            Self::default_seed(),
+        )?;
+
+        match &result {
+            Ok(bijection) => {
+                let mut pairs: Vec<String> = bijection
+                    .iter()
+                    .map(|(a, b)| format!("{} -> {}", a, b))
+                    .collect();
+                pairs.sort();
+                Self::record(&mut trace, || {
+                    format!("Isomorphic under bijection: {}", pairs.join(", "))
+                });
+            }
+            Err(reason) => {
+                Self::record(&mut trace, || format!("Not isomorphic: {:?}", reason));
+            }
+        }
+
+        Ok((result.is_ok(), trace.unwrap_or_default()))
+    }
+
+    /// List the differences that keep two RDF graphs from being isomorphic.
+    ///
+    /// Where `are_isomorphic` only answers `false`, this reports the triples without blank
+    /// nodes that only one of the graphs has, the number of blank nodes of each graph, and
+    /// the groups of blank nodes that hashing finds structurally alike but that differ in
+    /// size between the graphs. Duplicate triples are ignored.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tulna_rs::graph::{GraphIsomorphism, Triple, TripleNode};
+    ///
+    /// let graph1 = vec![
+    ///     Triple::new("?x", "<http://example.org/knows>", "?y"),
+    ///     Triple::new("<http://example.org/a>", "<http://example.org/p>", "\"1\""),
+    /// ];
+    /// let graph2 = vec![
+    ///     Triple::new("?x", "<http://example.org/knows>", "?x"),
+    ///     Triple::new("<http://example.org/a>", "<http://example.org/p>", "\"2\""),
+    /// ];
+    ///
+    /// let diff = GraphIsomorphism::diff(&graph1, &graph2);
+    /// assert_eq!(diff.only_in_first, vec![graph1[1].clone()]);
+    /// assert_eq!(diff.only_in_second, vec![graph2[1].clone()]);
+    /// assert_eq!(diff.blank_node_counts, (2, 1));
+    /// assert!(!diff.is_empty());
+    /// ```
+    pub fn diff(graph1: &[Triple], graph2: &[Triple]) -> GraphDiff {
+        let (normalized1, names1) = Self::normalize_bgp_with_names(graph1);
+        let (normalized2, names2) = Self::normalize_bgp_with_names(graph2);
+
+        // The normalized triples line up with the input triples, which are reported
+        let only_in = |graph: &[Triple], normalized: &[NormalizedTriple], other| {
+            let other: HashSet<NormalizedTriple> = Self::get_quads_without_blank_nodes(other)
+                .into_iter()
+                .collect();
+            let mut seen = HashSet::new();
+            graph
+                .iter()
+                .zip(normalized)
+                .filter(|(_, quad)| !quad.terms().any(|term| term.starts_with("_:")))
+                .filter(|(_, quad)| !other.contains(*quad) && seen.insert(*quad))
+                .map(|(triple, _)| triple.clone())
+                .collect()
+        };
+
+        // The hash signature of every blank node of a graph
+        let signatures = |normalized: &[NormalizedTriple], names| {
+            let blank_quads = Self::uniq_graph(&Self::get_quads_with_blank_nodes(normalized));
+            let blank_nodes = Self::get_graph_blank_nodes(normalized);
+            let (hashes, ungrounded_hashes) = Self::hash_terms(
+                &blank_quads,
+                &blank_nodes,
+                &HashMap::new(),
+                Self::FIXED_SEED,
+                &mut None,
+            );
+            blank_nodes
+                .iter()
+                .map(|node| {
+                    let hash = hashes.get(node).or_else(|| ungrounded_hashes.get(node));
+                    (*hash.unwrap_or(&0), Self::original_node(node, names))
+                })
+                .collect::<Vec<_>>()
+        };
+        let signatures1 = signatures(&normalized1, &names1);
+        let signatures2 = signatures(&normalized2, &names2);
+
+        let mut classes: BTreeMap<u64, (Vec<TripleNode>, Vec<TripleNode>)> = BTreeMap::new();
+        for (hash, node) in &signatures1 {
+            classes.entry(*hash).or_default().0.push(node.clone());
+        }
+        for (hash, node) in &signatures2 {
+            classes.entry(*hash).or_default().1.push(node.clone());
+        }
+
+        GraphDiff {
+            only_in_first: only_in(graph1, &normalized1, &normalized2),
+            only_in_second: only_in(graph2, &normalized2, &normalized1),
+            blank_node_counts: (signatures1.len(), signatures2.len()),
+            signature_mismatches: classes
+                .into_values()
+                .filter(|(nodes1, nodes2)| nodes1.len() != nodes2.len())
+                .collect(),
+        }
+    }
+
+    /// Compute a canonical string form of a graph.
+    ///
+    /// Blank nodes and variables are renamed to `_:c0`, `_:c1`, ... in an order derived from
+    /// their hash signatures, and the triples are serialized one per line in sorted order.
+    /// Isomorphic graphs yield byte-identical strings and non-isomorphic graphs yield
+    /// different ones, so the result can be stored and compared by string equality instead
+    /// of running `are_isomorphic` pairwise. Duplicate triples are ignored.
+    ///
+    /// When hashing cannot tell blank nodes apart, every candidate of the first ambiguous
+    /// group is speculatively grounded in turn and the smallest resulting form is kept, so
+    /// highly symmetric graphs are more expensive to canonicalize. After
+    /// `DEFAULT_MAX_SPECULATIONS` speculative groundings `TulnaError::LimitExceeded` is
+    /// returned; see `canonical_form_with_limit` to choose another limit.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tulna_rs::graph::{GraphIsomorphism, Triple, TripleNode};
+    ///
+    /// let knows = |s: &str, o: &str| Triple {
+    ///     subject: TripleNode::Variable(s.to_string()),
+    ///     predicate: TripleNode::IRI("http://example.org/knows".to_string()),
+    ///     object: TripleNode::Variable(o.to_string()),
+    ///     graph: None,
+    /// };
+    ///
+    /// let form1 = GraphIsomorphism::canonical_form(&[knows("x", "y")]).unwrap();
+    /// let form2 = GraphIsomorphism::canonical_form(&[knows("a", "b")]).unwrap();
+    /// assert_eq!(form1, form2);
+    /// ```
+    pub fn canonical_form(graph: &[Triple]) -> Result<String, TulnaError> {
+        Self::canonical_form_with_limit(graph, Self::DEFAULT_MAX_SPECULATIONS)
+    }
+
+    /// Compute the canonical string form of a graph, trying at most `max_speculations`
+    /// speculative groundings.
+    ///
+    /// See `canonical_form`, which tries `DEFAULT_MAX_SPECULATIONS` of them.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(form)` - The canonical form of the graph
+    /// * `Err(TulnaError::LimitExceeded(_))` - The form needs more speculations
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tulna_rs::graph::{GraphIsomorphism, Triple};
+    /// use tulna_rs::TulnaError;
+    ///
+    /// // The two edges can only be told apart by grounding one of them
+    /// let graph = vec![
+    ///     Triple::new("?a", "<http://example.org/knows>", "?b"),
+    ///     Triple::new("?c", "<http://example.org/knows>", "?d"),
+    /// ];
+    ///
+    /// assert!(GraphIsomorphism::canonical_form_with_limit(&graph, 10).is_ok());
+    /// assert!(matches!(
+    ///     GraphIsomorphism::canonical_form_with_limit(&graph, 0),
+    ///     Err(TulnaError::LimitExceeded(_))
+    /// ));
+    /// ```
+    pub fn canonical_form_with_limit(
+        graph: &[Triple],
+        max_speculations: usize,
+    ) -> Result<String, TulnaError> {
+        let graph = Self::uniq_graph(&Self::normalize_bgp(graph));
+        let blank_quads = Self::get_quads_with_blank_nodes(&graph);
+        let blank_nodes = Self::get_graph_blank_nodes(&graph);
+
+        let mut budget = SearchBudget::new(max_speculations);
+        let (form, _) = Self::canonical_form_inner(
+            &graph,
+            &blank_quads,
+            &blank_nodes,
+            &HashMap::new(),
+            &mut budget,
+        );
+        budget.check(form)
+    }
+
+    /// Compute a fingerprint of a graph that is shared by all graphs isomorphic to it.
+    ///
+    /// Every triple is hashed with its blank nodes and variables replaced by the hashes
+    /// `hash_terms` gives them, and the sorted triple hashes are combined into one digest, so
+    /// neither the order of the triples nor the names of the nodes matter. No speculative
+    /// grounding is done, which keeps the fingerprint cheap even for symmetric graphs.
+    ///
+    /// Unequal fingerprints guarantee that two graphs are not isomorphic, but equal ones do
+    /// not guarantee that they are: the hashes can collide, and nodes that hashing cannot
+    /// tell apart get the same hash. Use the fingerprint to rule out candidates, for example
+    /// in a hash map or bloom filter, and confirm the remaining ones with `are_isomorphic`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tulna_rs::graph::{GraphIsomorphism, Triple};
+    ///
+    /// let graph1 = vec![
+    ///     Triple::new("?x", "<http://example.org/knows>", "?y"),
+    ///     Triple::new("?y", "<http://example.org/name>", "\"Bob\""),
+    /// ];
+    /// let graph2 = vec![
+    ///     Triple::new("_:b", "<http://example.org/name>", "\"Bob\""),
+    ///     Triple::new("_:a", "<http://example.org/knows>", "_:b"),
+    /// ];
+    /// let graph3 = vec![
+    ///     Triple::new("?x", "<http://example.org/knows>", "?y"),
+    ///     Triple::new("?x", "<http://example.org/name>", "\"Bob\""),
+    /// ];
+    ///
+    /// let hash1 = GraphIsomorphism::graph_hash(&graph1);
+    /// assert_eq!(hash1, GraphIsomorphism::graph_hash(&graph2));
+    /// assert_ne!(hash1, GraphIsomorphism::graph_hash(&graph3));
+    /// ```
+    pub fn graph_hash(graph: &[Triple]) -> u64 {
+        let graph = Self::normalize_bgp(graph);
+        let blank_quads = Self::get_quads_with_blank_nodes(&graph);
+        let blank_nodes = Self::get_graph_blank_nodes(&graph);
+        let (_, node_hashes) = Self::hash_terms(
+            &blank_quads,
+            &blank_nodes,
+            &HashMap::new(),
+            Self::FIXED_SEED,
+            &mut None,
+        );
+
+        let signature = |term: &String| match node_hashes.get(term) {
+            Some(hash) => format!("_:{}", hash),
+            None => term.clone(),
+        };
+        let mut triple_hashes: Vec<u64> = graph
+            .iter()
+            .map(|quad| {
+                let signatures: Vec<String> = quad.positions().into_iter().map(signature).collect();
+                Self::hash_string(&signatures.join("|"), Self::FIXED_SEED)
+            })
+            .collect();
+        triple_hashes.sort_unstable();
+
+        let digest: Vec<String> = triple_hashes.iter().map(u64::to_string).collect();
+        Self::hash_string(&digest.join(" "), Self::FIXED_SEED)
+    }
+
+    /// List the variables and blank nodes of a graph in the order of their canonical labels.
+    ///
+    /// The order is the one `canonical_form` numbers them in, so isomorphic graphs list
+    /// corresponding nodes at the same positions. Like `canonical_form`, it returns
+    /// `TulnaError::LimitExceeded` after `DEFAULT_MAX_SPECULATIONS` speculative groundings.
+    pub(crate) fn canonical_order(graph: &[Triple]) -> Result<Vec<TripleNode>, TulnaError> {
+        let (graph, names) = Self::normalize_bgp_with_names(graph);
+        let graph = Self::uniq_graph(&graph);
+        let blank_quads = Self::get_quads_with_blank_nodes(&graph);
+        let blank_nodes = Self::get_graph_blank_nodes(&graph);
+
+        let mut budget = SearchBudget::new(Self::DEFAULT_MAX_SPECULATIONS);
+        let (_, ranks) = Self::canonical_form_inner(
+            &graph,
+            &blank_quads,
+            &blank_nodes,
+            &HashMap::new(),
+            &mut budget,
+        );
+        let mut ordered: Vec<(usize, &String)> = budget
+            .check(ranks)?
+            .into_iter()
+            .map(|(node, rank)| (rank, node))
+            .collect();
+        ordered.sort();
+
+        Ok(ordered
+            .into_iter()
+            .map(|(_, node)| Self::original_node(node, &names))
+            .collect())
+    }
+
+    /// Turn the identifier of a normalized variable or blank node back into the input node,
+    /// using the names returned by `normalize_bgp_with_names`
+    fn original_node(id: &str, names: &HashMap<String, String>) -> TripleNode {
+        let name = names.get(id).cloned().unwrap_or_default();
+        if id.starts_with("_:v") {
+            TripleNode::Variable(name)
+        } else {
+            TripleNode::BlankNode(name)
+        }
+    }
+
+    /// Parse an N-Triples document into a graph.
+    ///
+    /// Every line that is neither empty nor a `#` comment must hold one triple ending with
+    /// `.`. IRIs are written as `<iri>`, blank nodes as `_:label` and literals as `"value"`,
+    /// optionally followed by a `@lang` tag or a `^^<datatype>`. Escape sequences inside
+    /// literals are kept as they are written.
+    ///
+    /// # Errors
+    ///
+    /// Returns `TulnaError::ParseError` with the line number of the first malformed line.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tulna_rs::graph::GraphIsomorphism;
+    ///
+    /// let graph1 = GraphIsomorphism::parse_ntriples(
+    ///     "_:a <http://example.org/knows> _:b .\n_:b <http://example.org/name> \"Bob\" .",
+    /// )
+    /// .unwrap();
+    /// let graph2 = GraphIsomorphism::parse_ntriples(
+    ///     "_:x <http://example.org/knows> _:y .\n_:y <http://example.org/name> \"Bob\" .",
+    /// )
+    /// .unwrap();
+    ///
+    /// assert!(GraphIsomorphism::are_isomorphic(&graph1, &graph2).unwrap());
+    /// ```
+    pub fn parse_ntriples(input: &str) -> Result<Vec<Triple>, TulnaError> {
+        let mut graph = Vec::new();
+
+        for (index, line) in input.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let error = |message: String| {
+                TulnaError::ParseError(format!("N-Triples line {}: {}", index + 1, message))
+            };
+
+            let mut rest = line;
+            let mut terms = Vec::with_capacity(3);
+            while terms.len() < 3 {
+                let (term, remaining) = Self::next_ntriples_term(rest)
+                    .ok_or_else(|| error(format!("expected a term at '{}'", rest.trim())))?;
+                terms.push(term);
+                rest = remaining;
+            }
+
+            let rest = rest
+                .trim_start()
+                .strip_prefix('.')
+                .ok_or_else(|| error("expected '.' after the object".to_string()))?
+                .trim_start();
+            if !rest.is_empty() && !rest.starts_with('#') {
+                return Err(error(format!("unexpected text '{}' after '.'", rest)));
+            }
+
+            let object = terms.pop().unwrap();
+            let predicate = terms.pop().unwrap();
+            let subject = terms.pop().unwrap();
+            if !matches!(subject, TripleNode::IRI(_) | TripleNode::BlankNode(_)) {
+                return Err(error(format!(
+                    "subject {} is not an IRI or blank node",
+                    subject
+                )));
+            }
+            if !matches!(predicate, TripleNode::IRI(_)) {
+                return Err(error(format!("predicate {} is not an IRI", predicate)));
+            }
+
+            graph.push(Triple {
+                subject,
+                predicate,
+                object,
+                graph: None,
+            });
+        }
+
+        Ok(graph)
+    }
+
+    /// Split the next N-Triples term off the start of `text`, returning it with the rest
+    fn next_ntriples_term(text: &str) -> Option<(TripleNode, &str)> {
+        let text = text.trim_start();
+
+        let end = if text.starts_with('<') {
+            text.find('>')? + 1
+        } else if text.starts_with("_:") {
+            // A label may contain dots, but not end with one
+            let label_end = text.find(char::is_whitespace).unwrap_or(text.len());
+            let label_end = text[..label_end].trim_end_matches('.').len();
+            if label_end == 2 {
+                return None;
+            }
+            label_end
+        } else if text.starts_with('"') {
+            let mut escaped = false;
+            let close = text
+                .char_indices()
+                .skip(1)
+                .find(|&(_, c)| {
+                    let closes = c == '"' && !escaped;
+                    escaped = c == '\\' && !escaped;
+                    closes
+                })?
+                .0;
+
+            let suffix = &text[close + 1..];
+            close
+                + 1
+                + if let Some(lang) = suffix.strip_prefix('@') {
+                    1 + lang
+                        .find(|c: char| !c.is_ascii_alphanumeric() && c != '-')
+                        .unwrap_or(lang.len())
+                } else if suffix.starts_with("^^<") {
+                    suffix.find('>')? + 1
+                } else {
+                    0
+                }
+        } else {
+            return None;
+        };
+
+        Some((TripleNode::from(&text[..end]), &text[end..]))
+    }
+
+    /// Parse a Turtle document into a graph.
+    ///
+    /// Supports the common subset of Turtle: `@prefix` and `@base` directives (or their
+    /// SPARQL-style `PREFIX` and `BASE` forms), prefixed names, the `a` shorthand for
+    /// `rdf:type`, predicate-object lists with `;`, object lists with `,`, anonymous blank
+    /// nodes written with `[ ]`, collections written with `( )`, and literals with a
+    /// language tag or datatype, including numbers and booleans.
+    ///
+    /// # Errors
+    ///
+    /// Returns `TulnaError::ParseError` with the line number of the first malformed
+    /// statement, such as one using an undeclared prefix or a query variable.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tulna_rs::graph::GraphIsomorphism;
+    ///
+    /// let turtle = GraphIsomorphism::parse_turtle(
+    ///     "@prefix ex: <http://example.org/> .\n\
+    ///      ex:alice a ex:Person ; ex:knows ex:bob, ex:carol .",
+    /// )
+    /// .unwrap();
+    /// let ntriples = GraphIsomorphism::parse_ntriples(
+    ///     "<http://example.org/alice> <http://www.w3.org/1999/02/22-rdf-syntax-ns#type> <http://example.org/Person> .\n\
+    ///      <http://example.org/alice> <http://example.org/knows> <http://example.org/bob> .\n\
+    ///      <http://example.org/alice> <http://example.org/knows> <http://example.org/carol> .",
+    /// )
+    /// .unwrap();
+    ///
+    /// assert!(GraphIsomorphism::are_isomorphic(&turtle, &ntriples).unwrap());
+    /// ```
+    pub fn parse_turtle(input: &str) -> Result<Vec<Triple>, TulnaError> {
+        TurtleParser::parse(input)
+    }
+
+    /// Check if a graph contains any blank nodes.
+    ///
+    /// Variables count as blank nodes here, since the algorithm treats them the same way.
+    /// A graph without blank nodes can only be isomorphic to a graph with exactly the
+    /// same triples.
+    ///
     /// # Examples
     ///
     /// ```
     /// use tulna_rs::graph::{GraphIsomorphism, Triple, TripleNode};
     ///
-    /// let graph1 = vec![
-    ///     Triple {
-    ///         subject: TripleNode::IRI("http://example.org/alice".to_string()),
-    ///         predicate: TripleNode::IRI("http://example.org/name".to_string()),
-    ///         object: TripleNode::Literal("Alice".to_string()),
-    ///     }
-    /// ];
-    ///
-    /// let graph2 = vec![
-    ///     Triple {
-    ///         subject: TripleNode::IRI("http://example.org/alice".to_string()),
-    ///         predicate: TripleNode::IRI("http://example.org/name".to_string()),
-    ///         object: TripleNode::Literal("Alice".to_string()),
-    ///     }
-    /// ];
+    /// let graph = vec![Triple {
+    ///     subject: TripleNode::Variable("x".to_string()),
+    ///     predicate: TripleNode::IRI("http://example.org/knows".to_string()),
+    ///     object: TripleNode::IRI("http://example.org/bob".to_string()),
+    ///     graph: None,
+    /// }];
     ///
-    /// assert!(GraphIsomorphism::are_isomorphic(&graph1, &graph2).unwrap());
+    /// assert!(GraphIsomorphism::contains_blank_nodes(&graph));
     /// ```
-    pub fn are_isomorphic(graph1: &[Triple], graph2: &[Triple]) -> Result<bool, TulnaError> {
-        Self::check_bgp_isomorphism(graph1, graph2)
+    pub fn contains_blank_nodes(graph: &[Triple]) -> bool {
+        graph
+            .iter()
+            .any(|triple| triple.has_blank_node() || triple.has_variable())
     }
 
     /// Check if two BGPs are isomorphic using hash-based grounding algorithm.
     /// This converts variables to blank nodes and checks for graph isomorphism.
     ///
-    /// This method is used internally and by the query isomorphism API.
+    /// This method is used internally and by the query isomorphism API. BGPs are sets of
+    /// triple patterns, so duplicate triples are ignored.
     pub fn check_bgp_isomorphism(bgp1: &[Triple], bgp2: &[Triple]) -> Result<bool, TulnaError> {
-        if bgp1.len() != bgp2.len() {
+        // Convert to normalized string representations, dropping duplicates before the sizes
+        // are compared
+        let graph1 = Self::uniq_graph(&Self::normalize_bgp(bgp1));
+        let graph2 = Self::uniq_graph(&Self::normalize_bgp(bgp2));
+        if graph1.len() != graph2.len() {
             return Ok(false);
         }
 
-        // Convert to normalized string representations
-        let graph1 = Self::normalize_bgp(bgp1);
-        let graph2 = Self::normalize_bgp(bgp2);
-
         // Check if graphs are isomorphic using hash-based algorithm
-        Ok(Self::is_isomorphic(&graph1, &graph2))
+        Self::is_isomorphic(&graph1, &graph2)
     }
 
     /// Normalize a BGP by converting it to a canonical form
     /// Variables are replaced with blank node identifiers
     fn normalize_bgp(bgp: &[Triple]) -> Vec<NormalizedTriple> {
+        Self::normalize_bgp_with_names(bgp).0
+    }
+
+    /// Normalize a BGP as in `normalize_bgp`, also returning the original name of every
+    /// normalized blank node identifier
+    fn normalize_bgp_with_names(
+        bgp: &[Triple],
+    ) -> (Vec<NormalizedTriple>, HashMap<String, String>) {
         let mut var_map: HashMap<String, String> = HashMap::new();
         let mut counter = 0;
 
-        bgp.iter()
-            .map(|triple| {
-                let subject = Self::normalize_node(&triple.subject, &mut var_map, &mut counter);
-                let predicate = Self::normalize_node(&triple.predicate, &mut var_map, &mut counter);
-                let object = Self::normalize_node(&triple.object, &mut var_map, &mut counter);
+        let graph = bgp
+            .iter()
+            .map(|triple| Self::normalize_triple(triple, &mut var_map, &mut counter))
+            .collect();
+
+        let mut names: HashMap<String, String> =
+            var_map.into_iter().map(|(var, id)| (id, var)).collect();
+        for triple in bgp {
+            let nodes = [&triple.subject, &triple.predicate, &triple.object];
+            for node in nodes.into_iter().chain(triple.graph.as_ref()) {
+                if let TripleNode::BlankNode(id) = node {
+                    names
+                        .entry(format!("_:bn{}", id))
+                        .or_insert_with(|| id.clone());
+                }
+            }
+        }
+
+        (graph, names)
+    }
+
+    /// Add a marker triple for every variable of a normalized graph.
+    ///
+    /// A bijection has to map the marker triples of one graph onto those of the other, so
+    /// variables can then only map onto variables, and blank nodes onto blank nodes.
+    fn mark_variables(mut graph: Vec<NormalizedTriple>) -> Vec<NormalizedTriple> {
+        let variables: HashSet<String> = graph
+            .iter()
+            .flat_map(|quad| quad.terms())
+            .filter(|term| term.starts_with("_:v"))
+            .cloned()
+            .collect();
+
+        graph.extend(variables.into_iter().map(|variable| NormalizedTriple {
+            subject: variable,
+            predicate: VARIABLE_MARKER.to_string(),
+            object: "\"\"".to_string(),
+            graph: String::new(),
+        }));
+        graph
+    }
+
+    /// Rewrite the literals of `data` that `matching` lets match a literal of `pattern` into
+    /// that literal, so that an exact comparison of the two graphs applies the matching
+    fn match_literals(
+        pattern: &[Triple],
+        data: &[Triple],
+        matching: &LiteralMatching,
+    ) -> Vec<Triple> {
+        let mut ranges: HashMap<&str, Vec<&str>> = HashMap::new();
+        if matching.lang_range_matching {
+            for triple in pattern {
+                let nodes = [&triple.subject, &triple.predicate, &triple.object];
+                for node in nodes.into_iter().chain(triple.graph.as_ref()) {
+                    if let TripleNode::LangLiteral { value, lang } = node {
+                        ranges
+                            .entry(value.as_str())
+                            .or_default()
+                            .push(lang.as_str());
+                    }
+                }
+            }
+        }
 
-                NormalizedTriple {
-                    subject,
-                    predicate,
-                    object,
+        let match_node = |node: &TripleNode| match node {
+            TripleNode::LangLiteral { value, lang } => {
+                let tag = lang.to_ascii_lowercase();
+                let range = ranges
+                    .get(value.as_str())
+                    .into_iter()
+                    .flatten()
+                    .filter(|range| {
+                        let range = range.to_ascii_lowercase();
+                        tag == range
+                            || tag
+                                .strip_prefix(range.as_str())
+                                .is_some_and(|rest| rest.starts_with('-'))
+                    })
+                    .max_by_key(|range| range.len());
+                match range {
+                    Some(range) => TripleNode::LangLiteral {
+                        value: value.clone(),
+                        lang: range.to_string(),
+                    },
+                    None => node.clone(),
                 }
+            }
+            _ => node.clone(),
+        };
+
+        data.iter()
+            .map(|triple| Triple {
+                subject: match_node(&triple.subject),
+                predicate: match_node(&triple.predicate),
+                object: match_node(&triple.object),
+                graph: triple.graph.as_ref().map(match_node),
+            })
+            .collect()
+    }
+
+    /// Replace every aliased datatype of the typed literals of `graph` by the datatype it is an
+    /// alias of
+    fn alias_datatypes(graph: &[Triple], aliases: &HashMap<String, String>) -> Vec<Triple> {
+        let alias_node = |node: &TripleNode| match node {
+            TripleNode::TypedLiteral { value, datatype } => TripleNode::TypedLiteral {
+                value: value.clone(),
+                datatype: aliases.get(datatype).unwrap_or(datatype).clone(),
+            },
+            _ => node.clone(),
+        };
+
+        graph
+            .iter()
+            .map(|triple| Triple {
+                subject: alias_node(&triple.subject),
+                predicate: alias_node(&triple.predicate),
+                object: alias_node(&triple.object),
+                graph: triple.graph.as_ref().map(alias_node),
             })
             .collect()
     }
 
+    /// Normalize a triple, numbering its new variables from `counter` on
+    fn normalize_triple(
+        triple: &Triple,
+        var_map: &mut HashMap<String, String>,
+        counter: &mut u32,
+    ) -> NormalizedTriple {
+        let subject = Self::normalize_node(&triple.subject, var_map, counter);
+        let predicate = Self::normalize_node(&triple.predicate, var_map, counter);
+        let object = Self::normalize_node(&triple.object, var_map, counter);
+        let graph = triple
+            .graph
+            .as_ref()
+            .map(|graph| Self::normalize_node(graph, var_map, counter))
+            .unwrap_or_default();
+
+        NormalizedTriple {
+            subject,
+            predicate,
+            object,
+            graph,
+        }
+    }
+
     /// Normalize a node, converting variables to blank nodes with consistent IDs
+    ///
+    /// Variables become `_:v0`, `_:v1`, ... and blank nodes `_:bn<id>`, so that `?b0` and
+    /// `_:b0` are still two different nodes after normalization.
     fn normalize_node(
         node: &TripleNode,
         var_map: &mut HashMap<String, String>,
         counter: &mut u32,
     ) -> String {
         match node {
-            TripleNode::IRI(iri) => format!("<{}>", iri),
             TripleNode::Variable(var) => {
                 // Map each variable to a unique blank node ID
                 if !var_map.contains_key(var) {
-                    var_map.insert(var.clone(), format!("_:b{}", counter));
+                    var_map.insert(var.clone(), format!("_:v{}", counter));
                     *counter += 1;
                 }
                 var_map.get(var).unwrap().clone()
             }
-            TripleNode::Literal(lit) => format!("\"{}\"", lit),
-            TripleNode::BlankNode(id) => format!("_:{}", id),
+            TripleNode::BlankNode(id) => format!("_:bn{}", id),
+            other => other.to_string(),
         }
     }
 
     /// Check if two normalized graphs are isomorphic using hash-based grounding
-    fn is_isomorphic(graph_a: &[NormalizedTriple], graph_b: &[NormalizedTriple]) -> bool {
+    fn is_isomorphic(
+        graph_a: &[NormalizedTriple],
+        graph_b: &[NormalizedTriple],
+    ) -> Result<bool, TulnaError> {
         if graph_a.len() != graph_b.len() {
-            return false;
+            return Ok(false);
         }
 
         // Get bijection using hash-based algorithm
-        Self::get_bijection(graph_a, graph_b).is_some()
+        let bijection = Self::get_bijection(
+            graph_a,
+            graph_b,
+            SearchBudget::new(Self::DEFAULT_MAX_SPECULATIONS),
+            Self::default_seed(),
+        )?;
+        Ok(bijection.is_some())
     }
 
     /// Calculate a bijection from graph A blank nodes to graph B blank nodes.
@@ -324,50 +2125,97 @@ impl GraphIsomorphism {
     ///
     /// * `graph_a` - First normalized graph
     /// * `graph_b` - Second normalized graph
+    /// * `budget` - Speculative groundings and time the search may spend
+    /// * `seed` - Seed of the hashes of both graphs
     ///
     /// # Returns
     ///
-    /// * `Some(bijection)` - A mapping from graph A blank nodes to graph B blank nodes if graphs are isomorphic
-    /// * `None` - If graphs are not isomorphic
+    /// * `Ok(Some(bijection))` - A mapping from graph A blank nodes to graph B blank nodes if graphs are isomorphic
+    /// * `Ok(None)` - If graphs are not isomorphic
+    /// * `Err(TulnaError::LimitExceeded(_))` - If the search needs more speculations
+    /// * `Err(TulnaError::Timeout(_))` - If the search runs past the deadline of the budget
     fn get_bijection(
         graph_a: &[NormalizedTriple],
         graph_b: &[NormalizedTriple],
-    ) -> Option<HashMap<String, String>> {
-        // Check if all non-blank-node-containing quads in the two graphs are equal
-        let non_blank_a = Self::get_quads_without_blank_nodes(graph_a);
-        let non_blank_b = Self::get_quads_without_blank_nodes(graph_b);
+        budget: SearchBudget,
+        seed: u32,
+    ) -> Result<Option<HashMap<String, String>>, TulnaError> {
+        Ok(Self::get_bijection_or_reason(graph_a, graph_b, &mut None, budget, seed)?.ok())
+    }
+
+    /// Calculate a bijection as in `get_bijection`, reporting why none exists on failure.
+    ///
+    /// When `trace` holds a vector, every step of the derivation is recorded in it.
+    fn get_bijection_or_reason(
+        graph_a: &[NormalizedTriple],
+        graph_b: &[NormalizedTriple],
+        trace: &mut Option<Vec<String>>,
+        budget: SearchBudget,
+        seed: u32,
+    ) -> Result<Result<HashMap<String, String>, IsoResult>, TulnaError> {
+        Self::get_prepared_bijection_or_reason(
+            &PreparedGraph::new(graph_a.len(), graph_a, seed),
+            &PreparedGraph::new(graph_b.len(), graph_b, seed),
+            trace,
+            budget,
+        )
+    }
 
-        let index_a = Self::index_graph(&non_blank_a);
-        let index_b = Self::index_graph(&non_blank_b);
+    /// Calculate a bijection as in `get_bijection_or_reason` between two prepared graphs.
+    ///
+    /// Both graphs have to be prepared with the same seed.
+    fn get_prepared_bijection_or_reason(
+        graph_a: &PreparedGraph,
+        graph_b: &PreparedGraph,
+        trace: &mut Option<Vec<String>>,
+        mut budget: SearchBudget,
+    ) -> Result<Result<HashMap<String, String>, IsoResult>, TulnaError> {
+        // Check if all non-blank-node-containing quads in the two graphs are equal
+        let index_a = &graph_a.ground_index;
+        let index_b = &graph_b.ground_index;
 
         if index_a.len() != index_b.len() {
-            return None;
+            return Ok(Err(IsoResult::DifferentGroundTriples));
         }
 
         for key in index_a.keys() {
             if !index_b.contains_key(key) {
-                return None;
+                return Ok(Err(IsoResult::DifferentGroundTriples));
             }
         }
+        Self::record(trace, || {
+            format!("{} triples without blank nodes match", index_a.len())
+        });
 
-        // Pre-process data for iteration
-        let blank_quads_a = Self::uniq_graph(&Self::get_quads_with_blank_nodes(graph_a));
-        let blank_quads_b = Self::uniq_graph(&Self::get_quads_with_blank_nodes(graph_b));
-        let blank_nodes_a = Self::get_graph_blank_nodes(graph_a);
-        let blank_nodes_b = Self::get_graph_blank_nodes(graph_b);
-
-        if blank_nodes_a.len() != blank_nodes_b.len() {
-            return None;
+        if graph_a.blank_nodes.len() != graph_b.blank_nodes.len() {
+            return Ok(Err(IsoResult::DifferentBlankCount));
         }
+        Self::record(trace, || {
+            format!("Both graphs have {} blank nodes", graph_a.blank_nodes.len())
+        });
+
+        // The cached hashes skip the trace of the first hashing round, so only use them
+        // when no trace is collected
+        let initial_hashes = if trace.is_none() {
+            Some((graph_a.initial_hashes(), graph_b.initial_hashes()))
+        } else {
+            None
+        };
 
-        Self::get_bijection_inner(
-            &blank_quads_a,
-            &blank_quads_b,
-            &blank_nodes_a,
-            &blank_nodes_b,
+        let bijection = Self::get_bijection_inner(
+            &graph_a.blank_quads,
+            &graph_b.blank_quads,
+            &graph_a.blank_nodes,
+            &graph_b.blank_nodes,
             &HashMap::new(),
             &HashMap::new(),
-        )
+            initial_hashes,
+            graph_a.seed,
+            trace,
+            &mut budget,
+        );
+
+        budget.check(bijection.ok_or(IsoResult::NoBijection))
     }
 
     /// Inner recursive bijection finder using iterative hash-based grounding.
@@ -403,11 +2251,17 @@ impl GraphIsomorphism {
     /// * `blank_nodes_b` - Set of blank node identifiers in graph B
     /// * `grounded_hashes_a` - Already-grounded blank nodes and their hash values for graph A
     /// * `grounded_hashes_b` - Already-grounded blank nodes and their hash values for graph B
+    /// * `initial_hashes` - Precomputed `hash_terms` results for both graphs, if available
+    /// * `seed` - Seed of the hashes of both graphs
+    /// * `trace` - Collects the steps of the derivation when it holds a vector
+    /// * `budget` - Remaining speculative groundings and deadline; marked as exceeded when
+    ///   either runs out
     ///
     /// # Returns
     ///
     /// * `Some(bijection)` - Valid mapping from graph A to graph B blank nodes
-    /// * `None` - No valid bijection exists with current groundings
+    /// * `None` - No valid bijection exists with current groundings, or the budget ran out
+    #[allow(clippy::too_many_arguments)]
     fn get_bijection_inner(
         blank_quads_a: &[NormalizedTriple],
         blank_quads_b: &[NormalizedTriple],
@@ -415,26 +2269,48 @@ impl GraphIsomorphism {
         blank_nodes_b: &[String],
         grounded_hashes_a: &HashMap<String, u64>,
         grounded_hashes_b: &HashMap<String, u64>,
+        initial_hashes: Option<(&TermHashes, &TermHashes)>,
+        seed: u32,
+        trace: &mut Option<Vec<String>>,
+        budget: &mut SearchBudget,
     ) -> Option<HashMap<String, String>> {
+        if budget.out_of_time() {
+            Self::record(trace, || "Deadline exceeded".to_string());
+            return None;
+        }
+
         // Hash every term based on the signature of the quads it appears in
-        let (hashes_a, ungrounded_hashes_a) =
-            Self::hash_terms(blank_quads_a, blank_nodes_a, grounded_hashes_a);
-        let (hashes_b, ungrounded_hashes_b) =
-            Self::hash_terms(blank_quads_b, blank_nodes_b, grounded_hashes_b);
+        let (hashed_a, hashed_b): (Cow<TermHashes>, Cow<TermHashes>) = match initial_hashes {
+            Some((hashed_a, hashed_b)) => (Cow::Borrowed(hashed_a), Cow::Borrowed(hashed_b)),
+            None => {
+                Self::record(trace, || "Hashing graph A".to_string());
+                let hashed_a =
+                    Self::hash_terms(blank_quads_a, blank_nodes_a, grounded_hashes_a, seed, trace);
+                Self::record(trace, || "Hashing graph B".to_string());
+                let hashed_b =
+                    Self::hash_terms(blank_quads_b, blank_nodes_b, grounded_hashes_b, seed, trace);
+                (Cow::Owned(hashed_a), Cow::Owned(hashed_b))
+            }
+        };
+        let (hashes_a, ungrounded_hashes_a) = &*hashed_a;
+        let (hashes_b, ungrounded_hashes_b) = &*hashed_b;
 
         // Break quickly if graphs contain different grounded nodes
         if hashes_a.len() != hashes_b.len() {
-            println!(
-                "DEBUG: Different grounded count: {} vs {}",
-                hashes_a.len(),
-                hashes_b.len()
-            );
+            Self::record(trace, || {
+                format!(
+                    "Different number of grounded nodes: {} vs {}",
+                    hashes_a.len(),
+                    hashes_b.len()
+                )
+            });
             return None;
         }
 
+        let hash_values_b: HashSet<u64> = hashes_b.values().copied().collect();
         for hash_value in hashes_a.values() {
-            if !Self::hash_contains_value(&hashes_b, *hash_value) {
-                println!("DEBUG: Hash mismatch in grounded");
+            if !hash_values_b.contains(hash_value) {
+                Self::record(trace, || "Grounded node hashes differ".to_string());
                 return None;
             }
         }
@@ -442,23 +2318,25 @@ impl GraphIsomorphism {
         // Map blank nodes from graph A to graph B using created hashes
         // Only map grounded nodes here; leave ambiguous nodes for speculation phase
         let mut bijection: HashMap<String, String> = HashMap::new();
-        let mut used_b_nodes: HashSet<String> = HashSet::new();
+
+        // Group the grounded nodes of graph B by hash, in reverse order so that popping a
+        // group yields its nodes in the order of `blank_nodes_b`
+        let mut grounded_b_by_hash: HashMap<u64, Vec<String>> = HashMap::new();
+        for node_b in blank_nodes_b.iter().rev() {
+            if let Some(&hash_b) = hashes_b.get(node_b) {
+                grounded_b_by_hash
+                    .entry(hash_b)
+                    .or_default()
+                    .push(node_b.clone());
+            }
+        }
 
         for node_a in blank_nodes_a {
             // Only map if this node is grounded (uniquely identifiable)
-            if let Some(&hash_a) = hashes_a.get(node_a) {
-                for node_b in blank_nodes_b {
-                    if used_b_nodes.contains(node_b) {
-                        continue;
-                    }
-                    // Match against grounded nodes in graph B
-                    if let Some(&hash_b) = hashes_b.get(node_b) {
-                        if hash_a == hash_b {
-                            bijection.insert(node_a.clone(), node_b.clone());
-                            used_b_nodes.insert(node_b.clone());
-                            break;
-                        }
-                    }
+            if let Some(hash_a) = hashes_a.get(node_a) {
+                // Match against the first unused grounded node in graph B with the same hash
+                if let Some(node_b) = grounded_b_by_hash.get_mut(hash_a).and_then(Vec::pop) {
+                    bijection.insert(node_a.clone(), node_b);
                 }
             }
         }
@@ -493,8 +2371,12 @@ impl GraphIsomorphism {
                         ungrounded_hashes_b.get(node_b),
                     ) {
                         if hash_a == hash_b {
-                            println!("DEBUG: Speculating {} -> {}", node_a, node_b);
-                            let new_hash = Self::hash_string(node_a);
+                            if !budget.spend() {
+                                Self::record(trace, || "Speculation limit exceeded".to_string());
+                                return None;
+                            }
+                            Self::record(trace, || format!("Speculating {} -> {}", node_a, node_b));
+                            let new_hash = Self::hash_string(node_a, seed);
                             let mut new_grounded_a = grounded_hashes_a.clone();
                             new_grounded_a.insert(node_a.clone(), new_hash);
                             let mut new_grounded_b = grounded_hashes_b.clone();
@@ -507,14 +2389,23 @@ impl GraphIsomorphism {
                                 blank_nodes_b,
                                 &new_grounded_a,
                                 &new_grounded_b,
+                                None,
+                                seed,
+                                trace,
+                                budget,
                             ) {
                                 return Some(result);
                             }
+                            if budget.exceeded {
+                                return None;
+                            }
                         }
                     }
                 }
             }
-            println!("DEBUG: Recursion failed");
+            Self::record(trace, || {
+                "No speculative assignment leads to a bijection".to_string()
+            });
             return None;
         }
 
@@ -522,11 +2413,115 @@ impl GraphIsomorphism {
         if Self::verify_bijection(blank_quads_a, blank_quads_b, &bijection) {
             Some(bijection)
         } else {
-            println!("DEBUG: Verification failed");
+            Self::record(trace, || {
+                "Bijection does not preserve the graph structure".to_string()
+            });
             None
         }
     }
 
+    /// Record a step of the derivation if a trace is being collected
+    fn record(trace: &mut Option<Vec<String>>, step: impl FnOnce() -> String) {
+        if let Some(steps) = trace {
+            steps.push(step());
+        }
+    }
+
+    /// Recursively compute the canonical form of a graph with some blank nodes speculatively
+    /// grounded.
+    ///
+    /// The hashes computed by `hash_terms` only depend on the structure of the graph, never on
+    /// blank node identifiers, so they can be used to order the blank nodes. If all blank nodes
+    /// have distinct hashes, they are labelled by the rank of their hash. Otherwise, each node
+    /// of the ambiguous group with the smallest hash is grounded in turn and the smallest form
+    /// is returned, which makes the choice independent of the original identifiers. The form
+    /// comes with the rank of every blank node, which is the number of its `_:c` label.
+    ///
+    /// Every grounding takes one speculation from `budget`. Once it runs out the remaining
+    /// candidates are skipped, so the result is meaningless and `SearchBudget::check` has to
+    /// turn it into an error.
+    ///
+    /// # Arguments
+    ///
+    /// * `graph` - The full normalized graph to serialize
+    /// * `blank_quads` - Triples containing blank nodes
+    /// * `blank_nodes` - Blank node identifiers in the graph
+    /// * `speculated_hashes` - Speculatively grounded blank nodes and their hash values
+    /// * `budget` - Speculations left for the search
+    fn canonical_form_inner<'a>(
+        graph: &[NormalizedTriple],
+        blank_quads: &[NormalizedTriple],
+        blank_nodes: &'a [String],
+        speculated_hashes: &HashMap<String, u64>,
+        budget: &mut SearchBudget,
+    ) -> (String, HashMap<&'a String, usize>) {
+        let (hashes, ungrounded_hashes) = Self::hash_terms(
+            blank_quads,
+            blank_nodes,
+            speculated_hashes,
+            Self::FIXED_SEED,
+            &mut None,
+        );
+
+        let mut groups: BTreeMap<u64, Vec<&String>> = BTreeMap::new();
+        for node in blank_nodes {
+            let hash = hashes.get(node).or_else(|| ungrounded_hashes.get(node));
+            groups.entry(*hash.unwrap_or(&0)).or_default().push(node);
+        }
+
+        if let Some((&hash, candidates)) = groups.iter().find(|(_, nodes)| nodes.len() > 1) {
+            // The hash of a speculated node must not depend on its identifier
+            let speculated_hash = Self::hash_string(
+                &format!("@speculated|{}|{}", speculated_hashes.len(), hash),
+                Self::FIXED_SEED,
+            );
+
+            let mut smallest: Option<(String, HashMap<&'a String, usize>)> = None;
+            for &node in candidates {
+                if !budget.spend() {
+                    break;
+                }
+                let mut new_speculated = speculated_hashes.clone();
+                new_speculated.insert(node.clone(), speculated_hash);
+                let candidate = Self::canonical_form_inner(
+                    graph,
+                    blank_quads,
+                    blank_nodes,
+                    &new_speculated,
+                    budget,
+                );
+                if smallest
+                    .as_ref()
+                    .is_none_or(|(form, _)| candidate.0 < *form)
+                {
+                    smallest = Some(candidate);
+                }
+            }
+            return smallest.unwrap_or_default();
+        }
+
+        let ranks: HashMap<&String, usize> = groups
+            .values()
+            .enumerate()
+            .map(|(rank, nodes)| (nodes[0], rank))
+            .collect();
+        let label = |term: &String| match ranks.get(term) {
+            Some(rank) => format!("_:c{}", rank),
+            None => term.clone(),
+        };
+
+        let mut lines: Vec<String> = graph
+            .iter()
+            .map(|quad| {
+                let mut line = quad.terms().map(label).collect::<Vec<_>>().join(" ");
+                line.push_str(" .");
+                line
+            })
+            .collect();
+        lines.sort();
+        (lines.join("\n"), ranks)
+    }
+
     /// Verify that applying the bijection to graph A yields graph B.
     fn verify_bijection(
         graph_a: &[NormalizedTriple],
@@ -543,8 +2538,9 @@ impl GraphIsomorphism {
             let s = bijection.get(&quad.subject).unwrap_or(&quad.subject);
             let p = bijection.get(&quad.predicate).unwrap_or(&quad.predicate);
             let o = bijection.get(&quad.object).unwrap_or(&quad.object);
+            let g = bijection.get(&quad.graph).unwrap_or(&quad.graph);
 
-            let key = format!("{}|{}|{}", s, p, o);
+            let key = format!("{}|{}|{}|{}", s, p, o, g);
             if !index_b.contains_key(&key) {
                 return false;
             }
@@ -585,6 +2581,8 @@ impl GraphIsomorphism {
     /// * `quads` - The triples containing blank nodes to analyze
     /// * `terms` - The blank node identifiers to compute hashes for
     /// * `grounded_hashes` - Previously grounded nodes with their assigned hash values
+    /// * `seed` - Seed of the hashes
+    /// * `trace` - Collects the nodes grounded in each iteration when it holds a vector
     ///
     /// # Returns
     ///
@@ -595,44 +2593,181 @@ impl GraphIsomorphism {
         quads: &[NormalizedTriple],
         terms: &[String],
         grounded_hashes: &HashMap<String, u64>,
+        seed: u32,
+        trace: &mut Option<Vec<String>>,
+    ) -> (HashMap<String, u64>, HashMap<String, u64>) {
+        Self::hash_terms_reusing(quads, terms, grounded_hashes, seed, trace, None, &mut None)
+    }
+
+    /// Create hash signatures for blank nodes as `hash_terms` does, reusing the hashes of an
+    /// earlier version of the graph where they cannot have changed.
+    ///
+    /// A blank node keeps the hash it had in the same iteration of the earlier version when
+    /// its triples are unchanged and its neighbours are grounded as they were then (see
+    /// `StaleHashes::reusable`), so only the nodes near the changed triples are hashed
+    /// again. Grounding by unique hashes still looks at every node, so the result is the
+    /// same as without `stale`. `stale` must come from a run without `grounded_hashes`.
+    ///
+    /// # Arguments
+    ///
+    /// * `stale` - Hashes of an earlier version of the graph, if any
+    /// * `iterations` - Collects the hashes of the nodes in each iteration when it holds a
+    ///   history
+    ///
+    /// See `hash_terms` for the other arguments and the result.
+    fn hash_terms_reusing(
+        quads: &[NormalizedTriple],
+        terms: &[String],
+        grounded_hashes: &HashMap<String, u64>,
+        seed: u32,
+        trace: &mut Option<Vec<String>>,
+        stale: Option<&StaleHashes>,
+        iterations: &mut Option<IterationHistory>,
     ) -> (HashMap<String, u64>, HashMap<String, u64>) {
+        // The triples holding each blank node, so hashing a node does not scan the whole
+        // graph, and the blank nodes sharing a triple with it
+        let mut adjacency: HashMap<&str, Vec<&NormalizedTriple>> = HashMap::new();
+        let mut neighbours: HashMap<&str, HashSet<&str>> = HashMap::new();
+        for quad in quads {
+            let blank_terms: Vec<&str> = quad
+                .terms()
+                .filter(|term| term.starts_with("_:"))
+                .map(String::as_str)
+                .collect();
+            for &term in &blank_terms {
+                let term_quads = adjacency.entry(term).or_default();
+                // A node holding several positions of the triple is listed once
+                if !term_quads
+                    .last()
+                    .is_some_and(|last| std::ptr::eq(*last, quad))
+                {
+                    term_quads.push(quad);
+                }
+                let entry = neighbours.entry(term).or_default();
+                entry.extend(blank_terms.iter().filter(|&&other| other != term));
+            }
+        }
+        let positions: HashMap<&str, usize> = terms
+            .iter()
+            .enumerate()
+            .map(|(position, term)| (term.as_str(), position))
+            .collect();
+
         let mut hashes = grounded_hashes.clone();
         let mut ungrounded_hashes: HashMap<String, u64> = HashMap::new();
+        // The nodes holding each hash in `ungrounded_hashes`
+        let mut holders_by_hash: HashMap<u64, HashSet<&str>> = HashMap::new();
+        // A node only gets another hash once one of its neighbours is grounded, so each
+        // iteration hashes the nodes whose neighbours were grounded since they were last
+        // hashed, in the order of `terms`, and keeps the hashes of the other nodes
+        let mut pending: BTreeSet<usize> = (0..terms.len())
+            .filter(|&position| !hashes.contains_key(&terms[position]))
+            .collect();
         let mut hash_needed = true;
+        let mut iteration = 0;
 
         // Iteratively mark nodes as grounded
         while hash_needed {
             let initial_grounded_count = hashes.len();
+            iteration += 1;
+            // Only needed to report which nodes this iteration grounds
+            let previously_grounded: HashSet<String> = match trace {
+                Some(_) => hashes.keys().cloned().collect(),
+                None => HashSet::new(),
+            };
 
-            for term in terms {
-                if !hashes.contains_key(term) {
-                    let (grounded, hash) = Self::hash_term(term, quads, &hashes);
-                    if grounded {
-                        hashes.insert(term.clone(), hash);
+            let mut current = std::mem::take(&mut pending);
+            let mut changed_hashes = HashSet::new();
+            let mut newly_grounded = Vec::new();
+            while let Some(position) = current.pop_first() {
+                let term = terms[position].as_str();
+                if hashes.contains_key(term) {
+                    continue;
+                }
+                let term_neighbours = neighbours.get(term);
+                let reused = stale.and_then(|stale| {
+                    stale.reusable(term, iteration - 1, term_neighbours?, &hashes)
+                });
+                let hashed = reused.unwrap_or_else(|| {
+                    let term_quads = adjacency.get(term).map_or(&[][..], Vec::as_slice);
+                    let (grounded, hash) = Self::hash_term(term, term_quads, &hashes, seed);
+                    IterationHash {
+                        hash,
+                        grounded,
+                        reused: false,
                     }
-                    ungrounded_hashes.insert(term.clone(), hash);
+                });
+                if let Some(iterations) = iterations {
+                    let term_hashes = iterations.hashes.entry(term.to_string()).or_default();
+                    term_hashes.push((iteration - 1, hashed));
                 }
-            }
 
-            // All terms that have a unique hash at this point can be marked as grounded
-            let mut hash_to_term: HashMap<u64, Option<String>> = HashMap::new();
-            for (term, &hash) in &ungrounded_hashes {
-                if let Some(existing) = hash_to_term.get(&hash) {
-                    if existing.is_some() {
-                        hash_to_term.insert(hash, None); // Mark as non-unique
+                if let Some(previous) = ungrounded_hashes.insert(term.to_string(), hashed.hash) {
+                    if let Some(holders) = holders_by_hash.get_mut(&previous) {
+                        holders.remove(term);
+                    }
+                    changed_hashes.insert(previous);
+                }
+                holders_by_hash.entry(hashed.hash).or_default().insert(term);
+                changed_hashes.insert(hashed.hash);
+
+                if hashed.grounded {
+                    hashes.insert(term.to_string(), hashed.hash);
+                    newly_grounded.push(term);
+                    // Later nodes see the grounded neighbour in this iteration, earlier
+                    // ones in the next
+                    for neighbour in term_neighbours.into_iter().flatten() {
+                        if let Some(&other) = positions.get(neighbour) {
+                            if other > position {
+                                current.insert(other);
+                            } else {
+                                pending.insert(other);
+                            }
+                        }
                     }
-                } else {
-                    hash_to_term.insert(hash, Some(term.clone()));
                 }
             }
 
-            for (hash, term_opt) in hash_to_term {
-                if let Some(term) = term_opt {
-                    hashes.insert(term, hash);
+            // All terms that have a unique hash at this point can be marked as grounded. Only
+            // the hashes that changed hands can have become unique.
+            for hash in changed_hashes {
+                let holders = &holders_by_hash[&hash];
+                if holders.len() == 1 {
+                    let term = *holders.iter().next().unwrap();
+                    if hashes.insert(term.to_string(), hash).is_none() {
+                        newly_grounded.push(term);
+                        for neighbour in neighbours.get(term).into_iter().flatten() {
+                            if let Some(&other) = positions.get(neighbour) {
+                                pending.insert(other);
+                            }
+                        }
+                    }
+                }
+            }
+            if let Some(iterations) = iterations {
+                iterations.count = iteration;
+                for term in newly_grounded {
+                    iterations.grounded.insert(term.to_string(), iteration);
                 }
             }
 
             hash_needed = initial_grounded_count != hashes.len();
+
+            Self::record(trace, || {
+                let mut grounded: Vec<&str> = terms
+                    .iter()
+                    .filter(|term| {
+                        hashes.contains_key(*term) && !previously_grounded.contains(*term)
+                    })
+                    .map(|term| term.as_str())
+                    .collect();
+                grounded.sort();
+                if grounded.is_empty() {
+                    format!("Iteration {}: no new nodes grounded", iteration)
+                } else {
+                    format!("Iteration {}: grounded {}", iteration, grounded.join(", "))
+                }
+            });
         }
 
         (hashes, ungrounded_hashes)
@@ -640,7 +2775,7 @@ impl GraphIsomorphism {
 
     /// Generate a hash signature for a single blank node.
     ///
-    /// This method finds all triples containing the target blank node and creates a
+    /// This method takes the triples containing the target blank node and creates a
     /// structural signature that captures the node's context. The signature includes
     /// information about connected predicates and objects/subjects.
     ///
@@ -660,8 +2795,9 @@ impl GraphIsomorphism {
     /// # Arguments
     ///
     /// * `term` - The blank node identifier to hash
-    /// * `quads` - All triples to search for occurrences of this node
+    /// * `quads` - The triples containing this node, each listed once
     /// * `hashes` - Currently grounded nodes and their hash values
+    /// * `seed` - Seed of the hash
     ///
     /// # Returns
     ///
@@ -670,27 +2806,27 @@ impl GraphIsomorphism {
     /// * `hash` - The computed hash signature for this node
     fn hash_term(
         term: &str,
-        quads: &[NormalizedTriple],
+        quads: &[&NormalizedTriple],
         hashes: &HashMap<String, u64>,
+        seed: u32,
     ) -> (bool, u64) {
         let mut quad_signatures = Vec::new();
         let mut grounded = true;
 
         for quad in quads {
-            let terms_in_quad = [&quad.subject, &quad.predicate, &quad.object];
-            if terms_in_quad.iter().any(|&t| t == term) {
-                quad_signatures.push(Self::quad_to_signature(quad, hashes, term));
+            quad_signatures.push(Self::quad_to_signature(quad, hashes, term));
 
-                for quad_term in &terms_in_quad {
-                    if !Self::is_term_grounded(quad_term, hashes) && *quad_term != term {
-                        grounded = false;
-                    }
+            // The term itself is skipped in every position it holds, so a self-loop
+            // does not keep it from being grounded
+            for quad_term in quad.terms() {
+                if !Self::is_term_grounded(quad_term, hashes) && quad_term != term {
+                    grounded = false;
                 }
             }
         }
 
         quad_signatures.sort();
-        let hash = Self::hash_string(&quad_signatures.join(""));
+        let hash = Self::hash_string(&quad_signatures.join(""), seed);
         (grounded, hash)
     }
 
@@ -702,7 +2838,7 @@ impl GraphIsomorphism {
     ///
     /// # Format
     ///
-    /// `"<subject_sig>|<predicate_sig>|<object_sig>"`
+    /// `"<subject_sig>|<predicate_sig>|<object_sig>|<graph_sig>"`
     ///
     /// Where each position uses:
     /// - `@self` for the target blank node
@@ -713,8 +2849,8 @@ impl GraphIsomorphism {
     /// # Example
     ///
     /// For triple `_:b1 <knows> _:b2` with target `_:b1`:
-    /// - If `_:b2` is grounded with hash `12345`: `"@self|<knows>|12345"`
-    /// - If `_:b2` is not grounded: `"@self|<knows>|@blank"`
+    /// - If `_:b2` is grounded with hash `12345`: `"@self|<knows>|12345|"`
+    /// - If `_:b2` is not grounded: `"@self|<knows>|@blank|"`
     fn quad_to_signature(
         quad: &NormalizedTriple,
         hashes: &HashMap<String, u64>,
@@ -723,7 +2859,8 @@ impl GraphIsomorphism {
         let s_sig = Self::term_to_signature(&quad.subject, hashes, term);
         let p_sig = Self::term_to_signature(&quad.predicate, hashes, term);
         let o_sig = Self::term_to_signature(&quad.object, hashes, term);
-        format!("{}|{}|{}", s_sig, p_sig, o_sig)
+        let g_sig = Self::term_to_signature(&quad.graph, hashes, term);
+        format!("{}|{}|{}|{}", s_sig, p_sig, o_sig, g_sig)
     }
 
     /// Convert a single term to its signature representation.
@@ -781,37 +2918,23 @@ impl GraphIsomorphism {
     /// # Arguments
     ///
     /// * `data` - The string to hash
+    /// * `seed` - The MurmurHash3 seed
     ///
     /// # Returns
     ///
     /// A 64-bit hash value
-    fn hash_string(data: &str) -> u64 {
-        let mut cursor = Cursor::new(data.as_bytes());
-        let hash128 = murmur3::murmur3_x64_128(&mut cursor, 0).unwrap_or(0);
+    fn hash_string(data: &str, seed: u32) -> u64 {
+        // A byte slice is a reader of its own, so the hash reads `data` in place; reading
+        // from memory cannot fail
+        let hash128 = murmur3::murmur3_x64_128(&mut data.as_bytes(), seed).unwrap_or(0);
         // Use the lower 64 bits of the 128-bit hash
         (hash128 & 0xFFFFFFFFFFFFFFFF) as u64
     }
 
-    /// Check if a hash map contains a specific value.
-    ///
-    /// Helper function to determine if any key in the map has the given value.
-    ///
-    /// # Arguments
-    ///
-    /// * `hash` - The hash map to search
-    /// * `value` - The value to look for
-    ///
-    /// # Returns
-    ///
-    /// `true` if the value exists in the map, `false` otherwise
-    fn hash_contains_value(hash: &HashMap<String, u64>, value: u64) -> bool {
-        hash.values().any(|&v| v == value)
-    }
-
     /// Filter triples to only those containing at least one blank node.
     ///
-    /// Extracts all triples where the subject, predicate, or object is a blank node
-    /// (identifier starts with "_:"). These triples require structural analysis for
+    /// Extracts all triples where the subject, predicate, object or graph name is a blank
+    /// node (identifier starts with "_:"). These triples require structural analysis for
     /// isomorphism checking.
     ///
     /// # Arguments
@@ -824,18 +2947,14 @@ impl GraphIsomorphism {
     fn get_quads_with_blank_nodes(graph: &[NormalizedTriple]) -> Vec<NormalizedTriple> {
         graph
             .iter()
-            .filter(|quad| {
-                quad.subject.starts_with("_:")
-                    || quad.predicate.starts_with("_:")
-                    || quad.object.starts_with("_:")
-            })
+            .filter(|quad| !quad.is_ground())
             .cloned()
             .collect()
     }
 
     /// Filter triples to only those without any blank nodes.
     ///
-    /// Extracts all triples where none of the subject, predicate, or object positions
+    /// Extracts all triples where none of the subject, predicate, object or graph positions
     /// contain blank nodes. These triples must match exactly between isomorphic graphs
     /// and serve as an early-exit optimization.
     ///
@@ -849,18 +2968,14 @@ impl GraphIsomorphism {
     fn get_quads_without_blank_nodes(graph: &[NormalizedTriple]) -> Vec<NormalizedTriple> {
         graph
             .iter()
-            .filter(|quad| {
-                !quad.subject.starts_with("_:")
-                    && !quad.predicate.starts_with("_:")
-                    && !quad.object.starts_with("_:")
-            })
+            .filter(|quad| quad.is_ground())
             .cloned()
             .collect()
     }
 
     /// Create a hash map index of triples for fast lookup.
     ///
-    /// Converts each triple to a canonical string key (subject|predicate|object) and
+    /// Converts each triple to a canonical string key (subject|predicate|object|graph) and
     /// stores it in a hash map. This enables O(1) membership testing for comparing
     /// non-blank triples between graphs.
     ///
@@ -874,17 +2989,17 @@ impl GraphIsomorphism {
     fn index_graph(graph: &[NormalizedTriple]) -> HashMap<String, bool> {
         let mut index = HashMap::new();
         for quad in graph {
-            let key = format!("{}|{}|{}", quad.subject, quad.predicate, quad.object);
-            index.insert(key, true);
+            index.insert(quad.index_key(), true);
         }
         index
     }
 
     /// Remove duplicate triples from a graph.
     ///
-    /// Uses hash map indexing to identify and remove duplicate triples, returning
-    /// only unique triples. This is necessary because the algorithm may generate
-    /// duplicate entries during processing.
+    /// Uses a hash set to identify and remove duplicate triples, returning only unique
+    /// triples in their original order. This is necessary because the algorithm may
+    /// generate duplicate entries during processing. The triples are compared directly
+    /// rather than through their index keys, as literals may themselves contain `|`.
     ///
     /// # Arguments
     ///
@@ -894,24 +3009,18 @@ impl GraphIsomorphism {
     ///
     /// Vector of unique triples
     fn uniq_graph(graph: &[NormalizedTriple]) -> Vec<NormalizedTriple> {
-        let index = Self::index_graph(graph);
-        index
-            .keys()
-            .map(|key| {
-                let parts: Vec<&str> = key.split('|').collect();
-                NormalizedTriple {
-                    subject: parts[0].to_string(),
-                    predicate: parts[1].to_string(),
-                    object: parts[2].to_string(),
-                }
-            })
+        let mut seen = HashSet::new();
+        graph
+            .iter()
+            .filter(|quad| seen.insert((*quad).clone()))
+            .cloned()
             .collect()
     }
 
     /// Extract all unique blank node identifiers from a graph.
     ///
     /// Scans all triples and collects unique blank node identifiers (those starting
-    /// with "_:") from subject, predicate, and object positions. Returns them in
+    /// with "_:") from subject, predicate, object and graph positions. Returns them in
     /// sorted order for consistent processing.
     ///
     /// # Arguments
@@ -924,35 +3033,185 @@ impl GraphIsomorphism {
     fn get_graph_blank_nodes(graph: &[NormalizedTriple]) -> Vec<String> {
         let mut blanks = HashSet::new();
         for quad in graph {
-            if quad.subject.starts_with("_:") {
-                blanks.insert(quad.subject.clone());
-            }
-            if quad.predicate.starts_with("_:") {
-                blanks.insert(quad.predicate.clone());
-            }
-            if quad.object.starts_with("_:") {
-                blanks.insert(quad.object.clone());
+            for term in quad.terms() {
+                if term.starts_with("_:") {
+                    blanks.insert(term.clone());
+                }
             }
         }
         let mut result: Vec<String> = blanks.into_iter().collect();
         result.sort();
         result
     }
+
+    /// Check whether two normalized graphs differ in a cheap isomorphism invariant.
+    ///
+    /// Returns `true` if the graphs are certainly not isomorphic, so the grounding algorithm
+    /// can be skipped. A `false` result says nothing about isomorphism.
+    fn quick_reject(graph_a: &[NormalizedTriple], graph_b: &[NormalizedTriple]) -> bool {
+        Self::invariant_profile(graph_a) != Self::invariant_profile(graph_b)
+    }
+
+    /// Compute invariants of a normalized graph that any bijection of its blank nodes
+    /// preserves.
+    ///
+    /// Duplicate triples are counted once, as the algorithm compares graphs as sets of
+    /// triples.
+    ///
+    /// # Returns
+    ///
+    /// A tuple of:
+    /// * The number of triples per predicate, with all blank node predicates counted together
+    /// * The sorted (out-degree, in-degree) pairs of the blank nodes in subject or object position
+    fn invariant_profile(
+        graph: &[NormalizedTriple],
+    ) -> (BTreeMap<&str, usize>, Vec<(usize, usize)>) {
+        let distinct: HashSet<&NormalizedTriple> = graph.iter().collect();
+
+        let mut predicates: BTreeMap<&str, usize> = BTreeMap::new();
+        let mut degrees: HashMap<&str, (usize, usize)> = HashMap::new();
+        for quad in distinct {
+            let predicate = if quad.predicate.starts_with("_:") {
+                "_:"
+            } else {
+                quad.predicate.as_str()
+            };
+            *predicates.entry(predicate).or_insert(0) += 1;
+
+            if quad.subject.starts_with("_:") {
+                degrees.entry(&quad.subject).or_default().0 += 1;
+            }
+            if quad.object.starts_with("_:") {
+                degrees.entry(&quad.object).or_default().1 += 1;
+            }
+        }
+
+        let mut degree_histogram: Vec<(usize, usize)> = degrees.into_values().collect();
+        degree_histogram.sort_unstable();
+        (predicates, degree_histogram)
+    }
+
+    /// Check whether a triple could be mapped onto another one, ignoring the blank nodes it
+    /// holds.
+    fn ground_terms_match(quad: &NormalizedTriple, image: &NormalizedTriple) -> bool {
+        quad.positions()
+            .into_iter()
+            .zip(image.positions())
+            .all(|(term, image_term)| term.starts_with("_:") || term == image_term)
+    }
+
+    /// Map the triples of `candidates` from `index` on onto one of their candidate images,
+    /// extending the blank node mapping built so far.
+    ///
+    /// Every complete embedding is passed to `on_embedding`, and the search stops once that
+    /// returns `true`. Every assignment tried for a triple with more than one candidate
+    /// spends from the budget. When the search stops, the mapping holds the last embedding;
+    /// otherwise it is left as it was.
+    fn extend_embedding<'a>(
+        candidates: &[(&'a NormalizedTriple, Vec<&'a NormalizedTriple>)],
+        index: usize,
+        mapping: &mut HashMap<&'a str, &'a str>,
+        used: &mut HashSet<&'a str>,
+        budget: &mut SearchBudget,
+        on_embedding: &mut dyn FnMut() -> bool,
+    ) -> bool {
+        let Some((quad, images)) = candidates.get(index) else {
+            return on_embedding();
+        };
+
+        for image in images {
+            if images.len() > 1 && !budget.spend() {
+                return false;
+            }
+
+            let mut bound: Vec<&str> = Vec::new();
+            let consistent = quad
+                .positions()
+                .into_iter()
+                .zip(image.positions())
+                .filter(|(term, _)| term.starts_with("_:"))
+                .all(|(term, image_term)| match mapping.get(term.as_str()) {
+                    Some(&mapped) => mapped == image_term,
+                    None if used.contains(image_term.as_str()) => false,
+                    None => {
+                        mapping.insert(term, image_term);
+                        used.insert(image_term);
+                        bound.push(term);
+                        true
+                    }
+                });
+
+            if consistent
+                && Self::extend_embedding(
+                    candidates,
+                    index + 1,
+                    mapping,
+                    used,
+                    budget,
+                    on_embedding,
+                )
+            {
+                return true;
+            }
+
+            for term in bound {
+                if let Some(image_term) = mapping.remove(term) {
+                    used.remove(image_term);
+                }
+            }
+            if budget.exceeded {
+                return false;
+            }
+        }
+        false
+    }
 }
 
 /// Normalized triple representation with string-based node values.
 ///
 /// Internal representation used by the graph isomorphism algorithm. All nodes
-/// (subjects, predicates, objects) are normalized to string representations:
+/// (subjects, predicates, objects, graph names) are normalized to string representations:
 /// - IRIs: `"<http://example.org/iri>"`
 /// - Literals: `"\"literal value\""`
-/// - Blank nodes: `"_:identifier"`
-/// - Variables (treated as blank nodes): `"_:b0"`, `"_:b1"`, etc.
+/// - Blank nodes: `"_:bnidentifier"`
+/// - Variables (treated as blank nodes): `"_:v0"`, `"_:v1"`, etc.
+///
+/// The `bn` and `v` prefixes keep a variable and a blank node with the same name apart.
+/// Triples in the default graph have an empty graph name.
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 struct NormalizedTriple {
     subject: String,
     predicate: String,
     object: String,
+    graph: String,
+}
+
+impl NormalizedTriple {
+    /// The subject, predicate, object and graph name, which is empty for the default graph
+    fn positions(&self) -> [&String; 4] {
+        [&self.subject, &self.predicate, &self.object, &self.graph]
+    }
+
+    /// The subject, predicate and object, followed by the graph name outside the default graph
+    fn terms(&self) -> impl Iterator<Item = &String> {
+        [&self.subject, &self.predicate, &self.object]
+            .into_iter()
+            .chain(Some(&self.graph).filter(|graph| !graph.is_empty()))
+    }
+
+    /// Whether none of the terms is a blank node
+    fn is_ground(&self) -> bool {
+        !self.terms().any(|term| term.starts_with("_:"))
+    }
+
+    /// The key of the triple in an index built by `GraphIsomorphism::index_graph`
+    fn index_key(&self) -> String {
+        format!(
+            "{}|{}|{}|{}",
+            self.subject, self.predicate, self.object, self.graph
+        )
+    }
 }
 
 #[cfg(test)]
@@ -966,6 +3225,7 @@ mod tests {
             subject: TripleNode::Variable("s".to_string()),
             predicate: TripleNode::IRI("http://example.org/predicate".to_string()),
             object: TripleNode::Variable("o".to_string()),
+            graph: None,
         }];
 
         let normalized = GraphIsomorphism::normalize_bgp(&bgp);
@@ -974,18 +3234,62 @@ mod tests {
         assert!(normalized[0].object.starts_with("_:"));
     }
 
+    #[test]
+    fn test_normalize_bgp_reuses_predicate_variables() {
+        // ?p is a predicate and an object in the first triple and the subject of the second
+        let bgp = vec![
+            Triple::new("?s", "?p", "?p"),
+            Triple::new("?p", "<http://example.org/label>", "?o"),
+        ];
+
+        let normalized = GraphIsomorphism::normalize_bgp(&bgp);
+        assert_eq!(normalized[0].predicate, normalized[0].object);
+        assert_eq!(normalized[0].predicate, normalized[1].subject);
+        assert_ne!(normalized[0].subject, normalized[0].predicate);
+        assert_ne!(normalized[1].object, normalized[0].predicate);
+
+        let renamed = vec![
+            Triple::new("?b", "<http://example.org/label>", "?c"),
+            Triple::new("?a", "?b", "?b"),
+        ];
+        let broken = vec![
+            Triple::new("?a", "?b", "?b"),
+            Triple::new("?a", "<http://example.org/label>", "?c"),
+        ];
+        assert!(GraphIsomorphism::are_isomorphic(&bgp, &renamed).unwrap());
+        assert!(!GraphIsomorphism::are_isomorphic(&bgp, &broken).unwrap());
+    }
+
+    #[test]
+    fn test_predicate_variables_are_hashed() {
+        let graph = vec![Triple::new("?s", "?p", "\"lit\"")];
+        let prepared = GraphIsomorphism::prepare(&graph);
+        let (grounded, _) = prepared.initial_hashes();
+
+        // The subject and predicate get different hashes, so both are grounded
+        let subject = &prepared.blank_nodes[0];
+        let predicate = &prepared.blank_nodes[1];
+        assert_eq!(grounded.len(), 2);
+        assert_ne!(grounded[subject], grounded[predicate]);
+
+        let swapped = vec![Triple::new("?p", "?s", "\"lit\"")];
+        assert!(GraphIsomorphism::are_isomorphic(&graph, &swapped).unwrap());
+    }
+
     #[test]
     fn test_isomorphic_bgps() {
         let bgp1 = vec![Triple {
             subject: TripleNode::Variable("x".to_string()),
             predicate: TripleNode::IRI("http://example.org/p".to_string()),
             object: TripleNode::Variable("y".to_string()),
+            graph: None,
         }];
 
         let bgp2 = vec![Triple {
             subject: TripleNode::Variable("a".to_string()),
             predicate: TripleNode::IRI("http://example.org/p".to_string()),
             object: TripleNode::Variable("b".to_string()),
+            graph: None,
         }];
 
         let result = GraphIsomorphism::check_bgp_isomorphism(&bgp1, &bgp2);
@@ -999,12 +3303,14 @@ mod tests {
             subject: TripleNode::Variable("x".to_string()),
             predicate: TripleNode::IRI("http://example.org/p1".to_string()),
             object: TripleNode::Variable("y".to_string()),
+            graph: None,
         }];
 
         let bgp2 = vec![Triple {
             subject: TripleNode::Variable("a".to_string()),
             predicate: TripleNode::IRI("http://example.org/p2".to_string()),
             object: TripleNode::Variable("b".to_string()),
+            graph: None,
         }];
 
         let result = GraphIsomorphism::check_bgp_isomorphism(&bgp1, &bgp2);
@@ -1019,11 +3325,13 @@ mod tests {
                 subject: TripleNode::Variable("x".to_string()),
                 predicate: TripleNode::IRI("http://example.org/p".to_string()),
                 object: TripleNode::Variable("y".to_string()),
+                graph: None,
             },
             Triple {
                 subject: TripleNode::Variable("y".to_string()),
                 predicate: TripleNode::IRI("http://example.org/q".to_string()),
                 object: TripleNode::Literal("value".to_string()),
+                graph: None,
             },
         ];
 
@@ -1032,27 +3340,92 @@ mod tests {
                 subject: TripleNode::Variable("a".to_string()),
                 predicate: TripleNode::IRI("http://example.org/p".to_string()),
                 object: TripleNode::Variable("b".to_string()),
+                graph: None,
             },
             Triple {
                 subject: TripleNode::Variable("b".to_string()),
                 predicate: TripleNode::IRI("http://example.org/q".to_string()),
                 object: TripleNode::Literal("value".to_string()),
+                graph: None,
             },
         ];
 
-        let result = GraphIsomorphism::check_bgp_isomorphism(&bgp1, &bgp2);
-        assert!(result.is_ok());
-        assert!(result.unwrap());
+        let result = GraphIsomorphism::check_bgp_isomorphism(&bgp1, &bgp2);
+        assert!(result.is_ok());
+        assert!(result.unwrap());
+    }
+
+    #[test]
+    fn test_hash_string() {
+        let hash1 = GraphIsomorphism::hash_string("test", 0);
+        let hash2 = GraphIsomorphism::hash_string("test", 0);
+        let hash3 = GraphIsomorphism::hash_string("different", 0);
+        let hash4 = GraphIsomorphism::hash_string("test", 1);
+
+        assert_eq!(hash1, hash2);
+        assert_ne!(hash1, hash3);
+        assert_ne!(hash1, hash4);
+    }
+
+    #[test]
+    fn test_are_isomorphic_seeded() {
+        let next = |s: &str, o: &str| Triple::new(s, "<http://example.org/next>", o);
+        let cycle = vec![next("?a", "?b"), next("?b", "?c"), next("?c", "?a")];
+        let renamed = vec![next("?y", "?z"), next("?x", "?y"), next("?z", "?x")];
+        let chain = vec![next("?a", "?b"), next("?b", "?c"), next("?c", "?d")];
+
+        for seed in [0, 1, 42, u32::MAX] {
+            assert!(GraphIsomorphism::are_isomorphic_seeded(&cycle, &renamed, seed).unwrap());
+            assert!(!GraphIsomorphism::are_isomorphic_seeded(&cycle, &chain, seed).unwrap());
+        }
+
+        // The process-wide seed stays the same between calls
+        assert_eq!(
+            GraphIsomorphism::default_seed(),
+            GraphIsomorphism::default_seed()
+        );
+        let hashes = |graph: &[Triple]| {
+            GraphIsomorphism::prepare(graph)
+                .initial_hashes()
+                .1
+                .values()
+                .copied()
+                .collect::<BTreeSet<u64>>()
+        };
+        assert_eq!(hashes(&cycle), hashes(&renamed));
     }
 
     #[test]
-    fn test_hash_string() {
-        let hash1 = GraphIsomorphism::hash_string("test");
-        let hash2 = GraphIsomorphism::hash_string("test");
-        let hash3 = GraphIsomorphism::hash_string("different");
+    fn test_are_isomorphic_empty_graphs() {
+        let ground = vec![Triple::new(
+            "<http://example.org/alice>",
+            "<http://example.org/knows>",
+            "<http://example.org/bob>",
+        )];
 
-        assert_eq!(hash1, hash2);
-        assert_ne!(hash1, hash3);
+        assert!(GraphIsomorphism::are_isomorphic(&[], &[]).unwrap());
+        assert!(!GraphIsomorphism::are_isomorphic(&[], &ground).unwrap());
+        assert!(!GraphIsomorphism::are_isomorphic(&ground, &[]).unwrap());
+        assert!(GraphIsomorphism::are_isomorphic(&ground, &ground).unwrap());
+        assert_eq!(
+            GraphIsomorphism::find_bijection(&[], &[]).unwrap(),
+            Some(HashMap::new())
+        );
+    }
+
+    #[test]
+    fn test_check_bgp_isomorphism_ignores_duplicates() {
+        let knows = Triple::new("?x", "<http://example.org/knows>", "?y");
+        let name = Triple::new("?y", "<http://example.org/name>", "\"Bob\"");
+
+        let duplicated = vec![knows.clone(), name.clone(), knows.clone()];
+        let single = vec![name.clone(), knows.clone()];
+        assert!(GraphIsomorphism::check_bgp_isomorphism(&duplicated, &single).unwrap());
+        assert!(GraphIsomorphism::check_bgp_isomorphism(&single, &duplicated).unwrap());
+
+        // Duplicates do not make up for a missing triple
+        let missing = vec![knows.clone(), knows];
+        assert!(!GraphIsomorphism::check_bgp_isomorphism(&missing, &single).unwrap());
     }
 
     #[test]
@@ -1061,6 +3434,7 @@ mod tests {
             subject: "_:b0".to_string(),
             predicate: "<http://example.org/p>".to_string(),
             object: "_:b1".to_string(),
+            graph: String::new(),
         }];
 
         let blanks = GraphIsomorphism::get_graph_blank_nodes(&graph);
@@ -1077,21 +3451,25 @@ mod tests {
                 subject: TripleNode::Variable("x".to_string()),
                 predicate: TripleNode::IRI("http://example.org/p".to_string()),
                 object: TripleNode::Variable("y".to_string()),
+                graph: None,
             },
             Triple {
                 subject: TripleNode::Variable("x".to_string()),
                 predicate: TripleNode::IRI("http://example.org/q".to_string()),
                 object: TripleNode::Variable("z".to_string()),
+                graph: None,
             },
             Triple {
                 subject: TripleNode::Variable("y".to_string()),
                 predicate: TripleNode::IRI("http://example.org/r".to_string()),
                 object: TripleNode::Literal("A".to_string()),
+                graph: None,
             },
             Triple {
                 subject: TripleNode::Variable("z".to_string()),
                 predicate: TripleNode::IRI("http://example.org/r".to_string()),
                 object: TripleNode::Literal("B".to_string()),
+                graph: None,
             },
         ];
 
@@ -1100,21 +3478,25 @@ mod tests {
                 subject: TripleNode::Variable("a".to_string()),
                 predicate: TripleNode::IRI("http://example.org/p".to_string()),
                 object: TripleNode::Variable("b".to_string()),
+                graph: None,
             },
             Triple {
                 subject: TripleNode::Variable("a".to_string()),
                 predicate: TripleNode::IRI("http://example.org/q".to_string()),
                 object: TripleNode::Variable("c".to_string()),
+                graph: None,
             },
             Triple {
                 subject: TripleNode::Variable("b".to_string()),
                 predicate: TripleNode::IRI("http://example.org/r".to_string()),
                 object: TripleNode::Literal("A".to_string()),
+                graph: None,
             },
             Triple {
                 subject: TripleNode::Variable("c".to_string()),
                 predicate: TripleNode::IRI("http://example.org/r".to_string()),
                 object: TripleNode::Literal("B".to_string()),
+                graph: None,
             },
         ];
 
@@ -1132,31 +3514,37 @@ mod tests {
                 subject: TripleNode::Variable("v1".to_string()),
                 predicate: TripleNode::IRI("http://example.org/p1".to_string()),
                 object: TripleNode::Literal("A".to_string()),
+                graph: None,
             },
             Triple {
                 subject: TripleNode::Variable("v2".to_string()),
                 predicate: TripleNode::IRI("http://example.org/p1".to_string()),
                 object: TripleNode::Literal("B".to_string()),
+                graph: None,
             },
             Triple {
                 subject: TripleNode::Variable("v3".to_string()),
                 predicate: TripleNode::IRI("http://example.org/p1".to_string()),
                 object: TripleNode::Literal("C".to_string()),
+                graph: None,
             },
             Triple {
                 subject: TripleNode::Variable("v4".to_string()),
                 predicate: TripleNode::IRI("http://example.org/p1".to_string()),
                 object: TripleNode::Literal("D".to_string()),
+                graph: None,
             },
             Triple {
                 subject: TripleNode::Variable("v5".to_string()),
                 predicate: TripleNode::IRI("http://example.org/p1".to_string()),
                 object: TripleNode::Literal("E".to_string()),
+                graph: None,
             },
             Triple {
                 subject: TripleNode::Variable("v6".to_string()),
                 predicate: TripleNode::IRI("http://example.org/p1".to_string()),
                 object: TripleNode::Literal("F".to_string()),
+                graph: None,
             },
         ];
 
@@ -1165,31 +3553,37 @@ mod tests {
                 subject: TripleNode::Variable("x1".to_string()),
                 predicate: TripleNode::IRI("http://example.org/p1".to_string()),
                 object: TripleNode::Literal("A".to_string()),
+                graph: None,
             },
             Triple {
                 subject: TripleNode::Variable("x2".to_string()),
                 predicate: TripleNode::IRI("http://example.org/p1".to_string()),
                 object: TripleNode::Literal("B".to_string()),
+                graph: None,
             },
             Triple {
                 subject: TripleNode::Variable("x3".to_string()),
                 predicate: TripleNode::IRI("http://example.org/p1".to_string()),
                 object: TripleNode::Literal("C".to_string()),
+                graph: None,
             },
             Triple {
                 subject: TripleNode::Variable("x4".to_string()),
                 predicate: TripleNode::IRI("http://example.org/p1".to_string()),
                 object: TripleNode::Literal("D".to_string()),
+                graph: None,
             },
             Triple {
                 subject: TripleNode::Variable("x5".to_string()),
                 predicate: TripleNode::IRI("http://example.org/p1".to_string()),
                 object: TripleNode::Literal("E".to_string()),
+                graph: None,
             },
             Triple {
                 subject: TripleNode::Variable("x6".to_string()),
                 predicate: TripleNode::IRI("http://example.org/p1".to_string()),
                 object: TripleNode::Literal("F".to_string()),
+                graph: None,
             },
         ];
 
@@ -1207,11 +3601,13 @@ mod tests {
                 subject: TripleNode::Variable("v1".to_string()),
                 predicate: TripleNode::IRI("http://example.org/p1".to_string()),
                 object: TripleNode::Literal("A".to_string()),
+                graph: None,
             },
             Triple {
                 subject: TripleNode::Variable("v2".to_string()),
                 predicate: TripleNode::IRI("http://example.org/p1".to_string()),
                 object: TripleNode::Literal("B".to_string()),
+                graph: None,
             },
         ];
 
@@ -1220,11 +3616,13 @@ mod tests {
                 subject: TripleNode::Variable("x1".to_string()),
                 predicate: TripleNode::IRI("http://example.org/p1".to_string()),
                 object: TripleNode::Literal("X".to_string()),
+                graph: None,
             },
             Triple {
                 subject: TripleNode::Variable("x2".to_string()),
                 predicate: TripleNode::IRI("http://example.org/p1".to_string()),
                 object: TripleNode::Literal("Y".to_string()),
+                graph: None,
             },
         ];
 
@@ -1232,4 +3630,1198 @@ mod tests {
         assert!(result.is_ok());
         assert!(!result.unwrap());
     }
+
+    #[test]
+    fn test_contains_blank_nodes() {
+        let ground = vec![Triple {
+            subject: TripleNode::IRI("http://example.org/a".to_string()),
+            predicate: TripleNode::IRI("http://example.org/p".to_string()),
+            object: TripleNode::Literal("A".to_string()),
+            graph: None,
+        }];
+        assert!(!GraphIsomorphism::contains_blank_nodes(&ground));
+        assert!(!GraphIsomorphism::contains_blank_nodes(&[]));
+
+        let mut with_blank = ground.clone();
+        with_blank.push(Triple {
+            subject: TripleNode::BlankNode("b0".to_string()),
+            predicate: TripleNode::IRI("http://example.org/p".to_string()),
+            object: TripleNode::Literal("B".to_string()),
+            graph: None,
+        });
+        assert!(GraphIsomorphism::contains_blank_nodes(&with_blank));
+
+        let mut with_variable = ground;
+        with_variable.push(Triple {
+            subject: TripleNode::IRI("http://example.org/a".to_string()),
+            predicate: TripleNode::IRI("http://example.org/p".to_string()),
+            object: TripleNode::Variable("o".to_string()),
+            graph: None,
+        });
+        assert!(GraphIsomorphism::contains_blank_nodes(&with_variable));
+    }
+
+    #[test]
+    fn test_find_bijection_uses_original_names() {
+        let bgp1 = vec![
+            Triple {
+                subject: TripleNode::Variable("person".to_string()),
+                predicate: TripleNode::IRI("http://xmlns.com/foaf/0.1/knows".to_string()),
+                object: TripleNode::BlankNode("friend".to_string()),
+                graph: None,
+            },
+            Triple {
+                subject: TripleNode::BlankNode("friend".to_string()),
+                predicate: TripleNode::IRI("http://xmlns.com/foaf/0.1/name".to_string()),
+                object: TripleNode::Literal("Bob".to_string()),
+                graph: None,
+            },
+        ];
+
+        let bgp2 = vec![
+            Triple {
+                subject: TripleNode::BlankNode("other".to_string()),
+                predicate: TripleNode::IRI("http://xmlns.com/foaf/0.1/name".to_string()),
+                object: TripleNode::Literal("Bob".to_string()),
+                graph: None,
+            },
+            Triple {
+                subject: TripleNode::Variable("x".to_string()),
+                predicate: TripleNode::IRI("http://xmlns.com/foaf/0.1/knows".to_string()),
+                object: TripleNode::BlankNode("other".to_string()),
+                graph: None,
+            },
+        ];
+
+        let bijection = GraphIsomorphism::find_bijection(&bgp1, &bgp2)
+            .unwrap()
+            .unwrap();
+        assert_eq!(bijection.len(), 2);
+        assert_eq!(bijection["person"], "x");
+        assert_eq!(bijection["friend"], "other");
+
+        let bgp3 = vec![bgp2[0].clone(), bgp2[0].clone()];
+        assert!(GraphIsomorphism::find_bijection(&bgp1, &bgp3)
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn test_typed_literals_keep_datatype() {
+        let typed = |datatype: &str| {
+            vec![Triple {
+                subject: TripleNode::Variable("x".to_string()),
+                predicate: TripleNode::IRI("http://example.org/age".to_string()),
+                object: TripleNode::TypedLiteral {
+                    value: "30".to_string(),
+                    datatype: datatype.to_string(),
+                },
+                graph: None,
+            }]
+        };
+        let integer = typed("http://www.w3.org/2001/XMLSchema#integer");
+        let string = typed("http://www.w3.org/2001/XMLSchema#string");
+
+        assert!(GraphIsomorphism::are_isomorphic(&integer, &integer).unwrap());
+        assert!(!GraphIsomorphism::are_isomorphic(&integer, &string).unwrap());
+    }
+
+    #[test]
+    fn test_are_isomorphic_explained_trace() {
+        let triple = |s: &str, p: &str, o: TripleNode| Triple {
+            subject: TripleNode::Variable(s.to_string()),
+            predicate: TripleNode::IRI(p.to_string()),
+            object: o,
+            graph: None,
+        };
+
+        let graph1 = vec![
+            triple(
+                "x",
+                "http://xmlns.com/foaf/0.1/knows",
+                TripleNode::Variable("y".to_string()),
+            ),
+            triple(
+                "y",
+                "http://xmlns.com/foaf/0.1/name",
+                TripleNode::Literal("Bob".to_string()),
+            ),
+        ];
+        let graph2 = vec![
+            triple(
+                "a",
+                "http://xmlns.com/foaf/0.1/knows",
+                TripleNode::Variable("b".to_string()),
+            ),
+            triple(
+                "b",
+                "http://xmlns.com/foaf/0.1/name",
+                TripleNode::Literal("Bob".to_string()),
+            ),
+        ];
+
+        let (isomorphic, trace) =
+            GraphIsomorphism::are_isomorphic_explained(&graph1, &graph2).unwrap();
+        assert!(isomorphic);
+        assert!(trace.contains(&"Graph A: normalized ?x as _:v0, ?y as _:v1".to_string()));
+        assert!(trace.contains(&"Iteration 1: grounded _:v0, _:v1".to_string()));
+        assert!(trace.contains(&"Iteration 2: no new nodes grounded".to_string()));
+        assert_eq!(
+            trace.last().unwrap(),
+            "Isomorphic under bijection: _:v0 -> _:v0, _:v1 -> _:v1"
+        );
+
+        // A symmetric cycle cannot be grounded by hashing alone and needs speculation
+        let cycle = vec![
+            triple(
+                "a",
+                "http://example.org/next",
+                TripleNode::Variable("b".to_string()),
+            ),
+            triple(
+                "b",
+                "http://example.org/next",
+                TripleNode::Variable("c".to_string()),
+            ),
+            triple(
+                "c",
+                "http://example.org/next",
+                TripleNode::Variable("a".to_string()),
+            ),
+        ];
+        let (isomorphic, trace) =
+            GraphIsomorphism::are_isomorphic_explained(&cycle, &cycle).unwrap();
+        assert!(isomorphic);
+        assert!(trace.iter().any(|step| step.starts_with("Speculating")));
+    }
+
+    #[test]
+    fn test_diff() {
+        let knows = |s: &str, o: &str| Triple::new(s, "<http://example.org/knows>", o);
+        let chain = vec![knows("?a", "?b"), knows("?b", "?c")];
+        let renamed = vec![knows("_:y", "_:z"), knows("?x", "_:y"), knows("?x", "_:y")];
+        let star = vec![knows("?a", "?b"), knows("?a", "?c")];
+
+        let diff = GraphIsomorphism::diff(&chain, &renamed);
+        assert!(diff.is_empty(), "{:?}", diff);
+        assert_eq!(diff.blank_node_counts, (3, 3));
+
+        // Same size and blank node count, but ?b and ?c of the star are alike
+        let diff = GraphIsomorphism::diff(&chain, &star);
+        assert!(diff.only_in_first.is_empty() && diff.only_in_second.is_empty());
+        assert_eq!(diff.blank_node_counts, (3, 3));
+        assert!(diff
+            .signature_mismatches
+            .contains(&(vec![], vec![TripleNode::var("b"), TripleNode::var("c")])));
+        assert!(!GraphIsomorphism::are_isomorphic(&chain, &star).unwrap());
+    }
+
+    #[test]
+    fn test_graph_hash() {
+        let graph = vec![
+            Triple::new("?a", "<http://example.org/knows>", "?b"),
+            Triple::new("?b", "<http://example.org/knows>", "?c"),
+            Triple::new("?c", "<http://example.org/knows>", "?a"),
+            Triple::new("?a", "<http://example.org/name>", "\"Alice\""),
+            Triple::new("_:x", "<http://example.org/likes>", "?a"),
+        ];
+        let hash = GraphIsomorphism::graph_hash(&graph);
+
+        // Every rotation of the triples, with the variables and blank nodes renamed
+        let rename = |node: &TripleNode| match node {
+            TripleNode::Variable(name) => TripleNode::Variable(format!("renamed_{}", name)),
+            TripleNode::BlankNode(id) => TripleNode::Variable(format!("blank_{}", id)),
+            other => other.clone(),
+        };
+        for shift in 0..graph.len() {
+            let mut permuted: Vec<Triple> = graph
+                .iter()
+                .map(|triple| Triple {
+                    subject: rename(&triple.subject),
+                    predicate: rename(&triple.predicate),
+                    object: rename(&triple.object),
+                    graph: None,
+                })
+                .collect();
+            permuted.rotate_left(shift);
+            assert_eq!(GraphIsomorphism::graph_hash(&permuted), hash);
+        }
+
+        // Reversing one edge of the cycle changes the structure, not the triple count
+        let mut reversed = graph.clone();
+        reversed[1] = Triple::new("?c", "<http://example.org/knows>", "?b");
+        assert_ne!(GraphIsomorphism::graph_hash(&reversed), hash);
+
+        let mut renamed_iri = graph.clone();
+        renamed_iri[4] = Triple::new("_:x", "<http://example.org/hates>", "?a");
+        assert_ne!(GraphIsomorphism::graph_hash(&renamed_iri), hash);
+
+        assert_ne!(GraphIsomorphism::graph_hash(&graph[..4]), hash);
+    }
+
+    #[test]
+    fn test_canonical_form_of_isomorphic_graphs() {
+        let triple = |s: &str, p: &str, o: &str| Triple {
+            subject: TripleNode::Variable(s.to_string()),
+            predicate: TripleNode::IRI(p.to_string()),
+            object: TripleNode::Variable(o.to_string()),
+            graph: None,
+        };
+
+        let graph1 = vec![
+            triple("x", "http://example.org/knows", "y"),
+            triple("y", "http://example.org/likes", "z"),
+        ];
+        let graph2 = vec![
+            triple("b", "http://example.org/likes", "c"),
+            triple("a", "http://example.org/knows", "b"),
+        ];
+
+        let form1 = GraphIsomorphism::canonical_form(&graph1).unwrap();
+        let form2 = GraphIsomorphism::canonical_form(&graph2).unwrap();
+        assert_eq!(form1, form2);
+        assert!(!form1.contains("_:b"));
+
+        let graph3 = vec![
+            triple("x", "http://example.org/likes", "y"),
+            triple("y", "http://example.org/knows", "z"),
+        ];
+        let form3 = GraphIsomorphism::canonical_form(&graph3).unwrap();
+        assert_ne!(form1, form3);
+    }
+
+    #[test]
+    fn test_canonical_form_of_symmetric_graphs() {
+        let next = |s: &str, o: &str| Triple {
+            subject: TripleNode::Variable(s.to_string()),
+            predicate: TripleNode::IRI("http://example.org/next".to_string()),
+            object: TripleNode::Variable(o.to_string()),
+            graph: None,
+        };
+
+        // Hashing alone cannot ground the nodes of a cycle, whatever its labelling
+        let cycle1 = vec![
+            next("a", "b"),
+            next("b", "c"),
+            next("c", "d"),
+            next("d", "a"),
+        ];
+        let cycle2 = vec![
+            next("w", "x"),
+            next("y", "z"),
+            next("z", "w"),
+            next("x", "y"),
+        ];
+        let two_cycles = vec![
+            next("a", "b"),
+            next("b", "a"),
+            next("c", "d"),
+            next("d", "c"),
+        ];
+
+        let form1 = GraphIsomorphism::canonical_form(&cycle1).unwrap();
+        let form2 = GraphIsomorphism::canonical_form(&cycle2).unwrap();
+        let form3 = GraphIsomorphism::canonical_form(&two_cycles).unwrap();
+        assert_eq!(form1, form2);
+        assert_ne!(form1, form3);
+        assert_eq!(form1.lines().count(), 4);
+    }
+
+    #[test]
+    fn test_parse_ntriples() {
+        let input = r#"
+# A small graph
+<http://ex.org/alice> <http://ex.org/knows> _:b1 .
+_:b1 <http://ex.org/name> "Bob \"the builder\"" .
+_:b1 <http://ex.org/age> "42"^^<http://www.w3.org/2001/XMLSchema#integer> . # comment
+_:b1 <http://ex.org/greeting> "hello there"@en-GB.
+"#;
+
+        let graph = GraphIsomorphism::parse_ntriples(input).unwrap();
+        assert_eq!(graph.len(), 4);
+        assert_eq!(graph[0].object, TripleNode::BlankNode("b1".to_string()));
+        assert_eq!(graph[1].object, TripleNode::lit(r#"Bob \"the builder\""#));
+        assert_eq!(
+            graph[2].object,
+            TripleNode::TypedLiteral {
+                value: "42".to_string(),
+                datatype: "http://www.w3.org/2001/XMLSchema#integer".to_string(),
+            }
+        );
+        assert_eq!(
+            graph[3].object,
+            TripleNode::LangLiteral {
+                value: "hello there".to_string(),
+                lang: "en-gb".to_string(),
+            }
+        );
+
+        // Printing the triples with Display gives N-Triples that parse to the same graph
+        let printed = graph
+            .iter()
+            .map(|triple| triple.to_string())
+            .collect::<Vec<_>>()
+            .join("\n");
+        assert_eq!(GraphIsomorphism::parse_ntriples(&printed).unwrap(), graph);
+    }
+
+    #[test]
+    fn test_parse_ntriples_reports_line_of_malformed_triple() {
+        let input = "<http://ex.org/a> <http://ex.org/p> <http://ex.org/b> .\n\
+                     <http://ex.org/a> <http://ex.org/p> .\n";
+
+        match GraphIsomorphism::parse_ntriples(input) {
+            Err(TulnaError::ParseError(message)) => assert!(message.contains("line 2")),
+            other => panic!("expected a parse error, got {:?}", other),
+        }
+
+        for line in [
+            "<http://ex.org/a> <http://ex.org/p> <http://ex.org/b>",
+            "\"a\" <http://ex.org/p> <http://ex.org/b> .",
+            "<http://ex.org/a> _:p <http://ex.org/b> .",
+            "?x <http://ex.org/p> <http://ex.org/b> .",
+        ] {
+            assert!(GraphIsomorphism::parse_ntriples(line).is_err(), "{}", line);
+        }
+    }
+
+    #[test]
+    fn test_parse_turtle() {
+        let input = r#"
+@prefix ex: <http://ex.org/> .
+PREFIX foaf: <http://xmlns.com/foaf/0.1/>
+
+# Alice knows two people
+ex:alice a foaf:Person ;
+    foaf:name "Alice"@en ;
+    foaf:knows ex:bob, _:someone .
+_:someone foaf:name "Carol" .
+"#;
+
+        let graph = GraphIsomorphism::parse_turtle(input).unwrap();
+        let expected = GraphIsomorphism::parse_ntriples(
+            r#"
+<http://ex.org/alice> <http://www.w3.org/1999/02/22-rdf-syntax-ns#type> <http://xmlns.com/foaf/0.1/Person> .
+<http://ex.org/alice> <http://xmlns.com/foaf/0.1/name> "Alice"@en .
+<http://ex.org/alice> <http://xmlns.com/foaf/0.1/knows> <http://ex.org/bob> .
+<http://ex.org/alice> <http://xmlns.com/foaf/0.1/knows> _:someone .
+_:someone <http://xmlns.com/foaf/0.1/name> "Carol" .
+"#,
+        )
+        .unwrap();
+
+        assert_eq!(graph, expected);
+    }
+
+    #[test]
+    fn test_parse_turtle_directives_and_literals() {
+        let input = r#"@prefix ex: <http://ex.org/> . ex:a ex:p ex:b .
+@base <http://base.org/dir/> .
+<a> ex:q 42, -1.5, .5, true ; ex:r """two
+lines""" , 'single'@EN-gb ; ex:s "7"^^ex:type ; .
+[ ex:p ex:b ] .
+"#;
+
+        let graph = GraphIsomorphism::parse_turtle(input).unwrap();
+        let expected = GraphIsomorphism::parse_ntriples(
+            r#"
+<http://ex.org/a> <http://ex.org/p> <http://ex.org/b> .
+<http://base.org/dir/a> <http://ex.org/q> "42"^^<http://www.w3.org/2001/XMLSchema#integer> .
+<http://base.org/dir/a> <http://ex.org/q> "-1.5"^^<http://www.w3.org/2001/XMLSchema#decimal> .
+<http://base.org/dir/a> <http://ex.org/q> ".5"^^<http://www.w3.org/2001/XMLSchema#decimal> .
+<http://base.org/dir/a> <http://ex.org/q> "true"^^<http://www.w3.org/2001/XMLSchema#boolean> .
+<http://base.org/dir/a> <http://ex.org/s> "7"^^<http://ex.org/type> .
+_:b <http://ex.org/p> <http://ex.org/b> .
+"#,
+        )
+        .unwrap();
+
+        assert_eq!(graph.len(), expected.len() + 2);
+        assert!(expected.iter().all(|triple| {
+            matches!(triple.subject, TripleNode::BlankNode(_)) || graph.contains(triple)
+        }));
+        assert!(graph.contains(&Triple {
+            subject: TripleNode::IRI("http://base.org/dir/a".to_string()),
+            predicate: TripleNode::IRI("http://ex.org/r".to_string()),
+            object: TripleNode::Literal("two\nlines".to_string()),
+            graph: None,
+        }));
+        assert!(graph.contains(&Triple {
+            subject: TripleNode::IRI("http://base.org/dir/a".to_string()),
+            predicate: TripleNode::IRI("http://ex.org/r".to_string()),
+            object: TripleNode::LangLiteral {
+                value: "single".to_string(),
+                lang: "en-gb".to_string(),
+            },
+            graph: None,
+        }));
+    }
+
+    #[test]
+    fn test_parse_turtle_rejects_malformed_documents() {
+        let input = "@prefix ex: <http://ex.org/> .\n\
+                     ex:a ex:p ex:b .\n\
+                     ex:a ex:p .\n";
+
+        match GraphIsomorphism::parse_turtle(input) {
+            Err(TulnaError::ParseError(message)) => assert!(message.contains("line 3")),
+            other => panic!("expected a parse error, got {:?}", other),
+        }
+
+        for document in [
+            // Stray and unbalanced brackets
+            "<http://ex.org/a> <http://ex.org/p> \"x\" . }",
+            "<http://ex.org/a> <http://ex.org/p> [ <http://ex.org/q> \"x\" .",
+            "<http://ex.org/a> <http://ex.org/p> ( \"x\" .",
+            // Missing and extra terms
+            "<http://ex.org/a> <http://ex.org/p> .",
+            "<http://ex.org/a> <http://ex.org/p> <http://ex.org/b> <http://ex.org/c> .",
+            "<http://ex.org/a> <http://ex.org/p> <http://ex.org/b>",
+            // Undeclared prefixes and query variables
+            "ex:a <http://ex.org/p> <http://ex.org/b> .",
+            "?x <http://ex.org/p> <http://ex.org/b> .",
+            "<http://ex.org/a> <http://ex.org/p> $y .",
+            // Malformed terms
+            "<http://ex.org/a> <http://ex.org/p> \"unterminated .",
+            "<http://ex.org/a> <http://ex.org/p> <http://ex.org/b .",
+            "<http://ex.org/a> <http://ex.org/p> _: .",
+            "<http://ex.org/a> a word .",
+            "@prefix ex <http://ex.org/> .",
+        ] {
+            assert!(
+                matches!(
+                    GraphIsomorphism::parse_turtle(document),
+                    Err(TulnaError::ParseError(_))
+                ),
+                "{}",
+                document
+            );
+        }
+    }
+
+    #[test]
+    fn test_parse_turtle_documents_isomorphic() {
+        let doc1 = "@prefix ex: <http://ex.org/> .\nex:alice ex:knows [ ex:name \"Bob\" ] .";
+        let doc2 = "@prefix e: <http://ex.org/> .\ne:alice e:knows _:b .\n_:b e:name \"Bob\" .";
+        let doc3 = "@prefix e: <http://ex.org/> .\ne:alice e:knows _:b .\n_:b e:name \"Carol\" .";
+
+        let graph1 = GraphIsomorphism::parse_turtle(doc1).unwrap();
+        let graph2 = GraphIsomorphism::parse_turtle(doc2).unwrap();
+        let graph3 = GraphIsomorphism::parse_turtle(doc3).unwrap();
+
+        assert!(GraphIsomorphism::are_isomorphic(&graph1, &graph2).unwrap());
+        assert!(!GraphIsomorphism::are_isomorphic(&graph1, &graph3).unwrap());
+
+        let list = "@prefix ex: <http://ex.org/> .\nex:alice ex:likes ( ex:tea \"cake\" ) .";
+        let expanded = "@prefix ex: <http://ex.org/> .\n\
+            @prefix rdf: <http://www.w3.org/1999/02/22-rdf-syntax-ns#> .\n\
+            ex:alice ex:likes _:a .\n\
+            _:a rdf:first ex:tea ; rdf:rest _:b .\n\
+            _:b rdf:first \"cake\" ; rdf:rest rdf:nil .";
+        let graph1 = GraphIsomorphism::parse_turtle(list).unwrap();
+        let graph2 = GraphIsomorphism::parse_turtle(expanded).unwrap();
+        assert_eq!(graph1.len(), 5);
+        assert!(GraphIsomorphism::are_isomorphic(&graph1, &graph2).unwrap());
+    }
+
+    #[test]
+    fn test_variable_and_blank_node_with_same_name_do_not_collide() {
+        let knows = |s: TripleNode, o: TripleNode| Triple {
+            subject: s,
+            predicate: TripleNode::IRI("http://example.org/knows".to_string()),
+            object: o,
+            graph: None,
+        };
+
+        let mixed = vec![knows(
+            TripleNode::Variable("b0".to_string()),
+            TripleNode::BlankNode("b0".to_string()),
+        )];
+        let distinct = vec![knows(
+            TripleNode::Variable("x".to_string()),
+            TripleNode::Variable("y".to_string()),
+        )];
+        let self_loop = vec![knows(
+            TripleNode::Variable("x".to_string()),
+            TripleNode::Variable("x".to_string()),
+        )];
+
+        let normalized = GraphIsomorphism::normalize_bgp(&mixed);
+        assert_ne!(normalized[0].subject, normalized[0].object);
+        assert!(GraphIsomorphism::are_isomorphic(&mixed, &distinct).unwrap());
+        assert!(!GraphIsomorphism::are_isomorphic(&mixed, &self_loop).unwrap());
+    }
+
+    #[test]
+    fn test_are_isomorphic_batch_matches_sequential_results() {
+        let cycle = |names: &[&str]| -> Vec<Triple> {
+            (0..names.len())
+                .map(|i| {
+                    let next = names[(i + 1) % names.len()];
+                    Triple::new(
+                        format!("?{}", names[i]),
+                        "<http://ex.org/next>",
+                        format!("?{}", next),
+                    )
+                })
+                .collect()
+        };
+        let graphs = [
+            cycle(&["a", "b", "c"]),
+            cycle(&["x", "y", "z"]),
+            cycle(&["a", "b", "c", "d"]),
+            vec![Triple::new("?a", "<http://ex.org/next>", "?a")],
+            Vec::new(),
+        ];
+
+        let pairs: Vec<(&[Triple], &[Triple])> = graphs
+            .iter()
+            .flat_map(|g1| graphs.iter().map(move |g2| (g1.as_slice(), g2.as_slice())))
+            .collect();
+
+        let batch = GraphIsomorphism::are_isomorphic_batch(&pairs);
+        assert_eq!(batch.len(), pairs.len());
+        for ((graph1, graph2), result) in pairs.iter().zip(batch) {
+            assert_eq!(
+                result.unwrap(),
+                GraphIsomorphism::are_isomorphic(graph1, graph2).unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn test_prepared_graph_matches_are_isomorphic() {
+        let graphs = [
+            vec![
+                Triple::new("?a", "<http://ex.org/next>", "?b"),
+                Triple::new("?b", "<http://ex.org/next>", "?a"),
+            ],
+            vec![
+                Triple::new("_:x", "<http://ex.org/next>", "?y"),
+                Triple::new("?y", "<http://ex.org/next>", "_:x"),
+            ],
+            vec![
+                Triple::new("?a", "<http://ex.org/next>", "?a"),
+                Triple::new("?b", "<http://ex.org/next>", "?b"),
+            ],
+            vec![
+                Triple::new("?a", "<http://ex.org/next>", "?b"),
+                Triple::new("<http://ex.org/c>", "<http://ex.org/next>", "?a"),
+            ],
+            vec![Triple::new("?a", "<http://ex.org/next>", "?a")],
+            Vec::new(),
+        ];
+
+        for graph1 in &graphs {
+            let prepared = GraphIsomorphism::prepare(graph1);
+            for graph2 in &graphs {
+                // Compare twice so the second check uses the cached hashes
+                for _ in 0..2 {
+                    assert_eq!(
+                        prepared.is_isomorphic_to(graph2).unwrap(),
+                        GraphIsomorphism::are_isomorphic(graph1, graph2).unwrap()
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_prepared_graph_incremental_updates() {
+        let p = "<http://ex.org/p>";
+        let candidates = [
+            vec![Triple::new("?a", p, "?b"), Triple::new("?b", p, "?c")],
+            vec![Triple::new("?a", p, "?b"), Triple::new("?b", p, "?a")],
+            vec![
+                Triple::new("?a", p, "?b"),
+                Triple::new("?b", p, "?c"),
+                Triple::new("<http://ex.org/s>", p, "<http://ex.org/o>"),
+            ],
+            vec![
+                Triple::new("?a", p, "?b"),
+                Triple::new("<http://ex.org/s>", p, "<http://ex.org/o>"),
+            ],
+            vec![Triple::new("_:x", p, "?y")],
+            Vec::new(),
+        ];
+        let updates = [
+            (true, Triple::new("?x", p, "?y")),
+            (true, Triple::new("?y", p, "?z")),
+            (
+                true,
+                Triple::new("<http://ex.org/s>", p, "<http://ex.org/o>"),
+            ),
+            (true, Triple::new("?x", p, "?y")),
+            (false, Triple::new("?x", p, "?y")),
+            (false, Triple::new("?y", p, "?z")),
+            (false, Triple::new("?unknown", p, "?z")),
+            (true, Triple::new("?y", p, "?x")),
+            (
+                false,
+                Triple::new("<http://ex.org/s>", p, "<http://ex.org/o>"),
+            ),
+            (false, Triple::new("?x", p, "?y")),
+            (false, Triple::new("?y", p, "?x")),
+            (true, Triple::new("_:x", p, "?y")),
+        ];
+
+        let mut graph: Vec<Triple> = Vec::new();
+        let mut prepared = GraphIsomorphism::prepare(&graph);
+        for (add, triple) in &updates {
+            if *add {
+                prepared = prepared.with_added_triple(triple);
+                graph.push(triple.clone());
+            } else {
+                prepared = prepared.with_removed_triple(triple);
+                if let Some(position) = graph.iter().position(|other| other == triple) {
+                    graph.remove(position);
+                }
+            }
+
+            let recomputed = GraphIsomorphism::prepare(&graph);
+            assert_eq!(prepared.size, recomputed.size);
+            assert_eq!(
+                *prepared.initial_hashes(),
+                GraphIsomorphism::hash_terms(
+                    &prepared.blank_quads,
+                    &prepared.blank_nodes,
+                    &HashMap::new(),
+                    prepared.seed,
+                    &mut None
+                )
+            );
+            assert_eq!(prepared.ground_index, recomputed.ground_index);
+            assert_eq!(prepared.blank_quads.len(), recomputed.blank_quads.len());
+            assert_eq!(prepared.blank_nodes.len(), recomputed.blank_nodes.len());
+            for candidate in &candidates {
+                assert_eq!(
+                    prepared.is_isomorphic_to(candidate).unwrap(),
+                    recomputed.is_isomorphic_to(candidate).unwrap()
+                );
+                assert_eq!(
+                    prepared
+                        .is_isomorphic_to_prepared(&GraphIsomorphism::prepare(candidate))
+                        .unwrap(),
+                    GraphIsomorphism::are_isomorphic(&graph, candidate).unwrap()
+                );
+            }
+        }
+
+        // Triples without blank nodes and duplicates keep the cached hashes
+        let chain = GraphIsomorphism::prepare(&candidates[0]);
+        chain.initial_hashes();
+        let ground = Triple::new("<http://ex.org/s>", p, "<http://ex.org/o>");
+        assert!(chain
+            .with_added_triple(&ground)
+            .initial_hashes
+            .get()
+            .is_some());
+        assert!(chain
+            .with_added_triple(&candidates[0][0])
+            .initial_hashes
+            .get()
+            .is_some());
+        assert!(chain
+            .with_added_triple(&Triple::new("?c", p, "?d"))
+            .initial_hashes
+            .get()
+            .is_none());
+    }
+
+    #[cfg(feature = "binary")]
+    #[test]
+    fn test_prepared_graph_binary_round_trip() {
+        let p = "<http://ex.org/p>";
+        let graph = vec![
+            Triple::new("?a", p, "?b"),
+            Triple::new("?b", p, "_:c"),
+            Triple::new("<http://ex.org/s>", p, "\"o\"@en"),
+        ];
+        let prepared = GraphIsomorphism::prepare(&graph);
+        prepared.initial_hashes();
+
+        let reloaded = PreparedGraph::from_bytes(&prepared.to_bytes()).unwrap();
+        assert_eq!(reloaded.size, prepared.size);
+        assert_eq!(reloaded.ground_index, prepared.ground_index);
+        assert_eq!(reloaded.blank_quads, prepared.blank_quads);
+        assert_eq!(reloaded.blank_nodes, prepared.blank_nodes);
+        assert_eq!(reloaded.initial_hashes(), prepared.initial_hashes());
+
+        let candidates = [
+            graph.clone(),
+            vec![
+                Triple::new("?x", p, "?y"),
+                Triple::new("?y", p, "_:z"),
+                Triple::new("<http://ex.org/s>", p, "\"o\"@en"),
+            ],
+            vec![
+                Triple::new("?x", p, "?y"),
+                Triple::new("?y", p, "?x"),
+                Triple::new("<http://ex.org/s>", p, "\"o\"@en"),
+            ],
+        ];
+        for candidate in &candidates {
+            assert_eq!(
+                reloaded.is_isomorphic_to(candidate).unwrap(),
+                prepared.is_isomorphic_to(candidate).unwrap()
+            );
+        }
+        assert!(reloaded.is_isomorphic_to_prepared(&prepared).unwrap());
+
+        // The variable names are kept, so the reloaded graph can still be updated
+        let removed = Triple::new("?a", p, "?b");
+        assert_eq!(
+            reloaded.with_removed_triple(&removed).size,
+            prepared.with_removed_triple(&removed).size
+        );
+        assert!(reloaded
+            .with_removed_triple(&removed)
+            .is_isomorphic_to_prepared(&prepared.with_removed_triple(&removed))
+            .unwrap());
+
+        assert!(matches!(
+            PreparedGraph::from_bytes(&[1, 2, 3]),
+            Err(TulnaError::ParseError(_))
+        ));
+    }
+
+    #[test]
+    fn test_prepared_graph_updates_keep_untouched_hashes() {
+        let p = "<http://ex.org/p>";
+        let q = "<http://ex.org/q>";
+        let full_hashes = |prepared: &PreparedGraph| {
+            GraphIsomorphism::hash_terms(
+                &prepared.blank_quads,
+                &prepared.blank_nodes,
+                &HashMap::new(),
+                prepared.seed,
+                &mut None,
+            )
+        };
+
+        // A path that takes several iterations to ground, next to a small separate component
+        let mut graph: Vec<Triple> = (0..5)
+            .map(|i| Triple::new(format!("_:a{}", i), p, format!("_:a{}", i + 1)))
+            .collect();
+        graph.push(Triple::new("_:b0", q, "_:b1"));
+        graph.push(Triple::new("_:b1", q, "_:b2"));
+        let prepared = GraphIsomorphism::prepare(&graph);
+        prepared.initial_hashes();
+
+        for updated in [
+            prepared.with_added_triple(&Triple::new("_:b2", q, "_:b3")),
+            prepared.with_removed_triple(&Triple::new("_:b1", q, "_:b2")),
+        ] {
+            let history = updated.hash_history();
+            assert_eq!(history.hashes, full_hashes(&updated));
+
+            // Only the nodes next to the changed triple are hashed again
+            let rehashed: HashSet<&str> = history
+                .iterations
+                .hashes
+                .iter()
+                .filter(|(_, hashes)| hashes.iter().any(|(_, hashed)| !hashed.reused))
+                .map(|(term, _)| term.as_str())
+                .collect();
+            assert!(!rehashed.is_empty());
+            assert!(rehashed.iter().all(|term| term.starts_with("_:b")));
+        }
+
+        // Random updates of a small graph always give the hashes of a full recomputation
+        let mut state: u64 = 7;
+        let mut random = |bound: u64| {
+            state = state
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            (state >> 33) % bound
+        };
+        let mut prepared = GraphIsomorphism::prepare(&[]);
+        for _ in 0..300 {
+            let triple = Triple::new(
+                format!("_:n{}", random(8)),
+                if random(2) == 0 { p } else { q },
+                format!("_:n{}", random(8)),
+            );
+            prepared = if random(3) == 0 {
+                prepared.with_removed_triple(&triple)
+            } else {
+                prepared.with_added_triple(&triple)
+            };
+            assert_eq!(*prepared.initial_hashes(), full_hashes(&prepared));
+        }
+    }
+
+    #[test]
+    fn test_quick_reject_profiles() {
+        let normalize = |graph: &[Triple]| GraphIsomorphism::normalize_bgp(graph);
+
+        // Same size, different predicates
+        let graph1 = normalize(&[
+            Triple::new("?a", "<http://ex.org/p>", "?b"),
+            Triple::new("?b", "<http://ex.org/p>", "?c"),
+        ]);
+        let graph2 = normalize(&[
+            Triple::new("?a", "<http://ex.org/p>", "?b"),
+            Triple::new("?b", "<http://ex.org/q>", "?c"),
+        ]);
+        assert!(GraphIsomorphism::quick_reject(&graph1, &graph2));
+
+        // Same predicates, different degrees: a chain versus a star
+        let graph3 = normalize(&[
+            Triple::new("?a", "<http://ex.org/p>", "?b"),
+            Triple::new("?a", "<http://ex.org/p>", "?c"),
+        ]);
+        assert!(GraphIsomorphism::quick_reject(&graph1, &graph3));
+
+        // Isomorphic graphs, with a variable predicate and a duplicate triple
+        let graph4 = normalize(&[
+            Triple::new("?a", "?p", "?b"),
+            Triple::new("?b", "<http://ex.org/p>", "<http://ex.org/c>"),
+            Triple::new("?b", "<http://ex.org/p>", "<http://ex.org/c>"),
+        ]);
+        let graph5 = normalize(&[
+            Triple::new("?y", "<http://ex.org/p>", "<http://ex.org/c>"),
+            Triple::new("?x", "?q", "?y"),
+            Triple::new("?x", "?q", "?y"),
+        ]);
+        assert!(!GraphIsomorphism::quick_reject(&graph4, &graph5));
+    }
+
+    #[test]
+    fn test_named_graphs() {
+        let knows = || Triple::new("?a", "<http://ex.org/knows>", "?b");
+
+        // The same triple in different named graphs
+        let graph1 = vec![knows().with_graph("<http://ex.org/g1>")];
+        let graph2 = vec![knows().with_graph("<http://ex.org/g2>")];
+        assert!(!GraphIsomorphism::are_isomorphic(&graph1, &graph2).unwrap());
+        assert_eq!(
+            GraphIsomorphism::classify(&graph1, &graph2).unwrap(),
+            IsoResult::NoBijection
+        );
+
+        // A named graph is not the default graph
+        assert!(!GraphIsomorphism::are_isomorphic(&graph1, &[knows()]).unwrap());
+        assert!(GraphIsomorphism::are_isomorphic(&graph1, &graph1).unwrap());
+
+        // Ground triples are compared with their graph name too
+        let ground = |graph: &str| {
+            vec![Triple::new(
+                "<http://ex.org/a>",
+                "<http://ex.org/knows>",
+                "<http://ex.org/b>",
+            )
+            .with_graph(graph)]
+        };
+        assert_eq!(
+            GraphIsomorphism::classify(
+                &ground("<http://ex.org/g1>"),
+                &ground("<http://ex.org/g2>")
+            )
+            .unwrap(),
+            IsoResult::DifferentGroundTriples
+        );
+
+        // Variable graph names are mapped like any other variable
+        let graph3 = vec![
+            knows().with_graph("?g"),
+            Triple::new("?b", "<http://ex.org/knows>", "?c").with_graph("?h"),
+        ];
+        let graph4 = vec![
+            Triple::new("?y", "<http://ex.org/knows>", "?z").with_graph("?k"),
+            Triple::new("?x", "<http://ex.org/knows>", "?y").with_graph("?j"),
+        ];
+        let bijection = GraphIsomorphism::find_bijection(&graph3, &graph4)
+            .unwrap()
+            .unwrap();
+        assert_eq!(bijection["g"], "j");
+        assert_eq!(bijection["h"], "k");
+
+        let graph5 = vec![
+            knows().with_graph("?g"),
+            Triple::new("?b", "<http://ex.org/knows>", "?c").with_graph("?g"),
+        ];
+        assert!(!GraphIsomorphism::are_isomorphic(&graph3, &graph5).unwrap());
+    }
+
+    #[test]
+    fn test_is_subgraph() {
+        let sub = vec![Triple::new("?a", "<http://ex.org/knows>", "?b")];
+        let super_ = vec![
+            Triple::new("?x", "<http://ex.org/knows>", "?y"),
+            Triple::new("?y", "<http://ex.org/name>", "?n"),
+        ];
+        assert!(GraphIsomorphism::is_subgraph(&sub, &super_).unwrap());
+        assert!(!GraphIsomorphism::is_subgraph(&super_, &sub).unwrap());
+
+        // Every graph is contained in itself
+        assert!(GraphIsomorphism::is_subgraph(&super_, &super_).unwrap());
+
+        // The predicate has to match
+        let other = vec![Triple::new("?a", "<http://ex.org/likes>", "?b")];
+        assert!(!GraphIsomorphism::is_subgraph(&other, &super_).unwrap());
+
+        // Variables may map onto IRIs and literals
+        let ground = vec![Triple::new(
+            "<http://ex.org/alice>",
+            "<http://ex.org/knows>",
+            "\"Bob\"",
+        )];
+        assert!(GraphIsomorphism::is_subgraph(&sub, &ground).unwrap());
+        assert!(!GraphIsomorphism::is_subgraph(&ground, &sub).unwrap());
+    }
+
+    #[test]
+    fn test_automorphism_count() {
+        let next = |s: &str, o: &str| Triple::new(s, "<http://ex.org/next>", o);
+        let count = |graph: &[Triple]| GraphIsomorphism::automorphism_count(graph).unwrap();
+
+        assert_eq!(count(&[next("?a", "?b"), next("?b", "?c")]), 1);
+        assert_eq!(count(&[next("?a", "?b"), next("?b", "?a")]), 2);
+        assert_eq!(
+            count(&[next("?a", "?b"), next("?b", "?c"), next("?c", "?a")]),
+            3
+        );
+        // The leaves of a star can be permuted freely
+        assert_eq!(
+            count(&[next("?c", "?x"), next("?c", "?y"), next("?c", "?z")]),
+            6
+        );
+        // Blank nodes count like variables, and duplicates are ignored
+        assert_eq!(
+            count(&[next("_:a", "?b"), next("?b", "_:a"), next("?b", "_:a")]),
+            2
+        );
+        assert_eq!(count(&[next("<http://ex.org/a>", "\"b\"")]), 1);
+        assert_eq!(count(&[]), 1);
+
+        // A complete graph on 8 nodes has 8! automorphisms
+        let nodes: Vec<String> = (0..8).map(|i| format!("?n{}", i)).collect();
+        let clique: Vec<Triple> = nodes
+            .iter()
+            .flat_map(|a| {
+                nodes
+                    .iter()
+                    .filter(move |b| a != *b)
+                    .map(move |b| next(a, b))
+            })
+            .collect();
+        assert!(matches!(
+            GraphIsomorphism::automorphism_count(&clique),
+            Err(TulnaError::LimitExceeded(_))
+        ));
+    }
+
+    #[test]
+    fn test_is_subgraph_is_injective() {
+        // Two variables cannot both map onto the same variable
+        let cycle = vec![
+            Triple::new("?a", "<http://ex.org/p>", "?b"),
+            Triple::new("?b", "<http://ex.org/p>", "?a"),
+        ];
+        let self_loop = vec![
+            Triple::new("?x", "<http://ex.org/p>", "?x"),
+            Triple::new("?x", "<http://ex.org/q>", "?y"),
+        ];
+        assert!(!GraphIsomorphism::is_subgraph(&cycle, &self_loop).unwrap());
+
+        // A single variable cannot map onto two different ones
+        let sub = vec![Triple::new("?a", "<http://ex.org/p>", "?a")];
+        let super_ = vec![
+            Triple::new("?x", "<http://ex.org/p>", "?y"),
+            Triple::new("?y", "<http://ex.org/q>", "?x"),
+        ];
+        assert!(!GraphIsomorphism::is_subgraph(&sub, &super_).unwrap());
+
+        // A variable cannot take the place of an IRI the pattern already mentions
+        let sub = vec![Triple::new(
+            "<http://ex.org/alice>",
+            "<http://ex.org/knows>",
+            "?b",
+        )];
+        let super_ = vec![Triple::new(
+            "<http://ex.org/alice>",
+            "<http://ex.org/knows>",
+            "<http://ex.org/alice>",
+        )];
+        assert!(!GraphIsomorphism::is_subgraph(&sub, &super_).unwrap());
+    }
+
+    #[test]
+    fn test_node_modes() {
+        let blank = |id: &str| TripleNode::BlankNode(id.to_string());
+        let knows = TripleNode::IRI("http://ex.org/knows".to_string());
+
+        let variables = vec![Triple::new("?s", "<http://ex.org/knows>", "?o")];
+        let renamed = vec![Triple::new("?x", "<http://ex.org/knows>", "?y")];
+        let blank_subject = vec![Triple {
+            subject: blank("b"),
+            predicate: knows.clone(),
+            object: TripleNode::Variable("o".to_string()),
+            graph: None,
+        }];
+        let blank_object = vec![Triple {
+            subject: TripleNode::Variable("s".to_string()),
+            predicate: knows.clone(),
+            object: blank("b"),
+            graph: None,
+        }];
+        let other_blank_subject = vec![Triple {
+            subject: blank("c"),
+            predicate: knows,
+            object: TripleNode::Variable("y".to_string()),
+            graph: None,
+        }];
+
+        for mode in [NodeMode::Lenient, NodeMode::Strict] {
+            assert!(
+                GraphIsomorphism::are_isomorphic_with_mode(&variables, &renamed, mode).unwrap()
+            );
+            assert!(GraphIsomorphism::are_isomorphic_with_mode(
+                &blank_subject,
+                &other_blank_subject,
+                mode
+            )
+            .unwrap());
+        }
+
+        // A blank node matches a variable only in lenient mode
+        assert!(GraphIsomorphism::are_isomorphic_with_mode(
+            &variables,
+            &blank_subject,
+            NodeMode::Lenient
+        )
+        .unwrap());
+        assert!(!GraphIsomorphism::are_isomorphic_with_mode(
+            &variables,
+            &blank_subject,
+            NodeMode::Strict
+        )
+        .unwrap());
+
+        // The same number of variables, but in different positions
+        assert!(GraphIsomorphism::are_isomorphic_with_mode(
+            &blank_subject,
+            &blank_object,
+            NodeMode::Lenient
+        )
+        .unwrap());
+        assert!(!GraphIsomorphism::are_isomorphic_with_mode(
+            &blank_subject,
+            &blank_object,
+            NodeMode::Strict
+        )
+        .unwrap());
+        assert_eq!(NodeMode::default(), NodeMode::Lenient);
+    }
+
+    #[test]
+    fn test_literal_matching_language_ranges() {
+        let labels = |tags: &[&str]| -> Vec<Triple> {
+            tags.iter()
+                .enumerate()
+                .map(|(i, tag)| Triple {
+                    subject: TripleNode::Variable(format!("s{}", i)),
+                    predicate: TripleNode::IRI("http://ex.org/label".to_string()),
+                    object: TripleNode::LangLiteral {
+                        value: "foo".to_string(),
+                        lang: tag.to_string(),
+                    },
+                    graph: None,
+                })
+                .collect()
+        };
+        let exact = LiteralMatching::default();
+        let ranges = LiteralMatching {
+            lang_range_matching: true,
+            ..LiteralMatching::default()
+        };
+        let matches = |pattern: &[&str], data: &[&str], matching: &LiteralMatching| {
+            GraphIsomorphism::are_isomorphic_with_literal_matching(
+                &labels(pattern),
+                &labels(data),
+                matching,
+            )
+            .unwrap()
+        };
+
+        // Exact matching is the default and keeps subtags significant
+        assert!(!exact.lang_range_matching);
+        assert!(matches(&["en-us"], &["en-us"], &exact));
+        assert!(!matches(&["en"], &["en-us"], &exact));
+
+        assert!(matches(&["en"], &["en-us"], &ranges));
+        assert!(matches(&["en"], &["en"], &ranges));
+        assert!(matches(&["en"], &["en-gb-oed"], &ranges));
+        assert!(matches(&["en-US"], &["en-us"], &ranges));
+        // A range only matches whole subtags, and only data tags within it
+        assert!(!matches(&["en"], &["eng"], &ranges));
+        assert!(!matches(&["en-us"], &["en"], &ranges));
+        assert!(!matches(&["de"], &["en-us"], &ranges));
+        // A tag goes to its longest matching range, so each range keeps its own literal
+        assert!(matches(&["en", "en-us"], &["en-us", "en-gb"], &ranges));
+        assert!(!matches(&["en", "en-us"], &["en-gb", "en-au"], &ranges));
+
+        // Literals with other values are left alone
+        let mut data = labels(&["en-us"]);
+        data[0].object = TripleNode::LangLiteral {
+            value: "bar".to_string(),
+            lang: "en-us".to_string(),
+        };
+        assert!(!GraphIsomorphism::are_isomorphic_with_literal_matching(
+            &labels(&["en"]),
+            &data,
+            &ranges
+        )
+        .unwrap());
+    }
+
+    #[test]
+    fn test_literal_matching_datatype_aliases() {
+        let xsd = |name: &str| format!("http://www.w3.org/2001/XMLSchema#{}", name);
+        let ages = |datatypes: &[&str]| -> Vec<Triple> {
+            datatypes
+                .iter()
+                .enumerate()
+                .map(|(i, datatype)| Triple {
+                    subject: TripleNode::BlankNode(format!("b{}", i)),
+                    predicate: TripleNode::IRI("http://ex.org/age".to_string()),
+                    object: TripleNode::TypedLiteral {
+                        value: "5".to_string(),
+                        datatype: xsd(datatype),
+                    },
+                    graph: None,
+                })
+                .collect()
+        };
+        let exact = LiteralMatching::default();
+        let aliased = LiteralMatching {
+            datatype_aliases: HashMap::from([(xsd("int"), xsd("integer"))]),
+            ..LiteralMatching::default()
+        };
+        let matches = |pattern: &[&str], data: &[&str], matching: &LiteralMatching| {
+            GraphIsomorphism::are_isomorphic_with_literal_matching(
+                &ages(pattern),
+                &ages(data),
+                matching,
+            )
+            .unwrap()
+        };
+
+        // Distinct datatypes stay distinct by default
+        assert!(exact.datatype_aliases.is_empty());
+        assert!(!matches(&["integer"], &["int"], &exact));
+        assert!(!GraphIsomorphism::are_isomorphic(&ages(&["integer"]), &ages(&["int"])).unwrap());
+
+        // The alias applies to either graph
+        assert!(matches(&["integer"], &["int"], &aliased));
+        assert!(matches(&["int"], &["integer"], &aliased));
+        assert!(matches(&["int", "integer"], &["integer", "int"], &aliased));
+        assert!(!matches(&["integer"], &["long"], &aliased));
+        assert!(!matches(
+            &["integer", "integer"],
+            &["int", "long"],
+            &aliased
+        ));
+    }
 }
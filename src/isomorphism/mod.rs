@@ -1,3 +1,5 @@
+#[cfg(feature = "spargebra-backend")]
+mod algebra;
 pub mod api;
 pub mod core;
 pub mod graph_isomorphism;
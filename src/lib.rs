@@ -13,6 +13,17 @@
 //! - **Query Parsing**: Auto-detect and parse different query languages
 //! - **Stream Parameters**: Validate stream and window parameters for streaming queries
 //!
+//! ## Feature Flags
+//!
+//! - `parallel`: compare the pairs given to `GraphIsomorphism::are_isomorphic_batch` in
+//!   parallel using rayon. Each single comparison stays single-threaded.
+//! - `spargebra-backend`: extract the triple patterns of WHERE clauses with the SPARQL 1.1
+//!   parser of the `spargebra` crate instead of the built-in tokenizer.
+//! - `serde`: implement `Serialize` and `Deserialize` for parsed queries (`IsomorphismQuery`),
+//!   triples and comparison results (`QueryComparisonResult`, `ComparisonOutcome`).
+//! - `binary`: enables `serde` and adds `PreparedGraph::to_bytes` and
+//!   `PreparedGraph::from_bytes`, which store a prepared graph in a compact bincode encoding.
+//!
 //! ## Quick Start
 //!
 //! ### Query Isomorphism
@@ -37,6 +48,7 @@
 //!         subject: TripleNode::Variable("x".to_string()),
 //!         predicate: TripleNode::IRI("http://example.org/knows".to_string()),
 //!         object: TripleNode::Variable("y".to_string()),
+//!         graph: None,
 //!     }
 //! ];
 //!
@@ -45,6 +57,7 @@
 //!         subject: TripleNode::Variable("a".to_string()),
 //!         predicate: TripleNode::IRI("http://example.org/knows".to_string()),
 //!         object: TripleNode::Variable("b".to_string()),
+//!         graph: None,
 //!     }
 //! ];
 //!
@@ -66,13 +79,18 @@ pub mod graph {
     //! graph isomorphism checking, independent of query parsing.
 
     pub use crate::isomorphism::core::{Triple, TripleNode};
-    pub use crate::isomorphism::graph_isomorphism::GraphIsomorphism;
+    pub use crate::isomorphism::graph_isomorphism::{
+        GraphDiff, GraphIsomorphism, IsoResult, LiteralMatching, NodeMode, PreparedGraph,
+    };
 }
 
 // Re-export query isomorphism API
 pub mod query {
     //! Query isomorphism checking for SPARQL, RSP-QL, and JanusQL.
 
-    pub use crate::isomorphism::api::{QueryComparisonResult, QueryIsomorphismAPI};
-    pub use crate::isomorphism::core::{IsomorphismQuery, QueryLanguage};
+    pub use crate::isomorphism::api::{
+        ComparisonOutcome, QueryComparisonResult, QueryIsomorphismAPI,
+    };
+    pub use crate::isomorphism::core::{InlineData, IsomorphismQuery, QueryLanguage, StreamWindow};
+    pub use crate::parsing::janusql_parser::WindowType;
 }
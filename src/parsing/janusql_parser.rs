@@ -1,7 +1,11 @@
+use crate::parsing::{parse_number, resolve_iri, strip_comments, unify_variable_markers};
+use crate::TulnaError;
 use regex::Regex;
 use std::collections::HashMap;
+use std::ops::Range;
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 /// Different types of windows supported in JanusQL.
 pub enum WindowType {
     Live,
@@ -55,6 +59,8 @@ pub struct ParsedJanusQuery {
     pub sparql_queries: Vec<String>,
     /// Prefix mappings
     pub prefixes: HashMap<String, String>,
+    /// BASE IRI if declared
+    pub base: Option<String>,
     /// WHERE clause
     pub where_clause: String,
     /// SELECT clause
@@ -66,78 +72,169 @@ pub struct JanusQLParser {
     historical_sliding_window: Regex,
     historical_fixed_window: Regex,
     live_sliding_window: Regex,
+    datetime: Regex,
+    where_keyword: Regex,
     register: Regex,
     prefix: Regex,
+    base: Regex,
 }
 
 /// Implement methods for JanusQLParser struct.
 impl JanusQLParser {
     /// Creates a new JanusQLParser instance.
-    pub fn new() -> Result<Self, Box<dyn std::error::Error>> {
+    pub fn new() -> Result<Self, TulnaError> {
         Ok(JanusQLParser {
             historical_sliding_window: Regex::new(
-                r"FROM\s+NAMED\s+WINDOW\s+([^\s]+)\s+ON\s+STREAM\s+([^\s]+)\s+\[OFFSET\s+(\d+)\s+RANGE\s+(\d+)\s+STEP\s+(\d+)\]",
+                r#"FROM\s+NAMED\s+WINDOW\s+([^\s]+)\s+ON\s+STREAM\s+([^\s]+)\s+\[OFFSET\s+(\d+|"[^"]*")\s+RANGE\s+(\d+)\s+STEP\s+(\d+)\]"#,
             )?,
             historical_fixed_window: Regex::new(
-                r"FROM\s+NAMED\s+WINDOW\s+([^\s]+)\s+ON\s+STREAM\s+([^\s]+)\s+\[START\s+(\d+)\s+END\s+(\d+)\]",
+                r#"FROM\s+NAMED\s+WINDOW\s+([^\s]+)\s+ON\s+STREAM\s+([^\s]+)\s+\[START\s+(\d+|"[^"]*")\s+END\s+(\d+|"[^"]*")\]"#,
             )?,
             live_sliding_window: Regex::new(
                 r"FROM\s+NAMED\s+WINDOW\s+([^\s]+)\s+ON\s+STREAM\s+([^\s]+)\s+\[RANGE\s+(\d+)\s+STEP\s+(\d+)\]",
             )?,
+            datetime: Regex::new(
+                r"^(\d{4})-(\d{2})-(\d{2})T(\d{2}):(\d{2}):(\d{2})(?:\.(\d+))?(Z|[+-]\d{2}:\d{2})?$",
+            )?,
+            where_keyword: Regex::new(r"(?:^|\s)WHERE\b")?,
             register: Regex::new(r"REGISTER\s+(\w+)\s+([^\s]+)\s+AS")?,
-            prefix: Regex::new(r"PREFIX\s+([^\s]+):\s*<([^>]+)>")?,
+            prefix: Regex::new(r"(?i)PREFIX\s+([^\s:]*):\s*<([^>]+)>")?,
+            base: Regex::new(r"(?i)\bBASE\s*<([^>]*)>")?,
+        })
+    }
+
+    /// Parses a window timestamp, given in epoch milliseconds or as a quoted ISO-8601
+    /// datetime such as `"2021-01-01T00:00:00Z"`
+    ///
+    /// Datetimes are converted to epoch milliseconds, so that both forms of the same instant
+    /// compare equal. A datetime without a timezone is taken to be in UTC.
+    fn parse_timestamp(&self, value: &str, context: &str) -> Result<u64, TulnaError> {
+        let Some(datetime) = value.strip_prefix('"').and_then(|v| v.strip_suffix('"')) else {
+            return parse_number(value, context);
+        };
+        self.epoch_millis(datetime).ok_or_else(|| {
+            TulnaError::ParseError(format!(
+                "invalid {} '{}': expected an ISO-8601 datetime after 1970",
+                context, value
+            ))
         })
     }
 
-    fn parse_window(
+    /// Converts an ISO-8601 datetime to milliseconds since the Unix epoch
+    fn epoch_millis(&self, datetime: &str) -> Option<u64> {
+        let captures = self.datetime.captures(datetime)?;
+        let field = |index: usize| captures[index].parse::<i64>().ok();
+        let (year, month, day) = (field(1)?, field(2)?, field(3)?);
+        let (hour, minute, second) = (field(4)?, field(5)?, field(6)?);
+
+        let leap = year % 4 == 0 && (year % 100 != 0 || year % 400 == 0);
+        let days_in_month = match month {
+            2 if leap => 29,
+            2 => 28,
+            4 | 6 | 9 | 11 => 30,
+            1..=12 => 31,
+            _ => return None,
+        };
+        if day < 1 || day > days_in_month || hour > 23 || minute > 59 || second > 59 {
+            return None;
+        }
+
+        // Days since 1970-01-01 of the proleptic Gregorian calendar, counting years from
+        // March so that the leap day comes last
+        let shifted_year = if month <= 2 { year - 1 } else { year };
+        let era = shifted_year.div_euclid(400);
+        let year_of_era = shifted_year - era * 400;
+        let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+        let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+        let days = era * 146_097 + day_of_era - 719_468;
+
+        // Only the milliseconds of a fraction count
+        let millis = captures.get(7).map_or(Some(0), |fraction| {
+            format!("{:0<3}", &fraction.as_str()[..fraction.len().min(3)])
+                .parse::<i64>()
+                .ok()
+        })?;
+        let offset_minutes = match captures.get(8).map(|zone| zone.as_str()) {
+            None | Some("Z") => 0,
+            Some(zone) => {
+                let (hours, minutes) = zone[1..].split_once(':')?;
+                let minutes = hours.parse::<i64>().ok()? * 60 + minutes.parse::<i64>().ok()?;
+                if zone.starts_with('-') {
+                    -minutes
+                } else {
+                    minutes
+                }
+            }
+        };
+
+        let seconds = ((days * 24 + hour) * 60 + minute - offset_minutes) * 60 + second;
+        u64::try_from(seconds * 1000 + millis).ok()
+    }
+
+    /// Parses the window declarations of a query, in the order in which they are written
+    ///
+    /// The windows are matched anywhere in the query text, so a declaration need not be on
+    /// a line of its own. The byte range of each declaration is returned along with it.
+    fn parse_windows(
         &self,
-        line: &str,
+        query: &str,
         prefix_mapper: &HashMap<String, String>,
-    ) -> Result<Option<WindowDefinition>, Box<dyn std::error::Error>> {
-        if let Some(captures) = self.historical_sliding_window.captures(line) {
-            return Ok(Some(WindowDefinition {
+    ) -> Result<Vec<(Range<usize>, WindowDefinition)>, TulnaError> {
+        let mut windows = Vec::new();
+
+        for captures in self.historical_sliding_window.captures_iter(query) {
+            let window = WindowDefinition {
                 window_name: self.unwrap_iri(&captures[1], prefix_mapper),
                 stream_name: self.unwrap_iri(&captures[2], prefix_mapper),
-                offset: Some(captures[3].parse()?),
-                width: captures[4].parse()?,
-                slide: captures[5].parse()?,
+                offset: Some(self.parse_timestamp(&captures[3], "window offset")?),
+                width: parse_number(&captures[4], "window range")?,
+                slide: parse_number(&captures[5], "window step")?,
                 start: None,
                 end: None,
                 window_type: WindowType::HistoricalSliding,
-            }));
+            };
+            windows.push((captures.get(0).unwrap().range(), window));
         }
 
-        if let Some(captures) = self.historical_fixed_window.captures(line) {
-            return Ok(Some(WindowDefinition {
+        for captures in self.historical_fixed_window.captures_iter(query) {
+            let window = WindowDefinition {
                 window_name: self.unwrap_iri(&captures[1], prefix_mapper),
                 stream_name: self.unwrap_iri(&captures[2], prefix_mapper),
-                start: Some(captures[3].parse()?),
-                end: Some(captures[4].parse()?),
+                start: Some(self.parse_timestamp(&captures[3], "window start")?),
+                end: Some(self.parse_timestamp(&captures[4], "window end")?),
                 width: 0,
                 slide: 0,
                 offset: None,
                 window_type: WindowType::HistoricalFixed,
-            }));
+            };
+            windows.push((captures.get(0).unwrap().range(), window));
         }
 
-        if let Some(captures) = self.live_sliding_window.captures(line) {
-            return Ok(Some(WindowDefinition {
+        for captures in self.live_sliding_window.captures_iter(query) {
+            let window = WindowDefinition {
                 window_name: self.unwrap_iri(&captures[1], prefix_mapper),
                 stream_name: self.unwrap_iri(&captures[2], prefix_mapper),
-                width: captures[3].parse()?,
-                slide: captures[4].parse()?,
+                width: parse_number(&captures[3], "window range")?,
+                slide: parse_number(&captures[4], "window step")?,
                 offset: None,
                 start: None,
                 end: None,
                 window_type: WindowType::Live,
-            }));
+            };
+            windows.push((captures.get(0).unwrap().range(), window));
         }
 
-        Ok(None)
+        windows.sort_by_key(|(range, _)| range.start);
+        Ok(windows)
     }
 
     /// Parses a JanusQL query string.
-    pub fn parse(&self, query: &str) -> Result<ParsedJanusQuery, Box<dyn std::error::Error>> {
+    ///
+    /// The prefixes, REGISTER clause and windows are matched anywhere in the query, so the
+    /// query may be written on any number of lines. The SELECT clause runs up to the
+    /// `WHERE` keyword, and the WHERE clause from there to the end of the query.
+    pub fn parse(&self, query: &str) -> Result<ParsedJanusQuery, TulnaError> {
+        let query = &unify_variable_markers(&strip_comments(query));
         let mut parsed = ParsedJanusQuery {
             r2s: None,
             live_windows: Vec::new(),
@@ -145,63 +242,71 @@ impl JanusQLParser {
             rspql_query: String::new(),
             sparql_queries: Vec::new(),
             prefixes: HashMap::new(),
+            base: None,
             where_clause: String::new(),
             select_clause: String::new(),
         };
 
-        let lines: Vec<&str> = query.lines().collect();
+        // Byte ranges of the declarations, which are left out of the SELECT and WHERE clauses
+        let mut declarations: Vec<Range<usize>> = Vec::new();
         let mut prefix_lines: Vec<String> = Vec::new();
-        let mut in_where_clause = false;
-        let mut where_lines: Vec<&str> = Vec::new();
-
-        for line in lines {
-            let trimmed_line = line.trim();
-
-            if trimmed_line.is_empty()
-                || trimmed_line.starts_with("/*")
-                || trimmed_line.starts_with('*')
-                || trimmed_line.starts_with("*/")
-            {
-                if in_where_clause && !trimmed_line.is_empty() {
-                    where_lines.push(trimmed_line);
-                }
-                continue;
-            }
 
-            if trimmed_line.starts_with("REGISTER") {
-                if let Some(captures) = self.register.captures(trimmed_line) {
-                    let operator = captures.get(1).unwrap().as_str().to_string();
-                    let name_raw = captures.get(2).unwrap().as_str();
-                    let name = self.unwrap_iri(name_raw, &parsed.prefixes);
-                    parsed.r2s = Some(R2SOperator { operator, name });
-                }
-            } else if trimmed_line.starts_with("PREFIX") {
-                if let Some(captures) = self.prefix.captures(trimmed_line) {
-                    let prefix = captures.get(1).unwrap().as_str().to_string();
-                    let namespace = captures.get(2).unwrap().as_str().to_string();
-                    parsed.prefixes.insert(prefix, namespace);
-                    prefix_lines.push(trimmed_line.to_string());
-                }
-            } else if trimmed_line.starts_with("SELECT") {
-                parsed.select_clause = trimmed_line.to_string();
-            } else if trimmed_line.starts_with("FROM NAMED WINDOW") {
-                if let Some(window) = self.parse_window(trimmed_line, &parsed.prefixes)? {
-                    match window.window_type {
-                        WindowType::Live => parsed.live_windows.push(window),
-                        WindowType::HistoricalSliding | WindowType::HistoricalFixed => {
-                            parsed.historical_windows.push(window);
-                        }
-                    }
+        if let Some(captures) = self.base.captures(query) {
+            parsed.base = Some(captures.get(1).unwrap().as_str().to_string());
+            // Kept in front of the prefixes of the generated queries
+            prefix_lines.push(captures[0].to_string());
+            declarations.push(captures.get(0).unwrap().range());
+        }
+
+        for captures in self.prefix.captures_iter(query) {
+            let prefix = captures.get(1).unwrap().as_str().to_string();
+            let namespace = captures.get(2).unwrap().as_str();
+            let namespace = match &parsed.base {
+                Some(base) => resolve_iri(base, namespace),
+                None => namespace.to_string(),
+            };
+            parsed.prefixes.insert(prefix, namespace);
+            prefix_lines.push(captures[0].to_string());
+            declarations.push(captures.get(0).unwrap().range());
+        }
+
+        if let Some(captures) = self.register.captures(query) {
+            let operator = captures.get(1).unwrap().as_str().to_string();
+            let name_raw = captures.get(2).unwrap().as_str();
+            let name = self.unwrap_iri(name_raw, &parsed.prefixes);
+            parsed.r2s = Some(R2SOperator { operator, name });
+            declarations.push(captures.get(0).unwrap().range());
+        }
+
+        for (range, window) in self.parse_windows(query, &parsed.prefixes)? {
+            declarations.push(range);
+            match window.window_type {
+                WindowType::Live => parsed.live_windows.push(window),
+                WindowType::HistoricalSliding | WindowType::HistoricalFixed => {
+                    parsed.historical_windows.push(window);
                 }
-            } else if trimmed_line.starts_with("WHERE") {
-                in_where_clause = true;
-                where_lines.push(line);
-            } else if in_where_clause {
-                where_lines.push(line);
             }
         }
 
-        parsed.where_clause = where_lines.join("\n");
+        declarations.sort_by_key(|range| range.start);
+        let mut body = String::with_capacity(query.len());
+        let mut position = 0;
+        for range in declarations {
+            if range.start >= position {
+                body.push_str(&query[position..range.start]);
+                position = range.end;
+            }
+        }
+        body.push_str(&query[position..]);
+
+        let where_start = self
+            .where_keyword
+            .find(&body)
+            .map_or(body.len(), |keyword| keyword.start());
+        if let Some(select_start) = body[..where_start].find("SELECT") {
+            parsed.select_clause = body[select_start..where_start].trim().to_string();
+        }
+        parsed.where_clause = body[where_start..].trim().to_string();
 
         if !parsed.live_windows.is_empty() {
             parsed.rspql_query = self.generate_rspql_query(&parsed, &prefix_lines);
@@ -299,19 +404,17 @@ impl JanusQLParser {
         match window.window_type {
             WindowType::HistoricalFixed => {
                 if let (Some(start), Some(end)) = (window.start, window.end) {
-                    let filter_clause = format!(
-                        "\n FILTER(?timestamp >= {} && ?timestamp <= {})",
-                        start, end
-                    );
-                    adapted.replace("}&", &format!("{}\n}}", filter_clause))
+                    let filter_clause =
+                        format!("FILTER(?timestamp >= {} && ?timestamp <= {})", start, end);
+                    Self::insert_into_where_group(&adapted, &filter_clause)
                 } else {
                     adapted
                 }
             }
             WindowType::HistoricalSliding => {
                 if let Some(offset) = window.offset {
-                    let filter_clause = format!("\n FILTER(?timestamp >= {})", offset);
-                    adapted.replace("}&", &format!("{}\n}}", filter_clause))
+                    let filter_clause = format!("FILTER(?timestamp >= {})", offset);
+                    Self::insert_into_where_group(&adapted, &filter_clause)
                 } else {
                     adapted
                 }
@@ -320,6 +423,41 @@ impl JanusQLParser {
         }
     }
 
+    /// Inserts a clause at the end of the group of a WHERE clause, before its closing brace
+    ///
+    /// Braces inside string literals are skipped. Without a complete group, the WHERE clause
+    /// is returned unchanged.
+    fn insert_into_where_group(where_clause: &str, clause: &str) -> String {
+        let mut depth = 0;
+        let mut quote = None;
+        let mut escaped = false;
+
+        for (idx, c) in where_clause.char_indices() {
+            match quote {
+                Some(_) if escaped => escaped = false,
+                Some(_) if c == '\\' => escaped = true,
+                Some(q) if c == q => quote = None,
+                Some(_) => {}
+                None => match c {
+                    '"' | '\'' => quote = Some(c),
+                    '{' => depth += 1,
+                    '}' if depth == 1 => {
+                        return format!(
+                            "{}\n {}\n{}",
+                            where_clause[..idx].trim_end(),
+                            clause,
+                            &where_clause[idx..]
+                        );
+                    }
+                    '}' => depth -= 1,
+                    _ => {}
+                },
+            }
+        }
+
+        where_clause.to_string()
+    }
+
     fn unwrap_iri(&self, prefixed_iri: &str, prefix_mapper: &HashMap<String, String>) -> String {
         let trimmed = prefixed_iri.trim();
 
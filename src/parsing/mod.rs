@@ -2,3 +2,268 @@ pub mod janusql_parser;
 pub mod parsed_rspql_query;
 pub mod rspql_parser;
 pub mod sparql_parser;
+pub(crate) mod turtle_parser;
+
+use crate::TulnaError;
+use std::fmt::Display;
+use std::str::FromStr;
+
+/// Parses a number captured from a query, naming the clause it belongs to on failure
+pub(crate) fn parse_number<T>(value: &str, context: &str) -> Result<T, TulnaError>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    value
+        .parse()
+        .map_err(|e| TulnaError::ParseError(format!("invalid {} '{}': {}", context, value, e)))
+}
+
+/// Removes the `#` comments of a query, each running to the end of its line
+///
+/// A `#` inside an IRI, such as `<http://www.w3.org/1999/02/22-rdf-syntax-ns#type>`, or
+/// inside a string literal does not start a comment. Line breaks are kept, so line numbers
+/// still match the original query.
+pub(crate) fn strip_comments(query: &str) -> String {
+    query
+        .split('\n')
+        .map(strip_line_comment)
+        .collect::<Vec<&str>>()
+        .join("\n")
+}
+
+/// Removes a trailing `#` comment from a single line
+///
+/// A `<` starts an IRI when the word following it holds a `>`, so that a comparison such as
+/// `?x < 5` in a FILTER does not hide the comment after it.
+fn strip_line_comment(line: &str) -> &str {
+    let mut quote = None;
+    let mut escaped = false;
+    let mut in_iri = false;
+
+    for (idx, c) in line.char_indices() {
+        match quote {
+            Some(_) if escaped => escaped = false,
+            Some(_) if c == '\\' => escaped = true,
+            Some(q) if c == q => quote = None,
+            Some(_) => {}
+            None if in_iri => in_iri = c != '>',
+            None => match c {
+                '"' | '\'' => quote = Some(c),
+                '<' => {
+                    in_iri = line[idx + 1..]
+                        .split(char::is_whitespace)
+                        .next()
+                        .is_some_and(|word| word.contains('>'))
+                }
+                '#' => return &line[..idx],
+                _ => {}
+            },
+        }
+    }
+
+    line
+}
+
+/// Writes every `$var` variable of a query as `?var`
+///
+/// SPARQL treats `$x` and `?x` as the same variable, so this lets later stages compare
+/// variables by their text. A `$` inside an IRI or a string literal is left alone; IRIs are
+/// recognized as in `strip_comments`.
+pub(crate) fn unify_variable_markers(query: &str) -> String {
+    let mut unified = String::with_capacity(query.len());
+    let mut quote = None;
+    let mut escaped = false;
+    let mut in_iri = false;
+
+    for (idx, c) in query.char_indices() {
+        match quote {
+            Some(_) if escaped => escaped = false,
+            Some(_) if c == '\\' => escaped = true,
+            Some(q) if c == q => quote = None,
+            Some(_) => {}
+            None if in_iri => in_iri = c != '>',
+            None => match c {
+                '"' | '\'' => quote = Some(c),
+                '<' => {
+                    in_iri = query[idx + 1..]
+                        .split(char::is_whitespace)
+                        .next()
+                        .is_some_and(|word| word.contains('>'))
+                }
+                '$' if query[idx + 1..].starts_with(|c: char| c.is_alphanumeric() || c == '_') => {
+                    unified.push('?');
+                    continue;
+                }
+                _ => {}
+            },
+        }
+        unified.push(c);
+    }
+
+    unified
+}
+
+/// Resolves an IRI reference against a base IRI, following RFC 3986
+///
+/// References that already have a scheme, such as `http://example.org/a` or an unexpanded
+/// `ex:a`, are returned unchanged.
+pub(crate) fn resolve_iri(base: &str, reference: &str) -> String {
+    if has_scheme(reference) {
+        return reference.to_string();
+    }
+    let Some((scheme, rest)) = base.split_once(':') else {
+        return reference.to_string();
+    };
+
+    // Split the base into its authority, path and query, dropping its fragment
+    let rest = rest.split('#').next().unwrap_or_default();
+    let (authority, path_and_query) = match rest.strip_prefix("//") {
+        Some(after) => {
+            let end = after.find(['/', '?']).unwrap_or(after.len());
+            (Some(&after[..end]), &after[end..])
+        }
+        None => (None, rest),
+    };
+    let (base_path, base_query) = match path_and_query.split_once('?') {
+        Some((path, query)) => (path, Some(query)),
+        None => (path_and_query, None),
+    };
+    let prefix = match authority {
+        Some(authority) => format!("{}://{}", scheme, authority),
+        None => format!("{}:", scheme),
+    };
+    let query = base_query
+        .map(|query| format!("?{}", query))
+        .unwrap_or_default();
+
+    if let Some(network_path) = reference.strip_prefix("//") {
+        return format!("{}://{}", scheme, network_path);
+    }
+    if reference.is_empty() || reference.starts_with('#') {
+        return format!("{}{}{}{}", prefix, base_path, query, reference);
+    }
+    if reference.starts_with('?') {
+        return format!("{}{}{}", prefix, base_path, reference);
+    }
+
+    let (path, suffix) = reference.split_at(reference.find(['?', '#']).unwrap_or(reference.len()));
+    let merged = if path.starts_with('/') {
+        path.to_string()
+    } else if authority.is_some() && base_path.is_empty() {
+        format!("/{}", path)
+    } else {
+        match base_path.rfind('/') {
+            Some(slash) => format!("{}{}", &base_path[..=slash], path),
+            None => path.to_string(),
+        }
+    };
+
+    format!("{}{}{}", prefix, remove_dot_segments(&merged), suffix)
+}
+
+/// Checks whether an IRI reference starts with a scheme, like `http:`
+fn has_scheme(reference: &str) -> bool {
+    match reference.find(':') {
+        Some(colon) => {
+            let scheme = &reference[..colon];
+            scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+                && scheme
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || "+-.".contains(c))
+        }
+        None => false,
+    }
+}
+
+/// Removes the `.` and `..` segments of a path
+fn remove_dot_segments(path: &str) -> String {
+    let segments: Vec<&str> = path.split('/').collect();
+    let mut output: Vec<&str> = Vec::new();
+
+    for (index, segment) in segments.iter().enumerate() {
+        let last = index == segments.len() - 1;
+        match *segment {
+            "." => {}
+            ".." => {
+                // The empty segment before a leading slash is never removed
+                if output.len() > 1 || output.first().is_some_and(|first| !first.is_empty()) {
+                    output.pop();
+                }
+            }
+            segment => {
+                output.push(segment);
+                continue;
+            }
+        }
+        // A path ending in a dot segment refers to a directory
+        if last {
+            output.push("");
+        }
+    }
+
+    output.join("/")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_strip_comments() {
+        assert_eq!(strip_comments("?s ?p ?o . # a comment"), "?s ?p ?o . ");
+        assert_eq!(
+            strip_comments("?s <http://www.w3.org/1999/02/22-rdf-syntax-ns#type> ?o . # type"),
+            "?s <http://www.w3.org/1999/02/22-rdf-syntax-ns#type> ?o . "
+        );
+        assert_eq!(
+            strip_comments("?s ?p \"#1\" . FILTER(?x < 5) # c"),
+            "?s ?p \"#1\" . FILTER(?x < 5) "
+        );
+        assert_eq!(
+            strip_comments("?s ?p \"say \\\"#1\\\"\" . # c\n# {\n?s ?q '#' # }"),
+            "?s ?p \"say \\\"#1\\\"\" . \n\n?s ?q '#' "
+        );
+    }
+
+    #[test]
+    fn test_unify_variable_markers() {
+        assert_eq!(
+            unify_variable_markers("SELECT $s WHERE { $s ?p $o_1 }"),
+            "SELECT ?s WHERE { ?s ?p ?o_1 }"
+        );
+        assert_eq!(
+            unify_variable_markers("?s <http://example.org/$a> \"$5\" . FILTER(?x < $y)"),
+            "?s <http://example.org/$a> \"$5\" . FILTER(?x < ?y)"
+        );
+    }
+
+    #[test]
+    fn test_resolve_iri() {
+        let base = "http://example.org/people/alice?q#me";
+
+        assert_eq!(resolve_iri(base, "bob"), "http://example.org/people/bob");
+        assert_eq!(
+            resolve_iri(base, "../places/gent"),
+            "http://example.org/places/gent"
+        );
+        assert_eq!(resolve_iri(base, "./"), "http://example.org/people/");
+        assert_eq!(resolve_iri(base, "/root"), "http://example.org/root");
+        assert_eq!(
+            resolve_iri(base, "#name"),
+            "http://example.org/people/alice?q#name"
+        );
+        assert_eq!(resolve_iri(base, "?x"), "http://example.org/people/alice?x");
+        assert_eq!(resolve_iri(base, ""), "http://example.org/people/alice?q");
+        assert_eq!(resolve_iri(base, "//other.org/a"), "http://other.org/a");
+        assert_eq!(
+            resolve_iri("http://example.org", "a"),
+            "http://example.org/a"
+        );
+        assert_eq!(
+            resolve_iri(base, "http://other.org/a"),
+            "http://other.org/a"
+        );
+        assert_eq!(resolve_iri(base, "ex:a"), "ex:a");
+    }
+}
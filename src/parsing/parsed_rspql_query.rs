@@ -1,17 +1,17 @@
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Operator {
     RStream,
     IStream,
     DStream,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct R2S {
     pub operator: Operator,
     pub name: String,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct WindowDefinition {
     pub window_name: String,
     pub stream_name: String,
@@ -19,10 +19,11 @@ pub struct WindowDefinition {
     pub slide: i64,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct ParsedQuery {
     pub sparql_query: String,
-    pub r2s: R2S,
+    /// R2S operator and output stream of the `REGISTER` clause, `None` without one
+    pub r2s: Option<R2S>,
     pub s2r: Vec<WindowDefinition>,
 }
 
@@ -30,10 +31,7 @@ impl ParsedQuery {
     pub fn new(sparql_query: String) -> Self {
         Self {
             sparql_query,
-            r2s: R2S {
-                operator: Operator::RStream,
-                name: "undefined".to_string(),
-            },
+            r2s: None,
             s2r: Vec::new(),
         }
     }
@@ -43,7 +41,7 @@ impl ParsedQuery {
     }
 
     pub fn set_r2s(&mut self, operator: Operator, name: String) {
-        self.r2s = R2S { operator, name };
+        self.r2s = Some(R2S { operator, name });
     }
 
     pub fn add_s2r_window(&mut self, window: WindowDefinition) {
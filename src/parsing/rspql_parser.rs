@@ -1,4 +1,5 @@
 use crate::parsing::parsed_rspql_query::{Operator, ParsedQuery, WindowDefinition};
+use crate::parsing::{strip_comments, unify_variable_markers};
 use regex::Regex;
 use std::collections::HashMap;
 
@@ -8,7 +9,9 @@ pub struct RSPQLParser {
 
 impl RSPQLParser {
     pub fn new(query: String) -> Self {
-        Self { rspql_query: query }
+        Self {
+            rspql_query: unify_variable_markers(&strip_comments(&query)),
+        }
     }
 
     pub fn parse(&self) -> ParsedQuery {
@@ -17,21 +20,22 @@ impl RSPQLParser {
         let mut prefix_mapper: HashMap<String, String> = HashMap::new();
 
         // Compile regex patterns once outside the loop
-        let register_re = Regex::new(r"REGISTER +([^ ]+) +<([^>]+)> AS").unwrap();
+        // The output stream is named by an `<iri>` or a `prefix:local` name
+        let register_re = Regex::new(r"REGISTER +([^ ]+) +(<[^>]+>|[^\s<>]+) +AS").unwrap();
         let window_re = Regex::new(
             r"FROM +NAMED +WINDOW +([^ ]+) +ON +STREAM +([^ ]+) +\[RANGE +([^ ]+) +STEP +([^ ]+)\]",
         )
         .unwrap();
-        let prefix_re = Regex::new(r"PREFIX +([^:]*): +<([^>]+)>").unwrap();
+        let prefix_re = Regex::new(r"(?i)PREFIX\s+([^\s:]*):\s*<([^>]+)>").unwrap();
 
         for line in self.rspql_query.lines() {
             let trimmed_line = line.trim();
             if trimmed_line.starts_with("REGISTER") {
                 for captures in register_re.captures_iter(trimmed_line) {
                     let op_str = captures.get(1).unwrap().as_str();
-                    let name = captures.get(2).unwrap().as_str();
+                    let name = Self::unwrap(captures.get(2).unwrap().as_str(), &prefix_mapper);
                     if let Some(operator) = Self::parse_operator(op_str) {
-                        parsed.set_r2s(operator, name.to_string());
+                        parsed.set_r2s(operator, name);
                     }
                 }
             } else if trimmed_line.starts_with("FROM NAMED WINDOW") {
@@ -65,7 +69,7 @@ impl RSPQLParser {
                 if sparql_line.starts_with("WINDOW") {
                     sparql_line = sparql_line.replace("WINDOW", "GRAPH");
                 }
-                if sparql_line.starts_with("PREFIX") {
+                if sparql_line.to_uppercase().starts_with("PREFIX") {
                     for captures in prefix_re.captures_iter(&sparql_line) {
                         let prefix = captures.get(1).unwrap().as_str().to_string();
                         let iri = captures.get(2).unwrap().as_str().to_string();
@@ -88,21 +92,23 @@ impl RSPQLParser {
         }
     }
 
+    /// Expand an `<iri>` or `prefix:local` name to its full IRI
+    ///
+    /// Names with an undeclared prefix, or without a prefix at all, are kept as written,
+    /// like `JanusQLParser` does, so that different names never collapse into one.
     fn unwrap(prefixed_iri: &str, mapper: &HashMap<String, String>) -> String {
         let trimmed = prefixed_iri.trim();
         if trimmed.starts_with('<') && trimmed.ends_with('>') {
-            trimmed[1..trimmed.len() - 1].to_string()
-        } else {
-            let parts: Vec<&str> = trimmed.split(':').collect();
-            if parts.len() == 2 {
-                if let Some(iri) = mapper.get(parts[0]) {
-                    format!("{}{}", iri, parts[1])
-                } else {
-                    "".to_string()
-                }
-            } else {
-                "".to_string()
+            return trimmed[1..trimmed.len() - 1].to_string();
+        }
+
+        // The local part may hold colons of its own, as in `ex:a:b`
+        if let Some((prefix, local)) = trimmed.split_once(':') {
+            if let Some(iri) = mapper.get(prefix) {
+                return format!("{}{}", iri, local);
             }
         }
+
+        trimmed.to_string()
     }
 }
@@ -1,8 +1,11 @@
+use crate::parsing::{parse_number, resolve_iri, strip_comments, unify_variable_markers};
+use crate::TulnaError;
 use regex::Regex;
 use std::collections::HashMap;
 
 /// Type of SPARQL query
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum QueryType {
     Select,
     Construct,
@@ -15,16 +18,22 @@ pub enum QueryType {
 pub struct ParsedSparqlQuery {
     /// Type of query (SELECT, CONSTRUCT, ASK, DESCRIBE)
     pub query_type: QueryType,
+    /// BASE IRI that relative IRIs are resolved against
+    pub base: Option<String>,
     /// Prefix mappings
     pub prefixes: HashMap<String, String>,
-    /// SELECT clause (variables or *)
+    /// SELECT clause (variables or *), or the resources named by a DESCRIBE query
     pub select_clause: String,
+    /// Triple patterns of the CONSTRUCT template, without the surrounding braces
+    pub construct_template: Option<String>,
     /// FROM clauses (default graphs)
     pub from_clauses: Vec<String>,
     /// FROM NAMED clauses (named graphs)
     pub from_named_clauses: Vec<String>,
     /// WHERE clause
     pub where_clause: String,
+    /// GROUP BY clause
+    pub group_by: Option<String>,
     /// ORDER BY clause
     pub order_by: Option<String>,
     /// LIMIT value
@@ -35,12 +44,15 @@ pub struct ParsedSparqlQuery {
     pub distinct: bool,
     /// REDUCED flag
     pub reduced: bool,
+    /// Whether the SELECT clause uses an aggregate function such as `COUNT`
+    pub has_aggregates: bool,
     /// Original query
     pub original_query: String,
 }
 
 /// Parser for SPARQL queries
 pub struct SparqlParser {
+    base: Regex,
     prefix: Regex,
     select: Regex,
     construct: Regex,
@@ -48,6 +60,8 @@ pub struct SparqlParser {
     describe: Regex,
     from: Regex,
     from_named: Regex,
+    group_by: Regex,
+    aggregate: Regex,
     order_by: Regex,
     limit: Regex,
     offset: Regex,
@@ -55,46 +69,103 @@ pub struct SparqlParser {
 
 impl SparqlParser {
     /// Creates a new SparqlParser instance
-    pub fn new() -> Result<Self, Box<dyn std::error::Error>> {
+    pub fn new() -> Result<Self, TulnaError> {
         Ok(SparqlParser {
-            prefix: Regex::new(r"(?i)PREFIX\s+([^\s]+):\s*<([^>]+)>")?,
-            select: Regex::new(r"(?i)SELECT\s+(DISTINCT\s+|REDUCED\s+)?(.+?)(?:WHERE|FROM|\{)")?,
+            base: Regex::new(r"(?i)\bBASE\s*<([^>]*)>")?,
+            prefix: Regex::new(r"(?i)PREFIX\s+([^\s:]*):\s*<([^>]+)>")?,
+            select: Regex::new(r"(?i)SELECT\s+(DISTINCT\s+|REDUCED\s+)?(.+?)(?:WHERE|FROM|\{|$)")?,
             construct: Regex::new(r"(?i)CONSTRUCT\s*\{")?,
             ask: Regex::new(r"(?i)ASK\s*\{")?,
-            describe: Regex::new(r"(?i)DESCRIBE\s+(.+?)(?:WHERE|FROM|\{)")?,
+            // A DESCRIBE query may consist of its resources alone, so they also end with the
+            // query; IRIs are matched whole, so a keyword inside one does not end them
+            describe: Regex::new(
+                r"(?is)\bDESCRIBE\s+((?:<[^>]*>|[^<{])+?)\s*(?:\bWHERE\b|\bFROM\b|\{|$)",
+            )?,
             from: Regex::new(r"(?i)^FROM\s+(<[^>]+>|\S+)")?,
             from_named: Regex::new(r"(?i)FROM\s+NAMED\s+(<[^>]+>|\S+)")?,
-            order_by: Regex::new(r"(?i)ORDER\s+BY\s+(.+?)(?:LIMIT|OFFSET|$)")?,
+            group_by: Regex::new(r"(?im)GROUP\s+BY\s+(.+?)(?:HAVING|ORDER\s+BY|LIMIT|OFFSET|$)")?,
+            aggregate: Regex::new(r"(?i)\b(?:COUNT|SUM|MIN|MAX|AVG|SAMPLE|GROUP_CONCAT)\s*\(")?,
+            order_by: Regex::new(r"(?im)ORDER\s+BY\s+(.+?)(?:LIMIT|OFFSET|$)")?,
             limit: Regex::new(r"(?i)LIMIT\s+(\d+)")?,
             offset: Regex::new(r"(?i)OFFSET\s+(\d+)")?,
         })
     }
 
     /// Parses a SPARQL query string
-    pub fn parse(&self, query: &str) -> Result<ParsedSparqlQuery, Box<dyn std::error::Error>> {
+    pub fn parse(&self, query: &str) -> Result<ParsedSparqlQuery, TulnaError> {
+        let original_query = query.to_string();
+        let query = &unify_variable_markers(&strip_comments(query));
         let mut parsed = ParsedSparqlQuery {
             query_type: QueryType::Select,
+            base: None,
             prefixes: HashMap::new(),
             select_clause: String::new(),
+            construct_template: None,
             from_clauses: Vec::new(),
             from_named_clauses: Vec::new(),
             where_clause: String::new(),
+            group_by: None,
             order_by: None,
             limit: None,
             offset: None,
             distinct: false,
             reduced: false,
-            original_query: query.to_string(),
+            has_aggregates: false,
+            original_query,
         };
 
-        let lines: Vec<&str> = query.lines().collect();
+        // Determine query type
+        parsed.query_type = self.determine_query_type(query)?;
+
+        // Extract the BASE IRI, which relative namespaces are resolved against as well
+        if let Some(captures) = self.base.captures(query) {
+            parsed.base = Some(captures.get(1).unwrap().as_str().to_string());
+        }
+
+        // Extract prefixes from the whole query, so that several declarations on one line,
+        // or declarations sharing a line with the query form, are all found
+        for captures in self.prefix.captures_iter(query) {
+            let prefix = captures.get(1).unwrap().as_str().to_string();
+            let namespace = captures.get(2).unwrap().as_str();
+            let namespace = match &parsed.base {
+                Some(base) => resolve_iri(base, namespace),
+                None => namespace.to_string(),
+            };
+            parsed.prefixes.insert(prefix, namespace);
+        }
+
+        // The template braces would otherwise be taken for the start of the WHERE clause
+        let body = if parsed.query_type == QueryType::Construct {
+            let (template, rest) = self.split_construct_template(query);
+            parsed.construct_template = template;
+            rest
+        } else {
+            query.to_string()
+        };
+
+        // Without the BASE and prefix declarations, a query form on the same line starts
+        // that line
+        let body = self.base.replace_all(&body, "");
+        let body = self.prefix.replace_all(&body, "");
+
+        // The resources of a DESCRIBE query may span several lines
+        if parsed.query_type == QueryType::Describe {
+            if let Some(captures) = self.describe.captures(&body) {
+                parsed.select_clause = captures
+                    .get(1)
+                    .unwrap()
+                    .as_str()
+                    .split_whitespace()
+                    .collect::<Vec<_>>()
+                    .join(" ");
+            }
+        }
+
+        let lines: Vec<&str> = body.lines().collect();
         let mut in_where_clause = false;
         let mut where_lines: Vec<&str> = Vec::new();
         let mut brace_count = 0;
 
-        // Determine query type
-        parsed.query_type = self.determine_query_type(query)?;
-
         for line in &lines {
             let trimmed_line = line.trim();
 
@@ -105,16 +176,8 @@ impl SparqlParser {
                 continue;
             }
 
-            // Extract prefixes
-            if trimmed_line.to_uppercase().starts_with("PREFIX") {
-                if let Some(captures) = self.prefix.captures(trimmed_line) {
-                    let prefix = captures.get(1).unwrap().as_str().to_string();
-                    let namespace = captures.get(2).unwrap().as_str().to_string();
-                    parsed.prefixes.insert(prefix, namespace);
-                }
-            }
             // Extract SELECT clause
-            else if trimmed_line.to_uppercase().starts_with("SELECT") {
+            if trimmed_line.to_uppercase().starts_with("SELECT") {
                 if let Some(captures) = self.select.captures(trimmed_line) {
                     let modifier = captures.get(1).map(|m| m.as_str().trim()).unwrap_or("");
                     let vars = captures.get(2).unwrap().as_str().trim();
@@ -131,28 +194,40 @@ impl SparqlParser {
             }
             // Extract FROM clauses
             if trimmed_line.to_uppercase().starts_with("FROM NAMED") {
-                if let Some(captures) = self.from_named.captures(trimmed_line) {
+                // Several FROM NAMED declarations may share a single line
+                for captures in self.from_named.captures_iter(trimmed_line) {
                     let graph = captures.get(1).unwrap().as_str();
-                    parsed
-                        .from_named_clauses
-                        .push(self.unwrap_iri(graph, &parsed.prefixes));
+                    parsed.from_named_clauses.push(self.unwrap_iri(
+                        graph,
+                        &parsed.prefixes,
+                        parsed.base.as_deref(),
+                    ));
                 }
             } else if trimmed_line.to_uppercase().starts_with("FROM")
                 && !trimmed_line.to_uppercase().contains("NAMED")
             {
                 if let Some(captures) = self.from.captures(trimmed_line) {
                     let graph = captures.get(1).unwrap().as_str();
-                    parsed
-                        .from_clauses
-                        .push(self.unwrap_iri(graph, &parsed.prefixes));
+                    parsed.from_clauses.push(self.unwrap_iri(
+                        graph,
+                        &parsed.prefixes,
+                        parsed.base.as_deref(),
+                    ));
                 }
             }
 
             // Track WHERE clause
             // We check for WHERE or { to start the clause. We use contains because
             // WHERE might be on the same line as SELECT.
+            // ASK has no WHERE keyword, so its pattern may start on the ASK line itself.
+            // A VALUES block after the pattern is kept with the WHERE clause.
             if !in_where_clause
-                && (trimmed_line.to_uppercase().contains("WHERE") || trimmed_line.starts_with('{'))
+                && (trimmed_line.to_uppercase().contains("WHERE")
+                    || trimmed_line.starts_with('{')
+                    || trimmed_line.to_uppercase().starts_with("VALUES")
+                    || (parsed.query_type == QueryType::Ask
+                        && trimmed_line.to_uppercase().starts_with("ASK")
+                        && trimmed_line.contains('{')))
             {
                 in_where_clause = true;
             }
@@ -171,6 +246,14 @@ impl SparqlParser {
 
         parsed.where_clause = where_lines.join("\n");
 
+        // Extract GROUP BY
+        if let Some(captures) = self.group_by.captures(query) {
+            parsed.group_by = Some(captures.get(1).unwrap().as_str().trim().to_string());
+        }
+
+        // Detect aggregates, which group the solutions even without GROUP BY
+        parsed.has_aggregates = self.aggregate.is_match(&parsed.select_clause);
+
         // Extract ORDER BY
         if let Some(captures) = self.order_by.captures(query) {
             parsed.order_by = Some(captures.get(1).unwrap().as_str().trim().to_string());
@@ -178,19 +261,19 @@ impl SparqlParser {
 
         // Extract LIMIT
         if let Some(captures) = self.limit.captures(query) {
-            parsed.limit = Some(captures.get(1).unwrap().as_str().parse()?);
+            parsed.limit = Some(parse_number(captures.get(1).unwrap().as_str(), "LIMIT")?);
         }
 
         // Extract OFFSET
         if let Some(captures) = self.offset.captures(query) {
-            parsed.offset = Some(captures.get(1).unwrap().as_str().parse()?);
+            parsed.offset = Some(parse_number(captures.get(1).unwrap().as_str(), "OFFSET")?);
         }
 
         Ok(parsed)
     }
 
     /// Determines the type of SPARQL query
-    fn determine_query_type(&self, query: &str) -> Result<QueryType, Box<dyn std::error::Error>> {
+    fn determine_query_type(&self, query: &str) -> Result<QueryType, TulnaError> {
         let upper_query = query.to_uppercase();
 
         if self.select.is_match(query) {
@@ -204,17 +287,60 @@ impl SparqlParser {
         } else if upper_query.contains("SELECT") {
             Ok(QueryType::Select)
         } else {
-            Err("Unable to determine query type".into())
+            Err(TulnaError::ParseError(
+                "Unable to determine query type: expected SELECT, CONSTRUCT, ASK or DESCRIBE"
+                    .to_string(),
+            ))
         }
     }
 
-    /// Unwraps a prefixed IRI to its full form
-    fn unwrap_iri(&self, prefixed_iri: &str, prefix_mapper: &HashMap<String, String>) -> String {
+    /// Splits the template off a CONSTRUCT query
+    ///
+    /// Returns the contents of the template braces together with the query text that
+    /// remains after removing them.
+    fn split_construct_template(&self, query: &str) -> (Option<String>, String) {
+        let Some(keyword) = self.construct.find(query) else {
+            return (None, query.to_string());
+        };
+
+        // The CONSTRUCT pattern ends with the opening brace of the template
+        let open = keyword.end() - 1;
+        let mut depth = 0;
+        for (i, c) in query[open..].char_indices() {
+            match c {
+                '{' => depth += 1,
+                '}' => {
+                    depth -= 1;
+                    if depth == 0 {
+                        let close = open + i;
+                        let template = query[open + 1..close].trim().to_string();
+                        let rest = format!("{}{}", &query[..open], &query[close + 1..]);
+                        return (Some(template), rest);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        (None, query.to_string())
+    }
+
+    /// Unwraps a prefixed IRI to its full form, resolving a relative IRI against `base`
+    fn unwrap_iri(
+        &self,
+        prefixed_iri: &str,
+        prefix_mapper: &HashMap<String, String>,
+        base: Option<&str>,
+    ) -> String {
         let trimmed = prefixed_iri.trim();
 
         // Already a full IRI
         if trimmed.starts_with('<') && trimmed.ends_with('>') {
-            return trimmed[1..trimmed.len() - 1].to_string();
+            let iri = &trimmed[1..trimmed.len() - 1];
+            return match base {
+                Some(base) => resolve_iri(base, iri),
+                None => iri.to_string(),
+            };
         }
 
         // Handle prefixed form
@@ -274,16 +400,20 @@ impl ParsedSparqlQuery {
     pub fn new() -> Self {
         Self {
             query_type: QueryType::Select,
+            base: None,
             prefixes: HashMap::new(),
             select_clause: String::new(),
+            construct_template: None,
             from_clauses: Vec::new(),
             from_named_clauses: Vec::new(),
             where_clause: String::new(),
+            group_by: None,
             order_by: None,
             limit: None,
             offset: None,
             distinct: false,
             reduced: false,
+            has_aggregates: false,
             original_query: String::new(),
         }
     }
@@ -292,12 +422,18 @@ impl ParsedSparqlQuery {
     pub fn to_query_string(&self) -> String {
         let mut lines: Vec<String> = Vec::new();
 
-        // Add prefixes
-        for (prefix, namespace) in &self.prefixes {
+        if let Some(ref base) = self.base {
+            lines.push(format!("BASE <{}>", base));
+        }
+
+        // Add prefixes, sorted so that the output does not depend on the map order
+        let mut prefixes: Vec<(&String, &String)> = self.prefixes.iter().collect();
+        prefixes.sort();
+        for (prefix, namespace) in prefixes {
             lines.push(format!("PREFIX {}: <{}>", prefix, namespace));
         }
 
-        if !self.prefixes.is_empty() {
+        if self.base.is_some() || !self.prefixes.is_empty() {
             lines.push(String::new());
         }
 
@@ -315,7 +451,8 @@ impl ParsedSparqlQuery {
                 lines.push(select);
             }
             QueryType::Construct => {
-                lines.push("CONSTRUCT {".to_string());
+                let template = self.construct_template.as_deref().unwrap_or_default();
+                lines.push(format!("CONSTRUCT {{ {} }}", template));
             }
             QueryType::Ask => {
                 lines.push("ASK".to_string());
@@ -340,6 +477,10 @@ impl ParsedSparqlQuery {
         }
 
         // Add solution modifiers
+        if let Some(ref group_by) = self.group_by {
+            lines.push(format!("GROUP BY {}", group_by));
+        }
+
         if let Some(ref order_by) = self.order_by {
             lines.push(format!("ORDER BY {}", order_by));
         }
@@ -0,0 +1,501 @@
+use crate::isomorphism::core::{
+    FreshLabels, Triple, TripleNode, RDF_FIRST, RDF_NIL, RDF_REST, RDF_TYPE,
+};
+use crate::parsing::resolve_iri;
+use crate::TulnaError;
+use std::collections::HashMap;
+
+/// Token of a Turtle document
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    /// `<iri>`, without the angle brackets
+    Iri(String),
+    /// `prefix:local`, where either part may be empty
+    PrefixedName(String, String),
+    /// `_:label`, without the `_:`
+    BlankNode(String),
+    /// Quoted string, without its quotes; escape sequences are kept as written
+    String(String),
+    /// `@` followed by a language tag or the `prefix` and `base` directives
+    At(String),
+    /// Number, boolean, the `a` shorthand or the `PREFIX` and `BASE` keywords
+    Word(String),
+    /// `^^` between a string and its datatype
+    DatatypeMarker,
+    /// One of `.`, `;`, `,`, `[`, `]`, `(` and `)`
+    Punctuation(char),
+}
+
+/// Parser for the common subset of Turtle used to write test graphs
+///
+/// Supports `@prefix`/`PREFIX` and `@base`/`BASE` directives, IRIs, prefixed names, the
+/// `a` shorthand, blank node labels, anonymous blank nodes with `[ ]`, collections with
+/// `( )`, quoted and long strings with a language tag or datatype, numbers and booleans.
+/// Every malformed statement is reported with its line number.
+pub(crate) struct TurtleParser {
+    tokens: Vec<(Token, usize)>,
+    position: usize,
+    prefixes: HashMap<String, String>,
+    base: Option<String>,
+    labels: FreshLabels,
+    triples: Vec<Triple>,
+}
+
+impl TurtleParser {
+    /// Parse a Turtle document into its triples
+    pub(crate) fn parse(input: &str) -> Result<Vec<Triple>, TulnaError> {
+        let mut parser = TurtleParser {
+            tokens: Self::tokenize(input)?,
+            position: 0,
+            prefixes: HashMap::new(),
+            base: None,
+            labels: FreshLabels::default(),
+            triples: Vec::new(),
+        };
+        while parser.peek().is_some() {
+            parser.statement()?;
+        }
+        Ok(parser.triples)
+    }
+
+    /// Split a document into tokens, each with the line it starts on
+    fn tokenize(input: &str) -> Result<Vec<(Token, usize)>, TulnaError> {
+        let chars: Vec<char> = input.chars().collect();
+        let is_name_char =
+            |c: char| c.is_alphanumeric() || matches!(c, '_' | '-' | ':' | '%' | '+' | '\\');
+        let mut tokens = Vec::new();
+        let mut line = 1;
+        let mut i = 0;
+
+        while i < chars.len() {
+            let c = chars[i];
+            let start_line = line;
+            let error = |message: String| {
+                TulnaError::ParseError(format!("Turtle line {}: {}", start_line, message))
+            };
+
+            let token = match c {
+                '\n' => {
+                    line += 1;
+                    i += 1;
+                    continue;
+                }
+                _ if c.is_whitespace() => {
+                    i += 1;
+                    continue;
+                }
+                '#' => {
+                    while i < chars.len() && chars[i] != '\n' {
+                        i += 1;
+                    }
+                    continue;
+                }
+                '<' => {
+                    let length = chars[i + 1..]
+                        .iter()
+                        .position(|&c| c == '>' || c.is_whitespace())
+                        .filter(|&length| chars[i + 1 + length] == '>')
+                        .ok_or_else(|| error("unterminated IRI".to_string()))?;
+                    let iri: String = chars[i + 1..i + 1 + length].iter().collect();
+                    i += length + 2;
+                    Token::Iri(iri)
+                }
+                '"' | '\'' => {
+                    let long = chars.get(i + 1) == Some(&c) && chars.get(i + 2) == Some(&c);
+                    let quotes = if long { 3 } else { 1 };
+                    let mut end = i + quotes;
+                    let mut escaped = false;
+                    loop {
+                        let next = *chars
+                            .get(end)
+                            .ok_or_else(|| error("unterminated string".to_string()))?;
+                        if escaped {
+                            escaped = false;
+                        } else if next == '\\' {
+                            escaped = true;
+                        } else if next == '\n' && !long {
+                            return Err(error("unterminated string".to_string()));
+                        } else if next == c && (!long || chars[end..].starts_with(&[c, c, c])) {
+                            break;
+                        }
+                        if next == '\n' {
+                            line += 1;
+                        }
+                        end += 1;
+                    }
+                    let value: String = chars[i + quotes..end].iter().collect();
+                    i = end + quotes;
+                    Token::String(value)
+                }
+                '@' => {
+                    let length = chars[i + 1..]
+                        .iter()
+                        .position(|&c| !c.is_ascii_alphanumeric() && c != '-')
+                        .unwrap_or(chars.len() - i - 1);
+                    if length == 0 {
+                        return Err(error(
+                            "expected a language tag or directive after '@'".to_string(),
+                        ));
+                    }
+                    let name: String = chars[i + 1..i + 1 + length].iter().collect();
+                    i += length + 1;
+                    Token::At(name)
+                }
+                '^' => {
+                    if chars.get(i + 1) != Some(&'^') {
+                        return Err(error("expected '^^' before a datatype".to_string()));
+                    }
+                    i += 2;
+                    Token::DatatypeMarker
+                }
+                // A dot followed by a digit starts a decimal such as `.5`
+                '.' if !chars.get(i + 1).is_some_and(char::is_ascii_digit) => {
+                    i += 1;
+                    Token::Punctuation(c)
+                }
+                ';' | ',' | '[' | ']' | '(' | ')' => {
+                    i += 1;
+                    Token::Punctuation(c)
+                }
+                _ if is_name_char(c) || c == '.' => {
+                    // A name may contain dots, but not end with one, as that dot ends the
+                    // statement
+                    let mut end = i;
+                    while end < chars.len()
+                        && (is_name_char(chars[end])
+                            || chars[end] == '.'
+                                && chars.get(end + 1).is_some_and(|&c| is_name_char(c)))
+                    {
+                        end += 1;
+                    }
+                    let word: String = chars[i..end].iter().collect();
+                    i = end;
+                    if let Some(label) = word.strip_prefix("_:") {
+                        if label.is_empty() {
+                            return Err(error(
+                                "expected a blank node label after '_:'".to_string(),
+                            ));
+                        }
+                        Token::BlankNode(label.to_string())
+                    } else if let Some((prefix, local)) = word.split_once(':') {
+                        Token::PrefixedName(prefix.to_string(), local.to_string())
+                    } else {
+                        Token::Word(word)
+                    }
+                }
+                _ => return Err(error(format!("unexpected character '{}'", c))),
+            };
+            tokens.push((token, start_line));
+        }
+
+        Ok(tokens)
+    }
+
+    /// The next token, without consuming it
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position).map(|(token, _)| token)
+    }
+
+    /// Consume the next token
+    fn advance(&mut self) -> Option<Token> {
+        let token = self
+            .tokens
+            .get(self.position)
+            .map(|(token, _)| token.clone());
+        self.position += 1;
+        token
+    }
+
+    /// Whether the next token is the given punctuation
+    fn at(&self, punctuation: char) -> bool {
+        self.peek() == Some(&Token::Punctuation(punctuation))
+    }
+
+    /// A parse error at the line of the next token, or of the last one at the end
+    fn error(&self, message: impl Into<String>) -> TulnaError {
+        let line = self
+            .tokens
+            .get(self.position.min(self.tokens.len().saturating_sub(1)))
+            .map_or(1, |(_, line)| *line);
+        TulnaError::ParseError(format!("Turtle line {}: {}", line, message.into()))
+    }
+
+    /// Consume the given punctuation, or fail naming what was expected
+    fn expect(&mut self, punctuation: char) -> Result<(), TulnaError> {
+        if !self.at(punctuation) {
+            return Err(self.unexpected(&format!("'{}'", punctuation)));
+        }
+        self.position += 1;
+        Ok(())
+    }
+
+    /// An error for the next token, which is not the one that was expected
+    fn unexpected(&self, expected: &str) -> TulnaError {
+        match self.peek() {
+            Some(token) => self.error(format!("expected {}, found {:?}", expected, token)),
+            None => self.error(format!("expected {} at the end of the document", expected)),
+        }
+    }
+
+    /// Parse a directive or a set of triples ending with `.`
+    fn statement(&mut self) -> Result<(), TulnaError> {
+        match self.peek() {
+            Some(Token::At(name)) if name == "prefix" => {
+                self.position += 1;
+                self.prefix_declaration()?;
+                self.expect('.')
+            }
+            Some(Token::At(name)) if name == "base" => {
+                self.position += 1;
+                self.base_declaration()?;
+                self.expect('.')
+            }
+            Some(Token::Word(word)) if word.eq_ignore_ascii_case("prefix") => {
+                self.position += 1;
+                self.prefix_declaration()
+            }
+            Some(Token::Word(word)) if word.eq_ignore_ascii_case("base") => {
+                self.position += 1;
+                self.base_declaration()
+            }
+            _ => {
+                self.triples()?;
+                self.expect('.')
+            }
+        }
+    }
+
+    /// Parse the `ex: <iri>` of a prefix declaration
+    fn prefix_declaration(&mut self) -> Result<(), TulnaError> {
+        let prefix = match self.peek() {
+            Some(Token::PrefixedName(prefix, local)) if local.is_empty() => prefix.clone(),
+            _ => return Err(self.unexpected("a prefix name such as 'ex:'")),
+        };
+        self.position += 1;
+        let namespace = self.iri_reference()?;
+        self.prefixes.insert(prefix, namespace);
+        Ok(())
+    }
+
+    /// Parse the `<iri>` of a base declaration
+    fn base_declaration(&mut self) -> Result<(), TulnaError> {
+        self.base = Some(self.iri_reference()?);
+        Ok(())
+    }
+
+    /// Parse an `<iri>`, resolved against the base IRI
+    fn iri_reference(&mut self) -> Result<String, TulnaError> {
+        match self.peek() {
+            Some(Token::Iri(iri)) => {
+                let iri = self.resolve(iri);
+                self.position += 1;
+                Ok(iri)
+            }
+            _ => Err(self.unexpected("an IRI")),
+        }
+    }
+
+    /// Resolve a relative IRI against the base IRI, if one was declared
+    fn resolve(&self, iri: &str) -> String {
+        match &self.base {
+            Some(base) => resolve_iri(base, iri),
+            None => iri.to_string(),
+        }
+    }
+
+    /// Parse an `<iri>` or a prefixed name with a declared prefix, if one comes next
+    fn iri(&mut self) -> Result<Option<TripleNode>, TulnaError> {
+        let iri = match self.peek() {
+            Some(Token::Iri(iri)) => self.resolve(iri),
+            Some(Token::PrefixedName(prefix, local)) => match self.prefixes.get(prefix) {
+                Some(namespace) => format!("{}{}", namespace, local),
+                None => return Err(self.error(format!("undeclared prefix '{}:'", prefix))),
+            },
+            _ => return Ok(None),
+        };
+        self.position += 1;
+        Ok(Some(TripleNode::IRI(iri)))
+    }
+
+    /// Parse a subject and its predicate-object list, or a blank node property list with an
+    /// optional predicate-object list
+    fn triples(&mut self) -> Result<(), TulnaError> {
+        if self.at('[') {
+            let subject = self.blank_node_property_list()?;
+            if !self.at('.') {
+                self.predicate_object_list(&subject)?;
+            }
+            return Ok(());
+        }
+
+        let subject = match self.iri()? {
+            Some(iri) => iri,
+            None => match self.peek() {
+                Some(Token::BlankNode(label)) => {
+                    let node = TripleNode::BlankNode(label.clone());
+                    self.position += 1;
+                    node
+                }
+                Some(Token::Punctuation('(')) => self.collection()?,
+                _ => return Err(self.unexpected("a subject")),
+            },
+        };
+        self.predicate_object_list(&subject)
+    }
+
+    /// Parse predicates with their objects, separated by `;`
+    fn predicate_object_list(&mut self, subject: &TripleNode) -> Result<(), TulnaError> {
+        loop {
+            let predicate = self.verb()?;
+            self.object_list(subject, &predicate)?;
+
+            if !self.at(';') {
+                return Ok(());
+            }
+            while self.at(';') {
+                self.position += 1;
+            }
+            // A `;` may also end the list
+            if matches!(
+                self.peek(),
+                None | Some(Token::Punctuation('.')) | Some(Token::Punctuation(']'))
+            ) {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Parse a predicate, which is an IRI or `a` for `rdf:type`
+    fn verb(&mut self) -> Result<TripleNode, TulnaError> {
+        if self.peek() == Some(&Token::Word("a".to_string())) {
+            self.position += 1;
+            return Ok(TripleNode::IRI(RDF_TYPE.to_string()));
+        }
+        self.iri()?.ok_or_else(|| self.unexpected("a predicate"))
+    }
+
+    /// Parse objects separated by `,`, adding a triple for each
+    fn object_list(
+        &mut self,
+        subject: &TripleNode,
+        predicate: &TripleNode,
+    ) -> Result<(), TulnaError> {
+        loop {
+            let object = self.object()?;
+            self.triples.push(Triple {
+                subject: subject.clone(),
+                predicate: predicate.clone(),
+                object,
+                graph: None,
+            });
+
+            if !self.at(',') {
+                return Ok(());
+            }
+            self.position += 1;
+        }
+    }
+
+    /// Parse an object: an IRI, a blank node, a collection or a literal
+    fn object(&mut self) -> Result<TripleNode, TulnaError> {
+        if let Some(iri) = self.iri()? {
+            return Ok(iri);
+        }
+        match self.peek() {
+            Some(Token::BlankNode(label)) => {
+                let node = TripleNode::BlankNode(label.clone());
+                self.position += 1;
+                Ok(node)
+            }
+            Some(Token::Punctuation('[')) => self.blank_node_property_list(),
+            Some(Token::Punctuation('(')) => self.collection(),
+            Some(Token::String(_)) => self.literal(),
+            // Numbers and booleans become typed literals, as in SPARQL
+            Some(Token::Word(word)) => match TripleNode::from(word.as_str()) {
+                literal @ TripleNode::TypedLiteral { .. } => {
+                    self.position += 1;
+                    Ok(literal)
+                }
+                _ => Err(self.unexpected("an object")),
+            },
+            _ => Err(self.unexpected("an object")),
+        }
+    }
+
+    /// Parse a quoted string with its optional language tag or datatype
+    ///
+    /// Language tags are case-insensitive, so they are stored in lowercase.
+    fn literal(&mut self) -> Result<TripleNode, TulnaError> {
+        let value = match self.advance() {
+            Some(Token::String(value)) => value,
+            _ => return Err(self.unexpected("a string")),
+        };
+        match self.peek() {
+            Some(Token::At(lang)) => {
+                let lang = lang.to_lowercase();
+                self.position += 1;
+                Ok(TripleNode::LangLiteral { value, lang })
+            }
+            Some(Token::DatatypeMarker) => {
+                self.position += 1;
+                match self.iri()? {
+                    Some(TripleNode::IRI(datatype)) => {
+                        Ok(TripleNode::TypedLiteral { value, datatype })
+                    }
+                    _ => Err(self.unexpected("a datatype IRI")),
+                }
+            }
+            _ => Ok(TripleNode::Literal(value)),
+        }
+    }
+
+    /// Parse the inside of `[ ]` after its opening bracket, returning its blank node
+    fn blank_node_property_list(&mut self) -> Result<TripleNode, TulnaError> {
+        self.expect('[')?;
+        let node = TripleNode::from(self.labels.next("anon"));
+        if !self.at(']') {
+            self.predicate_object_list(&node)?;
+        }
+        self.expect(']')?;
+        Ok(node)
+    }
+
+    /// Parse a collection, linking its items with `rdf:first` and `rdf:rest`
+    ///
+    /// Returns the first cell of the list, or `rdf:nil` for an empty collection.
+    fn collection(&mut self) -> Result<TripleNode, TulnaError> {
+        self.expect('(')?;
+        let mut items = Vec::new();
+        while !self.at(')') {
+            if self.peek().is_none() {
+                return Err(self.unexpected("')'"));
+            }
+            items.push(self.object()?);
+        }
+        self.position += 1;
+
+        let cells: Vec<TripleNode> = items
+            .iter()
+            .map(|_| TripleNode::from(self.labels.next("list")))
+            .collect();
+        let link = |subject: &TripleNode, predicate: &str, object: TripleNode| Triple {
+            subject: subject.clone(),
+            predicate: TripleNode::IRI(predicate.to_string()),
+            object,
+            graph: None,
+        };
+        for (index, (cell, item)) in cells.iter().zip(items).enumerate() {
+            let rest = cells
+                .get(index + 1)
+                .cloned()
+                .unwrap_or_else(|| TripleNode::IRI(RDF_NIL.to_string()));
+            self.triples.push(link(cell, RDF_FIRST, item));
+            self.triples.push(link(cell, RDF_REST, rest));
+        }
+
+        Ok(cells
+            .into_iter()
+            .next()
+            .unwrap_or_else(|| TripleNode::IRI(RDF_NIL.to_string())))
+    }
+}
@@ -1,30 +1,14 @@
-use tulna_rs::graph::{GraphIsomorphism, Triple, TripleNode};
-
-fn make_triple(s: &str, p: &str, o: &str) -> Triple {
-    Triple {
-        subject: if s.starts_with('?') {
-            TripleNode::Variable(s.to_string())
-        } else {
-            TripleNode::IRI(s.to_string())
-        },
-        predicate: TripleNode::IRI(p.to_string()),
-        object: if o.starts_with('?') {
-            TripleNode::Variable(o.to_string())
-        } else if o.starts_with('"') {
-            TripleNode::Literal(o.trim_matches('"').to_string())
-        } else {
-            TripleNode::IRI(o.to_string())
-        },
-    }
-}
+use std::time::{Duration, Instant};
+use tulna_rs::graph::{GraphIsomorphism, IsoResult, Triple};
+use tulna_rs::TulnaError;
 
 #[test]
 fn test_coverage_mismatch_non_blank_count() {
     // Branch: if index_a.len() != index_b.len()
-    let graph1 = vec![make_triple("http://a", "http://p", "http://b")];
+    let graph1 = vec![Triple::new("http://a", "http://p", "http://b")];
     let graph2 = vec![
-        make_triple("http://a", "http://p", "http://b"),
-        make_triple("http://a", "http://p", "http://c"),
+        Triple::new("http://a", "http://p", "http://b"),
+        Triple::new("http://a", "http://p", "http://c"),
     ];
 
     assert!(!GraphIsomorphism::are_isomorphic(&graph1, &graph2).unwrap());
@@ -33,8 +17,8 @@ fn test_coverage_mismatch_non_blank_count() {
 #[test]
 fn test_coverage_mismatch_non_blank_content() {
     // Branch: if !index_b.contains_key(key)
-    let graph1 = vec![make_triple("http://a", "http://p", "http://b")];
-    let graph2 = vec![make_triple("http://a", "http://p", "http://c")];
+    let graph1 = vec![Triple::new("http://a", "http://p", "http://b")];
+    let graph2 = vec![Triple::new("http://a", "http://p", "http://c")];
 
     assert!(!GraphIsomorphism::are_isomorphic(&graph1, &graph2).unwrap());
 }
@@ -42,10 +26,10 @@ fn test_coverage_mismatch_non_blank_content() {
 #[test]
 fn test_coverage_mismatch_blank_node_count() {
     // Branch: if blank_nodes_a.len() != blank_nodes_b.len()
-    let graph1 = vec![make_triple("?x", "http://p", "http://a")];
+    let graph1 = vec![Triple::new("?x", "http://p", "http://a")];
     let graph2 = vec![
-        make_triple("?x", "http://p", "http://a"),
-        make_triple("?y", "http://p", "http://b"), // Extra blank node ?y
+        Triple::new("?x", "http://p", "http://a"),
+        Triple::new("?y", "http://p", "http://b"), // Extra blank node ?y
     ];
 
     assert!(!GraphIsomorphism::are_isomorphic(&graph1, &graph2).unwrap());
@@ -56,15 +40,15 @@ fn test_coverage_symmetric_cycle_speculation() {
     // A 3-cycle: A->B->C->A
     // Requires speculation because all nodes look identical (1 incoming, 1 outgoing, same predicates)
     let graph1 = vec![
-        make_triple("?a", "http://next", "?b"),
-        make_triple("?b", "http://next", "?c"),
-        make_triple("?c", "http://next", "?a"),
+        Triple::new("?a", "http://next", "?b"),
+        Triple::new("?b", "http://next", "?c"),
+        Triple::new("?c", "http://next", "?a"),
     ];
 
     let graph2 = vec![
-        make_triple("?x", "http://next", "?y"),
-        make_triple("?y", "http://next", "?z"),
-        make_triple("?z", "http://next", "?x"),
+        Triple::new("?x", "http://next", "?y"),
+        Triple::new("?y", "http://next", "?z"),
+        Triple::new("?z", "http://next", "?x"),
     ];
 
     assert!(GraphIsomorphism::are_isomorphic(&graph1, &graph2).unwrap());
@@ -78,23 +62,23 @@ fn test_coverage_false_positive_regular_graphs() {
     // Pure color refinement might fail to distinguish them without recursion.
 
     let graph1 = vec![
-        make_triple("?1", "http://next", "?2"),
-        make_triple("?2", "http://next", "?3"),
-        make_triple("?3", "http://next", "?4"),
-        make_triple("?4", "http://next", "?5"),
-        make_triple("?5", "http://next", "?6"),
-        make_triple("?6", "http://next", "?1"),
+        Triple::new("?1", "http://next", "?2"),
+        Triple::new("?2", "http://next", "?3"),
+        Triple::new("?3", "http://next", "?4"),
+        Triple::new("?4", "http://next", "?5"),
+        Triple::new("?5", "http://next", "?6"),
+        Triple::new("?6", "http://next", "?1"),
     ];
 
     let graph2 = vec![
         // First 3-cycle
-        make_triple("?a", "http://next", "?b"),
-        make_triple("?b", "http://next", "?c"),
-        make_triple("?c", "http://next", "?a"),
+        Triple::new("?a", "http://next", "?b"),
+        Triple::new("?b", "http://next", "?c"),
+        Triple::new("?c", "http://next", "?a"),
         // Second 3-cycle
-        make_triple("?x", "http://next", "?y"),
-        make_triple("?y", "http://next", "?z"),
-        make_triple("?z", "http://next", "?x"),
+        Triple::new("?x", "http://next", "?y"),
+        Triple::new("?y", "http://next", "?z"),
+        Triple::new("?z", "http://next", "?x"),
     ];
 
     // These should NOT be isomorphic
@@ -111,9 +95,9 @@ fn test_coverage_grounded_hash_mismatch() {
     // because the speculation will try to map a node from 6-cycle to 3-cycle,
     // propagate constraints, and eventually realize the hashes don't match up or bijection isn't total.
 
-    let graph1 = vec![make_triple("?a", "http://p", "?b")];
+    let graph1 = vec![Triple::new("?a", "http://p", "?b")];
     let graph2 = vec![
-        make_triple("?x", "http://p", "?x"), // Self-loop
+        Triple::new("?x", "http://p", "?x"), // Self-loop
     ];
 
     // Initial signatures:
@@ -138,11 +122,268 @@ fn test_full_recursion_depth() {
     for i in 0..4 {
         for j in 0..4 {
             if i != j {
-                clique1.push(make_triple(nodes1[i], "http://edge", nodes1[j]));
-                clique2.push(make_triple(nodes2[i], "http://edge", nodes2[j]));
+                clique1.push(Triple::new(nodes1[i], "http://edge", nodes1[j]));
+                clique2.push(Triple::new(nodes2[i], "http://edge", nodes2[j]));
             }
         }
     }
 
     assert!(GraphIsomorphism::are_isomorphic(&clique1, &clique2).unwrap());
 }
+
+#[test]
+fn test_classify_isomorphic() {
+    let graph1 = vec![Triple::new("?x", "http://p", "?y")];
+    let graph2 = vec![Triple::new("?a", "http://p", "?b")];
+
+    assert_eq!(
+        GraphIsomorphism::classify(&graph1, &graph2).unwrap(),
+        IsoResult::Isomorphic
+    );
+}
+
+#[test]
+fn test_classify_different_size() {
+    let graph1 = vec![Triple::new("http://a", "http://p", "http://b")];
+    let graph2 = vec![
+        Triple::new("http://a", "http://p", "http://b"),
+        Triple::new("http://a", "http://p", "http://c"),
+    ];
+
+    assert_eq!(
+        GraphIsomorphism::classify(&graph1, &graph2).unwrap(),
+        IsoResult::DifferentSize
+    );
+}
+
+#[test]
+fn test_classify_different_ground_triples() {
+    let graph1 = vec![Triple::new("http://a", "http://p", "http://b")];
+    let graph2 = vec![Triple::new("http://a", "http://p", "http://c")];
+
+    assert_eq!(
+        GraphIsomorphism::classify(&graph1, &graph2).unwrap(),
+        IsoResult::DifferentGroundTriples
+    );
+}
+
+#[test]
+fn test_classify_different_blank_count() {
+    let graph1 = vec![Triple::new("?x", "http://p", "?x")];
+    let graph2 = vec![Triple::new("?x", "http://p", "?y")];
+
+    assert_eq!(
+        GraphIsomorphism::classify(&graph1, &graph2).unwrap(),
+        IsoResult::DifferentBlankCount
+    );
+}
+
+#[test]
+fn test_classify_no_bijection() {
+    // Same size, ground triples and blank node count, but a 6-cycle is not two 3-cycles
+    let graph1 = vec![
+        Triple::new("?1", "http://next", "?2"),
+        Triple::new("?2", "http://next", "?3"),
+        Triple::new("?3", "http://next", "?4"),
+        Triple::new("?4", "http://next", "?5"),
+        Triple::new("?5", "http://next", "?6"),
+        Triple::new("?6", "http://next", "?1"),
+    ];
+
+    let graph2 = vec![
+        Triple::new("?a", "http://next", "?b"),
+        Triple::new("?b", "http://next", "?c"),
+        Triple::new("?c", "http://next", "?a"),
+        Triple::new("?x", "http://next", "?y"),
+        Triple::new("?y", "http://next", "?z"),
+        Triple::new("?z", "http://next", "?x"),
+    ];
+
+    assert_eq!(
+        GraphIsomorphism::classify(&graph1, &graph2).unwrap(),
+        IsoResult::NoBijection
+    );
+}
+
+/// A directed cycle `?{prefix}0 -> ?{prefix}1 -> ... -> ?{prefix}0` over `len` variables
+fn make_cycle(prefix: &str, len: usize) -> Vec<Triple> {
+    (0..len)
+        .map(|i| {
+            Triple::new(
+                format!("?{}{}", prefix, i),
+                "http://next",
+                format!("?{}{}", prefix, (i + 1) % len),
+            )
+        })
+        .collect()
+}
+
+fn make_path(prefix: &str, len: usize) -> Vec<Triple> {
+    (1..len)
+        .map(|i| {
+            Triple::new(
+                format!("?{}{}", prefix, i - 1),
+                "http://next",
+                format!("?{}{}", prefix, i),
+            )
+        })
+        .collect()
+}
+
+#[test]
+fn test_speculation_limit_exceeded() {
+    // A 12-cycle and two 6-cycles can only be told apart by speculating
+    let graph1 = make_cycle("a", 12);
+    let mut graph2 = make_cycle("b", 6);
+    graph2.extend(make_cycle("c", 6));
+
+    assert!(matches!(
+        GraphIsomorphism::are_isomorphic_with_limit(&graph1, &graph2, 1),
+        Err(TulnaError::LimitExceeded(_))
+    ));
+    assert!(!GraphIsomorphism::are_isomorphic(&graph1, &graph2).unwrap());
+
+    let renamed = make_cycle("d", 12);
+    assert!(matches!(
+        GraphIsomorphism::are_isomorphic_with_limit(&graph1, &renamed, 0),
+        Err(TulnaError::LimitExceeded(_))
+    ));
+    assert!(GraphIsomorphism::are_isomorphic_with_limit(&graph1, &renamed, 1).unwrap());
+}
+
+#[test]
+fn test_speculation_limit_applied_by_default() {
+    // Two 12-node graphs where every node has 5 neighbours: grounding one node by
+    // speculation never grounds the rest, so the search space grows factorially
+    let link = |prefix: &str, i: usize, j: usize| {
+        Triple::new(
+            format!("?{}{}", prefix, i),
+            "http://link",
+            format!("?{}{}", prefix, j),
+        )
+    };
+
+    // Two disjoint complete graphs on 6 nodes
+    let mut graph1 = Vec::new();
+    for offset in [0, 6] {
+        for i in 0..6 {
+            for j in (0..6).filter(|&j| j != i) {
+                graph1.push(link("a", offset + i, offset + j));
+            }
+        }
+    }
+
+    // A 12-node ring where every node links to the nodes 1 and 2 steps away and the opposite node
+    let mut graph2 = Vec::new();
+    for i in 0..12 {
+        for step in [1, 2, 6, 10, 11] {
+            graph2.push(link("b", i, (i + step) % 12));
+        }
+    }
+
+    assert!(matches!(
+        GraphIsomorphism::are_isomorphic(&graph1, &graph2),
+        Err(TulnaError::LimitExceeded(_))
+    ));
+    assert!(GraphIsomorphism::are_isomorphic(&graph2, &graph2).unwrap());
+}
+
+#[test]
+fn test_canonical_form_limit_applied_by_default() {
+    // Nine disjoint edges can only be ordered by grounding them one at a time
+    let graph: Vec<Triple> = (0..9)
+        .map(|i| Triple::new(format!("?s{}", i), "http://link", format!("?o{}", i)))
+        .collect();
+
+    let start = Instant::now();
+    assert!(matches!(
+        GraphIsomorphism::canonical_form(&graph),
+        Err(TulnaError::LimitExceeded(_))
+    ));
+    assert!(start.elapsed() < Duration::from_secs(5));
+
+    assert!(matches!(
+        GraphIsomorphism::canonical_form_with_limit(&graph[..3], 1),
+        Err(TulnaError::LimitExceeded(_))
+    ));
+    assert_eq!(
+        GraphIsomorphism::canonical_form_with_limit(&graph[..3], 100).unwrap(),
+        GraphIsomorphism::canonical_form(&graph[..3]).unwrap()
+    );
+}
+
+#[test]
+fn test_timeout_exceeded() {
+    // Two disjoint 5-regular graphs on 6 nodes against a 5-regular ring on 12 nodes, which
+    // the speculative search cannot tell apart without a factorial number of groundings
+    let link = |prefix: &str, i: usize, j: usize| {
+        Triple::new(
+            format!("?{}{}", prefix, i),
+            "http://link",
+            format!("?{}{}", prefix, j),
+        )
+    };
+
+    let mut graph1 = Vec::new();
+    for offset in [0, 6] {
+        for i in 0..6 {
+            for j in (0..6).filter(|&j| j != i) {
+                graph1.push(link("a", offset + i, offset + j));
+            }
+        }
+    }
+
+    let mut graph2 = Vec::new();
+    for i in 0..12 {
+        for step in [1, 2, 6, 10, 11] {
+            graph2.push(link("b", i, (i + step) % 12));
+        }
+    }
+
+    assert!(matches!(
+        GraphIsomorphism::are_isomorphic_timeout(&graph1, &graph2, Duration::from_millis(1)),
+        Err(TulnaError::Timeout(_))
+    ));
+}
+
+#[test]
+fn test_timeout_not_exceeded() {
+    // Without a speculation limit the cycles are told apart well within the timeout
+    let graph1 = make_cycle("a", 12);
+    let mut graph2 = make_cycle("b", 6);
+    graph2.extend(make_cycle("c", 6));
+    let timeout = Duration::from_secs(60);
+
+    assert!(!GraphIsomorphism::are_isomorphic_timeout(&graph1, &graph2, timeout).unwrap());
+    assert!(
+        GraphIsomorphism::are_isomorphic_timeout(&graph1, &make_cycle("d", 12), timeout).unwrap()
+    );
+}
+
+#[test]
+fn test_path_comparison_scales_near_linearly() {
+    // A path is grounded from its two ends inwards, two nodes per iteration, so rehashing
+    // every node in each iteration would make the comparison quadratic or worse
+    let fastest = |len: usize| {
+        let graph1 = make_path("a", len);
+        let mut graph2 = make_path("b", len);
+        graph2.reverse();
+        (0..5)
+            .map(|_| {
+                let start = Instant::now();
+                assert!(GraphIsomorphism::are_isomorphic(&graph1, &graph2).unwrap());
+                start.elapsed()
+            })
+            .min()
+            .unwrap()
+    };
+
+    let small = fastest(100);
+    let large = fastest(1000);
+    assert!(
+        large < small * 30,
+        "1000 nodes took {:?}, 100 nodes {:?}",
+        large,
+        small
+    );
+}
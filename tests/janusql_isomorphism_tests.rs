@@ -1,4 +1,7 @@
+use tulna_rs::graph::TripleNode;
 use tulna_rs::isomorphism::api::QueryIsomorphismAPI;
+use tulna_rs::query::WindowType;
+use tulna_rs::TulnaError;
 
 #[test]
 fn test_simple_janusql_live_window_isomorphism() {
@@ -326,10 +329,6 @@ WHERE {
     assert!(!result.unwrap());
 }
 
-// Note: BGP extraction with prefixed predicates needs improvement
-// Currently, prefixed predicates in JanusQL WHERE clauses may not be properly expanded
-// This test is commented out until prefix handling in BGP extraction is fixed
-/*
 #[test]
 fn test_janusql_not_isomorphic_different_bgp() {
     let query1 = r#"
@@ -356,7 +355,6 @@ WHERE {
     assert!(result.is_ok());
     assert!(!result.unwrap());
 }
-*/
 
 #[test]
 fn test_janusql_detect_language() {
@@ -527,3 +525,417 @@ WHERE {
     assert!(result.is_ok());
     assert!(result.unwrap());
 }
+
+#[test]
+fn test_janusql_registered_vs_unregistered_not_isomorphic() {
+    let query1 = r#"
+PREFIX ex: <http://example.org/>
+REGISTER RStream ex:output AS
+SELECT ?sensor ?value
+FROM NAMED WINDOW ex:w ON STREAM ex:sensors [START 1000 END 2000]
+WHERE {
+    WINDOW ex:w { ?sensor ex:hasValue ?value . }
+}
+"#;
+
+    let query2 = r#"
+PREFIX ex: <http://example.org/>
+SELECT ?s ?v
+FROM NAMED WINDOW ex:w ON STREAM ex:sensors [START 1000 END 2000]
+WHERE {
+    WINDOW ex:w { ?s ex:hasValue ?v . }
+}
+"#;
+
+    let result = QueryIsomorphismAPI::is_isomorphic(query1, query2);
+    assert!(result.is_ok());
+    assert!(!result.unwrap());
+}
+
+#[test]
+fn test_janusql_decimal_window_range_rejected() {
+    let query1 = r#"
+PREFIX ex: <http://example.org/>
+REGISTER RStream ex:output AS
+SELECT ?s ?o
+FROM NAMED WINDOW ex:w ON STREAM ex:stream [OFFSET 0 RANGE 1.5 STEP 10]
+WHERE {
+    WINDOW ex:w { ?s ex:p ?o . }
+}
+"#;
+
+    let query2 = r#"
+PREFIX ex: <http://example.org/>
+REGISTER RStream ex:output AS
+SELECT ?s ?o
+FROM NAMED WINDOW ex:w ON STREAM ex:stream [OFFSET 0 RANGE 100 STEP 10]
+WHERE {
+    WINDOW ex:w { ?s ex:p ?o . }
+}
+"#;
+
+    match QueryIsomorphismAPI::is_isomorphic(query1, query2) {
+        Err(TulnaError::ParseError(message)) => assert!(message.contains("1.5")),
+        other => panic!("Expected a parse error, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_janusql_prefix_declaration_case_and_whitespace() {
+    let expanded = r#"
+REGISTER RStream <output> AS
+SELECT ?s ?o
+FROM NAMED WINDOW <http://example.org/w> ON STREAM <http://example.org/stream> [OFFSET 0 RANGE 100 STEP 10]
+WHERE {
+    WINDOW <http://example.org/w> { ?s <http://example.org/p> ?o . }
+}
+"#;
+
+    for declaration in [
+        "PREFIX ex: <http://example.org/>",
+        "prefix ex: <http://example.org/>",
+        "PREFIX ex:<http://example.org/>",
+        "Prefix  ex:   <http://example.org/>",
+    ] {
+        let prefixed = format!(
+            r#"
+{}
+REGISTER RStream <output> AS
+SELECT ?s ?o
+FROM NAMED WINDOW ex:w ON STREAM ex:stream [OFFSET 0 RANGE 100 STEP 10]
+WHERE {{
+    WINDOW ex:w {{ ?s <http://example.org/p> ?o . }}
+}}
+"#,
+            declaration
+        );
+
+        assert!(
+            QueryIsomorphismAPI::is_isomorphic(&prefixed, expanded).unwrap(),
+            "Prefix not registered with '{}'",
+            declaration
+        );
+    }
+}
+
+#[test]
+fn test_janusql_prefixed_and_full_stream_iris_isomorphic() {
+    let query1 = r#"
+PREFIX ex: <http://example.org/>
+REGISTER RStream <output> AS
+SELECT ?s ?o
+FROM NAMED WINDOW ex:w ON STREAM ex:s [OFFSET 0 RANGE 100 STEP 10]
+WHERE {
+    WINDOW ex:w { ?s <http://example.org/p> ?o . }
+}
+"#;
+
+    let query2 = r#"
+REGISTER RStream <output> AS
+SELECT ?x ?y
+FROM NAMED WINDOW <http://example.org/w> ON STREAM <http://example.org/s> [OFFSET 0 RANGE 100 STEP 10]
+WHERE {
+    WINDOW <http://example.org/w> { ?x <http://example.org/p> ?y . }
+}
+"#;
+
+    let result = QueryIsomorphismAPI::is_isomorphic(query1, query2);
+    assert!(result.is_ok());
+    assert!(result.unwrap());
+}
+
+#[test]
+fn test_janusql_undeclared_prefix_kept_verbatim() {
+    // Without a PREFIX declaration the stream name stays `ex:s`, which is not the full IRI
+    let undeclared = r#"
+REGISTER RStream <output> AS
+SELECT ?s ?o
+FROM NAMED WINDOW <http://example.org/w> ON STREAM ex:s [OFFSET 0 RANGE 100 STEP 10]
+WHERE {
+    WINDOW <http://example.org/w> { ?s <http://example.org/p> ?o . }
+}
+"#;
+
+    let full = r#"
+REGISTER RStream <output> AS
+SELECT ?s ?o
+FROM NAMED WINDOW <http://example.org/w> ON STREAM <http://example.org/s> [OFFSET 0 RANGE 100 STEP 10]
+WHERE {
+    WINDOW <http://example.org/w> { ?s <http://example.org/p> ?o . }
+}
+"#;
+
+    let other_undeclared = undeclared.replace("ex:s", "other:s");
+
+    assert!(!QueryIsomorphismAPI::is_isomorphic(undeclared, full).unwrap());
+    assert!(!QueryIsomorphismAPI::is_isomorphic(undeclared, &other_undeclared).unwrap());
+}
+
+#[test]
+fn test_janusql_multiple_windows_order_independent() {
+    let query1 = r#"
+PREFIX ex: <http://example.org/>
+REGISTER RStream <output> AS
+SELECT ?s ?o
+FROM NAMED WINDOW ex:live ON STREAM ex:stream1 [RANGE 10 STEP 5]
+FROM NAMED WINDOW ex:hist ON STREAM ex:stream2 [OFFSET 0 RANGE 100 STEP 10]
+WHERE {
+    WINDOW ex:live { ?s <http://example.org/p> ?o . }
+    WINDOW ex:hist { ?o <http://example.org/q> ?s . }
+}
+"#;
+
+    let query2 = r#"
+PREFIX ex: <http://example.org/>
+REGISTER RStream <output> AS
+SELECT ?s ?o
+FROM NAMED WINDOW ex:hist ON STREAM ex:stream2 [OFFSET 0 RANGE 100 STEP 10]
+FROM NAMED WINDOW ex:live ON STREAM ex:stream1 [RANGE 10 STEP 5]
+WHERE {
+    WINDOW ex:live { ?s <http://example.org/p> ?o . }
+    WINDOW ex:hist { ?o <http://example.org/q> ?s . }
+}
+"#;
+
+    assert!(QueryIsomorphismAPI::is_isomorphic(query1, query2).unwrap());
+    assert!(QueryIsomorphismAPI::check_stream_parameters(query1, query2).unwrap());
+}
+
+#[test]
+fn test_janusql_different_second_window_not_isomorphic() {
+    let query1 = r#"
+PREFIX ex: <http://example.org/>
+REGISTER RStream <output> AS
+SELECT ?s ?o
+FROM NAMED WINDOW ex:live ON STREAM ex:stream1 [RANGE 10 STEP 5]
+FROM NAMED WINDOW ex:hist ON STREAM ex:stream2 [OFFSET 0 RANGE 100 STEP 10]
+WHERE {
+    WINDOW ex:live { ?s <http://example.org/p> ?o . }
+    WINDOW ex:hist { ?o <http://example.org/q> ?s . }
+}
+"#;
+
+    let query2 = r#"
+PREFIX ex: <http://example.org/>
+REGISTER RStream <output> AS
+SELECT ?s ?o
+FROM NAMED WINDOW ex:live ON STREAM ex:stream1 [RANGE 10 STEP 5]
+FROM NAMED WINDOW ex:hist ON STREAM ex:stream2 [OFFSET 50 RANGE 100 STEP 10]
+WHERE {
+    WINDOW ex:live { ?s <http://example.org/p> ?o . }
+    WINDOW ex:hist { ?o <http://example.org/q> ?s . }
+}
+"#;
+
+    assert!(!QueryIsomorphismAPI::is_isomorphic(query1, query2).unwrap());
+}
+
+#[test]
+fn test_janusql_filter_bounds_compared() {
+    let query1 = r#"
+PREFIX ex: <http://example.org/>
+REGISTER RStream <output> AS
+SELECT ?s ?o
+FROM NAMED WINDOW ex:w ON STREAM ex:s [OFFSET 0 RANGE 100 STEP 10]
+WHERE {
+    WINDOW ex:w { ?s <http://example.org/value> ?o . FILTER(?o > 5) }
+}
+"#;
+
+    let renamed = query1.replace("?s", "?sensor").replace("?o", "?value");
+    let other_bound = query1.replace("?o > 5", "?o > 10");
+    let no_filter = query1.replace("FILTER(?o > 5)", "");
+
+    assert!(QueryIsomorphismAPI::is_isomorphic(query1, &renamed).unwrap());
+    assert!(!QueryIsomorphismAPI::is_isomorphic(query1, &other_bound).unwrap());
+    assert!(!QueryIsomorphismAPI::is_isomorphic(query1, &no_filter).unwrap());
+}
+
+#[test]
+fn test_janusql_rstream_and_dstream_not_isomorphic() {
+    let query1 = r#"
+PREFIX ex: <http://example.org/>
+REGISTER RStream ex:output AS
+SELECT ?s ?o
+FROM NAMED WINDOW ex:w ON STREAM ex:stream [START 1000 END 2000]
+WHERE {
+    WINDOW ex:w { ?s ex:p ?o . }
+}
+"#;
+
+    let query2 = query1.replace("RStream", "DStream");
+
+    assert!(!QueryIsomorphismAPI::is_isomorphic(query1, &query2).unwrap());
+    assert!(QueryIsomorphismAPI::is_isomorphic(&query2, &query2).unwrap());
+}
+
+#[test]
+fn test_janusql_window_type() {
+    let query = |windows: &[&str]| {
+        let declarations: Vec<String> = windows
+            .iter()
+            .enumerate()
+            .map(|(i, window)| format!("FROM NAMED WINDOW ex:w{} ON STREAM ex:s {}", i, window))
+            .collect();
+        format!(
+            "PREFIX ex: <http://example.org/>\nREGISTER RStream <output> AS\nSELECT ?s\n{}\nWHERE {{ WINDOW ex:w0 {{ ?s ex:p ?o . }} }}",
+            declarations.join("\n")
+        )
+    };
+    let window_type = |windows: &[&str]| {
+        QueryIsomorphismAPI::parse_query(&query(windows))
+            .unwrap()
+            .window_type()
+    };
+
+    assert_eq!(window_type(&["[RANGE 10 STEP 5]"]), Some(WindowType::Live));
+    assert_eq!(
+        window_type(&["[OFFSET 0 RANGE 100 STEP 10]"]),
+        Some(WindowType::HistoricalSliding)
+    );
+    assert_eq!(
+        window_type(&["[START 1000 END 2000]"]),
+        Some(WindowType::HistoricalFixed)
+    );
+
+    let mixed =
+        QueryIsomorphismAPI::parse_query(&query(&["[RANGE 10 STEP 5]", "[START 1000 END 2000]"]))
+            .unwrap();
+    assert_eq!(mixed.window_type(), None);
+    let mut types: Vec<WindowType> = mixed.windows.iter().map(|w| w.window_type).collect();
+    types.sort();
+    assert_eq!(types, [WindowType::Live, WindowType::HistoricalFixed]);
+
+    let sparql = QueryIsomorphismAPI::parse_query("SELECT ?s WHERE { ?s ?p ?o }").unwrap();
+    assert_eq!(sparql.window_type(), None);
+}
+
+#[test]
+fn test_janusql_iso_datetime_windows() {
+    let query = |window: &str| {
+        format!(
+            r#"
+PREFIX ex: <http://example.org/>
+SELECT ?sensor ?value
+FROM NAMED WINDOW ex:w ON STREAM ex:sensors {}
+WHERE {{
+    WINDOW ex:w {{ ?sensor ex:hasValue ?value . }}
+}}
+"#,
+            window
+        )
+    };
+    let epoch_fixed = query("[START 1609459200000 END 1609462800000]");
+    let iso_fixed = query(r#"[START "2021-01-01T00:00:00Z" END "2021-01-01T02:00:00.000+01:00"]"#);
+    let epoch_sliding = query("[OFFSET 1609459200500 RANGE 100 STEP 10]");
+    let iso_sliding = query(r#"[OFFSET "2021-01-01T00:00:00.5Z" RANGE 100 STEP 10]"#);
+
+    assert!(QueryIsomorphismAPI::is_isomorphic(&epoch_fixed, &iso_fixed).unwrap());
+    assert!(QueryIsomorphismAPI::is_isomorphic(&epoch_sliding, &iso_sliding).unwrap());
+
+    let parsed = QueryIsomorphismAPI::parse_query(&iso_fixed).unwrap();
+    assert_eq!(parsed.windows[0].start, Some(1609459200000));
+    assert_eq!(parsed.windows[0].end, Some(1609462800000));
+
+    let later = query(r#"[START "2021-01-01T00:00:01Z" END "2021-01-01T01:00:00Z"]"#);
+    assert!(!QueryIsomorphismAPI::is_isomorphic(&epoch_fixed, &later).unwrap());
+
+    let invalid = query(r#"[START "2021-02-30T00:00:00Z" END "2021-03-01T00:00:00Z"]"#);
+    assert!(matches!(
+        QueryIsomorphismAPI::parse_query(&invalid),
+        Err(TulnaError::ParseError(_))
+    ));
+}
+
+#[test]
+fn test_janusql_single_line_query() {
+    let multi_line = r#"
+PREFIX ex: <http://example.org/>
+REGISTER RStream <output> AS
+SELECT ?s ?o
+FROM NAMED WINDOW ex:live ON STREAM ex:stream1 [RANGE 10 STEP 5]
+FROM NAMED WINDOW ex:hist ON STREAM ex:stream2 [OFFSET 0 RANGE 100 STEP 10]
+WHERE {
+    WINDOW ex:live { ?s ex:p ?o . }
+    WINDOW ex:hist { ?o ex:q ?s . }
+}
+"#;
+    let single_line = "PREFIX ex: <http://example.org/> REGISTER RStream <output> AS SELECT ?a ?b FROM NAMED WINDOW ex:live ON STREAM ex:stream1 [RANGE 10 STEP 5] FROM NAMED WINDOW ex:hist ON STREAM ex:stream2 [OFFSET 0 RANGE 100 STEP 10] WHERE { WINDOW ex:live { ?a ex:p ?b . } WINDOW ex:hist { ?b ex:q ?a . } }";
+
+    let parsed = QueryIsomorphismAPI::parse_query(single_line).unwrap();
+    assert_eq!(parsed.windows.len(), 2);
+    assert_eq!(parsed.bgp.len(), 2);
+    assert_eq!(parsed.projection, ["?a", "?b"]);
+    assert!(QueryIsomorphismAPI::is_isomorphic(multi_line, single_line).unwrap());
+
+    let other_stream = single_line.replace("ex:stream2", "ex:stream3");
+    assert!(!QueryIsomorphismAPI::is_isomorphic(multi_line, &other_stream).unwrap());
+}
+
+#[test]
+fn test_janusql_historical_queries_filter_timestamps() {
+    let query = r#"
+PREFIX ex: <http://example.org/>
+SELECT ?sensor ?value
+FROM NAMED WINDOW ex:fixed ON STREAM ex:sensors [START 1000 END 2000]
+FROM NAMED WINDOW ex:sliding ON STREAM ex:sensors [OFFSET 500 RANGE 100 STEP 10]
+WHERE {
+    WINDOW ex:fixed { ?sensor ex:label "a } b" . }
+    { ?sensor ex:hasValue ?value . }
+}
+LIMIT 5
+"#;
+
+    let parsed = QueryIsomorphismAPI::parse_janusql(query).unwrap();
+    assert_eq!(parsed.sparql_queries.len(), 2);
+
+    let fixed = &parsed.sparql_queries[0];
+    let filter = "FILTER(?timestamp >= 1000 && ?timestamp <= 2000)";
+    assert!(fixed.contains(filter), "{}", fixed);
+    // The filter closes the WHERE group, after the nested groups and before LIMIT
+    let filter_at = fixed.find(filter).unwrap();
+    assert!(filter_at > fixed.find("?sensor ex:hasValue ?value").unwrap());
+    assert!(fixed[filter_at..]
+        .trim_start_matches(filter)
+        .trim_start()
+        .starts_with('}'));
+    assert!(fixed.trim_end().ends_with("LIMIT 5"));
+
+    let sliding = &parsed.sparql_queries[1];
+    assert!(sliding.contains("FILTER(?timestamp >= 500)"), "{}", sliding);
+    assert!(!sliding.contains("?timestamp <="));
+}
+
+#[test]
+fn test_janusql_relative_iris_resolved_against_base() {
+    let query = |base: &str| {
+        format!(
+            r#"
+BASE <{}>
+PREFIX ex: <vocab#>
+REGISTER RStream <output> AS
+SELECT ?s
+FROM NAMED WINDOW <w> ON STREAM <stream> [RANGE 10 STEP 5]
+WHERE {{
+    WINDOW <w> {{ ?s <p> <people/bob> . ?s ex:name ?name . }}
+}}
+"#,
+            base
+        )
+    };
+
+    let parsed = QueryIsomorphismAPI::parse_query(&query("http://a.org/")).unwrap();
+    let name = TripleNode::IRI("http://a.org/vocab#name".to_string());
+    let bob = TripleNode::IRI("http://a.org/people/bob".to_string());
+    assert!(parsed.bgp.iter().any(|triple| triple.predicate == name));
+    assert!(parsed.bgp.iter().any(|triple| triple.object == bob));
+
+    assert!(
+        QueryIsomorphismAPI::is_isomorphic(&query("http://a.org/"), &query("http://a.org/"))
+            .unwrap()
+    );
+    assert!(
+        !QueryIsomorphismAPI::is_isomorphic(&query("http://a.org/"), &query("http://b.org/"))
+            .unwrap()
+    );
+}
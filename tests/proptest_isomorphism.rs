@@ -8,11 +8,11 @@ fn arb_triple_node() -> impl Strategy<Value = TripleNode> {
         // IRIs
         "[a-z][a-z0-9_]*".prop_map(|s| TripleNode::IRI(format!("http://example.org/{}", s))),
         // Variables
-        "[a-z][a-z0-9_]*".prop_map(|s| TripleNode::Variable(s)),
+        "[a-z][a-z0-9_]*".prop_map(TripleNode::Variable),
         // Literals
-        "[a-zA-Z0-9 ]+".prop_map(|s| TripleNode::Literal(s)),
+        "[a-zA-Z0-9 ]+".prop_map(TripleNode::Literal),
         // Blank Nodes
-        "[a-z0-9]+".prop_map(|s| TripleNode::BlankNode(s)),
+        "[a-z0-9]+".prop_map(TripleNode::BlankNode),
     ]
 }
 
@@ -22,6 +22,7 @@ fn arb_triple() -> impl Strategy<Value = Triple> {
         subject: s,
         predicate: p,
         object: o,
+        graph: None,
     })
 }
 
@@ -61,6 +62,12 @@ proptest! {
         let result2 = GraphIsomorphism::are_isomorphic(&shuffled_graph, &graph);
         prop_assert!(result2.is_ok());
         prop_assert_eq!(r1, result2.unwrap());
+
+        // Isomorphic graphs share their fingerprint
+        prop_assert_eq!(
+            GraphIsomorphism::graph_hash(&graph),
+            GraphIsomorphism::graph_hash(&shuffled_graph)
+        );
     }
 
     // Property 3: Variable Renaming
@@ -91,6 +98,7 @@ proptest! {
                 subject: map_node(&t.subject),
                 predicate: map_node(&t.predicate),
                 object: map_node(&t.object),
+                graph: None,
             }
         }).collect();
 
@@ -98,5 +106,9 @@ proptest! {
         let result = GraphIsomorphism::are_isomorphic(&graph, &graph2);
         prop_assert!(result.is_ok());
         prop_assert!(result.unwrap());
+        prop_assert_eq!(
+            GraphIsomorphism::graph_hash(&graph),
+            GraphIsomorphism::graph_hash(&graph2)
+        );
     }
 }
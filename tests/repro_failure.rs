@@ -6,11 +6,13 @@ fn test_repro_proptest_failure() {
         subject: TripleNode::BlankNode("0".to_string()),
         predicate: TripleNode::Variable("a".to_string()),
         object: TripleNode::Variable("aa".to_string()),
+        graph: None,
     };
     let t2 = Triple {
         subject: TripleNode::Variable("a0".to_string()),
         predicate: TripleNode::Variable("ab".to_string()),
         object: TripleNode::BlankNode("1".to_string()),
+        graph: None,
     };
 
     let graph1 = vec![t1.clone(), t2.clone()];
@@ -22,3 +24,54 @@ fn test_repro_proptest_failure() {
     assert!(result.is_ok());
     assert!(result.unwrap());
 }
+
+#[test]
+fn test_self_loops_with_distinguishing_triple() {
+    let graph = |triples: &[(&str, &str, &str)]| -> Vec<Triple> {
+        triples
+            .iter()
+            .map(|(s, p, o)| Triple::new(*s, *p, *o))
+            .collect()
+    };
+
+    // A self-loop whose node also has an outgoing edge
+    let graph1 = graph(&[("?x", "<p>", "?x"), ("?x", "<q>", "?y")]);
+    let graph2 = graph(&[("?b", "<q>", "?c"), ("?b", "<p>", "?b")]);
+    assert!(GraphIsomorphism::are_isomorphic(&graph1, &graph2).unwrap());
+
+    // The same triples, but the edge points into the self-loop
+    let incoming = graph(&[("?a", "<p>", "?a"), ("?c", "<q>", "?a")]);
+    assert!(!GraphIsomorphism::are_isomorphic(&graph1, &incoming).unwrap());
+
+    // A self-loop is not a two-node edge
+    let loop_and_edge = graph(&[("?x", "<p>", "?x"), ("?y", "<p>", "?z")]);
+    let edge_and_loop = graph(&[("?a", "<p>", "?b"), ("?c", "<p>", "?c")]);
+    let cycle = graph(&[("?a", "<p>", "?b"), ("?b", "<p>", "?a")]);
+    assert!(GraphIsomorphism::are_isomorphic(&loop_and_edge, &edge_and_loop).unwrap());
+    assert!(!GraphIsomorphism::are_isomorphic(&loop_and_edge, &cycle).unwrap());
+
+    // Two self-loops told apart by a single ground triple
+    let graph1 = graph(&[
+        ("?x", "<p>", "?x"),
+        ("?y", "<p>", "?y"),
+        ("?x", "<q>", "\"a\""),
+    ]);
+    let graph2 = graph(&[
+        ("?a", "<p>", "?a"),
+        ("?b", "<p>", "?b"),
+        ("?b", "<q>", "\"a\""),
+    ]);
+    let other_literal = graph(&[
+        ("?a", "<p>", "?a"),
+        ("?b", "<p>", "?b"),
+        ("?b", "<q>", "\"b\""),
+    ]);
+    assert!(GraphIsomorphism::are_isomorphic(&graph1, &graph2).unwrap());
+    assert!(!GraphIsomorphism::are_isomorphic(&graph1, &other_literal).unwrap());
+
+    let bijection = GraphIsomorphism::find_bijection(&graph1, &graph2)
+        .unwrap()
+        .unwrap();
+    assert_eq!(bijection["x"], "b");
+    assert_eq!(bijection["y"], "a");
+}
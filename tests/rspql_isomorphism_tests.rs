@@ -1,4 +1,7 @@
 use tulna_rs::isomorphism::api::QueryIsomorphismAPI;
+use tulna_rs::parsing::parsed_rspql_query::{Operator, ParsedQuery, WindowDefinition};
+use tulna_rs::parsing::rspql_parser::RSPQLParser;
+use tulna_rs::TulnaError;
 
 #[test]
 fn test_simple_rspql_isomorphism() {
@@ -235,10 +238,6 @@ WHERE {
     assert!(!result.unwrap());
 }
 
-// Note: BGP extraction with prefixed predicates needs improvement
-// Currently, prefixed predicates in RSPQL WHERE clauses may not be properly expanded
-// This test is commented out until prefix handling in BGP extraction is fixed
-/*
 #[test]
 fn test_rspql_not_isomorphic_different_bgp() {
     let query1 = r#"
@@ -265,7 +264,6 @@ WHERE {
     assert!(result.is_ok());
     assert!(!result.unwrap());
 }
-*/
 
 #[test]
 fn test_rspql_detect_language() {
@@ -393,3 +391,652 @@ WHERE {
     assert!(result.is_ok());
     assert!(result.unwrap());
 }
+
+#[test]
+fn test_rspql_empty_bgp_same_window_isomorphic() {
+    let query1 = r#"
+REGISTER RStream <output> AS
+SELECT *
+FROM NAMED WINDOW <w> ON STREAM <stream> [RANGE 10 STEP 5]
+WHERE {
+}
+"#;
+
+    let query2 = r#"
+REGISTER RStream <output> AS
+SELECT *
+FROM NAMED WINDOW <w> ON STREAM <stream> [RANGE 10 STEP 5]
+WHERE {
+}
+"#;
+
+    assert!(QueryIsomorphismAPI::extract_bgp(query1).unwrap().is_empty());
+
+    let result = QueryIsomorphismAPI::is_isomorphic(query1, query2);
+    assert!(result.is_ok());
+    assert!(result.unwrap());
+}
+
+#[test]
+fn test_rspql_empty_bgp_different_window_not_isomorphic() {
+    let query1 = r#"
+REGISTER RStream <output> AS
+SELECT *
+FROM NAMED WINDOW <w> ON STREAM <stream> [RANGE 10 STEP 5]
+WHERE {
+}
+"#;
+
+    let query2 = r#"
+REGISTER RStream <output> AS
+SELECT *
+FROM NAMED WINDOW <w> ON STREAM <stream> [RANGE 30 STEP 5]
+WHERE {
+}
+"#;
+
+    let result = QueryIsomorphismAPI::is_isomorphic(query1, query2);
+    assert!(result.is_ok());
+    assert!(!result.unwrap());
+}
+
+#[test]
+fn test_rspql_different_operator_not_equivalent() {
+    let query1 = r#"
+REGISTER RStream <output> AS
+SELECT ?s ?o
+FROM NAMED WINDOW <w> ON STREAM <stream> [RANGE 10 STEP 5]
+WHERE {
+    ?s <http://example.org/p> ?o .
+}
+"#;
+
+    let query2 = r#"
+REGISTER IStream <output> AS
+SELECT ?x ?y
+FROM NAMED WINDOW <w> ON STREAM <stream> [RANGE 10 STEP 5]
+WHERE {
+    ?x <http://example.org/p> ?y .
+}
+"#;
+
+    assert!(!QueryIsomorphismAPI::is_isomorphic(query1, query2).unwrap());
+    assert!(!QueryIsomorphismAPI::is_strictly_equivalent(query1, query2).unwrap());
+    assert!(QueryIsomorphismAPI::is_strictly_equivalent(query1, query1).unwrap());
+}
+
+#[test]
+fn test_rspql_missing_register_clause() {
+    let unregistered = r#"
+SELECT ?s
+FROM NAMED WINDOW <w> ON STREAM <stream> [RANGE 10 STEP 5]
+WHERE {
+    WINDOW <w> { ?s <http://example.org/p> ?o . }
+}
+"#;
+    let registered = format!("REGISTER RStream undefined AS{}", unregistered);
+
+    let parsed = RSPQLParser::new(unregistered.to_string()).parse();
+    assert!(parsed.r2s.is_none());
+    let registered_query = QueryIsomorphismAPI::parse_query(&registered).unwrap();
+    assert_eq!(registered_query.r2s_operator.as_deref(), Some("RStream"));
+    assert!(!QueryIsomorphismAPI::is_strictly_equivalent(unregistered, &registered).unwrap());
+}
+
+#[test]
+fn test_rspql_embedded_query_errors_propagated() {
+    let no_query_form = r#"
+REGISTER RStream <output> AS
+FROM NAMED WINDOW <w> ON STREAM <stream> [RANGE 10 STEP 5]
+WHERE {
+    WINDOW <w> { ?s <http://example.org/p> ?o . }
+}
+"#;
+    let valid = no_query_form.replace("FROM NAMED", "SELECT ?s\nFROM NAMED");
+
+    assert!(QueryIsomorphismAPI::is_isomorphic(&valid, &valid).unwrap());
+    assert!(matches!(
+        QueryIsomorphismAPI::parse_query(no_query_form),
+        Err(TulnaError::ParseError(_))
+    ));
+}
+
+#[test]
+fn test_rspql_negative_window_range_rejected() {
+    let query1 = r#"
+REGISTER RStream <output> AS
+SELECT ?s ?o
+FROM NAMED WINDOW <w> ON STREAM <stream> [RANGE -5 STEP 5]
+WHERE {
+    WINDOW <w> { ?s <http://example.org/p> ?o . }
+}
+"#;
+
+    let query2 = r#"
+REGISTER RStream <output> AS
+SELECT ?s ?o
+FROM NAMED WINDOW <w> ON STREAM <stream> [RANGE 5 STEP 5]
+WHERE {
+    WINDOW <w> { ?s <http://example.org/p> ?o . }
+}
+"#;
+
+    match QueryIsomorphismAPI::is_isomorphic(query1, query2) {
+        Err(TulnaError::ParseError(message)) => assert!(message.contains("-5")),
+        other => panic!("Expected a parse error, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_rspql_prefix_declaration_case_and_whitespace() {
+    let expanded = r#"
+REGISTER RStream <output> AS
+SELECT ?s ?o
+FROM NAMED WINDOW <http://example.org/w> ON STREAM <http://example.org/stream> [RANGE 10 STEP 5]
+WHERE {
+    WINDOW <http://example.org/w> { ?s <http://example.org/p> ?o . }
+}
+"#;
+
+    for declaration in [
+        "PREFIX ex: <http://example.org/>",
+        "prefix ex: <http://example.org/>",
+        "PREFIX ex:<http://example.org/>",
+        "Prefix  ex:   <http://example.org/>",
+    ] {
+        let prefixed = format!(
+            r#"
+{}
+REGISTER RStream <output> AS
+SELECT ?s ?o
+FROM NAMED WINDOW ex:w ON STREAM ex:stream [RANGE 10 STEP 5]
+WHERE {{
+    WINDOW ex:w {{ ?s <http://example.org/p> ?o . }}
+}}
+"#,
+            declaration
+        );
+
+        assert!(
+            QueryIsomorphismAPI::check_window_names(&prefixed, expanded).unwrap(),
+            "Window name not expanded with '{}'",
+            declaration
+        );
+        assert!(
+            QueryIsomorphismAPI::check_stream_parameters(&prefixed, expanded).unwrap(),
+            "Stream name not expanded with '{}'",
+            declaration
+        );
+    }
+}
+
+#[test]
+fn test_rspql_prefixed_and_full_stream_iris_isomorphic() {
+    let query1 = r#"
+PREFIX ex: <http://example.org/>
+REGISTER RStream <output> AS
+SELECT ?s ?o
+FROM NAMED WINDOW ex:w ON STREAM ex:s [RANGE 10 STEP 5]
+WHERE {
+    WINDOW ex:w { ?s <http://example.org/p> ?o . }
+}
+"#;
+
+    let query2 = r#"
+REGISTER RStream <output> AS
+SELECT ?x ?y
+FROM NAMED WINDOW <http://example.org/w> ON STREAM <http://example.org/s> [RANGE 10 STEP 5]
+WHERE {
+    WINDOW <http://example.org/w> { ?x <http://example.org/p> ?y . }
+}
+"#;
+
+    let result = QueryIsomorphismAPI::is_isomorphic(query1, query2);
+    assert!(result.is_ok());
+    assert!(result.unwrap());
+}
+
+#[test]
+fn test_rspql_multiple_windows_order_independent() {
+    let query1 = r#"
+PREFIX ex: <http://example.org/>
+REGISTER RStream <output> AS
+SELECT ?s ?o
+FROM NAMED WINDOW ex:w1 ON STREAM ex:stream1 [RANGE 10 STEP 5]
+FROM NAMED WINDOW ex:w2 ON STREAM ex:stream2 [RANGE 60 STEP 30]
+WHERE {
+    WINDOW ex:w1 { ?s <http://example.org/p> ?o . }
+    WINDOW ex:w2 { ?o <http://example.org/q> ?s . }
+}
+"#;
+
+    let query2 = r#"
+PREFIX ex: <http://example.org/>
+REGISTER RStream <output> AS
+SELECT ?s ?o
+FROM NAMED WINDOW ex:w2 ON STREAM ex:stream2 [RANGE 60 STEP 30]
+FROM NAMED WINDOW ex:w1 ON STREAM ex:stream1 [RANGE 10 STEP 5]
+WHERE {
+    WINDOW ex:w1 { ?s <http://example.org/p> ?o . }
+    WINDOW ex:w2 { ?o <http://example.org/q> ?s . }
+}
+"#;
+
+    assert!(QueryIsomorphismAPI::is_isomorphic(query1, query2).unwrap());
+    assert!(QueryIsomorphismAPI::check_stream_parameters(query1, query2).unwrap());
+}
+
+#[test]
+fn test_rspql_windows_referenced_in_different_order() {
+    let query1 = r#"
+PREFIX ex: <http://example.org/>
+REGISTER RStream <output> AS
+SELECT ?s ?o
+FROM NAMED WINDOW ex:w1 ON STREAM ex:stream1 [RANGE 10 STEP 5]
+FROM NAMED WINDOW ex:w2 ON STREAM ex:stream2 [RANGE 60 STEP 30]
+WHERE {
+    WINDOW ex:w1 { ?s <http://example.org/p> ?o . }
+    WINDOW ex:w2 { ?o <http://example.org/q> ?s . }
+}
+"#;
+
+    let query2 = r#"
+PREFIX ex: <http://example.org/>
+REGISTER RStream <output> AS
+SELECT ?s ?o
+FROM NAMED WINDOW ex:w2 ON STREAM ex:stream2 [RANGE 60 STEP 30]
+FROM NAMED WINDOW ex:w1 ON STREAM ex:stream1 [RANGE 10 STEP 5]
+WHERE {
+    WINDOW ex:w2 { ?o <http://example.org/q> ?s . }
+    WINDOW ex:w1 { ?s <http://example.org/p> ?o . }
+}
+"#;
+
+    assert!(QueryIsomorphismAPI::is_isomorphic(query1, query2).unwrap());
+    assert!(QueryIsomorphismAPI::check_stream_parameters(query1, query2).unwrap());
+    assert!(QueryIsomorphismAPI::check_window_names(query1, query2).unwrap());
+}
+
+#[test]
+fn test_rspql_check_stream_parameters_pairs_names_with_streams() {
+    let query1 = r#"
+PREFIX ex: <http://example.org/>
+REGISTER RStream <output> AS
+SELECT ?s ?o
+FROM NAMED WINDOW ex:w1 ON STREAM ex:stream1 [RANGE 10 STEP 5]
+FROM NAMED WINDOW ex:w2 ON STREAM ex:stream2 [RANGE 60 STEP 30]
+WHERE {
+    WINDOW ex:w1 { ?s <http://example.org/p> ?o . }
+    WINDOW ex:w2 { ?o <http://example.org/q> ?s . }
+}
+"#;
+
+    // Same names and same window parameters, but each name is bound to the other window
+    let query2 = r#"
+PREFIX ex: <http://example.org/>
+REGISTER RStream <output> AS
+SELECT ?s ?o
+FROM NAMED WINDOW ex:w2 ON STREAM ex:stream1 [RANGE 10 STEP 5]
+FROM NAMED WINDOW ex:w1 ON STREAM ex:stream2 [RANGE 60 STEP 30]
+WHERE {
+    WINDOW ex:w1 { ?s <http://example.org/p> ?o . }
+    WINDOW ex:w2 { ?o <http://example.org/q> ?s . }
+}
+"#;
+
+    assert!(QueryIsomorphismAPI::check_window_names(query1, query2).unwrap());
+    assert!(!QueryIsomorphismAPI::check_stream_parameters(query1, query2).unwrap());
+    assert!(!QueryIsomorphismAPI::is_isomorphic(query1, query2).unwrap());
+}
+
+#[test]
+fn test_rspql_different_second_window_not_isomorphic() {
+    let query1 = r#"
+PREFIX ex: <http://example.org/>
+REGISTER RStream <output> AS
+SELECT ?s ?o
+FROM NAMED WINDOW ex:w1 ON STREAM ex:stream1 [RANGE 10 STEP 5]
+FROM NAMED WINDOW ex:w2 ON STREAM ex:stream2 [RANGE 60 STEP 30]
+WHERE {
+    WINDOW ex:w1 { ?s <http://example.org/p> ?o . }
+    WINDOW ex:w2 { ?o <http://example.org/q> ?s . }
+}
+"#;
+
+    let query2 = r#"
+PREFIX ex: <http://example.org/>
+REGISTER RStream <output> AS
+SELECT ?s ?o
+FROM NAMED WINDOW ex:w1 ON STREAM ex:stream1 [RANGE 10 STEP 5]
+FROM NAMED WINDOW ex:w2 ON STREAM ex:stream3 [RANGE 60 STEP 30]
+WHERE {
+    WINDOW ex:w1 { ?s <http://example.org/p> ?o . }
+    WINDOW ex:w2 { ?o <http://example.org/q> ?s . }
+}
+"#;
+
+    assert!(!QueryIsomorphismAPI::is_isomorphic(query1, query2).unwrap());
+    assert!(!QueryIsomorphismAPI::check_stream_parameters(query1, query2).unwrap());
+}
+
+#[test]
+fn test_rspql_find_matches_in_catalog() {
+    let query = r#"
+PREFIX ex: <http://example.org/>
+REGISTER RStream <output> AS
+SELECT ?s ?o
+FROM NAMED WINDOW ex:w ON STREAM ex:stream [RANGE 10 STEP 5]
+WHERE {
+    WINDOW ex:w { ?s <http://example.org/p> ?o . }
+}
+"#;
+
+    let renamed = query.replace("?s", "?x").replace("?o", "?y");
+    let other_range = query.replace("RANGE 10", "RANGE 20");
+    let other_stream = query.replace("ex:stream", "ex:otherStream");
+    let candidates = [
+        other_range.as_str(),
+        query,
+        other_stream.as_str(),
+        renamed.as_str(),
+    ];
+
+    let matches = QueryIsomorphismAPI::find_matches(query, &candidates).unwrap();
+    assert_eq!(matches, vec![1, 3]);
+    assert!(QueryIsomorphismAPI::find_matches(query, &[])
+        .unwrap()
+        .is_empty());
+}
+
+#[test]
+fn test_rspql_normalize_query() {
+    let query1 = r#"
+PREFIX ex: <http://example.org/>
+REGISTER RStream <output> AS
+SELECT ?s ?o
+FROM NAMED WINDOW ex:w ON STREAM ex:stream [RANGE 10 STEP 5]
+WHERE {
+    WINDOW ex:w { ?s ex:p ?o . ?o ex:q ?s . }
+}
+"#;
+    let query2 = r#"
+REGISTER RStream <output> AS
+SELECT ?a ?b
+FROM NAMED WINDOW <http://example.org/w> ON STREAM <http://example.org/stream> [RANGE 10 STEP 5]
+WHERE {
+    WINDOW <http://example.org/w> { ?b <http://example.org/q> ?a . ?a <http://example.org/p> ?b . }
+}
+"#;
+
+    let normalized = QueryIsomorphismAPI::normalize_query(query1).unwrap();
+    assert_eq!(
+        normalized,
+        QueryIsomorphismAPI::normalize_query(query2).unwrap()
+    );
+    assert!(normalized.starts_with("REGISTER RStream <output> AS\nSELECT"));
+    assert!(normalized.contains(
+        "FROM NAMED WINDOW <http://example.org/w> ON STREAM <http://example.org/stream> [RANGE 10 STEP 5]"
+    ));
+    assert!(normalized.contains("WINDOW <http://example.org/w> { ?v"));
+
+    let other_range = query1.replace("RANGE 10", "RANGE 20");
+    assert_ne!(
+        normalized,
+        QueryIsomorphismAPI::normalize_query(&other_range).unwrap()
+    );
+}
+
+#[test]
+fn test_rspql_normalize_query_round_trip() {
+    let query = r#"
+PREFIX ex: <http://example.org/>
+REGISTER IStream ex:output AS
+SELECT ?s ?o
+FROM NAMED WINDOW ex:w1 ON STREAM ex:stream1 [RANGE 10 STEP 5]
+FROM NAMED WINDOW ex:w2 ON STREAM ex:stream2 [RANGE 20 STEP 10]
+WHERE {
+    WINDOW ex:w1 { ?s ex:p ?o . }
+    WINDOW ex:w2 { ?o ex:q ?x . }
+    FILTER(?x > 3)
+}
+"#;
+
+    let normalized = QueryIsomorphismAPI::normalize_query(query).unwrap();
+    assert!(normalized.starts_with("REGISTER IStream <http://example.org/output> AS\n"));
+
+    // The normalized query parses to the same query and normalizes to itself
+    assert!(QueryIsomorphismAPI::is_isomorphic(query, &normalized).unwrap());
+    assert_eq!(
+        QueryIsomorphismAPI::normalize_query(&normalized).unwrap(),
+        normalized
+    );
+}
+
+#[test]
+fn test_rspql_rstream_and_dstream_not_isomorphic() {
+    let query1 = r#"
+REGISTER RStream <output> AS
+SELECT ?s ?o
+FROM NAMED WINDOW <w> ON STREAM <stream> [RANGE 10 STEP 5]
+WHERE {
+    WINDOW <w> { ?s <http://example.org/p> ?o . }
+}
+"#;
+
+    let query2 = query1.replace("RStream", "DStream");
+    let renamed = query2.replace("?s", "?x").replace("?o", "?y");
+
+    assert!(!QueryIsomorphismAPI::is_isomorphic(query1, &query2).unwrap());
+    assert!(
+        !QueryIsomorphismAPI::compare_queries(query1, &query2)
+            .unwrap()
+            .is_isomorphic
+    );
+    assert!(QueryIsomorphismAPI::is_isomorphic(&query2, &renamed).unwrap());
+}
+
+#[test]
+fn test_rspql_compare_queries_mismatch_reason() {
+    let query1 = r#"
+REGISTER RStream <output> AS
+SELECT ?s ?o
+FROM NAMED WINDOW <w> ON STREAM <stream> [RANGE 10 STEP 5]
+WHERE {
+    WINDOW <w> { ?s <http://example.org/p> ?o . }
+}
+"#;
+    let reason = |other: &str| {
+        QueryIsomorphismAPI::compare_queries(query1, other)
+            .unwrap()
+            .mismatch_reason
+    };
+
+    assert_eq!(reason(query1), None);
+    assert_eq!(
+        reason(&query1.replace("RANGE 10", "RANGE 20")).as_deref(),
+        Some("stream width 10 != 20")
+    );
+    assert_eq!(
+        reason(&query1.replace("STEP 5", "STEP 2")).as_deref(),
+        Some("stream slide 5 != 2")
+    );
+    assert_eq!(
+        reason(&query1.replace("RStream", "IStream")).as_deref(),
+        Some("R2S operator RStream != IStream")
+    );
+
+    let summary =
+        QueryIsomorphismAPI::compare_queries(query1, &query1.replace("RANGE 10", "RANGE 20"))
+            .unwrap()
+            .summary();
+    assert!(
+        summary.ends_with("Mismatch: stream width 10 != 20"),
+        "{}",
+        summary
+    );
+}
+
+#[test]
+fn test_rspql_comments_ignored() {
+    let plain = r#"
+PREFIX ex: <http://example.org/>
+REGISTER RStream <output> AS
+SELECT ?s
+FROM NAMED WINDOW ex:w ON STREAM ex:stream [RANGE 10 STEP 5]
+WHERE {
+    WINDOW ex:w { ?s ex:p ?o }
+}
+"#;
+    let commented = r#"
+PREFIX ex: <http://example.org/>
+# REGISTER IStream <other> AS
+REGISTER RStream <output> AS
+SELECT ?s # FROM NAMED WINDOW ex:v ON STREAM ex:other [RANGE 1 STEP 1]
+FROM NAMED WINDOW ex:w ON STREAM ex:stream [RANGE 10 STEP 5] # [OFFSET 1 RANGE 2 STEP 3]
+WHERE {
+    # WINDOW ex:v { ?x ex:q ?y }
+    WINDOW ex:w { ?s ex:p ?o } # } }
+}
+"#;
+
+    assert!(QueryIsomorphismAPI::is_isomorphic(plain, commented).unwrap());
+    assert_eq!(
+        QueryIsomorphismAPI::parse_query(plain).unwrap(),
+        QueryIsomorphismAPI::parse_query(commented).unwrap()
+    );
+}
+
+#[test]
+fn test_rspql_parsed_queries_compare_by_equality() {
+    let query = r#"
+PREFIX ex: <http://example.org/>
+REGISTER IStream <output> AS
+SELECT ?s
+FROM NAMED WINDOW ex:w ON STREAM ex:stream [RANGE 10 STEP 5]
+WHERE {
+    WINDOW ex:w { ?s ex:p ?o }
+}
+"#;
+    let parsed = RSPQLParser::new(query.to_string()).parse();
+
+    let mut expected = ParsedQuery::new(parsed.sparql_query.clone());
+    expected.set_r2s(Operator::IStream, "output".to_string());
+    expected.add_s2r_window(WindowDefinition {
+        window_name: "http://example.org/w".to_string(),
+        stream_name: "http://example.org/stream".to_string(),
+        width: 10,
+        slide: 5,
+    });
+    assert_eq!(parsed, expected);
+    assert_eq!(parsed, RSPQLParser::new(query.to_string()).parse());
+
+    let other = RSPQLParser::new(query.replace("STEP 5", "STEP 2")).parse();
+    assert_ne!(parsed, other);
+    assert_ne!(parsed.s2r, other.s2r);
+    assert_eq!(parsed.r2s, other.r2s);
+}
+
+#[test]
+fn test_rspql_prefixed_register_target() {
+    let query = r#"
+PREFIX ex: <http://example.org/>
+REGISTER RStream ex:output AS
+SELECT ?s
+FROM NAMED WINDOW ex:w ON STREAM ex:stream [RANGE 10 STEP 5]
+WHERE {
+    WINDOW ex:w { ?s ex:p ?o }
+}
+"#;
+    let parsed = RSPQLParser::new(query.to_string()).parse();
+    let r2s = parsed.r2s.as_ref().unwrap();
+    assert_eq!(r2s.operator, Operator::RStream);
+    assert_eq!(r2s.name, "http://example.org/output");
+
+    let full_iri = query.replace("ex:output", "<http://example.org/output>");
+    assert_eq!(RSPQLParser::new(full_iri.clone()).parse(), parsed);
+
+    // The operator is no longer dropped, so it takes part in the comparison
+    let istream = query.replace("RStream", "IStream");
+    assert!(QueryIsomorphismAPI::is_isomorphic(query, &full_iri).unwrap());
+    assert!(!QueryIsomorphismAPI::is_isomorphic(query, &istream).unwrap());
+}
+
+#[test]
+fn test_rspql_undeclared_prefix_kept_verbatim() {
+    // Without a PREFIX declaration the stream name stays `ex:s` instead of being blanked out
+    let undeclared = r#"
+REGISTER RStream <output> AS
+SELECT ?s ?o
+FROM NAMED WINDOW <http://example.org/w> ON STREAM ex:s [RANGE 100 STEP 10]
+WHERE {
+    WINDOW <http://example.org/w> { ?s <http://example.org/p> ?o . }
+}
+"#;
+    let full = undeclared.replace("ex:s", "<http://example.org/s>");
+    let other_undeclared = undeclared.replace("ex:s", "other:s");
+
+    let parsed = RSPQLParser::new(undeclared.to_string()).parse();
+    assert_eq!(parsed.s2r[0].stream_name, "ex:s");
+
+    assert!(!QueryIsomorphismAPI::is_isomorphic(undeclared, &full).unwrap());
+    assert!(!QueryIsomorphismAPI::is_isomorphic(undeclared, &other_undeclared).unwrap());
+}
+
+#[test]
+fn test_rspql_bgp_isomorphic_ignores_windows() {
+    let query1 = r#"
+PREFIX ex: <http://example.org/>
+REGISTER RStream <output> AS
+SELECT ?sensor ?value
+FROM NAMED WINDOW ex:w ON STREAM ex:stream [RANGE 10 STEP 5]
+WHERE {
+    WINDOW ex:w { ?sensor ex:hasValue ?value . }
+}
+"#;
+    let query2 = r#"
+PREFIX ex: <http://example.org/>
+REGISTER IStream <output> AS
+SELECT ?s ?v
+FROM NAMED WINDOW ex:other ON STREAM ex:stream [RANGE 60 STEP 30]
+WHERE {
+    WINDOW ex:other { ?s <http://example.org/hasValue> ?v . }
+}
+"#;
+    let sparql = "SELECT ?x ?y WHERE { ?x <http://example.org/hasValue> ?y . }";
+    let different = query2.replace("hasValue", "hasUnit");
+
+    assert!(!QueryIsomorphismAPI::is_isomorphic(query1, query2).unwrap());
+    assert!(QueryIsomorphismAPI::is_bgp_isomorphic(query1, query2).unwrap());
+    assert!(QueryIsomorphismAPI::is_bgp_isomorphic(query1, sparql).unwrap());
+    assert!(!QueryIsomorphismAPI::is_bgp_isomorphic(query1, &different).unwrap());
+}
+
+#[test]
+fn test_rspql_bgp_equivalent_to_sparql() {
+    let rspql = r#"
+PREFIX ex: <http://example.org/>
+REGISTER RStream <output> AS
+SELECT ?sensor ?value
+FROM NAMED WINDOW ex:w ON STREAM ex:stream [RANGE 10 STEP 5]
+WHERE {
+    WINDOW ex:w {
+        ?sensor ex:hasValue ?value .
+        ?sensor ex:locatedIn ?room .
+    }
+}
+"#;
+    let sparql = r#"
+PREFIX ex: <http://example.org/>
+SELECT ?s WHERE {
+    ?s ex:locatedIn ?r .
+    ?s ex:hasValue ?v .
+    FILTER(?v > 20)
+}
+"#;
+    let other = sparql.replace("ex:locatedIn", "ex:partOf");
+
+    assert!(!QueryIsomorphismAPI::is_isomorphic(rspql, sparql).unwrap());
+    assert!(!QueryIsomorphismAPI::is_bgp_isomorphic(rspql, sparql).unwrap());
+    assert!(QueryIsomorphismAPI::bgp_equivalent_cross_language(rspql, sparql).unwrap());
+    assert!(!QueryIsomorphismAPI::bgp_equivalent_cross_language(rspql, &other).unwrap());
+}
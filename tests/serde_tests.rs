@@ -0,0 +1,91 @@
+#![cfg(feature = "serde")]
+
+use tulna_rs::graph::{Triple, TripleNode};
+use tulna_rs::query::{IsomorphismQuery, QueryComparisonResult, QueryIsomorphismAPI};
+
+#[test]
+fn test_triple_node_serialized_with_kind() {
+    let node = TripleNode::var("x");
+    assert_eq!(
+        serde_json::to_string(&node).unwrap(),
+        r#"{"kind":"variable","value":"x"}"#
+    );
+
+    let node = TripleNode::iri("http://example.org/a");
+    assert_eq!(
+        serde_json::to_string(&node).unwrap(),
+        r#"{"kind":"iri","value":"http://example.org/a"}"#
+    );
+
+    let node = TripleNode::LangLiteral {
+        value: "hello".to_string(),
+        lang: "en".to_string(),
+    };
+    let json = serde_json::to_string(&node).unwrap();
+    assert_eq!(
+        json,
+        r#"{"kind":"lang_literal","value":{"value":"hello","lang":"en"}}"#
+    );
+    assert_eq!(serde_json::from_str::<TripleNode>(&json).unwrap(), node);
+}
+
+#[test]
+fn test_parsed_query_round_trip() {
+    let query = r#"
+PREFIX ex: <http://example.org/>
+SELECT DISTINCT ?name WHERE {
+    ?person a ex:Person ;
+            ex:name ?name ;
+            ex:age "30"^^<http://www.w3.org/2001/XMLSchema#integer> .
+    OPTIONAL { ?person ex:email ?email }
+    FILTER(?name != "Bob"@en)
+    VALUES ?person { ex:alice ex:carol }
+}
+ORDER BY ?name
+LIMIT 10
+"#;
+    let parsed = QueryIsomorphismAPI::parse_query(query).unwrap();
+
+    let json = serde_json::to_string(&parsed).unwrap();
+    let restored: IsomorphismQuery = serde_json::from_str(&json).unwrap();
+    assert_eq!(restored, parsed);
+
+    let triples: Vec<Triple> =
+        serde_json::from_str(&serde_json::to_string(&parsed.bgp).unwrap()).unwrap();
+    assert_eq!(triples, parsed.bgp);
+}
+
+#[test]
+fn test_streaming_query_round_trip() {
+    let query = r#"
+PREFIX ex: <http://example.org/>
+REGISTER RStream <output> AS
+SELECT ?s
+FROM NAMED WINDOW ex:w ON STREAM ex:stream [RANGE 10 STEP 5]
+WHERE {
+    WINDOW ex:w { ?s ex:p ?o }
+}
+"#;
+    let parsed = QueryIsomorphismAPI::parse_query(query).unwrap();
+
+    let json = serde_json::to_string(&parsed).unwrap();
+    let restored: IsomorphismQuery = serde_json::from_str(&json).unwrap();
+    assert_eq!(restored, parsed);
+    assert_eq!(restored.windows.len(), 1);
+    assert_eq!(restored.windows, parsed.windows);
+}
+
+#[test]
+fn test_comparison_result_round_trip() {
+    let result = QueryIsomorphismAPI::compare_queries(
+        "SELECT ?s WHERE { ?s <http://example.org/p> ?o }",
+        "SELECT ?s WHERE { ?s <http://example.org/q> ?o }",
+    )
+    .unwrap();
+    assert!(result.mismatch_reason.is_some());
+
+    let json = serde_json::to_string(&result).unwrap();
+    let restored: QueryComparisonResult = serde_json::from_str(&json).unwrap();
+    assert_eq!(restored.summary(), result.summary());
+    assert_eq!(restored.mismatch_reason, result.mismatch_reason);
+}
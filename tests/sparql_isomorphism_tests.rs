@@ -1,4 +1,6 @@
+use tulna_rs::graph::TripleNode;
 use tulna_rs::isomorphism::api::QueryIsomorphismAPI;
+use tulna_rs::TulnaError;
 
 #[test]
 fn test_simple_sparql_isomorphism() {
@@ -284,4 +286,1761 @@ fn test_sparql_compare_queries_detailed() {
     assert!(result.same_language);
     assert!(result.same_bgp_size);
     assert!(result.bgp_isomorphic);
+    assert_eq!(result.mismatch_reason, None);
+}
+
+#[test]
+fn test_sparql_compare_queries_mismatch_reason() {
+    let query = "SELECT ?s WHERE { ?s <http://example.org/p> ?o . } LIMIT 10";
+    let reason = |other: &str| {
+        QueryIsomorphismAPI::compare_queries(query, other)
+            .unwrap()
+            .mismatch_reason
+    };
+
+    assert_eq!(
+        reason("SELECT ?s WHERE { ?s <http://example.org/p> ?o . } LIMIT 20").as_deref(),
+        Some("LIMIT 10 != 20")
+    );
+    assert_eq!(
+        reason("ASK { ?s <http://example.org/p> ?o . }").as_deref(),
+        Some("query form Select != Ask")
+    );
+    assert_eq!(
+        reason("SELECT ?s WHERE { ?s <http://example.org/p> ?o . ?o <http://example.org/p> ?s . } LIMIT 10")
+            .as_deref(),
+        Some("BGP size 1 != 2")
+    );
+
+    // A triple pattern written twice is still one pattern
+    let repeated = "SELECT ?x WHERE { ?x <http://example.org/p> ?y . ?x <http://example.org/p> ?y . } LIMIT 10";
+    assert_eq!(reason(repeated), None);
+    assert!(
+        QueryIsomorphismAPI::compare_queries(query, repeated)
+            .unwrap()
+            .same_bgp_size
+    );
+    assert!(QueryIsomorphismAPI::is_isomorphic(query, repeated).unwrap());
+
+    let different_predicate =
+        reason("SELECT ?s WHERE { ?s <http://example.org/q> ?o . } LIMIT 10").unwrap();
+    assert!(
+        different_predicate.starts_with("BGP not isomorphic: "),
+        "{}",
+        different_predicate
+    );
+}
+
+#[test]
+fn test_sparql_reordered_from_named_isomorphic() {
+    let query1 = r#"
+PREFIX ex: <http://example.org/>
+SELECT ?s ?o
+FROM NAMED ex:g1
+FROM NAMED <http://example.org/g2>
+WHERE {
+    ?s ex:p ?o .
+}
+"#;
+
+    let query2 = r#"
+PREFIX ex: <http://example.org/>
+SELECT ?x ?y
+FROM NAMED ex:g2 FROM NAMED <http://example.org/g1>
+WHERE {
+    ?x ex:p ?y .
+}
+"#;
+
+    let result = QueryIsomorphismAPI::is_isomorphic(query1, query2);
+    assert!(result.is_ok());
+    assert!(result.unwrap());
+}
+
+#[test]
+fn test_sparql_not_isomorphic_different_from_named() {
+    let query1 = r#"
+SELECT ?s ?o
+FROM NAMED <http://example.org/g1>
+FROM NAMED <http://example.org/g2>
+WHERE {
+    ?s <http://example.org/p> ?o .
+}
+"#;
+
+    let query2 = r#"
+SELECT ?x ?y
+FROM NAMED <http://example.org/g1>
+FROM NAMED <http://example.org/g3>
+WHERE {
+    ?x <http://example.org/p> ?y .
+}
+"#;
+
+    let result = QueryIsomorphismAPI::is_isomorphic(query1, query2);
+    assert!(result.is_ok());
+    assert!(!result.unwrap());
+}
+
+#[test]
+fn test_sparql_strictly_equivalent_renamed_query() {
+    let query1 = r#"
+PREFIX foaf: <http://xmlns.com/foaf/0.1/>
+SELECT DISTINCT ?person ?name
+WHERE {
+    ?person foaf:name ?name .
+    ?person foaf:knows ?friend .
+}
+ORDER BY DESC(?name)
+LIMIT 10
+"#;
+
+    let query2 = r#"
+PREFIX foaf: <http://xmlns.com/foaf/0.1/>
+SELECT DISTINCT ?x ?n
+WHERE {
+    ?x foaf:name ?n .
+    ?x foaf:knows ?y .
+}
+ORDER BY DESC( ?n )
+LIMIT 10
+"#;
+
+    let result = QueryIsomorphismAPI::is_strictly_equivalent(query1, query2);
+    assert!(result.is_ok());
+    assert!(result.unwrap());
+}
+
+#[test]
+fn test_sparql_isomorphic_but_not_strictly_equivalent_projection() {
+    // Same BGP, but one query projects the subject and the other the object
+    let query1 = "SELECT ?s WHERE { ?s <http://example.org/p> ?o . }";
+    let query2 = "SELECT ?o WHERE { ?s <http://example.org/p> ?o . }";
+
+    assert!(QueryIsomorphismAPI::is_isomorphic(query1, query2).unwrap());
+    assert!(!QueryIsomorphismAPI::is_strictly_equivalent(query1, query2).unwrap());
+}
+
+#[test]
+fn test_sparql_projection_encoding_never_matches_blank_nodes() {
+    let labelled = "SELECT ?s WHERE { _:tulna_projection_0 <http://example.org/p> ?s . }";
+    let plain = "SELECT ?s WHERE { _:b <http://example.org/p> ?s . }";
+
+    assert!(QueryIsomorphismAPI::is_strictly_equivalent(labelled, plain).unwrap());
+}
+
+#[test]
+fn test_sparql_projection_arity() {
+    let one = "SELECT ?s WHERE { ?s ?p ?o }";
+    let two = "SELECT ?s ?p WHERE { ?s ?p ?o }";
+    let two_renamed = "SELECT ?b ?a WHERE { ?a ?b ?c }";
+    let all = "SELECT * WHERE { ?s ?p ?o }";
+
+    // Only the BGP is compared by default
+    assert!(QueryIsomorphismAPI::is_isomorphic(one, two).unwrap());
+
+    assert!(!QueryIsomorphismAPI::is_strictly_equivalent(one, two).unwrap());
+    assert!(!QueryIsomorphismAPI::is_strictly_equivalent(two, one).unwrap());
+    assert!(QueryIsomorphismAPI::is_strictly_equivalent(two, two_renamed).unwrap());
+    assert!(!QueryIsomorphismAPI::is_strictly_equivalent(one, all).unwrap());
+
+    let parsed = |query| QueryIsomorphismAPI::parse_query(query).unwrap().projection;
+    assert_eq!(parsed(one).len(), 1);
+    assert_eq!(parsed(two), vec!["?s", "?p"]);
+}
+
+#[test]
+fn test_sparql_not_strictly_equivalent_modifiers() {
+    let base = "SELECT ?s WHERE { ?s <http://example.org/p> ?o . }";
+    let distinct = "SELECT DISTINCT ?s WHERE { ?s <http://example.org/p> ?o . }";
+    let limited = "SELECT ?s WHERE { ?s <http://example.org/p> ?o . } LIMIT 5";
+    let ascending = "SELECT ?s WHERE { ?s <http://example.org/p> ?o . } ORDER BY ASC(?o)";
+    let descending = "SELECT ?s WHERE { ?s <http://example.org/p> ?o . } ORDER BY DESC(?o)";
+
+    assert!(QueryIsomorphismAPI::is_strictly_equivalent(base, base).unwrap());
+    assert!(!QueryIsomorphismAPI::is_strictly_equivalent(base, distinct).unwrap());
+    assert!(!QueryIsomorphismAPI::is_strictly_equivalent(base, limited).unwrap());
+    assert!(!QueryIsomorphismAPI::is_strictly_equivalent(ascending, descending).unwrap());
+}
+
+#[test]
+fn test_sparql_different_limit_not_isomorphic() {
+    let ten = "SELECT ?s WHERE { ?s <http://example.org/p> ?o . } LIMIT 10";
+    let twenty = "SELECT ?x WHERE { ?x <http://example.org/p> ?y . } LIMIT 20";
+    let unlimited = "SELECT ?x WHERE { ?x <http://example.org/p> ?y . }";
+    let offset = "SELECT ?x WHERE { ?x <http://example.org/p> ?y . } LIMIT 10 OFFSET 5";
+
+    assert!(!QueryIsomorphismAPI::is_isomorphic(ten, twenty).unwrap());
+    assert!(!QueryIsomorphismAPI::is_isomorphic(ten, unlimited).unwrap());
+    assert!(!QueryIsomorphismAPI::is_isomorphic(ten, offset).unwrap());
+    assert!(QueryIsomorphismAPI::is_isomorphic(ten, &twenty.replace("20", "10")).unwrap());
+}
+
+#[test]
+fn test_sparql_distinct_presence_not_isomorphic() {
+    let distinct = "SELECT DISTINCT ?s WHERE { ?s <http://example.org/p> ?o . }";
+    let reduced = "SELECT REDUCED ?s WHERE { ?s <http://example.org/p> ?o . }";
+    let plain = "SELECT ?x WHERE { ?x <http://example.org/p> ?y . }";
+
+    assert!(!QueryIsomorphismAPI::is_isomorphic(distinct, plain).unwrap());
+    assert!(!QueryIsomorphismAPI::is_isomorphic(distinct, reduced).unwrap());
+    assert!(!QueryIsomorphismAPI::is_isomorphic(reduced, plain).unwrap());
+}
+
+#[test]
+fn test_sparql_isomorphic_ignoring_modifiers() {
+    let modified = "SELECT DISTINCT ?s WHERE { ?s <http://example.org/p> ?o . } LIMIT 10";
+    let plain = "SELECT ?x WHERE { ?x <http://example.org/p> ?y . }";
+    let other_predicate = "SELECT ?x WHERE { ?x <http://example.org/q> ?y . }";
+
+    assert!(!QueryIsomorphismAPI::is_isomorphic(modified, plain).unwrap());
+    assert!(QueryIsomorphismAPI::is_isomorphic_ignoring_modifiers(modified, plain).unwrap());
+    assert!(
+        !QueryIsomorphismAPI::is_isomorphic_ignoring_modifiers(modified, other_predicate).unwrap()
+    );
+}
+
+#[test]
+fn test_sparql_prefix_declarations_on_one_line() {
+    let expanded =
+        "SELECT ?s\nWHERE { ?s <http://xmlns.com/foaf/0.1/knows> <http://example.org/bob> . }";
+
+    for prefixed in [
+        "PREFIX ex: <http://example.org/> PREFIX foaf: <http://xmlns.com/foaf/0.1/>\nSELECT ?s\nWHERE { ?s foaf:knows ex:bob . }",
+        "PREFIX ex: <http://example.org/> PREFIX foaf: <http://xmlns.com/foaf/0.1/> SELECT ?s\nWHERE { ?s foaf:knows ex:bob . }",
+        "PREFIX ex: <http://example.org/>\nPREFIX foaf: <http://xmlns.com/foaf/0.1/> SELECT ?s WHERE {\n ?s foaf:knows ex:bob .\n}",
+    ] {
+        let parsed = QueryIsomorphismAPI::parse_query(prefixed).unwrap();
+        assert_eq!(parsed.bgp.len(), 1, "{}", prefixed);
+        assert!(
+            QueryIsomorphismAPI::is_isomorphic(prefixed, expanded).unwrap(),
+            "Prefixes not expanded in '{}'",
+            prefixed
+        );
+    }
+}
+
+#[test]
+fn test_sparql_relative_iris_resolved_against_base() {
+    let query = |base: &str| {
+        format!(
+            "BASE <{}>\nSELECT ?s\nWHERE {{ ?s <knows> <people/bob> . }}",
+            base
+        )
+    };
+    let absolute =
+        "SELECT ?x\nWHERE { ?x <http://example.org/knows> <http://example.org/people/bob> . }";
+
+    let parsed = QueryIsomorphismAPI::parse_query(&query("http://example.org/")).unwrap();
+    assert_eq!(
+        parsed.bgp[0].object,
+        TripleNode::IRI("http://example.org/people/bob".to_string())
+    );
+
+    assert!(QueryIsomorphismAPI::is_isomorphic(&query("http://example.org/"), absolute).unwrap());
+    assert!(!QueryIsomorphismAPI::is_isomorphic(
+        &query("http://example.org/"),
+        &query("http://example.com/")
+    )
+    .unwrap());
+}
+
+#[test]
+fn test_sparql_base_applies_to_prefixes_and_dataset() {
+    let relative = r#"
+BASE <http://example.org/data/>
+PREFIX ex: <vocab#>
+SELECT ?s
+FROM <graph>
+WHERE { ?s ex:p ?o . }
+"#;
+    let absolute = r#"
+SELECT ?s
+FROM <http://example.org/data/graph>
+WHERE { ?s <http://example.org/data/vocab#p> ?o . }
+"#;
+
+    assert!(QueryIsomorphismAPI::is_isomorphic(relative, absolute).unwrap());
+    assert!(!QueryIsomorphismAPI::is_isomorphic(
+        relative,
+        &relative.replace("example.org", "example.com")
+    )
+    .unwrap());
+}
+
+#[test]
+fn test_sparql_prefix_declaration_case_and_whitespace() {
+    let expanded = "SELECT ?s\nFROM <http://example.org/g>\nWHERE { ?s ?p ?o . }";
+
+    for declaration in [
+        "PREFIX ex: <http://example.org/>",
+        "prefix ex: <http://example.org/>",
+        "PREFIX ex:<http://example.org/>",
+        "Prefix  ex:   <http://example.org/>",
+    ] {
+        let prefixed = format!(
+            "{}\nSELECT ?s\nFROM ex:g\nWHERE {{ ?s ?p ?o . }}",
+            declaration
+        );
+
+        assert!(
+            QueryIsomorphismAPI::is_isomorphic(&prefixed, expanded).unwrap(),
+            "Prefix not registered with '{}'",
+            declaration
+        );
+    }
+}
+
+#[test]
+fn test_sparql_aggregate_expressions_strictly_equivalent() {
+    for aggregate in ["SUM", "MIN", "MAX", "AVG", "SAMPLE", "COUNT"] {
+        let query1 = format!(
+            "SELECT ?person ({}(?age) AS ?result)\nWHERE {{ ?person <http://example.org/age> ?age . }}\nGROUP BY ?person",
+            aggregate
+        );
+        let query2 = format!(
+            "SELECT ?p ({}( ?a ) AS ?r)\nWHERE {{ ?p <http://example.org/age> ?a . }}\nGROUP BY ?p",
+            aggregate.to_lowercase()
+        );
+
+        assert!(
+            QueryIsomorphismAPI::is_strictly_equivalent(&query1, &query2).unwrap(),
+            "{} should match its renamed twin",
+            aggregate
+        );
+    }
+}
+
+#[test]
+fn test_sparql_different_aggregate_not_strictly_equivalent() {
+    let sum = "SELECT (SUM(?x) AS ?s)\nWHERE { ?e <http://example.org/v> ?x . }";
+    let avg = "SELECT (AVG(?x) AS ?s)\nWHERE { ?e <http://example.org/v> ?x . }";
+    // Same aggregate, but over the subject instead of the object
+    let sum_subject = "SELECT (SUM(?e) AS ?s)\nWHERE { ?e <http://example.org/v> ?x . }";
+
+    assert!(QueryIsomorphismAPI::is_isomorphic(sum, avg).unwrap());
+    assert!(!QueryIsomorphismAPI::is_strictly_equivalent(sum, avg).unwrap());
+    assert!(!QueryIsomorphismAPI::is_strictly_equivalent(sum, sum_subject).unwrap());
+}
+
+#[test]
+fn test_sparql_grouped_and_ungrouped_not_isomorphic() {
+    let ungrouped = "SELECT ?s\nWHERE { ?s <http://example.org/p> ?o . }";
+    let grouped = "SELECT ?s\nWHERE { ?s <http://example.org/p> ?o . }\nGROUP BY ?s";
+    let renamed = "SELECT ?x\nWHERE { ?x <http://example.org/p> ?y . }\nGROUP BY ?x";
+
+    assert!(!QueryIsomorphismAPI::is_isomorphic(ungrouped, grouped).unwrap());
+    assert!(!QueryIsomorphismAPI::is_strictly_equivalent(ungrouped, grouped).unwrap());
+    assert!(QueryIsomorphismAPI::is_isomorphic(grouped, renamed).unwrap());
+}
+
+#[test]
+fn test_sparql_group_by_different_variable_not_isomorphic() {
+    let by_subject = "SELECT ?s\nWHERE { ?s <http://example.org/p> ?o . }\nGROUP BY ?s";
+    let by_object = "SELECT ?o\nWHERE { ?s <http://example.org/p> ?o . }\nGROUP BY ?o";
+
+    assert!(!QueryIsomorphismAPI::is_isomorphic(by_subject, by_object).unwrap());
+}
+
+#[test]
+fn test_sparql_aggregate_without_group_by_not_isomorphic() {
+    // An aggregate without GROUP BY collapses all solutions into a single group
+    let counted = "SELECT (COUNT(?s) AS ?n)\nWHERE { ?s <http://example.org/p> ?o . }";
+    let plain = "SELECT ?s\nWHERE { ?s <http://example.org/p> ?o . }";
+    let renamed = "SELECT (COUNT(?x) AS ?c)\nWHERE { ?x <http://example.org/p> ?y . }";
+
+    assert!(!QueryIsomorphismAPI::is_isomorphic(counted, plain).unwrap());
+    assert!(QueryIsomorphismAPI::is_isomorphic(counted, renamed).unwrap());
+}
+
+#[test]
+fn test_sparql_group_concat_separator() {
+    let comma = r#"SELECT (GROUP_CONCAT(?n; SEPARATOR=", ") AS ?names)
+WHERE { ?p <http://example.org/name> ?n . }"#;
+    let comma_renamed = r#"SELECT (group_concat(?x ; separator = ", ") AS ?all)
+WHERE { ?q <http://example.org/name> ?x . }"#;
+    let semicolon = r#"SELECT (GROUP_CONCAT(?n; SEPARATOR="; ") AS ?names)
+WHERE { ?p <http://example.org/name> ?n . }"#;
+
+    assert!(QueryIsomorphismAPI::is_strictly_equivalent(comma, comma_renamed).unwrap());
+    assert!(!QueryIsomorphismAPI::is_strictly_equivalent(comma, semicolon).unwrap());
+}
+
+#[test]
+fn test_sparql_typed_literals_with_different_datatypes_not_isomorphic() {
+    let query1 = r#"
+PREFIX xsd: <http://www.w3.org/2001/XMLSchema#>
+SELECT ?person WHERE {
+    ?person <http://example.org/age> "30"^^xsd:integer .
+}
+"#;
+
+    let query2 = r#"
+PREFIX xsd: <http://www.w3.org/2001/XMLSchema#>
+SELECT ?p WHERE {
+    ?p <http://example.org/age> "30"^^xsd:string .
+}
+"#;
+
+    let query3 = r#"
+PREFIX xsd: <http://www.w3.org/2001/XMLSchema#>
+SELECT ?p WHERE {
+    ?p <http://example.org/age> "30"^^xsd:integer .
+}
+"#;
+
+    assert!(!QueryIsomorphismAPI::is_isomorphic(query1, query2).unwrap());
+    assert!(QueryIsomorphismAPI::is_isomorphic(query1, query3).unwrap());
+}
+
+#[test]
+fn test_sparql_typed_literal_iri_on_a_single_line() {
+    // The `#` of the datatype IRI must not be read as a comment hiding the closing brace
+    let query1 = r#"SELECT ?s WHERE { ?s <http://example.org/age> "30"^^<http://www.w3.org/2001/XMLSchema#integer> . ?s <http://example.org/name> ?n }"#;
+    let query2 = r#"SELECT ?x WHERE { ?x <http://example.org/age> "30"^^<http://www.w3.org/2001/XMLSchema#integer> . ?x <http://example.org/name> ?m }"#;
+    let query3 = r#"SELECT ?x WHERE { ?x <http://example.org/age> "30"^^<http://www.w3.org/2001/XMLSchema#string> . ?x <http://example.org/name> ?m }"#;
+
+    assert!(QueryIsomorphismAPI::is_isomorphic(query1, query2).unwrap());
+    assert!(!QueryIsomorphismAPI::is_isomorphic(query1, query3).unwrap());
+}
+
+#[test]
+fn test_sparql_bare_numbers_isomorphic_to_typed_literals() {
+    let bare = r#"
+PREFIX ex: <http://example.org/>
+SELECT ?person WHERE {
+    ?person ex:age 42 ;
+            ex:height 1.85 ;
+            ex:mass 7.2e1 ;
+            ex:active true .
+}
+"#;
+
+    let typed = r#"
+PREFIX ex: <http://example.org/>
+PREFIX xsd: <http://www.w3.org/2001/XMLSchema#>
+SELECT ?p WHERE {
+    ?p ex:age "42"^^xsd:integer ;
+       ex:height "1.85"^^xsd:decimal ;
+       ex:mass "7.2e1"^^xsd:double ;
+       ex:active "true"^^xsd:boolean .
+}
+"#;
+
+    let wrong_type = r#"
+PREFIX ex: <http://example.org/>
+PREFIX xsd: <http://www.w3.org/2001/XMLSchema#>
+SELECT ?p WHERE {
+    ?p ex:age "42"^^xsd:string ;
+       ex:height "1.85"^^xsd:decimal ;
+       ex:mass "7.2e1"^^xsd:double ;
+       ex:active "true"^^xsd:boolean .
+}
+"#;
+
+    assert_eq!(QueryIsomorphismAPI::extract_bgp(bare).unwrap().len(), 4);
+    assert!(QueryIsomorphismAPI::is_isomorphic(bare, typed).unwrap());
+    assert!(!QueryIsomorphismAPI::is_isomorphic(bare, wrong_type).unwrap());
+}
+
+#[test]
+fn test_sparql_language_tags_distinguish_literals() {
+    let english = r#"
+SELECT ?s WHERE {
+    ?s <http://example.org/label> "hello"@en .
+}
+"#;
+
+    let french = r#"
+SELECT ?x WHERE {
+    ?x <http://example.org/label> "hello"@fr .
+}
+"#;
+
+    let english_upper = r#"
+SELECT ?x WHERE {
+    ?x <http://example.org/label> "hello"@EN .
+}
+"#;
+
+    assert!(!QueryIsomorphismAPI::is_isomorphic(english, french).unwrap());
+    assert!(QueryIsomorphismAPI::is_isomorphic(english, english_upper).unwrap());
+}
+
+#[test]
+fn test_sparql_semicolon_shorthand_matches_expanded_triples() {
+    let shorthand = r#"
+PREFIX foaf: <http://xmlns.com/foaf/0.1/>
+SELECT ?s WHERE {
+    ?s foaf:name ?n ;
+       foaf:age ?a .
+}
+"#;
+
+    let expanded = r#"
+PREFIX foaf: <http://xmlns.com/foaf/0.1/>
+SELECT ?x WHERE {
+    ?x foaf:name ?name .
+    ?x foaf:age ?age .
+}
+"#;
+
+    let result = QueryIsomorphismAPI::is_isomorphic(shorthand, expanded);
+    assert!(result.is_ok());
+    assert!(result.unwrap());
+}
+
+#[test]
+fn test_sparql_prefixed_predicates_expanded() {
+    let prefixed = r#"
+PREFIX ex: <http://example.org/>
+SELECT ?s WHERE {
+    ?s ex:property1 ?o .
+}
+"#;
+
+    let full = r#"
+SELECT ?x WHERE {
+    ?x <http://example.org/property1> ?y .
+}
+"#;
+
+    let other_prefix = r#"
+PREFIX other: <http://example.org/>
+SELECT ?x WHERE {
+    ?x other:property1 ?y .
+}
+"#;
+
+    let different = r#"
+PREFIX ex: <http://example.org/>
+SELECT ?s WHERE {
+    ?s ex:property2 ?o .
+}
+"#;
+
+    assert!(QueryIsomorphismAPI::is_isomorphic(prefixed, full).unwrap());
+    assert!(QueryIsomorphismAPI::is_isomorphic(prefixed, other_prefix).unwrap());
+    assert!(!QueryIsomorphismAPI::is_isomorphic(prefixed, different).unwrap());
+}
+
+#[test]
+fn test_sparql_construct_templates_compared() {
+    let query1 = r#"
+PREFIX ex: <http://example.org/>
+CONSTRUCT {
+    ?person ex:knows ?friend .
+}
+WHERE {
+    ?person ex:friendOf ?friend .
+}
+"#;
+
+    let renamed = r#"
+PREFIX ex: <http://example.org/>
+CONSTRUCT { ?a ex:knows ?b . } WHERE { ?a ex:friendOf ?b . }
+"#;
+
+    let different_predicate = r#"
+PREFIX ex: <http://example.org/>
+CONSTRUCT {
+    ?person ex:likes ?friend .
+}
+WHERE {
+    ?person ex:friendOf ?friend .
+}
+"#;
+
+    // Same triples, but the template swaps the roles of the WHERE variables
+    let swapped_variables = r#"
+PREFIX ex: <http://example.org/>
+CONSTRUCT {
+    ?friend ex:knows ?person .
+}
+WHERE {
+    ?person ex:friendOf ?friend .
+}
+"#;
+
+    assert!(QueryIsomorphismAPI::is_isomorphic(query1, renamed).unwrap());
+    assert!(!QueryIsomorphismAPI::is_isomorphic(query1, different_predicate).unwrap());
+    assert!(!QueryIsomorphismAPI::is_isomorphic(query1, swapped_variables).unwrap());
+}
+
+#[test]
+fn test_sparql_construct_template_size_matters() {
+    let query1 = r#"
+PREFIX ex: <http://example.org/>
+CONSTRUCT {
+    ?s ex:p ?o .
+}
+WHERE {
+    ?s ex:p ?o .
+}
+"#;
+
+    let query2 = r#"
+PREFIX ex: <http://example.org/>
+CONSTRUCT {
+    ?s ex:p ?o .
+    ?o ex:q ?s .
+}
+WHERE {
+    ?s ex:p ?o .
+}
+"#;
+
+    let select = r#"
+PREFIX ex: <http://example.org/>
+SELECT ?s ?o
+WHERE {
+    ?s ex:p ?o .
+}
+"#;
+
+    assert!(!QueryIsomorphismAPI::is_isomorphic(query1, query2).unwrap());
+    assert!(!QueryIsomorphismAPI::is_isomorphic(query1, select).unwrap());
+}
+
+#[test]
+fn test_sparql_construct_template_repeated_triple() {
+    let repeated = r#"
+PREFIX ex: <http://example.org/>
+CONSTRUCT {
+    ?s ex:p ?o .
+    ?s ex:p ?o .
+}
+WHERE {
+    ?s ex:p ?o .
+}
+"#;
+
+    let single = r#"
+PREFIX ex: <http://example.org/>
+CONSTRUCT {
+    ?x ex:p ?y .
+}
+WHERE {
+    ?x ex:p ?y .
+}
+"#;
+
+    // A template is a set of triples, as it is for `IsomorphismQuery` equality
+    assert!(QueryIsomorphismAPI::is_isomorphic(repeated, single).unwrap());
+    assert_eq!(
+        QueryIsomorphismAPI::parse_query(repeated).unwrap(),
+        QueryIsomorphismAPI::parse_query(&single.replace("?x", "?s").replace("?y", "?o")).unwrap()
+    );
+}
+
+#[test]
+fn test_sparql_ask_and_select_not_isomorphic() {
+    let ask = "ASK { ?s ?p ?o }";
+    let select = "SELECT ?s WHERE { ?s ?p ?o }";
+    let other_ask = r#"
+ASK {
+    ?x ?y ?z .
+}
+"#;
+
+    assert!(!QueryIsomorphismAPI::is_isomorphic(ask, select).unwrap());
+    assert!(QueryIsomorphismAPI::is_isomorphic(ask, other_ask).unwrap());
+
+    let result = QueryIsomorphismAPI::compare_queries(ask, select).unwrap();
+    assert!(!result.is_isomorphic);
+    assert!(result.same_language);
+    assert!(!result.same_query_form);
+    assert!(result.same_bgp_size);
+    assert!(result.bgp_isomorphic);
+}
+
+#[test]
+fn test_sparql_ask_pattern_extracted() {
+    let bgp = QueryIsomorphismAPI::extract_bgp("ASK { ?s <http://example.org/p> ?o }").unwrap();
+    assert_eq!(bgp.len(), 1);
+
+    let ask1 = "ASK { ?s <http://example.org/p> ?o }";
+    let ask2 = "ASK { ?s <http://example.org/q> ?o }";
+    assert!(!QueryIsomorphismAPI::is_isomorphic(ask1, ask2).unwrap());
+}
+
+#[test]
+fn test_sparql_find_matches() {
+    let query = "SELECT ?s WHERE { ?s <http://example.org/p> ?o . }";
+    let candidates = [
+        "SELECT ?x WHERE { ?x <http://example.org/p> ?y . }",
+        "SELECT ?x WHERE { ?x <http://example.org/q> ?y . }",
+        "SELECT ?x WHERE { ?x <http://example.org/p> ?y . ?y <http://example.org/p> ?z . }",
+        "ASK { ?x <http://example.org/p> ?y }",
+    ];
+
+    let matches = QueryIsomorphismAPI::find_matches(query, &candidates).unwrap();
+    assert_eq!(matches, vec![0]);
+}
+
+#[test]
+fn test_sparql_filter_expressions_compared() {
+    let query1 = r#"
+SELECT ?s WHERE {
+    ?s <http://example.org/age> ?age .
+    ?s <http://example.org/height> ?height .
+    FILTER(?age > 18)
+    FILTER (?height < 200)
+}
+"#;
+
+    // Same constraints on renamed variables, in a different order
+    let reordered = r#"
+SELECT ?x WHERE {
+    ?x <http://example.org/age> ?a .
+    ?x <http://example.org/height> ?h .
+    FILTER(?h < 200)
+    FILTER(?a > 18)
+}
+"#;
+
+    // The bound is applied to the other variable
+    let swapped = r#"
+SELECT ?s WHERE {
+    ?s <http://example.org/age> ?age .
+    ?s <http://example.org/height> ?height .
+    FILTER(?height > 18)
+    FILTER (?age < 200)
+}
+"#;
+
+    let other_bound = query1.replace("?age > 18", "?age > 21");
+
+    assert!(QueryIsomorphismAPI::is_isomorphic(query1, reordered).unwrap());
+    assert!(!QueryIsomorphismAPI::is_isomorphic(query1, swapped).unwrap());
+    assert!(!QueryIsomorphismAPI::is_isomorphic(query1, &other_bound).unwrap());
+}
+
+#[test]
+fn test_sparql_filter_prefixed_names_expanded() {
+    let query1 = r#"
+PREFIX a: <http://example.org/>
+SELECT ?s WHERE {
+    ?s <http://example.org/p> ?o .
+    FILTER(?o = a:b || datatype(?o) = a:t)
+}
+"#;
+
+    let other_prefix = r#"
+PREFIX ex: <http://example.org/>
+SELECT ?s WHERE {
+    ?s <http://example.org/p> ?o .
+    FILTER(?o = ex:b || datatype(?o) = <http://example.org/t>)
+}
+"#;
+
+    let other_namespace = query1.replace("<http://example.org/>", "<http://other.org/>");
+
+    assert!(QueryIsomorphismAPI::is_isomorphic(query1, other_prefix).unwrap());
+    assert!(!QueryIsomorphismAPI::is_isomorphic(query1, &other_namespace).unwrap());
+}
+
+#[test]
+fn test_sparql_filters_stay_in_their_group() {
+    let in_optional = r#"
+PREFIX ex: <http://example.org/>
+SELECT ?s WHERE {
+    ?s ex:p ?o .
+    OPTIONAL { ?s ex:q ?x . FILTER(?x > 5) }
+}
+"#;
+
+    let global = r#"
+PREFIX ex: <http://example.org/>
+SELECT ?s WHERE {
+    ?s ex:p ?o .
+    OPTIONAL { ?s ex:q ?x . }
+    FILTER(?x > 5)
+}
+"#;
+
+    let in_branch = r#"
+PREFIX ex: <http://example.org/>
+SELECT ?s WHERE {
+    { ?s ex:p ?o . FILTER(?o > 5) } UNION { ?s ex:q ?o . }
+}
+"#;
+
+    let in_other_branch = r#"
+PREFIX ex: <http://example.org/>
+SELECT ?s WHERE {
+    { ?s ex:p ?o . } UNION { ?s ex:q ?o . FILTER(?o > 5) }
+}
+"#;
+
+    let reordered_branches = r#"
+PREFIX ex: <http://example.org/>
+SELECT ?x WHERE {
+    { ?x ex:q ?y . } UNION { FILTER(?y > 5) ?x ex:p ?y . }
+}
+"#;
+
+    assert!(!QueryIsomorphismAPI::is_isomorphic(in_optional, global).unwrap());
+    assert!(!QueryIsomorphismAPI::is_isomorphic(in_branch, in_other_branch).unwrap());
+    assert!(QueryIsomorphismAPI::is_isomorphic(in_branch, reordered_branches).unwrap());
+
+    let parsed = QueryIsomorphismAPI::parse_query(in_optional).unwrap();
+    assert!(parsed.filters.is_empty());
+    assert_eq!(parsed.optional_filters, vec![vec!["(?x > 5)".to_string()]]);
+}
+
+#[test]
+fn test_sparql_nested_group_triples_extracted() {
+    let nested = r#"
+SELECT ?s WHERE {
+    ?s <http://example.org/p> ?o .
+    {
+        ?o <http://example.org/q> ?x .
+        { ?x <http://example.org/r> ?y . }
+    }
+}
+"#;
+
+    let flat = r#"
+SELECT ?a WHERE {
+    ?a <http://example.org/p> ?b .
+    ?b <http://example.org/q> ?c .
+    ?c <http://example.org/r> ?d .
+}
+"#;
+
+    let different_inner = nested.replace("example.org/r", "example.org/other");
+
+    let bgp = QueryIsomorphismAPI::extract_bgp(nested).unwrap();
+    assert_eq!(bgp.len(), 3);
+    assert!(QueryIsomorphismAPI::is_isomorphic(nested, flat).unwrap());
+    assert!(!QueryIsomorphismAPI::is_isomorphic(nested, &different_inner).unwrap());
+}
+
+#[test]
+fn test_sparql_optional_not_isomorphic_to_required() {
+    let required = r#"
+PREFIX ex: <http://example.org/>
+SELECT ?s ?o WHERE { ?s ex:p ?o }
+"#;
+
+    let optional = r#"
+PREFIX ex: <http://example.org/>
+SELECT ?s ?o WHERE { OPTIONAL { ?s ex:p ?o } }
+"#;
+
+    let renamed_optional = r#"
+PREFIX ex: <http://example.org/>
+SELECT ?x ?y WHERE {
+    OPTIONAL {
+        ?x ex:p ?y .
+    }
+}
+"#;
+
+    assert!(!QueryIsomorphismAPI::is_isomorphic(required, optional).unwrap());
+    assert!(QueryIsomorphismAPI::is_isomorphic(optional, renamed_optional).unwrap());
+}
+
+#[test]
+fn test_sparql_optional_groups_compared_with_required_pattern() {
+    let query1 = r#"
+PREFIX ex: <http://example.org/>
+SELECT ?person ?name ?email WHERE {
+    ?person ex:name ?name .
+    OPTIONAL { ?person ex:email ?email . }
+}
+"#;
+
+    let reordered = r#"
+PREFIX ex: <http://example.org/>
+SELECT ?p ?n ?e WHERE {
+    OPTIONAL { ?p ex:email ?e . }
+    ?p ex:name ?n .
+}
+"#;
+
+    // The optional triple no longer shares its subject with the required one
+    let disconnected = r#"
+PREFIX ex: <http://example.org/>
+SELECT ?person ?name ?email WHERE {
+    ?person ex:name ?name .
+    OPTIONAL { ?other ex:email ?email . }
+}
+"#;
+
+    // Two optional groups are not the same as one group with both triples
+    let split_groups = r#"
+PREFIX ex: <http://example.org/>
+SELECT ?person WHERE {
+    ?person ex:name ?name .
+    OPTIONAL { ?person ex:email ?email . }
+    OPTIONAL { ?person ex:phone ?phone . }
+}
+"#;
+
+    let joined_group = r#"
+PREFIX ex: <http://example.org/>
+SELECT ?person WHERE {
+    ?person ex:name ?name .
+    OPTIONAL { ?person ex:email ?email . ?person ex:phone ?phone . }
+}
+"#;
+
+    assert!(QueryIsomorphismAPI::is_isomorphic(query1, reordered).unwrap());
+    assert!(!QueryIsomorphismAPI::is_isomorphic(query1, disconnected).unwrap());
+    assert!(!QueryIsomorphismAPI::is_isomorphic(split_groups, joined_group).unwrap());
+}
+
+#[test]
+fn test_sparql_blank_node_labels_never_match_encoding_nodes() {
+    let optional_labelled = r#"
+PREFIX ex: <http://example.org/>
+SELECT ?o WHERE {
+    _:tulna_optional_0 ex:name ?o .
+    OPTIONAL { ?o ex:email ?e . }
+}
+"#;
+
+    let optional_plain = r#"
+PREFIX ex: <http://example.org/>
+SELECT ?o WHERE {
+    _:b ex:name ?o .
+    OPTIONAL { ?o ex:email ?e . }
+}
+"#;
+
+    let template_labelled = r#"
+PREFIX ex: <http://example.org/>
+CONSTRUCT { ?s ex:name ?o } WHERE {
+    _:tulna_template_0 ex:knows ?s .
+    ?s ex:name ?o .
+}
+"#;
+
+    let template_plain = r#"
+PREFIX ex: <http://example.org/>
+CONSTRUCT { ?s ex:name ?o } WHERE {
+    _:b ex:knows ?s .
+    ?s ex:name ?o .
+}
+"#;
+
+    assert!(QueryIsomorphismAPI::is_isomorphic(optional_labelled, optional_plain).unwrap());
+    assert!(QueryIsomorphismAPI::is_isomorphic(template_labelled, template_plain).unwrap());
+}
+
+#[test]
+fn test_sparql_union_not_isomorphic_to_conjunction() {
+    let union = r#"
+PREFIX ex: <http://example.org/>
+SELECT ?s ?o WHERE {
+    { ?s ex:p ?o } UNION { ?s ex:q ?o }
+}
+"#;
+
+    let conjunction = r#"
+PREFIX ex: <http://example.org/>
+SELECT ?s ?o WHERE {
+    { ?s ex:p ?o } { ?s ex:q ?o }
+}
+"#;
+
+    let flat = r#"
+PREFIX ex: <http://example.org/>
+SELECT ?s ?o WHERE { ?s ex:p ?o . ?s ex:q ?o . }
+"#;
+
+    let parsed = QueryIsomorphismAPI::parse_query(union).unwrap();
+    assert!(parsed.bgp.is_empty());
+    assert_eq!(parsed.union_bgps.len(), 1);
+    assert_eq!(parsed.union_bgps[0].len(), 2);
+
+    assert!(QueryIsomorphismAPI::is_isomorphic(conjunction, flat).unwrap());
+    assert!(!QueryIsomorphismAPI::is_isomorphic(union, conjunction).unwrap());
+    let result = QueryIsomorphismAPI::compare_queries(union, flat).unwrap();
+    assert_eq!(result.mismatch_reason.as_deref(), Some("BGP size 0 != 2"));
+}
+
+#[test]
+fn test_sparql_union_branches_compared_as_sets() {
+    let query1 = r#"
+PREFIX ex: <http://example.org/>
+SELECT ?person ?contact WHERE {
+    ?person a ex:Person .
+    { ?person ex:email ?contact } UNION { ?person ex:phone ?contact } UNION { ?person ex:fax ?contact }
+}
+"#;
+
+    // Same branches in another order, with renamed variables
+    let reordered = r#"
+PREFIX ex: <http://example.org/>
+SELECT ?p ?c WHERE {
+    { ?p ex:fax ?c } UNION { ?p ex:email ?c } UNION { ?p ex:phone ?c }
+    ?p a ex:Person .
+}
+"#;
+
+    // A branch that no longer shares its subject with the required pattern
+    let disconnected = r#"
+PREFIX ex: <http://example.org/>
+SELECT ?person ?contact WHERE {
+    ?person a ex:Person .
+    { ?person ex:email ?contact } UNION { ?other ex:phone ?contact } UNION { ?person ex:fax ?contact }
+}
+"#;
+
+    // The same triples split differently across the branches
+    let regrouped = r#"
+PREFIX ex: <http://example.org/>
+SELECT ?person ?contact WHERE {
+    ?person a ex:Person .
+    { ?person ex:email ?contact . ?person ex:phone ?contact } UNION { ?person ex:fax ?contact }
+}
+"#;
+
+    assert!(QueryIsomorphismAPI::is_isomorphic(query1, reordered).unwrap());
+    assert!(!QueryIsomorphismAPI::is_isomorphic(query1, disconnected).unwrap());
+    assert!(!QueryIsomorphismAPI::is_isomorphic(query1, regrouped).unwrap());
+    assert_eq!(
+        QueryIsomorphismAPI::normalize_query(query1).unwrap(),
+        QueryIsomorphismAPI::normalize_query(reordered).unwrap()
+    );
+}
+
+#[test]
+fn test_sparql_normalize_query_graph_round_trip() {
+    let query = "SELECT * WHERE { GRAPH <http://g> { ?s <http://p> ?o } }";
+    let normalized = QueryIsomorphismAPI::normalize_query(query).unwrap();
+    assert!(normalized.contains("GRAPH <http://g> { ?v0 <http://p> ?v1 . }"));
+
+    let query = r#"
+SELECT * WHERE {
+    ?s <http://q> ?g .
+    GRAPH ?g { ?s <http://p> ?o }
+    OPTIONAL { GRAPH ?g { ?o <http://r> ?x } }
+}
+"#;
+    let normalized = QueryIsomorphismAPI::normalize_query(query).unwrap();
+
+    // The normalized query parses to the same query and normalizes to itself
+    assert!(QueryIsomorphismAPI::is_isomorphic(query, &normalized).unwrap());
+    assert_eq!(
+        QueryIsomorphismAPI::normalize_query(&normalized).unwrap(),
+        normalized
+    );
+}
+
+#[test]
+fn test_sparql_normalize_query_limit_exceeded() {
+    // Nine disjoint edges can only be labelled by grounding them one at a time
+    let patterns: Vec<String> = (0..9)
+        .map(|i| format!("?s{} <http://example.org/p> ?o{} .", i, i))
+        .collect();
+    let query = format!("SELECT * WHERE {{ {} }}", patterns.join(" "));
+
+    assert!(matches!(
+        QueryIsomorphismAPI::normalize_query(&query),
+        Err(TulnaError::LimitExceeded(_))
+    ));
+}
+
+#[test]
+fn test_sparql_with_janusql_words_stays_sparql() {
+    let query1 = r#"
+SELECT ?start ?end WHERE {
+    ?start <http://example.org/next> ?end .
+}
+ORDER BY ?start
+LIMIT 10
+OFFSET 5
+"#;
+
+    let query2 = r#"
+SELECT ?a ?b WHERE {
+    ?a <http://example.org/next> ?b .
+}
+ORDER BY ?a
+LIMIT 10
+OFFSET 5
+"#;
+
+    assert_eq!(
+        QueryIsomorphismAPI::detect_query_language(query1),
+        tulna_rs::isomorphism::core::QueryLanguage::SPARQL
+    );
+    assert!(QueryIsomorphismAPI::is_isomorphic(query1, query2).unwrap());
+}
+
+#[test]
+fn test_sparql_a_and_rdf_type_equivalent() {
+    let shorthand = r#"
+PREFIX ex: <http://example.org/>
+SELECT ?s WHERE {
+    ?s a ex:Person .
+}
+"#;
+
+    let prefixed = r#"
+PREFIX ex: <http://example.org/>
+PREFIX rdf: <http://www.w3.org/1999/02/22-rdf-syntax-ns#>
+SELECT ?s WHERE {
+    ?s rdf:type ex:Person .
+}
+"#;
+
+    let full_iri = r#"
+SELECT ?s WHERE {
+    ?s <http://www.w3.org/1999/02/22-rdf-syntax-ns#type> <http://example.org/Person> .
+}
+"#;
+
+    let bgp_shorthand = QueryIsomorphismAPI::extract_bgp(shorthand).unwrap();
+    assert_eq!(
+        bgp_shorthand,
+        QueryIsomorphismAPI::extract_bgp(prefixed).unwrap()
+    );
+    assert_eq!(
+        bgp_shorthand,
+        QueryIsomorphismAPI::extract_bgp(full_iri).unwrap()
+    );
+
+    assert!(QueryIsomorphismAPI::is_isomorphic(shorthand, prefixed).unwrap());
+    assert!(QueryIsomorphismAPI::is_isomorphic(shorthand, full_iri).unwrap());
+}
+
+#[test]
+fn test_sparql_anonymous_blank_node_matches_variable() {
+    let anonymous = r#"
+PREFIX ex: <http://example.org/>
+SELECT ?s ?o WHERE {
+    ?s ex:knows [ ex:name ?o ] .
+}
+"#;
+
+    let variable = r#"
+PREFIX ex: <http://example.org/>
+SELECT ?s ?o WHERE {
+    ?s ex:knows ?b .
+    ?b ex:name ?o .
+}
+"#;
+
+    let bgp = QueryIsomorphismAPI::extract_bgp(anonymous).unwrap();
+    assert_eq!(bgp.len(), 2);
+    assert!(QueryIsomorphismAPI::is_isomorphic(anonymous, variable).unwrap());
+}
+
+#[test]
+fn test_sparql_empty_anonymous_blank_node() {
+    let anonymous = r#"
+PREFIX ex: <http://example.org/>
+SELECT ?s WHERE {
+    ?s ex:knows [] .
+    [] ex:name "Alice" .
+}
+"#;
+
+    let variables = r#"
+PREFIX ex: <http://example.org/>
+SELECT ?s WHERE {
+    ?s ex:knows ?a .
+    ?b ex:name "Alice" .
+}
+"#;
+
+    let joined = r#"
+PREFIX ex: <http://example.org/>
+SELECT ?s WHERE {
+    ?s ex:knows ?a .
+    ?a ex:name "Alice" .
+}
+"#;
+
+    assert!(QueryIsomorphismAPI::is_isomorphic(anonymous, variables).unwrap());
+    assert!(!QueryIsomorphismAPI::is_isomorphic(anonymous, joined).unwrap());
+}
+
+#[test]
+fn test_sparql_property_lists_create_distinct_blank_nodes() {
+    let two_nodes = r#"
+PREFIX ex: <http://example.org/>
+ASK {
+    [ ex:p 1 ] .
+    [ ex:p 2 ] .
+}
+"#;
+
+    let one_node = r#"
+PREFIX ex: <http://example.org/>
+ASK {
+    [ ex:p 1 ; ex:p 2 ] .
+}
+"#;
+
+    let two_variables = r#"
+PREFIX ex: <http://example.org/>
+ASK {
+    ?a ex:p 1 .
+    ?b ex:p 2 .
+}
+"#;
+
+    let one_variable = r#"
+PREFIX ex: <http://example.org/>
+ASK {
+    ?a ex:p 1 .
+    ?a ex:p 2 .
+}
+"#;
+
+    assert!(!QueryIsomorphismAPI::is_isomorphic(two_nodes, one_node).unwrap());
+    assert!(QueryIsomorphismAPI::is_isomorphic(two_nodes, two_variables).unwrap());
+    assert!(QueryIsomorphismAPI::is_isomorphic(one_node, one_variable).unwrap());
+}
+
+#[test]
+fn test_sparql_anonymous_blank_nodes_of_different_groups_are_distinct() {
+    let optional = r#"
+PREFIX ex: <http://example.org/>
+SELECT ?s WHERE {
+    ?s ex:knows [] .
+    OPTIONAL { [] ex:name "Alice" }
+}
+"#;
+
+    let union = r#"
+PREFIX ex: <http://example.org/>
+SELECT ?s WHERE {
+    ?s ex:knows [] .
+    { [] ex:name "Alice" } UNION { [] ex:name "Bob" }
+}
+"#;
+
+    let labelled = r#"
+PREFIX ex: <http://example.org/>
+SELECT ?s WHERE {
+    ?s ex:knows [] .
+    _:tulna_anon_0 ex:name "Alice" .
+}
+"#;
+
+    let optional_variables = r#"
+PREFIX ex: <http://example.org/>
+SELECT ?s WHERE {
+    ?s ex:knows ?a .
+    OPTIONAL { ?b ex:name "Alice" }
+}
+"#;
+
+    let optional_joined = r#"
+PREFIX ex: <http://example.org/>
+SELECT ?s WHERE {
+    ?s ex:knows ?a .
+    OPTIONAL { ?a ex:name "Alice" }
+}
+"#;
+
+    let union_variables = r#"
+PREFIX ex: <http://example.org/>
+SELECT ?s WHERE {
+    ?s ex:knows ?a .
+    { ?b ex:name "Alice" } UNION { ?c ex:name "Bob" }
+}
+"#;
+
+    let union_joined = r#"
+PREFIX ex: <http://example.org/>
+SELECT ?s WHERE {
+    ?s ex:knows ?a .
+    { ?a ex:name "Alice" } UNION { ?a ex:name "Bob" }
+}
+"#;
+
+    let labelled_variables = r#"
+PREFIX ex: <http://example.org/>
+SELECT ?s WHERE {
+    ?s ex:knows ?a .
+    ?b ex:name "Alice" .
+}
+"#;
+
+    assert!(QueryIsomorphismAPI::is_isomorphic(optional, optional_variables).unwrap());
+    assert!(!QueryIsomorphismAPI::is_isomorphic(optional, optional_joined).unwrap());
+    assert!(QueryIsomorphismAPI::is_isomorphic(union, union_variables).unwrap());
+    assert!(!QueryIsomorphismAPI::is_isomorphic(union, union_joined).unwrap());
+    assert!(QueryIsomorphismAPI::is_isomorphic(labelled, labelled_variables).unwrap());
+}
+
+#[test]
+fn test_sparql_parse_errors_are_structured() {
+    let valid = "SELECT ?s WHERE { ?s ?p ?o . }";
+
+    let no_query_form = "WHERE { ?s ?p ?o . }";
+    match QueryIsomorphismAPI::is_isomorphic(no_query_form, valid) {
+        Err(TulnaError::ParseError(message)) => {
+            assert!(message.contains("Unable to determine query type"))
+        }
+        other => panic!("Expected a parse error, got {:?}", other),
+    }
+
+    let huge_limit = "SELECT ?s WHERE { ?s ?p ?o . } LIMIT 99999999999999999999999";
+    match QueryIsomorphismAPI::is_isomorphic(huge_limit, valid) {
+        Err(TulnaError::ParseError(message)) => assert!(message.contains("LIMIT")),
+        other => panic!("Expected a parse error, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_sparql_missing_closing_brace_is_an_error() {
+    let query1 = r#"
+PREFIX ex: <http://example.org/>
+SELECT ?s WHERE {
+    ?s ex:knows ?o .
+    OPTIONAL { ?o ex:name ?name .
+}
+"#;
+
+    let query2 = r#"
+PREFIX ex: <http://example.org/>
+SELECT ?s WHERE {
+    ?s ex:knows ?o .
+    OPTIONAL { ?o ex:name ?name . }
+}
+"#;
+
+    match QueryIsomorphismAPI::is_isomorphic(query1, query2) {
+        Err(TulnaError::UnbalancedBraces(message)) => assert!(message.contains("line 3")),
+        other => panic!("Expected unbalanced braces, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_sparql_values_compared_up_to_renaming() {
+    let query1 = r#"
+PREFIX ex: <http://example.org/>
+SELECT ?s ?name WHERE {
+    VALUES (?s ?name) { (ex:alice "Alice") (ex:bob "Bob") }
+    ?s ex:name ?name .
+}
+"#;
+
+    // Renamed variables, swapped columns and reordered rows
+    let query2 = r#"
+PREFIX ex: <http://example.org/>
+SELECT ?x ?n WHERE {
+    ?x ex:name ?n .
+    VALUES (?n ?x) { ("Bob" ex:bob) ("Alice" ex:alice) }
+}
+"#;
+
+    // A trailing VALUES block after the graph pattern
+    let query3 = r#"
+PREFIX ex: <http://example.org/>
+SELECT ?s ?name WHERE {
+    ?s ex:name ?name .
+}
+VALUES (?s ?name) { (ex:alice "Alice") (ex:bob "Bob") }
+"#;
+
+    assert_eq!(QueryIsomorphismAPI::extract_bgp(query1).unwrap().len(), 1);
+    assert!(QueryIsomorphismAPI::is_isomorphic(query1, query2).unwrap());
+    assert!(QueryIsomorphismAPI::is_isomorphic(query1, query3).unwrap());
+}
+
+#[test]
+fn test_sparql_different_values_not_isomorphic() {
+    let query1 = r#"
+PREFIX ex: <http://example.org/>
+SELECT ?s WHERE {
+    VALUES ?s { ex:alice ex:bob }
+    ?s ex:name ?name .
+}
+"#;
+
+    let different_row = r#"
+PREFIX ex: <http://example.org/>
+SELECT ?s WHERE {
+    VALUES ?s { ex:alice ex:carol }
+    ?s ex:name ?name .
+}
+"#;
+
+    let other_variable = r#"
+PREFIX ex: <http://example.org/>
+SELECT ?s WHERE {
+    VALUES ?name { ex:alice ex:bob }
+    ?s ex:name ?name .
+}
+"#;
+
+    let without_values = r#"
+PREFIX ex: <http://example.org/>
+SELECT ?s WHERE {
+    ?s ex:name ?name .
+}
+"#;
+
+    assert!(!QueryIsomorphismAPI::is_isomorphic(query1, different_row).unwrap());
+    assert!(!QueryIsomorphismAPI::is_isomorphic(query1, other_variable).unwrap());
+    assert!(!QueryIsomorphismAPI::is_isomorphic(query1, without_values).unwrap());
+}
+
+#[test]
+fn test_sparql_property_paths() {
+    let one_or_more = r#"
+PREFIX foaf: <http://xmlns.com/foaf/0.1/>
+SELECT ?s ?o WHERE {
+    ?s foaf:knows+ ?o .
+}
+"#;
+
+    let one_or_more_renamed = r#"
+SELECT ?a ?b WHERE {
+    ?a <http://xmlns.com/foaf/0.1/knows>+ ?b .
+}
+"#;
+
+    let zero_or_more = r#"
+PREFIX foaf: <http://xmlns.com/foaf/0.1/>
+SELECT ?s ?o WHERE {
+    ?s foaf:knows* ?o .
+}
+"#;
+
+    let plain = r#"
+PREFIX foaf: <http://xmlns.com/foaf/0.1/>
+SELECT ?s ?o WHERE {
+    ?s foaf:knows ?o .
+}
+"#;
+
+    let bgp = QueryIsomorphismAPI::extract_bgp(one_or_more).unwrap();
+    assert_eq!(bgp.len(), 1);
+    assert_eq!(
+        bgp[0].predicate,
+        TripleNode::PropertyPath("<http://xmlns.com/foaf/0.1/knows>+".to_string())
+    );
+
+    assert!(QueryIsomorphismAPI::is_isomorphic(one_or_more, one_or_more).unwrap());
+    assert!(QueryIsomorphismAPI::is_isomorphic(one_or_more, one_or_more_renamed).unwrap());
+    assert!(!QueryIsomorphismAPI::is_isomorphic(one_or_more, zero_or_more).unwrap());
+    assert!(!QueryIsomorphismAPI::is_isomorphic(one_or_more, plain).unwrap());
+}
+
+#[test]
+fn test_sparql_speculation_limit_error_propagated() {
+    // Two disjoint complete graphs on 6 nodes against a 12-node ring where every node links
+    // to the nodes 1, 2 and 6 steps away in both directions: every node has 5 neighbours,
+    // so the speculative search gives up instead of answering
+    let query = |links: Vec<(usize, usize)>| {
+        let patterns: Vec<String> = links
+            .iter()
+            .map(|(i, j)| format!("?n{} <http://example.org/link> ?n{} .", i, j))
+            .collect();
+        format!("SELECT *\nWHERE {{\n{}\n}}", patterns.join("\n"))
+    };
+
+    let mut cliques = Vec::new();
+    for offset in [0, 6] {
+        for i in 0..6 {
+            for j in (0..6).filter(|&j| j != i) {
+                cliques.push((offset + i, offset + j));
+            }
+        }
+    }
+    let mut ring = Vec::new();
+    for i in 0..12 {
+        for step in [1, 2, 6, 10, 11] {
+            ring.push((i, (i + step) % 12));
+        }
+    }
+
+    assert!(matches!(
+        QueryIsomorphismAPI::is_isomorphic(&query(cliques), &query(ring)),
+        Err(TulnaError::LimitExceeded(_))
+    ));
+}
+
+#[test]
+fn test_sparql_comments_ignored() {
+    let plain = r#"
+PREFIX ex: <http://example.org/>
+SELECT ?s WHERE {
+    ?s ex:p "a # b" .
+    ?s <http://example.org/page#top> ?o .
+}
+LIMIT 5
+"#;
+    let commented = r#"
+# WINDOW ex:w { ?x ex:q ?y } REGISTER RStream <out> AS
+PREFIX ex: <http://example.org/> # PREFIX other: <http://other.org/>
+SELECT ?s WHERE { # } OPTIONAL {
+    ?s ex:p "a # b" . # ?s ex:hidden ?h }
+    ?s <http://example.org/page#top> ?o . # } UNION {
+}
+# OFFSET 10 LIMIT 99
+LIMIT 5 # ORDER BY ?s
+"#;
+
+    assert!(QueryIsomorphismAPI::is_isomorphic(plain, commented).unwrap());
+    assert_eq!(
+        QueryIsomorphismAPI::parse_query(plain).unwrap(),
+        QueryIsomorphismAPI::parse_query(commented).unwrap()
+    );
+}
+
+#[test]
+fn test_sparql_collection_isomorphic_to_expanded_list() {
+    let collection = r#"
+PREFIX ex: <http://example.org/>
+SELECT ?s WHERE {
+    ?s ex:members ( ex:alice ex:bob ) .
+}
+"#;
+    let expanded = r#"
+PREFIX ex: <http://example.org/>
+PREFIX rdf: <http://www.w3.org/1999/02/22-rdf-syntax-ns#>
+SELECT ?s WHERE {
+    ?s ex:members _:l1 .
+    _:l1 rdf:first ex:alice ; rdf:rest _:l2 .
+    _:l2 rdf:first ex:bob ; rdf:rest rdf:nil .
+}
+"#;
+    let reversed = r#"
+PREFIX ex: <http://example.org/>
+SELECT ?s WHERE {
+    ?s ex:members ( ex:bob ex:alice ) .
+}
+"#;
+
+    assert!(QueryIsomorphismAPI::is_isomorphic(collection, expanded).unwrap());
+    assert!(!QueryIsomorphismAPI::is_isomorphic(collection, reversed).unwrap());
+}
+
+#[test]
+fn test_sparql_collections_of_different_groups_are_distinct() {
+    let optional = r#"
+PREFIX ex: <http://example.org/>
+SELECT ?s WHERE {
+    ?s ex:members ( ex:alice ) .
+    OPTIONAL { ?s ex:owners ( ex:bob ) }
+}
+"#;
+    let separate = r#"
+PREFIX ex: <http://example.org/>
+PREFIX rdf: <http://www.w3.org/1999/02/22-rdf-syntax-ns#>
+SELECT ?s WHERE {
+    ?s ex:members ?l .
+    ?l rdf:first ex:alice ; rdf:rest rdf:nil .
+    OPTIONAL { ?s ex:owners ?m . ?m rdf:first ex:bob ; rdf:rest rdf:nil }
+}
+"#;
+    let joined = r#"
+PREFIX ex: <http://example.org/>
+PREFIX rdf: <http://www.w3.org/1999/02/22-rdf-syntax-ns#>
+SELECT ?s WHERE {
+    ?s ex:members ?l .
+    ?l rdf:first ex:alice ; rdf:rest rdf:nil .
+    OPTIONAL { ?s ex:owners ?l . ?l rdf:first ex:bob ; rdf:rest rdf:nil }
+}
+"#;
+
+    let labelled = r#"
+PREFIX ex: <http://example.org/>
+SELECT ?s WHERE {
+    ?s ex:members ( ex:alice ) .
+    _:tulna_list_0 ex:name "Bob" .
+}
+"#;
+    let labelled_variables = r#"
+PREFIX ex: <http://example.org/>
+PREFIX rdf: <http://www.w3.org/1999/02/22-rdf-syntax-ns#>
+SELECT ?s WHERE {
+    ?s ex:members ?l .
+    ?l rdf:first ex:alice ; rdf:rest rdf:nil .
+    ?m ex:name "Bob" .
+}
+"#;
+
+    assert!(QueryIsomorphismAPI::is_isomorphic(optional, separate).unwrap());
+    assert!(!QueryIsomorphismAPI::is_isomorphic(optional, joined).unwrap());
+    assert!(QueryIsomorphismAPI::is_isomorphic(labelled, labelled_variables).unwrap());
+}
+
+#[test]
+fn test_sparql_dollar_variables() {
+    let dollar = "SELECT $s $p $o WHERE { $s $p $o . }";
+    let question = "SELECT ?x ?y ?z WHERE { ?x ?y ?z . }";
+
+    assert!(QueryIsomorphismAPI::is_isomorphic(dollar, question).unwrap());
+
+    let dollar = r#"
+SELECT $s WHERE {
+    $s <http://example.org/p> $o .
+    FILTER($o > 3)
+}
+ORDER BY $s
+"#;
+    let question = r#"
+SELECT ?s WHERE {
+    ?s <http://example.org/p> ?o .
+    FILTER(?o > 3)
+}
+ORDER BY ?s
+"#;
+
+    assert_eq!(
+        QueryIsomorphismAPI::parse_query(dollar).unwrap(),
+        QueryIsomorphismAPI::parse_query(question).unwrap()
+    );
+}
+
+#[test]
+fn test_sparql_variable_predicates() {
+    let iso = |q1: &str, q2: &str| QueryIsomorphismAPI::is_isomorphic(q1, q2).unwrap();
+
+    assert!(iso(
+        r#"SELECT * WHERE { ?s ?p "lit" . }"#,
+        r#"SELECT * WHERE { ?a ?b "lit" . }"#
+    ));
+    assert!(!iso(
+        r#"SELECT * WHERE { ?s ?p "lit" . }"#,
+        r#"SELECT * WHERE { ?a ?b "other" . }"#
+    ));
+
+    // A predicate variable that is also a subject elsewhere
+    assert!(iso(
+        "SELECT * WHERE { ?s ?p ?o . ?p <http://example.org/label> ?l . }",
+        "SELECT * WHERE { ?y <http://example.org/label> ?z . ?x ?y ?w . }"
+    ));
+    assert!(!iso(
+        "SELECT * WHERE { ?s ?p ?o . ?p <http://example.org/label> ?l . }",
+        "SELECT * WHERE { ?x ?y ?w . ?x <http://example.org/label> ?z . }"
+    ));
+
+    // Two triples sharing a predicate variable differ from two with distinct predicates
+    assert!(iso(
+        "SELECT * WHERE { <http://example.org/a> ?p ?x . <http://example.org/b> ?p ?y . }",
+        "SELECT * WHERE { <http://example.org/b> ?q ?m . <http://example.org/a> ?q ?n . }"
+    ));
+    assert!(!iso(
+        "SELECT * WHERE { <http://example.org/a> ?p ?x . <http://example.org/b> ?p ?y . }",
+        "SELECT * WHERE { <http://example.org/a> ?p ?x . <http://example.org/b> ?q ?y . }"
+    ));
+    assert!(iso(
+        "SELECT * WHERE { ?s $p ?o . }",
+        "SELECT * WHERE { ?x ?y ?z . }"
+    ));
+
+    let bgp = QueryIsomorphismAPI::extract_bgp(r#"SELECT * WHERE { ?s ?p "lit" . }"#).unwrap();
+    assert_eq!(bgp[0].predicate, TripleNode::Variable("p".to_string()));
+}
+
+#[test]
+fn test_sparql_named_graphs_do_not_cross_match() {
+    let query = "PREFIX ex: <http://example.org/>
+        SELECT * WHERE {
+            GRAPH ex:g1 { ?s ex:p ?o . }
+            GRAPH ex:g2 { ?o ex:q ?x . }
+        }";
+    let renamed = "PREFIX ex: <http://example.org/>
+        SELECT * WHERE {
+            GRAPH ex:g2 { ?b ex:q ?c . }
+            GRAPH ex:g1 { ?a ex:p ?b . }
+        }";
+    let swapped = "PREFIX ex: <http://example.org/>
+        SELECT * WHERE {
+            GRAPH ex:g2 { ?s ex:p ?o . }
+            GRAPH ex:g1 { ?o ex:q ?x . }
+        }";
+    let default_graph = "PREFIX ex: <http://example.org/>
+        SELECT * WHERE { ?s ex:p ?o . ?o ex:q ?x . }";
+
+    assert!(QueryIsomorphismAPI::is_isomorphic(query, renamed).unwrap());
+    assert!(!QueryIsomorphismAPI::is_isomorphic(query, swapped).unwrap());
+    assert!(!QueryIsomorphismAPI::is_isomorphic(query, default_graph).unwrap());
+
+    let parsed = QueryIsomorphismAPI::parse_query(query).unwrap();
+    let graphs = parsed.graph_bgps();
+    assert_eq!(graphs.len(), 2);
+    assert_eq!(
+        graphs[0].0,
+        Some(TripleNode::IRI("http://example.org/g1".to_string()))
+    );
+    assert_eq!(graphs[0].1.len(), 1);
+    assert_eq!(
+        graphs[0].1[0].predicate,
+        TripleNode::IRI("http://example.org/p".to_string())
+    );
+    assert_eq!(
+        graphs[1].0,
+        Some(TripleNode::IRI("http://example.org/g2".to_string()))
+    );
+    assert_eq!(
+        graphs[1].1[0].predicate,
+        TripleNode::IRI("http://example.org/q".to_string())
+    );
+}
+
+#[test]
+fn test_sparql_describe_resources() {
+    let iso = |q1: &str, q2: &str| QueryIsomorphismAPI::is_isomorphic(q1, q2).unwrap();
+
+    // Without a WHERE clause only the described resources tell queries apart
+    assert!(!iso(
+        "DESCRIBE <http://example.org/a>",
+        "DESCRIBE <http://example.org/b>"
+    ));
+    assert!(iso(
+        "DESCRIBE <http://example.org/a>",
+        "PREFIX ex: <http://example.org/>\nDESCRIBE ex:a"
+    ));
+    assert!(iso(
+        "DESCRIBE <http://example.org/a> <http://example.org/b>",
+        "DESCRIBE <http://example.org/b>\n    <http://example.org/a>"
+    ));
+    assert!(!iso(
+        "DESCRIBE <http://example.org/a> <http://example.org/b>",
+        "DESCRIBE <http://example.org/a>"
+    ));
+
+    let parsed = QueryIsomorphismAPI::parse_query(
+        "BASE <http://example.org/>\nDESCRIBE <a>\nFROM <http://example.org/g>",
+    )
+    .unwrap();
+    assert_eq!(
+        parsed.describe_targets,
+        vec![TripleNode::IRI("http://example.org/a".to_string())]
+    );
+    assert_eq!(parsed.default_graphs, vec!["http://example.org/g"]);
+
+    // A described variable is matched under the same bijection as the WHERE clause
+    let query = "PREFIX ex: <http://example.org/>
+        DESCRIBE ?x WHERE { ?x ex:knows ?y . }";
+    assert!(iso(
+        query,
+        "PREFIX ex: <http://example.org/>
+        DESCRIBE ?a WHERE { ?a ex:knows ?b . }"
+    ));
+    assert!(!iso(
+        query,
+        "PREFIX ex: <http://example.org/>
+        DESCRIBE ?y WHERE { ?x ex:knows ?y . }"
+    ));
+    assert!(!iso(
+        query,
+        "PREFIX ex: <http://example.org/>
+        DESCRIBE ?x WHERE { ?x ex:likes ?y . }"
+    ));
+    assert!(QueryIsomorphismAPI::is_bgp_isomorphic(
+        query,
+        "PREFIX ex: <http://example.org/>
+        SELECT ?y WHERE { ?x ex:knows ?y . }"
+    )
+    .unwrap());
+
+    let canonical = QueryIsomorphismAPI::rename_variables_canonical(query).unwrap();
+    assert!(canonical.contains("DESCRIBE ?v0"), "{}", canonical);
+    assert!(iso(query, &canonical));
 }